@@ -0,0 +1,54 @@
+//! Validates the K4 banks in a System Exclusive file.
+//! Prints the findings and exits with zero only if every bank is clean.
+//!
+//! Usage: cargo run --example k4validate -- FILE.SYX
+
+use std::process;
+
+use ksynth::loader;
+use ksynth::k4::sysex::{Dump, Kind};
+use ksynth::k4::validate::validate_data;
+
+fn main() {
+    let path = match std::env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: k4validate FILE.SYX");
+            process::exit(2);
+        }
+    };
+    let data = match std::fs::read(&path) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(2);
+        }
+    };
+
+    let mut exit_code = 0;
+    let mut banks = 0;
+    for (offset, message) in loader::split(&data) {
+        if message.len() < 3 {
+            continue;
+        }
+        let dump = match Dump::identify(message[2..message.len() - 1].to_vec()) {
+            Ok(dump) if dump.kind == Kind::All => dump,
+            _ => continue,
+        };
+
+        banks += 1;
+        let report = validate_data(&dump.payload);
+        for finding in report.findings.iter() {
+            println!("{}@{}: {}", path, offset, finding);
+        }
+        if !report.is_clean() {
+            exit_code = report.exit_code();
+        }
+    }
+
+    if banks == 0 {
+        eprintln!("{}: no K4 banks found", path);
+        process::exit(2);
+    }
+    process::exit(exit_code);
+}
@@ -0,0 +1,44 @@
+//! Converts a dump between System Exclusive and the text format.
+//! The direction and the text format are picked by the file extensions.
+//!
+//! Usage: cargo run --features text --example syxtext -- INPUT OUTPUT
+//!
+//! For example, `syxtext A401.SYX A401.toml` writes the bank as TOML,
+//! and `syxtext A401.toml A401.SYX` writes it back.
+
+use std::process;
+
+use ksynth::text::{Document, Format};
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() != 2 {
+        eprintln!("usage: syxtext INPUT OUTPUT");
+        process::exit(2);
+    }
+    let (input, output) = (&args[0], &args[1]);
+
+    let result = match (Format::from_path(input), Format::from_path(output)) {
+        (None, Some(format)) => {
+            let data = std::fs::read(input).unwrap_or_else(|e| fail(format!("{}: {}", input, e)));
+            let document = Document::from_syx(&data).unwrap_or_else(|e| fail(format!("{}: {}", input, e)));
+            let text = document.to_text(format).unwrap_or_else(|e| fail(format!("{}: {}", output, e)));
+            std::fs::write(output, text)
+        },
+        (Some(format), None) => {
+            let text = std::fs::read_to_string(input).unwrap_or_else(|e| fail(format!("{}: {}", input, e)));
+            let document = Document::from_text(&text, format).unwrap_or_else(|e| fail(format!("{}: {}", input, e)));
+            std::fs::write(output, document.to_syx())
+        },
+        _ => fail("one of the files must be .toml, .yaml or .yml, and the other .syx".to_string()),
+    };
+
+    if let Err(e) = result {
+        fail(format!("{}: {}", output, e));
+    }
+}
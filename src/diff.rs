@@ -0,0 +1,303 @@
+//! Field-level comparison of patches.
+//!
+//! Any patch model can be compared with `diff`, which lists the fields
+//! that differ with their paths, like `sources[1].wave`, and their old
+//! and new values. The values are the same as in the text format,
+//! so waves and notes appear by name.
+//!
+//! K4 banks are compared patch by patch with `diff_k4_banks`.
+//! A patch that was moved, copied or renamed is reported as such,
+//! instead of as changes to every field of the patches in the way.
+//!
+
+use std::fmt;
+
+use serde::Serialize;
+pub use serde_json::Value;
+
+use crate::k4::bank::Bank;
+use crate::k4::edit::Group;
+
+/// A field that differs between two patches.
+/// The value is `None` on the side where the field does not exist,
+/// like a source that was added.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Change {
+    pub path: String,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let show = |value: &Option<Value>| match value {
+            Some(value) => value.to_string(),
+            None => "(none)".to_string(),
+        };
+        write!(f, "{}: {} -> {}", self.path, show(&self.old), show(&self.new))
+    }
+}
+
+/// Converts a patch model to a tree of values.
+pub(crate) fn to_value<T: Serialize>(patch: &T) -> Value {
+    serde_json::to_value(patch).expect("patch models have only string keys")
+}
+
+/// Returns the path of `field` in the object at `path`.
+pub(crate) fn field_path(path: &str, field: &str) -> String {
+    if path.is_empty() { field.to_string() } else { format!("{}.{}", path, field) }
+}
+
+/// Returns the fields that differ between `old` and `new`, in the order
+/// of the fields in the patch. Lists are compared item by item.
+pub fn diff<T: Serialize>(old: &T, new: &T) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_values("", Some(&to_value(old)), Some(&to_value(new)), &mut changes);
+    changes
+}
+
+fn diff_values(path: &str, old: Option<&Value>, new: Option<&Value>, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Some(Value::Object(a)), Some(Value::Object(b))) => {
+            for (field, value) in a.iter() {
+                diff_values(&field_path(path, field), Some(value), b.get(field), changes);
+            }
+            for (field, value) in b.iter().filter(|(field, _)| !a.contains_key(*field)) {
+                diff_values(&field_path(path, field), None, Some(value), changes);
+            }
+        },
+        (Some(Value::Array(a)), Some(Value::Array(b))) => {
+            for i in 0..a.len().max(b.len()) {
+                diff_values(&format!("{}[{}]", path, i), a.get(i), b.get(i), changes);
+            }
+        },
+        (a, b) if a != b => changes.push(Change {
+            path: path.to_string(),
+            old: a.cloned(),
+            new: b.cloned(),
+        }),
+        _ => {},
+    }
+}
+
+/// A difference between two K4 banks. The patch indexes are zero-based.
+#[derive(Debug, PartialEq, Clone)]
+pub enum BankChange {
+    /// The patch at `from` in the old bank is now at `to`,
+    /// and something else is at `from`.
+    Moved { group: Group, from: usize, to: usize },
+
+    /// The patch at `from` in the old bank was copied over the patch at `to`.
+    Copied { group: Group, from: usize, to: usize },
+
+    /// The patch at `index` has a new name, and is otherwise the same.
+    Renamed { group: Group, index: usize, old: String, new: String },
+
+    /// Fields of the patch at `index` were changed. The paths are
+    /// relative to the bank, like `singles[3].volume`.
+    Changed { group: Group, index: usize, changes: Vec<Change> },
+
+    /// Fields of the drum patch were changed.
+    Drum(Vec<Change>),
+}
+
+fn group_field(group: Group) -> &'static str {
+    match group {
+        Group::Singles => "singles",
+        Group::Multis => "multis",
+        Group::Effects => "effects",
+    }
+}
+
+impl fmt::Display for BankChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let write_changes = |f: &mut fmt::Formatter, changes: &[Change]| {
+            changes.iter().enumerate().try_for_each(|(i, change)| {
+                if i > 0 { writeln!(f)?; }
+                write!(f, "{}", change)
+            })
+        };
+
+        match self {
+            BankChange::Moved { group, from, to } =>
+                write!(f, "{0}[{1}] moved to {0}[{2}]", group_field(*group), from, to),
+            BankChange::Copied { group, from, to } =>
+                write!(f, "{0}[{1}] copied to {0}[{2}]", group_field(*group), from, to),
+            BankChange::Renamed { group, index, old, new } =>
+                write!(f, "{}[{}] renamed {:?} -> {:?}", group_field(*group), index, old, new),
+            BankChange::Changed { changes, .. } => write_changes(f, changes),
+            BankChange::Drum(changes) => write_changes(f, changes),
+        }
+    }
+}
+
+/// Compares two K4 banks patch by patch.
+///
+/// A patch whose fields other than the name are found at another index
+/// of the old bank is reported as moved there, or as copied if the old
+/// patch is still in its place. A patch that only has a new name is
+/// reported as renamed. Other patches are compared field by field.
+pub fn diff_k4_banks(old: &Bank, new: &Bank) -> Vec<BankChange> {
+    let mut changes = Vec::new();
+    diff_patches(Group::Singles, &old.singles, &new.singles, &mut changes);
+    diff_patches(Group::Multis, &old.multis, &new.multis, &mut changes);
+    let drum = diff(&old.drum, &new.drum);
+    if !drum.is_empty() {
+        changes.push(BankChange::Drum(prefixed("drum", drum)));
+    }
+    diff_patches(Group::Effects, &old.effects, &new.effects, &mut changes);
+    changes
+}
+
+/// Splits the value of a patch into its name and the rest.
+fn name_and_content<T: Serialize>(patch: &T) -> (String, Value) {
+    let mut value = to_value(patch);
+    let name = value.as_object_mut()
+        .and_then(|fields| fields.shift_remove("name"))
+        .and_then(|name| name.as_str().map(String::from))
+        .unwrap_or_default();
+    (name, value)
+}
+
+fn prefixed(prefix: &str, changes: Vec<Change>) -> Vec<Change> {
+    changes.into_iter()
+        .map(|change| Change { path: field_path(prefix, &change.path), ..change })
+        .collect()
+}
+
+fn diff_patches<T: Serialize>(group: Group, old: &[T], new: &[T], changes: &mut Vec<BankChange>) {
+    let old: Vec<(String, Value)> = old.iter().map(name_and_content).collect();
+    let new: Vec<(String, Value)> = new.iter().map(name_and_content).collect();
+    let stayed = |i: usize| old.get(i).zip(new.get(i)).is_some_and(|(a, b)| a.1 == b.1);
+
+    for (index, (name, content)) in new.iter().enumerate() {
+        let from = if stayed(index) {
+            Some(index)
+        } else {
+            // Prefer a patch that is no longer in its place, so that it shows as moved.
+            let candidates: Vec<usize> = (0..old.len())
+                .filter(|&i| i != index && old[i].1 == *content)
+                .collect();
+            candidates.iter().copied().find(|&i| !stayed(i)).or(candidates.first().copied())
+        };
+
+        match from {
+            Some(from) => {
+                if from != index {
+                    changes.push(if stayed(from) {
+                        BankChange::Copied { group, from, to: index }
+                    } else {
+                        BankChange::Moved { group, from, to: index }
+                    });
+                }
+                if old[from].0 != *name {
+                    changes.push(BankChange::Renamed { group, index, old: old[from].0.clone(), new: name.clone() });
+                }
+            },
+            None => {
+                let mut fields = Vec::new();
+                let path = format!("{}[{}]", group_field(group), index);
+                diff_values(&path, old.get(index).map(|(_, content)| content), Some(content), &mut fields);
+                if old.get(index).is_some_and(|(old_name, _)| old_name != name) {
+                    fields.insert(0, Change {
+                        path: field_path(&path, "name"),
+                        old: Some(Value::String(old[index].0.clone())),
+                        new: Some(Value::String(name.clone())),
+                    });
+                }
+                changes.push(BankChange::Changed { group, index, changes: fields });
+            },
+        }
+    }
+}
+
+/// Renders changes as text, one per line.
+pub fn render<T: fmt::Display>(changes: &[T]) -> String {
+    changes.iter().map(|change| format!("{}\n", change)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{*};
+    use crate::SystemExclusiveData;
+    use crate::k4;
+    use crate::k5000;
+
+    static K4_DATA: &[u8] = include_bytes!("k4/A401.SYX");
+    static K5000_DATA: &[u8] = include_bytes!("k5000/WizooIni.syx");
+
+    fn bank() -> Bank {
+        Bank::from_bytes(&K4_DATA[8..]).unwrap()
+    }
+
+    #[test]
+    fn test_k4_single_diff() {
+        let old = bank().singles[0].clone();
+        let mut new = old.clone();
+        new.volume = k4::Level::try_new(80).unwrap();
+        new.sources[1].wave = k4::wave::Wave::from_name("SAW 1").unwrap();
+
+        let changes = diff(&old, &new);
+        assert_eq!(render(&changes), "volume: 100 -> 80\nsources[1].wave: \"TRIANGLE\" -> \"SAW 1\"\n");
+        assert!(diff(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_k5000_single_diff() {
+        let old = k5000::single::SinglePatch::from_bytes(&K5000_DATA[9..]).unwrap();
+        let mut new = k5000::single::SinglePatch::from_bytes(&K5000_DATA[9..]).unwrap();
+        new.sources[1].control.zone.low = k5000::source::Key::from_name("C2").unwrap();
+        new.sources.pop();
+
+        let changes = diff(&old, &new);
+        assert_eq!(changes[0].path, "sources[1]");
+        assert_eq!(changes[0].new, None);
+
+        new.sources.push(k5000::single::SinglePatch::from_bytes(&K5000_DATA[9..]).unwrap().sources.remove(1));
+        new.sources[1].control.zone.low = k5000::source::Key::from_name("C2").unwrap();
+        let changes = diff(&old, &new);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, "sources[1].control.zone.low");
+        assert_eq!(changes[0].new, Some(Value::String("C2".to_string())));
+    }
+
+    #[test]
+    fn test_bank_move_and_rename() {
+        let old = bank();
+        let mut new = bank();
+        new.singles.swap(2, 5);
+        new.singles[7].name = "Renamed".to_string();
+        new.singles[9].volume = k4::Level::try_new(1).unwrap();
+
+        let changes = diff_k4_banks(&old, &new);
+        assert_eq!(changes.len(), 4, "{}", render(&changes));
+        assert_eq!(changes[0], BankChange::Moved { group: Group::Singles, from: 5, to: 2 });
+        assert_eq!(changes[1], BankChange::Moved { group: Group::Singles, from: 2, to: 5 });
+        assert!(matches!(&changes[2], BankChange::Renamed { index: 7, new, .. } if new == "Renamed"));
+        match &changes[3] {
+            BankChange::Changed { index: 9, changes, .. } => {
+                assert_eq!(changes.len(), 1);
+                assert_eq!(changes[0].path, "singles[9].volume");
+            },
+            other => panic!("expected changed fields, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_bank_copy() {
+        let old = bank();
+        let mut new = bank();
+        new.effects[3] = new.effects[0].clone();
+        new.drum.common.volume = k4::Level::try_new(50).unwrap();
+
+        let changes = diff_k4_banks(&old, &new);
+        let text = render(&changes);
+        assert!(text.contains("effects[0] copied to effects[3]\n"), "{}", text);
+        assert!(text.contains("drum.common.volume: 100 -> 50\n"), "{}", text);
+    }
+
+    #[test]
+    fn test_identical_banks() {
+        assert!(diff_k4_banks(&bank(), &bank()).is_empty());
+    }
+}
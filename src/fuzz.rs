@@ -0,0 +1,221 @@
+//! Randomized tests that feed garbage, truncated and mutated
+//! System Exclusive data to the parsers. Parsing must never panic;
+//! it should only ever return a `ParseError`.
+
+use std::fmt;
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use crate::{SystemExclusiveData, ParseError};
+use crate::k4;
+use crate::k5000;
+use crate::loader;
+
+static K4_DATA: &[u8] = include_bytes!("k4/A401.SYX");
+static K5000_DATA: &[u8] = include_bytes!("k5000/WizooIni.syx");
+
+const ROUNDS: usize = 200;
+
+/// Parses `data` and, if that succeeds, also generates bytes and
+/// text from the result, since those use the parsed values as indexes.
+fn exercise<T: SystemExclusiveData + fmt::Display>(data: &[u8]) -> Result<T, ParseError> {
+    let result = T::from_bytes(data);
+    if let Ok(value) = &result {
+        let _ = value.to_bytes();
+        let _ = format!("{}", value);
+    }
+    result
+}
+
+fn exercise_no_display<T: SystemExclusiveData>(data: &[u8]) -> Result<T, ParseError> {
+    let result = T::from_bytes(data);
+    if let Ok(value) = &result {
+        let _ = value.to_bytes();
+    }
+    result
+}
+
+fn exercise_k4(data: &[u8]) {
+    let _ = exercise::<k4::bank::Bank>(data);
+    let _ = exercise::<k4::single::SinglePatch>(data);
+    let _ = exercise::<k4::multi::MultiPatch>(data);
+    let _ = exercise::<k4::drum::DrumPatch>(data);
+    let _ = exercise::<k4::effect::EffectPatch>(data);
+    let _ = exercise::<k4::sysex::Header>(data);
+    let _ = exercise::<k4::sysex::Request>(data);
+    let _ = exercise::<k4::parameter::ParameterChange>(data);
+    let _ = exercise::<k4::sysex::Response>(data);
+    let _ = k4::sysex::Dump::identify(data.to_vec()).map(|dump| dump.data());
+    let _ = exercise::<k4::sysex::PatchDump>(data);
+    let _ = loader::load(data);
+}
+
+fn exercise_k5000(data: &[u8]) {
+    let _ = exercise::<k5000::single::SinglePatch>(data);
+    let _ = exercise::<k5000::single::Common>(data);
+    let _ = exercise::<k5000::source::Source>(data);
+    let _ = exercise_no_display::<k5000::addkit::AdditiveKit>(data);
+    let _ = exercise::<k5000::bank::Bank>(data);
+    let _ = exercise::<k5000::bank::PCMBank>(data);
+    let _ = k5000::single::SinglePatch::pcm_from_bytes(data);
+    let _ = k5000::single::SinglePatch::measure(data);
+    let _ = exercise::<k5000::multi::MultiPatch>(data);
+    let _ = k5000::multi::MultiPatch::combi_from_bytes(data);
+    let _ = exercise::<k5000::drum::DrumKit>(data);
+    let _ = exercise::<k5000::drum::DrumInstrument>(data);
+    let _ = exercise::<k5000::drum::InstrumentBank>(data);
+    let _ = exercise::<k5000::multi::MultiBank>(data);
+    let _ = exercise::<k5000::sysex::Header>(data);
+    let _ = exercise::<k5000::sysex::ToneMap>(data);
+    let _ = exercise::<k5000::wave::Wave>(data);
+    let _ = k5000::sysex::Header::identify_vec(data);
+    let _ = loader::load(data);
+    if let Ok(message) = exercise_no_display::<k5000::sysex::Message>(data) {
+        let _ = k5000::sysex::DumpRequest::from_message(&message);
+        let _ = k5000::sysex::Response::from_message(&message);
+        if let Ok(send) = k5000::parameter::ParameterSend::from_message(&message) {
+            let _ = send.to_string();
+        }
+        if let Ok(control) = k5000::parameter::TrackControl::from_message(&message) {
+            let _ = control.to_string();
+        }
+    }
+}
+
+/// Returns `data` with a few randomly chosen bytes replaced by random values.
+fn mutate(rng: &mut StdRng, data: &[u8]) -> Vec<u8> {
+    let mut result = data.to_vec();
+    let count = rng.gen_range(1..=8);
+    for _ in 0..count {
+        let index = rng.gen_range(0..result.len());
+        result[index] = rng.gen();
+    }
+    result
+}
+
+/// Returns a vector of random bytes with a random length up to `max_length`.
+fn garbage(rng: &mut StdRng, max_length: usize) -> Vec<u8> {
+    let length = rng.gen_range(0..=max_length);
+    (0..length).map(|_| rng.gen()).collect()
+}
+
+#[test]
+fn test_k4_garbage() {
+    let mut rng = StdRng::seed_from_u64(4);
+    for _ in 0..ROUNDS {
+        exercise_k4(&garbage(&mut rng, K4_DATA.len()));
+    }
+}
+
+#[test]
+fn test_k4_truncated() {
+    let payload = &K4_DATA[8..];
+    let mut rng = StdRng::seed_from_u64(40);
+    for _ in 0..ROUNDS {
+        let length = rng.gen_range(0..payload.len());
+        exercise_k4(&payload[..length]);
+    }
+}
+
+#[test]
+fn test_k4_mutated() {
+    let mut rng = StdRng::seed_from_u64(400);
+    for _ in 0..ROUNDS {
+        let data = mutate(&mut rng, K4_DATA);
+        exercise_k4(&data);
+        exercise_k4(&data[2..]);
+        exercise_k4(&data[8..]);
+    }
+}
+
+#[test]
+fn test_k5000_garbage() {
+    let mut rng = StdRng::seed_from_u64(5000);
+    for _ in 0..ROUNDS {
+        exercise_k5000(&garbage(&mut rng, K5000_DATA.len()));
+    }
+}
+
+#[test]
+fn test_k5000_truncated() {
+    let payload = &K5000_DATA[9..];
+    let mut rng = StdRng::seed_from_u64(50000);
+    for _ in 0..ROUNDS {
+        let length = rng.gen_range(0..payload.len());
+        exercise_k5000(&payload[..length]);
+        exercise_k5000(&K5000_DATA[2..2 + length]);
+    }
+}
+
+#[test]
+fn test_k5000_mutated() {
+    let mut rng = StdRng::seed_from_u64(500000);
+    for _ in 0..ROUNDS {
+        let data = mutate(&mut rng, K5000_DATA);
+        exercise_k5000(&data[2..]);
+        exercise_k5000(&data[9..]);
+        exercise_k5000(&data);
+    }
+}
+
+#[test]
+fn test_error_offset_is_absolute() {
+    // Put an out-of-range value into the second source of the second single patch.
+    // The K4 single source data is interleaved, so the source 2 delay is at 31.
+    let mut data = K4_DATA[8..].to_vec();
+    let offset = k4::single::SinglePatch::data_size() + 31;
+    data[offset] = 0x7f;
+    match k4::bank::Bank::from_bytes(&data) {
+        Err(ParseError::InvalidField(error)) => {
+            assert_eq!(error.path, "singles[1].sources[1].delay");
+            assert_eq!(error.offset, offset);
+            assert_eq!(error.value, 0x7f);
+        },
+        _ => panic!("expected an invalid field error"),
+    }
+}
+
+#[test]
+fn test_error_path_in_k5000_single() {
+    // The second source starts after the checksum, the common data
+    // and the first source. Its bender pitch is at offset 5.
+    let mut data = K5000_DATA[9..].to_vec();
+    let offset = 1 + k5000::single::Common::data_size() + k5000::source::Source::data_size() + 5;
+    data[offset] = 0x7f;
+    match k5000::single::SinglePatch::from_bytes(&data) {
+        Err(ParseError::InvalidField(error)) => {
+            assert_eq!(error.path, "sources[1].control.bender_pitch");
+            assert_eq!(error.offset, offset);
+            assert_eq!(error.value, 0x7f);
+        },
+        _ => panic!("expected an invalid field error"),
+    }
+}
+
+#[test]
+fn test_bad_source_count() {
+    // The source count follows the checksum, effects, GEQ, drum mark, name,
+    // volume, polyphony and the unused byte.
+    let mut data = K5000_DATA[9..].to_vec();
+    let offset = 1 + 31 + 7 + 1 + 8 + 3;
+    for count in [0, 7, 0x7f] {
+        data[offset] = count;
+        match k5000::single::SinglePatch::from_bytes(&data) {
+            Err(ParseError::InvalidField(error)) => {
+                assert_eq!(error.path, "common.source_count");
+                assert_eq!(error.offset, offset);
+                assert_eq!(error.value, count);
+            },
+            _ => panic!("expected an invalid field error"),
+        }
+        assert!(k5000::single::SinglePatch::measure(&data).is_err());
+    }
+}
+
+#[test]
+fn test_truncated_data_is_invalid_length() {
+    let payload = &K5000_DATA[9..];
+    let result = k5000::single::SinglePatch::from_bytes(&payload[..100]);
+    assert!(matches!(result, Err(ParseError::InvalidLength(_, _))));
+}
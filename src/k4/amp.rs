@@ -3,10 +3,11 @@
 
 use std::convert::TryInto;
 use std::fmt;
-use crate::{SystemExclusiveData, ParseError};
+use crate::{SystemExclusiveData, ParseError, check_length, invalid_field};
 use crate::k4::{EnvelopeTime, EnvelopeLevel, ModulationDepth, Level};
 
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Envelope {
     pub attack: EnvelopeTime,
     pub decay: EnvelopeTime,
@@ -43,11 +44,12 @@ impl fmt::Display for Envelope {
 
 impl SystemExclusiveData for Envelope {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(Envelope {
-            attack: EnvelopeTime::try_new(data[0] & 0x7f).unwrap(),
-            decay: EnvelopeTime::try_new(data[1] & 0x7f).unwrap(),
-            sustain: EnvelopeLevel::try_new(data[2] & 0x7f).unwrap(),
-            release: EnvelopeTime::try_new(data[3] & 0x7f).unwrap(),
+            attack: EnvelopeTime::try_new(data[0] & 0x7f).map_err(invalid_field("attack", data, 0))?,
+            decay: EnvelopeTime::try_new(data[1] & 0x7f).map_err(invalid_field("decay", data, 1))?,
+            sustain: EnvelopeLevel::try_new(data[2] & 0x7f).map_err(invalid_field("sustain", data, 2))?,
+            release: EnvelopeTime::try_new(data[3] & 0x7f).map_err(invalid_field("release", data, 3))?,
         })
     }
 
@@ -64,6 +66,7 @@ impl SystemExclusiveData for Envelope {
 }
 
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LevelModulation {
     pub velocity_depth: ModulationDepth,
     pub pressure_depth: ModulationDepth,
@@ -100,10 +103,11 @@ impl fmt::Display for LevelModulation {
 
 impl SystemExclusiveData for LevelModulation {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(LevelModulation {
-            velocity_depth: ModulationDepth::try_new((data[0] as i8) - 50).unwrap(),
-            pressure_depth: ModulationDepth::try_new((data[1] as i8) - 50).unwrap(),
-            key_scaling_depth: ModulationDepth::try_new((data[2] as i8) - 50).unwrap(),
+            velocity_depth: ModulationDepth::try_new(((data[0] & 0x7f) as i8) - 50).map_err(invalid_field("velocity_depth", data, 0))?,
+            pressure_depth: ModulationDepth::try_new(((data[1] & 0x7f) as i8) - 50).map_err(invalid_field("pressure_depth", data, 1))?,
+            key_scaling_depth: ModulationDepth::try_new(((data[2] & 0x7f) as i8) - 50).map_err(invalid_field("key_scaling_depth", data, 2))?,
         })
     }
 
@@ -119,6 +123,7 @@ impl SystemExclusiveData for LevelModulation {
 }
 
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TimeModulation {
     pub attack_velocity: ModulationDepth,
     pub release_velocity: ModulationDepth,
@@ -155,10 +160,11 @@ impl fmt::Display for TimeModulation {
 
 impl SystemExclusiveData for TimeModulation {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(TimeModulation {
-            attack_velocity: ModulationDepth::try_new((data[0] as i8) - 50).unwrap(),
-            release_velocity: ModulationDepth::try_new((data[1] as i8) - 50).unwrap(),
-            key_scaling: ModulationDepth::try_new((data[2] as i8) - 50).unwrap(),
+            attack_velocity: ModulationDepth::try_new(((data[0] & 0x7f) as i8) - 50).map_err(invalid_field("attack_velocity", data, 0))?,
+            release_velocity: ModulationDepth::try_new(((data[1] & 0x7f) as i8) - 50).map_err(invalid_field("release_velocity", data, 1))?,
+            key_scaling: ModulationDepth::try_new(((data[2] & 0x7f) as i8) - 50).map_err(invalid_field("key_scaling", data, 2))?,
         })
     }
 
@@ -174,6 +180,7 @@ impl SystemExclusiveData for TimeModulation {
 }
 
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Amplifier {
     pub level: Level,
     pub envelope: Envelope,
@@ -210,36 +217,41 @@ impl fmt::Display for Amplifier {
 
 impl SystemExclusiveData for Amplifier {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
+
         let mut offset: usize = 0;
         let mut start: usize;
         let mut end: usize;
 
         let b = data[offset];
+        let level = Level::try_new(b & 0x7f).map_err(invalid_field("level", data, offset))?;
         offset += 1;
-        let level = Level::try_new(b & 0x7f).unwrap();
 
         start = offset;
         end = start + 4;
         let envelope_bytes = &data[start..end];
-        let envelope = Envelope::from_bytes(&envelope_bytes);
+        let envelope = Envelope::from_bytes(envelope_bytes)
+            .map_err(|e| e.within("envelope", start))?;
         offset += 4;
 
         start = offset;
         end = start + 3;
         let level_mod_bytes = &data[start..end];
-        let level_modulation = LevelModulation::from_bytes(&level_mod_bytes);
+        let level_modulation = LevelModulation::from_bytes(level_mod_bytes)
+            .map_err(|e| e.within("level_modulation", start))?;
         offset += 3;
 
         start = offset;
         end = start + 3;
         let time_mod_bytes = &data[start..end];
-        let time_modulation = TimeModulation::from_bytes(&time_mod_bytes);
+        let time_modulation = TimeModulation::from_bytes(time_mod_bytes)
+            .map_err(|e| e.within("time_modulation", start))?;
 
         Ok(Amplifier {
             level,
-            envelope: envelope?,
-            level_modulation: level_modulation?,
-            time_modulation: time_modulation?,
+            envelope,
+            level_modulation,
+            time_modulation,
         })
     }
 
@@ -6,21 +6,32 @@ use log::debug;
 
 use crate::{
     SystemExclusiveData,
-    ParseError
+    ParseError,
+    MIDIChannel,
+    check_length
 };
 use crate::k4::single::SinglePatch;
 use crate::k4::multi::MultiPatch;
 use crate::k4::effect::EffectPatch;
 use crate::k4::drum::DrumPatch;
+use crate::k4::sysex::{DumpData, PatchDump, Locality};
 
 pub const SINGLE_PATCH_COUNT: usize = 64;  // number of single patches in a bank
 pub const MULTI_PATCH_COUNT: usize = 64;   // number of multi patches in a bank
 pub const EFFECT_PATCH_COUNT: usize = 32;  // number of effect patches in a bank
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bank {
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "crate::serialize::fixed_count::<_, _, SINGLE_PATCH_COUNT>"))]
     pub singles: Vec<SinglePatch>,
+
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "crate::serialize::fixed_count::<_, _, MULTI_PATCH_COUNT>"))]
     pub multis: Vec<MultiPatch>,
+
     pub drum: DrumPatch,
+
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "crate::serialize::fixed_count::<_, _, EFFECT_PATCH_COUNT>"))]
     pub effects: Vec<EffectPatch>,
 }
 
@@ -33,6 +44,82 @@ impl Bank {
             effects: vec![Default::default(); EFFECT_PATCH_COUNT],
         }
     }
+
+    /// Makes a bank from a set of dumps, like separate block dumps
+    /// of singles, multis and effects. Anything that is not in the dumps
+    /// is left at the defaults. Fails on the first dump that `merge` rejects.
+    pub fn from_dumps<I: IntoIterator<Item = DumpData>>(dumps: I) -> Result<Self, ParseError> {
+        let mut bank = Bank::new();
+        for data in dumps {
+            bank.merge(data)?;
+        }
+        Ok(bank)
+    }
+
+    /// Replaces the patches in this bank with the patches in the dump `data`.
+    /// A block dump replaces only the patches of its kind, and a one patch
+    /// dump only the patch with its number. A block dump without exactly
+    /// the patches of a full block, or a patch number that is not
+    /// in this bank, is an error, and leaves the bank unchanged.
+    pub fn merge(&mut self, data: DumpData) -> Result<(), ParseError> {
+        match data {
+            DumpData::OneSingle(number, patch) =>
+                replace(&mut self.singles, number.into_inner() as usize, patch, "singles"),
+            DumpData::OneMulti(number, patch) =>
+                replace(&mut self.multis, number.into_inner() as usize, patch, "multis"),
+            DumpData::Drum(patch) => {
+                self.drum = patch;
+                Ok(())
+            },
+            DumpData::OneEffect(number, patch) =>
+                replace(&mut self.effects, number.into_inner() as usize - 1, patch, "effects"),
+            DumpData::BlockSingle(patches) => replace_all(&mut self.singles, patches, SINGLE_PATCH_COUNT),
+            DumpData::BlockMulti(patches) => replace_all(&mut self.multis, patches, MULTI_PATCH_COUNT),
+            DumpData::BlockEffect(patches) => replace_all(&mut self.effects, patches, EFFECT_PATCH_COUNT),
+            DumpData::All(bank) => {
+                *self = bank;
+                Ok(())
+            },
+        }
+    }
+
+    /// Returns the block dumps of the singles, the multis and the effects
+    /// in this bank. The drum patch has no block dump; send it with
+    /// a one patch dump made with `drum_dump`.
+    pub fn block_dumps(&self, channel: MIDIChannel, locality: Locality) -> [PatchDump; 3] {
+        [
+            PatchDump { channel, locality, data: DumpData::BlockSingle(self.singles.clone()) },
+            PatchDump { channel, locality, data: DumpData::BlockMulti(self.multis.clone()) },
+            PatchDump { channel, locality, data: DumpData::BlockEffect(self.effects.clone()) },
+        ]
+    }
+
+    /// Returns a one patch dump of the drum patch in this bank.
+    pub fn drum_dump(&self, channel: MIDIChannel, locality: Locality) -> PatchDump {
+        PatchDump { channel, locality, data: DumpData::Drum(self.drum.clone()) }
+    }
+}
+
+/// Replaces the patch at `index` in `patches`, the field `field` of the bank.
+fn replace<T>(patches: &mut [T], index: usize, patch: T, field: &str) -> Result<(), ParseError> {
+    let count = patches.len();
+    match patches.get_mut(index) {
+        Some(slot) => {
+            *slot = patch;
+            Ok(())
+        },
+        None => Err(ParseError::InvalidData(0,
+            format!("no patch {}[{}] in a bank of {} patches", field, index, count))),
+    }
+}
+
+/// Replaces `patches` with the `count` patches of a block dump.
+fn replace_all<T>(patches: &mut Vec<T>, replacements: Vec<T>, count: usize) -> Result<(), ParseError> {
+    if replacements.len() != count {
+        return Err(ParseError::InvalidLength(replacements.len(), count));
+    }
+    *patches = replacements;
+    Ok(())
 }
 
 impl Default for Bank {
@@ -51,65 +138,53 @@ impl fmt::Display for Bank {
 
 impl SystemExclusiveData for Bank {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
+
         let mut offset = 0;
 
         debug!("Parsing single patches, offset = {}", offset);
 
         let mut singles = Vec::<SinglePatch>::new();
         for i in 0..SINGLE_PATCH_COUNT {
-            let single = SinglePatch::from_bytes(&data[offset..]);
-            debug!("{}: {}", i, single.as_ref().unwrap().name);
+            let single = SinglePatch::from_bytes(&data[offset..])
+                .map_err(|e| e.within(format!("singles[{}]", i), offset))?;
+            debug!("{}: {}", i, single.name);
             offset += SinglePatch::data_size();
-            singles.push(single?);
+            singles.push(single);
         }
 
-        let mut total = 0;
-        let mut block_size = SinglePatch::data_size() * SINGLE_PATCH_COUNT;
-        total += block_size;
-
-        assert_eq!(offset, total);
-
         debug!("Parsing multi patches, offset = {}", offset);
 
         let mut multis = Vec::<MultiPatch>::new();
         for i in 0..MULTI_PATCH_COUNT {
-            let multi = MultiPatch::from_bytes(&data[offset..]);
-            debug!("{}: {}", i, multi.as_ref().unwrap().name);
+            let multi = MultiPatch::from_bytes(&data[offset..])
+                .map_err(|e| e.within(format!("multis[{}]", i), offset))?;
+            debug!("{}: {}", i, multi.name);
             offset += MultiPatch::data_size();
-            multis.push(multi?);
+            multis.push(multi);
         }
 
-        block_size = MultiPatch::data_size() * MULTI_PATCH_COUNT;
-        total += block_size;
-        assert_eq!(offset, total);
-
         debug!("Parsing drum patches, offset = {}", offset);
 
-        let drum = DrumPatch::from_bytes(&data[offset..]);
+        let drum = DrumPatch::from_bytes(&data[offset..])
+            .map_err(|e| e.within("drum", offset))?;
         offset += DrumPatch::data_size();
 
-        block_size = DrumPatch::data_size();
-        total += block_size;
-        assert_eq!(offset, total);
-
         debug!("Parsing effect patches, offset = {}", offset);
 
         let mut effects = Vec::<EffectPatch>::new();
         for i in 0..EFFECT_PATCH_COUNT {
-            let effect = EffectPatch::from_bytes(&data[offset..]);
-            debug!("{}: {}", i, effect.as_ref().unwrap().effect);
+            let effect = EffectPatch::from_bytes(&data[offset..])
+                .map_err(|e| e.within(format!("effects[{}]", i), offset))?;
+            debug!("{}: {}", i, effect.effect);
             offset += EffectPatch::data_size();
-            effects.push(effect?);
+            effects.push(effect);
         }
 
-        block_size = EffectPatch::data_size() * EFFECT_PATCH_COUNT;
-        total += block_size;
-        assert_eq!(offset, total);
-
         Ok(Bank {
             singles,
             multis,
-            drum: drum.unwrap(),
+            drum,
             effects,
         })
     }
@@ -117,22 +192,22 @@ impl SystemExclusiveData for Bank {
     fn to_bytes(&self) -> Vec<u8> {
         let mut buf: Vec<u8> = Vec::new();
 
-        for i in 0..SINGLE_PATCH_COUNT {
-            buf.extend(self.singles[i].to_bytes());
+        for single in self.singles.iter() {
+            buf.extend(single.to_bytes());
         }
 
-        for i in 0..MULTI_PATCH_COUNT {
-            buf.extend(self.multis[i].to_bytes());
+        for multi in self.multis.iter() {
+            buf.extend(multi.to_bytes());
         }
 
         buf.extend(self.drum.to_bytes());
 
-        for i in 0..EFFECT_PATCH_COUNT {
-            buf.extend(self.effects[i].to_bytes());
+        for effect in self.effects.iter() {
+            buf.extend(effect.to_bytes());
         }
 
         // full bank minus SysEx header and terminator
-        assert_eq!(buf.len(), 15123 - 8 - 1);
+        debug_assert_eq!(buf.len(), Self::data_size());
 
         buf
     }
@@ -149,8 +224,9 @@ impl SystemExclusiveData for Bank {
 mod tests {
     use super::{*};
     use crate::k4::sysex::Header;
+    use crate::k4::PatchNumber;
 
-    static DATA: &'static [u8] = include_bytes!("A401.SYX");
+    static DATA: &[u8] = include_bytes!("A401.SYX");
 
     #[test]
     fn test_bank_from_bytes() {
@@ -160,4 +236,63 @@ mod tests {
         assert_eq!(bank.as_ref().unwrap().singles.len(), SINGLE_PATCH_COUNT);
         assert_eq!(bank.as_ref().unwrap().effects.len(), EFFECT_PATCH_COUNT);
     }
+
+    #[test]
+    fn test_split_and_merge_blocks() {
+        let start = 2 + Header::data_size();
+        let bank = Bank::from_bytes(&DATA[start..]).unwrap();
+        let channel = MIDIChannel::try_new(1).unwrap();
+
+        let messages: Vec<Vec<u8>> = bank.block_dumps(channel, Locality::Internal)
+            .iter()
+            .map(|dump| dump.to_bytes())
+            .collect();
+        assert_eq!(messages[0][..8], [0xF0, 0x40, 0x00, 0x21, 0x00, 0x04, 0x00, 0x00]);
+        assert_eq!(messages[1][..8], [0xF0, 0x40, 0x00, 0x21, 0x00, 0x04, 0x00, 0x40]);
+        assert_eq!(messages[2][..8], [0xF0, 0x40, 0x00, 0x21, 0x00, 0x04, 0x01, 0x00]);
+
+        let merged = Bank::from_dumps(messages.iter().map(|m| PatchDump::from_bytes(m).unwrap().data)).unwrap();
+        for i in 0..SINGLE_PATCH_COUNT {
+            assert_eq!(merged.singles[i].name, bank.singles[i].name);
+        }
+        for i in 0..MULTI_PATCH_COUNT {
+            assert_eq!(merged.multis[i].name, bank.multis[i].name);
+        }
+        for i in 0..EFFECT_PATCH_COUNT {
+            assert_eq!(merged.effects[i].effect, bank.effects[i].effect);
+        }
+    }
+
+    #[test]
+    fn test_merge_replaces_only_its_kind() {
+        let start = 2 + Header::data_size();
+        let source = Bank::from_bytes(&DATA[start..]).unwrap();
+        let mut bank = Bank::default();
+
+        bank.merge(DumpData::BlockSingle(source.singles.clone())).unwrap();
+        assert_eq!(bank.singles[0].name, source.singles[0].name);
+        assert_eq!(bank.multis[0].name, MultiPatch::default().name);
+
+        bank.merge(DumpData::OneMulti(PatchNumber::try_new(3).unwrap(), source.multis[3].clone())).unwrap();
+        assert_eq!(bank.multis[3].name, source.multis[3].name);
+        assert_eq!(bank.multis[2].name, MultiPatch::default().name);
+    }
+
+    #[test]
+    fn test_merge_rejects_partial_blocks() {
+        let start = 2 + Header::data_size();
+        let source = Bank::from_bytes(&DATA[start..]).unwrap();
+        let mut bank = Bank::default();
+
+        let short = source.singles[..10].to_vec();
+        assert_eq!(bank.merge(DumpData::BlockSingle(short)), Err(ParseError::InvalidLength(10, SINGLE_PATCH_COUNT)));
+        assert_eq!(bank.singles[0].name, SinglePatch::default().name);
+
+        let result = Bank::from_dumps([DumpData::BlockEffect(source.effects[..31].to_vec())]);
+        assert!(matches!(result, Err(ParseError::InvalidLength(31, EFFECT_PATCH_COUNT))));
+
+        bank.singles.truncate(10);
+        let number = PatchNumber::try_new(63).unwrap();
+        assert!(matches!(bank.merge(DumpData::OneSingle(number, source.singles[63].clone())), Err(ParseError::InvalidData(0, _))));
+    }
 }
@@ -9,7 +9,9 @@ use bit::BitIndex;
 use crate::{
     SystemExclusiveData,
     ParseError,
-    Checksum
+    Checksum,
+    check_length,
+    invalid_field
 };
 use crate::k4::{
     DRUM_NOTE_COUNT,
@@ -21,8 +23,11 @@ use crate::k4::{
 use crate::k4::wave::Wave;
 use crate::k4::effect::Submix;
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DrumPatch {
     pub common: Common,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::big_array"))]
     pub notes: [Note; DRUM_NOTE_COUNT],
 }
 
@@ -66,20 +71,23 @@ impl fmt::Display for DrumPatch {
 
 impl SystemExclusiveData for DrumPatch {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
-        let common = Common::from_bytes(&data[0..]);
+        check_length(data, Self::data_size())?;
+
+        let common = Common::from_bytes(&data[0..]).map_err(|e| e.in_field("common"))?;
         let mut offset = Common::data_size();
         let mut notes = [Default::default(); DRUM_NOTE_COUNT];
 
-        for i in 0..DRUM_NOTE_COUNT {
+        for (i, note) in notes.iter_mut().enumerate() {
             debug!("Parsing drum note {}, offset = {}", i, offset);
 
-            let note = Note::from_bytes(&data[offset..])?;
-            notes[i] = note;
+            let start = offset;
+            *note = Note::from_bytes(&data[offset..])
+                .map_err(|e| e.within(format!("notes[{}]", i), start))?;
             offset += Note::data_size();
         }
 
         Ok(DrumPatch {
-            common: common?,
+            common,
             notes,
         })
     }
@@ -99,10 +107,17 @@ impl SystemExclusiveData for DrumPatch {
 }
 
 /// Drum common data.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Common {
     pub channel: Channel,  // MIDI channel, here 1...16, stored in SysEx as 0...15
     pub volume: Level, // 0~100
     pub velocity_depth: ModulationDepth,  // 0~100
+
+    /// The seven dummy bytes after the settings. They are not used,
+    /// but are kept so that the patch is written back as it was read.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "crate::serialize::is_default"))]
+    pub dummy: [u8; 7],
 }
 
 impl Default for Common {
@@ -111,6 +126,7 @@ impl Default for Common {
             channel: Channel::try_new(10).unwrap(),
             volume: Level::try_new(100).unwrap(),
             velocity_depth: ModulationDepth::try_new(0).unwrap(),
+            dummy: [0; 7],
         }
     }
 }
@@ -135,12 +151,13 @@ impl fmt::Display for Common {
 
 impl Common {
     fn collect_data(&self) -> Vec<u8> {
-        vec![
+        let mut buf = vec![
             self.channel.into_inner() - 1,
             self.volume.into_inner(),
             (self.velocity_depth.into_inner() + 50) as u8,
-            0, 0, 0, 0, 0, 0, 0,  // seven dummy bytes by design
-        ]
+        ];
+        buf.extend(self.dummy);
+        buf
     }
 }
 
@@ -155,10 +172,12 @@ impl Checksum for Common {
 
 impl SystemExclusiveData for Common {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(Common {
-            channel: Channel::try_new(data[0] + 1).unwrap(),
-            volume: Level::try_new(data[1]).unwrap(),
-            velocity_depth: ModulationDepth::try_new(data[2] as i8 - 50).unwrap(),
+            channel: Channel::try_new((data[0] & 0x7f) + 1).map_err(invalid_field("channel", data, 0))?,
+            volume: Level::try_new(data[1]).map_err(invalid_field("volume", data, 1))?,
+            velocity_depth: ModulationDepth::try_new((data[2] & 0x7f) as i8 - 50).map_err(invalid_field("velocity_depth", data, 2))?,
+            dummy: data[3..10].try_into().unwrap(),
         })
     }
 
@@ -177,6 +196,7 @@ impl SystemExclusiveData for Common {
 
 /// Drum note.
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Note {
     pub submix: Submix,
     pub source1: Source,
@@ -234,6 +254,8 @@ impl Checksum for Note {
 
 impl SystemExclusiveData for Note {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
+
         // The bytes have S1 and S2 interleaved, so group them:
         let mut source1_bytes = Vec::<u8>::new();
         let mut source2_bytes = Vec::<u8>::new();
@@ -246,15 +268,15 @@ impl SystemExclusiveData for Note {
         }
 
         // Get the submix from S1 byte 0:
-        let submix = Submix::try_from(source1_bytes[0] >> 4).unwrap();
+        let submix = Submix::try_from(source1_bytes[0] >> 4).map_err(invalid_field("submix", data, 0))?;
 
         // Then mask it away:
         source1_bytes[0] &= 0b00001111;
 
         Ok(Note {
             submix,
-            source1: Source::from_bytes(&source1_bytes)?,
-            source2: Source::from_bytes(&source2_bytes)?,
+            source1: Source::from_bytes(&source1_bytes).map_err(|e| e.in_field("source1").map_offset(|o| o * 2))?,
+            source2: Source::from_bytes(&source2_bytes).map_err(|e| e.in_field("source2").map_offset(|o| o * 2 + 1))?,
         })
     }
 
@@ -276,6 +298,7 @@ impl SystemExclusiveData for Note {
 
 /// Drum source.
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Source {
     pub wave: Wave, // 1~256
     pub decay: Decay, // 1~100
@@ -309,11 +332,12 @@ impl fmt::Display for Source {
 
 impl SystemExclusiveData for Source {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(Source {
-            wave: Wave::from_bytes(&[data[0], data[1]])?,
-            decay: Decay::try_new(data[2]).unwrap(),
-            tune: ModulationDepth::try_new((data[3] as i8) - 50).unwrap(),  // adjust to -50~+50
-            level: Level::try_new(data[4]).unwrap(),
+            wave: Wave::from_bytes(&data[0..2]).map_err(|e| e.in_field("wave"))?,
+            decay: Decay::try_new(data[2]).map_err(invalid_field("decay", data, 2))?,
+            tune: ModulationDepth::try_new(((data[3] & 0x7f) as i8) - 50).map_err(invalid_field("tune", data, 3))?,  // adjust to -50~+50
+            level: Level::try_new(data[4]).map_err(invalid_field("level", data, 4))?,
         })
     }
 
@@ -341,7 +365,7 @@ mod tests {
         drum::DrumPatch
     };
 
-    static DATA: &'static [u8] = include_bytes!("A401.SYX");
+    static DATA: &[u8] = include_bytes!("A401.SYX");
 
     #[test]
     fn test_drum_patch_from_bytes() {
@@ -354,4 +378,15 @@ mod tests {
         assert_eq!(patch.unwrap().common.volume.into_inner(), 0x64);
     }
 
+    #[test]
+    fn test_drum_patch_round_trip() {
+        let start: usize =
+            2 +
+            Header::data_size() +
+            bank::SINGLE_PATCH_COUNT * SinglePatch::data_size() +
+            bank::MULTI_PATCH_COUNT * MultiPatch::data_size();
+        let data = &DATA[start..start + DrumPatch::data_size()];
+        assert_eq!(DrumPatch::from_bytes(data).unwrap().to_bytes(), data);
+    }
+
 }
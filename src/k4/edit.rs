@@ -0,0 +1,338 @@
+//! Editing operations for K4 banks.
+//!
+//! Moving patches around changes their numbers. Multi sections refer
+//! to singles by number, and singles and multis refer to effects by number,
+//! so every edit rewrites those references to follow the patches,
+//! and reports what it changed.
+//!
+
+use std::fmt;
+
+use crate::ValueError;
+use crate::k4::{PatchNumber, EffectNumber};
+use crate::k4::bank::{Bank, SINGLE_PATCH_COUNT, MULTI_PATCH_COUNT, EFFECT_PATCH_COUNT};
+use crate::k4::multi::SECTION_COUNT;
+
+/// The kinds of patches in a bank that can be edited.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Group {
+    Singles,
+    Multis,
+    Effects,
+}
+
+impl Group {
+    /// Returns the number of patches of this kind in a bank.
+    pub fn count(&self) -> usize {
+        match self {
+            Group::Singles => SINGLE_PATCH_COUNT,
+            Group::Multis => MULTI_PATCH_COUNT,
+            Group::Effects => EFFECT_PATCH_COUNT,
+        }
+    }
+
+    /// Checks that `index` is a valid zero-based patch index in this group.
+    pub fn check_index(&self, index: usize) -> Result<(), ValueError> {
+        if index < self.count() {
+            Ok(())
+        } else {
+            Err(ValueError(0, self.count() as i32 - 1, index as i32))
+        }
+    }
+}
+
+/// A place in a bank where one patch refers to another.
+/// The indexes are zero-based.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Reference {
+    /// The single of a section in a multi: multi index, section index.
+    Section(usize, usize),
+
+    /// The effect of a single.
+    SingleEffect(usize),
+
+    /// The effect of a multi.
+    MultiEffect(usize),
+}
+
+impl fmt::Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Reference::Section(multi, section) => write!(f, "multis[{}].sections[{}].single_number", multi, section),
+            Reference::SingleEffect(single) => write!(f, "singles[{}].effect", single),
+            Reference::MultiEffect(multi) => write!(f, "multis[{}].effect", multi),
+        }
+    }
+}
+
+/// A reference that was rewritten because the patch it refers to moved.
+/// The patch indexes are zero-based.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct Rewrite {
+    pub reference: Reference,
+    pub old: usize,
+    pub new: usize,
+}
+
+/// What a bank edit changed. The locations of the references
+/// are given as they are after the edit.
+#[derive(Debug, Default, Eq, PartialEq, Clone)]
+pub struct EditReport {
+    /// References that were rewritten to follow their patch.
+    pub rewrites: Vec<Rewrite>,
+
+    /// References to a patch that was overwritten or pushed out of the bank.
+    /// These are left unchanged, so they now refer to a different patch.
+    pub dangling: Vec<Reference>,
+}
+
+impl EditReport {
+    /// Returns true if the edit did not change any references.
+    pub fn is_empty(&self) -> bool {
+        self.rewrites.is_empty() && self.dangling.is_empty()
+    }
+}
+
+impl Bank {
+    /// Moves a patch from `from` to `to`, shifting the patches in between.
+    pub fn move_patch(&mut self, group: Group, from: usize, to: usize) -> Result<EditReport, ValueError> {
+        group.check_index(from)?;
+        group.check_index(to)?;
+        let mut order = identity(group);
+        let index = order.remove(from);
+        order.insert(to, index);
+        Ok(self.rearrange(group, &order, None))
+    }
+
+    /// Swaps the patches at `first` and `second`.
+    pub fn swap_patches(&mut self, group: Group, first: usize, second: usize) -> Result<EditReport, ValueError> {
+        group.check_index(first)?;
+        group.check_index(second)?;
+        let mut order = identity(group);
+        order.swap(first, second);
+        Ok(self.rearrange(group, &order, None))
+    }
+
+    /// Copies the patch at `from` over the patch at `to`.
+    /// References to the overwritten patch are reported as dangling.
+    pub fn copy_patch(&mut self, group: Group, from: usize, to: usize) -> Result<EditReport, ValueError> {
+        group.check_index(from)?;
+        group.check_index(to)?;
+        let mut order = identity(group);
+        order[to] = Some(from);
+        Ok(self.rearrange(group, &order, None))
+    }
+
+    /// Inserts the patch at `from` in the bank `source` into this bank at `to`,
+    /// shifting the following patches down. The last patch falls out of the bank,
+    /// and references to it are reported as dangling. References in the inserted
+    /// patch are kept as they were in the source bank.
+    pub fn insert_patch(&mut self, group: Group, source: &Bank, from: usize, to: usize) -> Result<EditReport, ValueError> {
+        group.check_index(from)?;
+        group.check_index(to)?;
+        let order: Vec<Option<usize>> = (0..group.count())
+            .map(|i| match i {
+                i if i < to => Some(i),
+                i if i == to => None,
+                i => Some(i - 1),
+            })
+            .collect();
+        Ok(self.rearrange(group, &order, Some((source, from))))
+    }
+
+    /// Sorts the patches by name. Patches with the same name keep
+    /// their order. Effect patches have no names, so they are left as they are.
+    pub fn sort_by_name(&mut self, group: Group) -> EditReport {
+        let mut order = identity(group);
+        match group {
+            Group::Singles => order.sort_by(|a, b| self.singles[a.unwrap()].name.cmp(&self.singles[b.unwrap()].name)),
+            Group::Multis => order.sort_by(|a, b| self.multis[a.unwrap()].name.cmp(&self.multis[b.unwrap()].name)),
+            Group::Effects => {},
+        }
+        self.rearrange(group, &order, None)
+    }
+
+    /// Puts the patches of `group` in the new `order`, where each element
+    /// is the old index of the patch, or `None` for a patch taken from
+    /// another bank. Then rewrites the references to follow the patches.
+    fn rearrange(&mut self, group: Group, order: &[Option<usize>], source: Option<(&Bank, usize)>) -> EditReport {
+        match group {
+            Group::Singles => reorder(&mut self.singles, order, source.map(|(bank, i)| bank.singles[i].clone())),
+            Group::Multis => reorder(&mut self.multis, order, source.map(|(bank, i)| bank.multis[i].clone())),
+            Group::Effects => reorder(&mut self.effects, order, source.map(|(bank, i)| bank.effects[i].clone())),
+        }
+        self.fix_references(group, order)
+    }
+
+    /// Rewrites the references to patches of `group` after they were put in `order`.
+    fn fix_references(&mut self, group: Group, order: &[Option<usize>]) -> EditReport {
+        // Map the old indexes to the new ones. If a patch was copied,
+        // the references stay with the original.
+        let mut mapping: Vec<Option<usize>> = vec![None; group.count()];
+        for (new, old) in order.iter().enumerate() {
+            if let Some(old) = *old {
+                if mapping[old].is_none() || old == new {
+                    mapping[old] = Some(new);
+                }
+            }
+        }
+
+        let mut report = EditReport::default();
+        let mut follow = |reference: Reference, old: usize| -> usize {
+            match mapping[old] {
+                Some(new) if new != old => {
+                    report.rewrites.push(Rewrite { reference, old, new });
+                    new
+                },
+                Some(_) => old,
+                None => {
+                    report.dangling.push(reference);
+                    old
+                },
+            }
+        };
+
+        match group {
+            Group::Singles => {
+                for (m, multi) in self.multis.iter_mut().enumerate() {
+                    for s in 0..SECTION_COUNT {
+                        let section = &mut multi.sections[s];
+                        let old = section.single_number.into_inner() as usize;
+                        let new = follow(Reference::Section(m, s), old);
+                        section.single_number = PatchNumber::try_new(new as u8).unwrap();
+                    }
+                }
+            },
+            Group::Effects => {
+                for (i, single) in self.singles.iter_mut().enumerate() {
+                    let old = single.effect.into_inner() as usize - 1;
+                    let new = follow(Reference::SingleEffect(i), old);
+                    single.effect = EffectNumber::try_new(new as u8 + 1).unwrap();
+                }
+                for (i, multi) in self.multis.iter_mut().enumerate() {
+                    let old = multi.effect.into_inner() as usize - 1;
+                    let new = follow(Reference::MultiEffect(i), old);
+                    multi.effect = EffectNumber::try_new(new as u8 + 1).unwrap();
+                }
+            },
+            Group::Multis => {},  // nothing refers to multis
+        }
+
+        report
+    }
+}
+
+/// Returns the order of patches in `group` before any edits.
+fn identity(group: Group) -> Vec<Option<usize>> {
+    (0..group.count()).map(Some).collect()
+}
+
+/// Puts `patches` in the new `order`. The `None` slot is filled with `new`.
+fn reorder<T: Clone>(patches: &mut Vec<T>, order: &[Option<usize>], new: Option<T>) {
+    let old = std::mem::take(patches);
+    *patches = order.iter()
+        .map(|index| match index {
+            Some(i) => old[*i].clone(),
+            None => new.clone().expect("a patch for the inserted slot"),
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{*};
+    use crate::SystemExclusiveData;
+
+    static DATA: &[u8] = include_bytes!("A401.SYX");
+
+    fn make_bank() -> Bank {
+        Bank::from_bytes(&DATA[8..]).unwrap()
+    }
+
+    /// Returns the names of the singles played by the sections of each multi.
+    fn section_singles(bank: &Bank) -> Vec<String> {
+        bank.multis.iter()
+            .flat_map(|m| m.sections.iter().map(|s| bank.singles[s.single_number.into_inner() as usize].name.clone()))
+            .collect()
+    }
+
+    fn make_section_refer_to(bank: &mut Bank, multi: usize, section: usize, single: u8) {
+        bank.multis[multi].sections[section].single_number = PatchNumber::try_new(single).unwrap();
+    }
+
+    #[test]
+    fn test_move_single() {
+        let mut bank = make_bank();
+        make_section_refer_to(&mut bank, 0, 0, 2);
+        let name = bank.singles[2].name.clone();
+        let before = section_singles(&bank);
+
+        let report = bank.move_patch(Group::Singles, 2, 10).unwrap();
+        assert_eq!(bank.singles[10].name, name);
+        assert_eq!(bank.multis[0].sections[0].single_number.into_inner(), 10);
+        assert!(report.rewrites.contains(&Rewrite { reference: Reference::Section(0, 0), old: 2, new: 10 }));
+        assert!(report.dangling.is_empty());
+        assert_eq!(section_singles(&bank), before);
+    }
+
+    #[test]
+    fn test_swap_effects() {
+        let mut bank = make_bank();
+        bank.singles[5].effect = EffectNumber::try_new(1).unwrap();
+        bank.multis[7].effect = EffectNumber::try_new(4).unwrap();
+
+        let report = bank.swap_patches(Group::Effects, 0, 3).unwrap();
+        assert_eq!(bank.singles[5].effect.into_inner(), 4);
+        assert_eq!(bank.multis[7].effect.into_inner(), 1);
+        assert!(report.rewrites.contains(&Rewrite { reference: Reference::SingleEffect(5), old: 0, new: 3 }));
+        assert!(report.rewrites.contains(&Rewrite { reference: Reference::MultiEffect(7), old: 3, new: 0 }));
+    }
+
+    #[test]
+    fn test_copy_reports_dangling() {
+        let mut bank = make_bank();
+        make_section_refer_to(&mut bank, 1, 2, 4);
+        make_section_refer_to(&mut bank, 1, 3, 6);
+        let name = bank.singles[4].name.clone();
+
+        let report = bank.copy_patch(Group::Singles, 4, 6).unwrap();
+        assert_eq!(bank.singles[6].name, name);
+        assert_eq!(bank.multis[1].sections[2].single_number.into_inner(), 4);
+        assert!(report.rewrites.is_empty());
+        assert!(report.dangling.contains(&Reference::Section(1, 3)));
+        assert!(!report.dangling.contains(&Reference::Section(1, 2)));
+    }
+
+    #[test]
+    fn test_insert_from_other_bank() {
+        let mut bank = make_bank();
+        let mut other = make_bank();
+        other.singles[0].name = "Inserted  ".to_string();
+        make_section_refer_to(&mut bank, 0, 0, 20);
+        make_section_refer_to(&mut bank, 0, 1, 63);
+        let name = bank.singles[20].name.clone();
+
+        let report = bank.insert_patch(Group::Singles, &other, 0, 10).unwrap();
+        assert_eq!(bank.singles[10].name, "Inserted  ");
+        assert_eq!(bank.singles[21].name, name);
+        assert_eq!(bank.multis[0].sections[0].single_number.into_inner(), 21);
+        assert!(report.dangling.contains(&Reference::Section(0, 1)));
+    }
+
+    #[test]
+    fn test_sort_by_name() {
+        let mut bank = make_bank();
+        let before = section_singles(&bank);
+
+        bank.sort_by_name(Group::Singles);
+        assert!(bank.singles.windows(2).all(|w| w[0].name <= w[1].name));
+        assert_eq!(section_singles(&bank), before);
+    }
+
+    #[test]
+    fn test_index_out_of_range() {
+        let mut bank = make_bank();
+        assert_eq!(bank.move_patch(Group::Effects, 0, 32), Err(ValueError(0, 31, 32)));
+        assert!(bank.swap_patches(Group::Multis, 64, 0).is_err());
+    }
+}
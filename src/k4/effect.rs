@@ -18,7 +18,9 @@ use crate::k4::{
 use crate::{
     SystemExclusiveData,
     ParseError,
-    Checksum
+    Checksum,
+    check_length,
+    invalid_field
 };
 
 static EFFECT_NAMES: &[&str] = &[
@@ -63,6 +65,8 @@ pub enum Effect {
     ChorusPlusStereoPanpotDelay,
 }
 
+serialize_by_name!(Effect);
+
 impl fmt::Display for Effect {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", EFFECT_NAMES[*self as usize])
@@ -70,11 +74,18 @@ impl fmt::Display for Effect {
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EffectPatch {
     pub effect: Effect,
     pub param1: SmallEffectParameter,
     pub param2: SmallEffectParameter,
     pub param3: BigEffectParameter,
+
+    /// The six dummy bytes after the parameters. They are not used,
+    /// but are kept so that the patch is written back as it was read.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "crate::serialize::is_default"))]
+    pub dummy: [u8; 6],
+
     pub submixes: [SubmixSettings; SUBMIX_COUNT],
 }
 
@@ -108,6 +119,7 @@ impl Default for EffectPatch {
             param1: SmallEffectParameter::try_new(0).unwrap(),
             param2: SmallEffectParameter::try_new(0).unwrap(),
             param3: BigEffectParameter::try_new(0).unwrap(),
+            dummy: [0; 6],
             submixes: [Default::default(); SUBMIX_COUNT],
         }
     }
@@ -135,7 +147,7 @@ impl EffectPatch {
             self.param3.into_inner()
         ];
 
-        buf.extend(vec![0, 0, 0, 0, 0, 0]); // six dummy bytes
+        buf.extend(self.dummy);
 
         for i in 0..SUBMIX_COUNT {
             buf.extend(self.submixes[i].to_bytes());
@@ -157,25 +169,24 @@ impl SystemExclusiveData for EffectPatch {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
         // data bytes 4...9 are the dummy bytes,
         // submix settings start at 10 with three bytes each
+        check_length(data, Self::data_size())?;
+
         let mut submixes = [Default::default(); SUBMIX_COUNT];
 
         let mut offset = 10;
-        let mut i = 0;
-        while i < SUBMIX_COUNT {
-            submixes[i] = SubmixSettings {
-                pan: data[offset] as i32 - 7,
-                send1: Level::try_new(data[offset + 1]).unwrap(),
-                send2: Level::try_new(data[offset + 2]).unwrap(),
-            };
+        for (i, submix) in submixes.iter_mut().enumerate() {
+            let start = offset;
+            *submix = SubmixSettings::from_bytes(&data[offset..offset + 3])
+                .map_err(|e| e.within(format!("submixes[{}]", i), start))?;
             offset += 3;
-            i += 1;
         }
 
         Ok(EffectPatch {
-            effect: Effect::try_from(data[0] + 1).unwrap(),
-            param1: SmallEffectParameter::try_new((data[1] as i8) - 7).unwrap(),
-            param2: SmallEffectParameter::try_new((data[2] as i8) - 7).unwrap(),
-            param3: BigEffectParameter::try_new(data[3]).unwrap(),
+            effect: Effect::try_from((data[0] & 0x0f) + 1).map_err(invalid_field("effect", data, 0))?,
+            param1: SmallEffectParameter::try_new(((data[1] & 0x7f) as i8) - 7).map_err(invalid_field("param1", data, 1))?,
+            param2: SmallEffectParameter::try_new(((data[2] & 0x7f) as i8) - 7).map_err(invalid_field("param2", data, 2))?,
+            param3: BigEffectParameter::try_new(data[3]).map_err(invalid_field("param3", data, 3))?,
+            dummy: data[4..10].try_into().unwrap(),
             submixes,
         })
     }
@@ -201,6 +212,7 @@ impl Checksum for EffectPatch {
 }
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SubmixSettings {
     // K4: -7~+7, stored in SysEx as 0~15
     // K4r: -7~+7, stored as 0~15 , or 16~21 / 1~6
@@ -223,10 +235,11 @@ impl Default for SubmixSettings {
 
 impl SystemExclusiveData for SubmixSettings {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(SubmixSettings {
             pan: data[0] as i32 - 7,
-            send1: Level::try_new(data[1]).unwrap(),
-            send2: Level::try_new(data[2]).unwrap(),
+            send1: Level::try_new(data[1]).map_err(invalid_field("send1", data, 1))?,
+            send2: Level::try_new(data[2]).map_err(invalid_field("send2", data, 2))?,
         })
     }
 
@@ -255,6 +268,8 @@ pub enum Submix {
     H = 7,
 }
 
+serialize_by_name!(Submix);
+
 impl Submix {
     pub fn name(&self) -> String {
         match self {
@@ -288,7 +303,7 @@ mod tests {
     
     use super::{*};
 
-    static DATA: &'static [u8] = include_bytes!("A401.SYX");
+    static DATA: &[u8] = include_bytes!("A401.SYX");
 
     #[test]
     fn test_submix_name() {
@@ -316,6 +331,7 @@ mod tests {
             param1: SmallEffectParameter::try_new(7).unwrap(),
             param2: SmallEffectParameter::try_new(5).unwrap(),
             param3: BigEffectParameter::try_new(31).unwrap(),
+            dummy: [0; 6],
             submixes: [Default::default(); SUBMIX_COUNT],
         };
 
@@ -334,6 +350,7 @@ mod tests {
             param1: SmallEffectParameter::try_new(7).unwrap(),
             param2: SmallEffectParameter::try_new(5).unwrap(),
             param3: BigEffectParameter::try_new(31).unwrap(),
+            dummy: [0; 6],
             submixes: [Default::default(); SUBMIX_COUNT],
         };
 
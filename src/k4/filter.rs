@@ -8,7 +8,9 @@ use bit::BitIndex;
 
 use crate::{
     SystemExclusiveData,
-    ParseError
+    ParseError,
+    check_length,
+    invalid_field
 };
 use crate::k4::{
     EnvelopeTime,
@@ -24,6 +26,7 @@ use crate::k4::amp::{
 
 /// Filter envelope.
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Envelope {
     pub attack: EnvelopeTime,
     pub decay: EnvelopeTime,
@@ -62,11 +65,12 @@ impl fmt::Display for Envelope {
 
 impl SystemExclusiveData for Envelope {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(Envelope {
-            attack: EnvelopeTime::try_new(data[0]).unwrap(),
-            decay: EnvelopeTime::try_new(data[1]).unwrap(),
-            sustain: FilterEnvelopeLevel::try_new((data[2] as i8) - 50).unwrap(),
-            release: EnvelopeTime::try_new(data[3]).unwrap(),
+            attack: EnvelopeTime::try_new(data[0]).map_err(invalid_field("attack", data, 0))?,
+            decay: EnvelopeTime::try_new(data[1]).map_err(invalid_field("decay", data, 1))?,
+            sustain: FilterEnvelopeLevel::try_new(((data[2] & 0x7f) as i8) - 50).map_err(invalid_field("sustain", data, 2))?,
+            release: EnvelopeTime::try_new(data[3]).map_err(invalid_field("release", data, 3))?,
         })
     }
 
@@ -84,6 +88,7 @@ impl SystemExclusiveData for Envelope {
 
 /// Filter (DCF).
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Filter {
     pub cutoff: Cutoff,  // 0~100
     pub resonance: Resonance,  // 0~7
@@ -133,57 +138,62 @@ impl fmt::Display for Filter {
 
 impl SystemExclusiveData for Filter {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
+
         let mut offset: usize = 0;
         let mut start: usize;
         let mut end: usize;
 
         let mut b: u8;
         b = data[offset];
+        let cutoff = Cutoff::try_new(b & 0x7f).map_err(invalid_field("cutoff", data, offset))?;
         offset += 1;
-        let cutoff = b & 0x7f;
 
         b = data[offset];
-        offset += 1;
 
         // The Kawai K4 SysEx spec has "0~7 / 1~8" for resonance,
         // but it's described as 0...7 in the user manual, so let's go with that.
-        let resonance = b & 0b111;  // use the bottom three bits
+        let resonance = Resonance::try_new(b & 0b111).map_err(invalid_field("resonance", data, offset))?;  // use the bottom three bits
         let lfo_modulates_cutoff = b.bit(3);
+        offset += 1;
 
         start = offset;
         end = start + 3;
         let cutoff_mod_bytes = &data[start..end];
-        let cutoff_mod = LevelModulation::from_bytes(&cutoff_mod_bytes);
-
+        let cutoff_mod = LevelModulation::from_bytes(cutoff_mod_bytes)
+            .map_err(|e| e.within("cutoff_mod", start))?;
         offset += 3;
+
         b = data[offset];
+        let env_depth = ModulationDepth::try_new(((b & 0x7f) as i8) - 50).map_err(invalid_field("env_depth", data, offset))?;
         offset += 1;
-        let env_depth = (b as i8) - 50;
 
         b = data[offset];
+        let env_vel_depth = ModulationDepth::try_new(((b & 0x7f) as i8) - 50).map_err(invalid_field("env_vel_depth", data, offset))?;
         offset += 1;
-        let env_vel_depth = (b as i8) - 50;
 
         start = offset;
         end = start + 4;
         let envelope_bytes = &data[start..end];
-        let envelope = Envelope::from_bytes(&envelope_bytes);
+        let envelope = Envelope::from_bytes(envelope_bytes)
+            .map_err(|e| e.within("envelope", start))?;
         offset += 4;
 
         start = offset;
         end = start + 3;
         let time_mod_bytes = &data[start..end];
-        let time_mod = TimeModulation::from_bytes(&time_mod_bytes);
+        let time_mod = TimeModulation::from_bytes(time_mod_bytes)
+            .map_err(|e| e.within("time_mod", start))?;
 
         Ok(Filter {
-            cutoff: Cutoff::try_new(cutoff).unwrap(),
-            resonance: Resonance::try_new(resonance).unwrap(),
-            cutoff_mod: cutoff_mod?,
+            cutoff,
+            resonance,
+            cutoff_mod,
             lfo_modulates_cutoff,
-            env_depth: ModulationDepth::try_new(env_depth).unwrap(),
-            env_vel_depth: ModulationDepth::try_new(env_vel_depth).unwrap(),
-            envelope: envelope?,
-            time_mod: time_mod?,
+            env_depth,
+            env_vel_depth,
+            envelope,
+            time_mod,
         })
     }
 
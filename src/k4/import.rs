@@ -0,0 +1,433 @@
+//! Importing patches from one K4 bank to another.
+//!
+//! A multi needs the singles played by its sections and its effect patch,
+//! and a single needs its effect patch. Importing a patch copies these
+//! dependencies along, and rewrites the references in the copies,
+//! so that the imported patch sounds the same in the destination bank.
+//!
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::{SystemExclusiveData, ValueError};
+use crate::k4::{PatchNumber, EffectNumber};
+use crate::k4::bank::Bank;
+use crate::k4::edit::{Group, Reference};
+use crate::k4::single::SinglePatch;
+use crate::k4::multi::MultiPatch;
+use crate::k4::effect::EffectPatch;
+
+/// Destination indexes of patches, by their index in the source bank.
+type SlotMap = BTreeMap<usize, usize>;
+
+/// Destination slots chosen by the caller. Anything left out
+/// is placed automatically, preferring an identical patch that is already
+/// in the destination bank, then a free slot, then the least used slot.
+/// All the indexes are zero-based.
+#[derive(Debug, Default, Clone)]
+pub struct Slots {
+    /// Destination of the imported patch itself.
+    pub target: Option<usize>,
+
+    /// Destinations of singles, by their index in the source bank.
+    pub singles: BTreeMap<usize, usize>,
+
+    /// Destinations of effects, by their index in the source bank.
+    pub effects: BTreeMap<usize, usize>,
+}
+
+/// Where the imported patch and its dependencies ended up.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct ImportReport {
+    /// Index of the imported patch in the destination bank.
+    pub target: usize,
+
+    /// Destinations of the singles, by their index in the source bank.
+    pub singles: BTreeMap<usize, usize>,
+
+    /// Destinations of the effects, by their index in the source bank.
+    pub effects: BTreeMap<usize, usize>,
+
+    /// References in the destination bank to patches that the import
+    /// overwrote. They now refer to the imported patches.
+    pub dangling: Vec<Reference>,
+}
+
+/// Error in importing a patch.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum ImportError {
+    /// A patch index is out of range.
+    InvalidIndex(Group, ValueError),
+
+    /// The caller chose the same destination slot for two different patches.
+    SlotConflict(Group, usize),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportError::InvalidIndex(group, e) => write!(f, "Invalid index for {:?}: {}", group, e),
+            ImportError::SlotConflict(group, index) => write!(f, "Slot {} of {:?} is used for two patches", index, group),
+        }
+    }
+}
+
+impl std::error::Error for ImportError { }
+
+/// Imports the patch at `index` of `group` from `source` into `destination`,
+/// with the singles and effects it depends on.
+pub fn import(source: &Bank, group: Group, index: usize, destination: &mut Bank, slots: &Slots) -> Result<ImportReport, ImportError> {
+    check_index(group, index)?;
+    let (chosen_singles, chosen_effects) = chosen_slots(group, index, slots)?;
+
+    // Find out what the patch depends on.
+    let (single_sources, effect_sources): (BTreeSet<usize>, BTreeSet<usize>) = match group {
+        Group::Multis => {
+            let multi = &source.multis[index];
+            let singles: BTreeSet<usize> = multi.sections.iter()
+                .map(|s| s.single_number.into_inner() as usize)
+                .collect();
+            let mut effects: BTreeSet<usize> = singles.iter()
+                .map(|&s| effect_index(&source.singles[s]))
+                .collect();
+            effects.insert(effect_index_of(multi.effect));
+            (singles, effects)
+        },
+        Group::Singles => (BTreeSet::from([index]), BTreeSet::from([effect_index(&source.singles[index])])),
+        Group::Effects => (BTreeSet::new(), BTreeSet::from([index])),
+    };
+
+    let mut report = ImportReport::default();
+
+    // Place the effects first, since the singles need to refer to them.
+    // The slots chosen by the caller are reserved before any automatic placement.
+    let effect_usage = destination.effect_usage();
+    let mut reserved = reserve_chosen(Group::Effects, &effect_sources, &chosen_effects)?;
+    let mut overwritten_effects = BTreeSet::new();
+    for &e in effect_sources.iter() {
+        let patch = source.effects[e].clone();
+        let chosen = match chosen_effects.get(&e) {
+            Some(&chosen) => chosen,
+            None => {
+                let chosen = find_identical(&destination.effects, &patch, &reserved)
+                    .unwrap_or_else(|| choose_slot(Group::Effects, destination, &effect_usage, &reserved));
+                reserve(Group::Effects, &mut reserved, chosen)?;
+                chosen
+            },
+        };
+        if destination.effects[chosen].to_bytes() != patch.to_bytes() {
+            overwritten_effects.insert(chosen);
+        }
+        report.effects.insert(e, chosen);
+    }
+
+    let single_usage = destination.single_usage();
+    let mut reserved = reserve_chosen(Group::Singles, &single_sources, &chosen_singles)?;
+    let mut overwritten_singles = BTreeSet::new();
+    let mut singles = Vec::new();
+    for &s in single_sources.iter() {
+        let mut patch = source.singles[s].clone();
+        patch.effect = remap_effect(patch.effect, &report.effects);
+        let chosen = match chosen_singles.get(&s) {
+            Some(&chosen) => chosen,
+            None => {
+                let chosen = find_identical(&destination.singles, &patch, &reserved)
+                    .unwrap_or_else(|| choose_slot(Group::Singles, destination, &single_usage, &reserved));
+                reserve(Group::Singles, &mut reserved, chosen)?;
+                chosen
+            },
+        };
+        if destination.singles[chosen].to_bytes() != patch.to_bytes() {
+            overwritten_singles.insert(chosen);
+        }
+        report.singles.insert(s, chosen);
+        singles.push((chosen, patch));
+    }
+
+    let multi = if group == Group::Multis {
+        let mut patch = source.multis[index].clone();
+        patch.effect = remap_effect(patch.effect, &report.effects);
+        for section in patch.sections.iter_mut() {
+            let new = report.singles[&(section.single_number.into_inner() as usize)];
+            section.single_number = PatchNumber::try_new(new as u8).unwrap();
+        }
+        let chosen = slots.target.unwrap_or_else(|| choose_slot(Group::Multis, destination, &vec![0; Group::Multis.count()], &BTreeSet::new()));
+        Some((chosen, patch))
+    } else {
+        None
+    };
+
+    report.target = match group {
+        Group::Multis => multi.as_ref().unwrap().0,
+        Group::Singles => report.singles[&index],
+        Group::Effects => report.effects[&index],
+    };
+
+    // Note the references to the patches about to be overwritten,
+    // except from patches that are themselves overwritten.
+    for (m, patch) in destination.multis.iter().enumerate() {
+        if multi.as_ref().map(|(i, _)| *i) == Some(m) {
+            continue;
+        }
+        for (s, section) in patch.sections.iter().enumerate() {
+            if overwritten_singles.contains(&(section.single_number.into_inner() as usize)) {
+                report.dangling.push(Reference::Section(m, s));
+            }
+        }
+        if overwritten_effects.contains(&effect_index_of(patch.effect)) {
+            report.dangling.push(Reference::MultiEffect(m));
+        }
+    }
+    for (i, patch) in destination.singles.iter().enumerate() {
+        if !report.singles.values().any(|&s| s == i) && overwritten_effects.contains(&effect_index(patch)) {
+            report.dangling.push(Reference::SingleEffect(i));
+        }
+    }
+
+    // Finally copy the patches over.
+    for (&e, &chosen) in report.effects.iter() {
+        destination.effects[chosen] = source.effects[e].clone();
+    }
+    for (chosen, patch) in singles {
+        destination.singles[chosen] = patch;
+    }
+    if let Some((chosen, patch)) = multi {
+        destination.multis[chosen] = patch;
+    }
+
+    Ok(report)
+}
+
+impl Bank {
+    /// Returns the number of multi sections that play each single.
+    pub fn single_usage(&self) -> Vec<usize> {
+        let mut usage = vec![0; Group::Singles.count()];
+        for multi in self.multis.iter() {
+            for section in multi.sections.iter() {
+                usage[section.single_number.into_inner() as usize] += 1;
+            }
+        }
+        usage
+    }
+
+    /// Returns the number of singles and multis that use each effect.
+    pub fn effect_usage(&self) -> Vec<usize> {
+        let mut usage = vec![0; Group::Effects.count()];
+        for single in self.singles.iter() {
+            usage[effect_index(single)] += 1;
+        }
+        for multi in self.multis.iter() {
+            usage[effect_index_of(multi.effect)] += 1;
+        }
+        usage
+    }
+
+    /// Returns true if the patch at `index` of `group` is the same
+    /// as a newly initialized patch.
+    pub fn is_free(&self, group: Group, index: usize) -> bool {
+        match group {
+            Group::Singles => self.singles[index].to_bytes() == SinglePatch::default().to_bytes(),
+            Group::Multis => self.multis[index].to_bytes() == MultiPatch::default().to_bytes(),
+            Group::Effects => self.effects[index].to_bytes() == EffectPatch::default().to_bytes(),
+        }
+    }
+}
+
+fn check_index(group: Group, index: usize) -> Result<(), ImportError> {
+    group.check_index(index).map_err(|e| ImportError::InvalidIndex(group, e))
+}
+
+/// Checks the indexes chosen by the caller, and returns the chosen
+/// destinations of the singles and the effects. For a single or an effect,
+/// the target is the destination of the patch itself.
+fn chosen_slots(group: Group, index: usize, slots: &Slots) -> Result<(SlotMap, SlotMap), ImportError> {
+    if let Some(target) = slots.target {
+        check_index(group, target)?;
+    }
+    for (&from, &to) in slots.singles.iter() {
+        check_index(Group::Singles, from)?;
+        check_index(Group::Singles, to)?;
+    }
+    for (&from, &to) in slots.effects.iter() {
+        check_index(Group::Effects, from)?;
+        check_index(Group::Effects, to)?;
+    }
+
+    let mut singles = slots.singles.clone();
+    let mut effects = slots.effects.clone();
+    if let Some(target) = slots.target {
+        let chosen = match group {
+            Group::Singles => Some(&mut singles),
+            Group::Effects => Some(&mut effects),
+            Group::Multis => None,
+        };
+        if let Some(chosen) = chosen {
+            if *chosen.entry(index).or_insert(target) != target {
+                return Err(ImportError::SlotConflict(group, target));
+            }
+        }
+    }
+    Ok((singles, effects))
+}
+
+/// Marks `index` as used by this import.
+fn reserve(group: Group, reserved: &mut BTreeSet<usize>, index: usize) -> Result<(), ImportError> {
+    if reserved.insert(index) {
+        Ok(())
+    } else {
+        Err(ImportError::SlotConflict(group, index))
+    }
+}
+
+/// Reserves the slots chosen by the caller for the patches in `sources`.
+fn reserve_chosen(group: Group, sources: &BTreeSet<usize>, chosen: &SlotMap) -> Result<BTreeSet<usize>, ImportError> {
+    let mut reserved = BTreeSet::new();
+    for index in sources.iter().filter_map(|s| chosen.get(s)) {
+        reserve(group, &mut reserved, *index)?;
+    }
+    Ok(reserved)
+}
+
+/// Returns the index of a patch in `patches` that is identical to `patch`.
+fn find_identical<T: SystemExclusiveData>(patches: &[T], patch: &T, reserved: &BTreeSet<usize>) -> Option<usize> {
+    let data = patch.to_bytes();
+    (0..patches.len()).find(|i| !reserved.contains(i) && patches[*i].to_bytes() == data)
+}
+
+/// Chooses a slot automatically: a free slot if there is one, otherwise
+/// the least used slot. Later slots are preferred, since the end of a bank
+/// is where spare patches are usually kept.
+fn choose_slot(group: Group, bank: &Bank, usage: &[usize], reserved: &BTreeSet<usize>) -> usize {
+    (0..group.count())
+        .filter(|i| !reserved.contains(i))
+        .min_by_key(|&i| (!bank.is_free(group, i), usage[i], std::cmp::Reverse(i)))
+        .expect("more slots than patches to import")
+}
+
+/// Returns the zero-based effect index of a single.
+fn effect_index(single: &SinglePatch) -> usize {
+    effect_index_of(single.effect)
+}
+
+fn effect_index_of(effect: EffectNumber) -> usize {
+    effect.into_inner() as usize - 1
+}
+
+fn remap_effect(effect: EffectNumber, effects: &BTreeMap<usize, usize>) -> EffectNumber {
+    EffectNumber::try_new(effects[&effect_index_of(effect)] as u8 + 1).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{*};
+
+    static DATA: &[u8] = include_bytes!("A401.SYX");
+
+    fn make_bank() -> Bank {
+        Bank::from_bytes(&DATA[8..]).unwrap()
+    }
+
+    /// Returns a description of how a multi sounds in `bank`:
+    /// the data of the singles and the effects it uses.
+    fn sound_of_multi(bank: &Bank, index: usize) -> Vec<Vec<u8>> {
+        let multi = &bank.multis[index];
+        let mut result = vec![bank.effects[effect_index_of(multi.effect)].to_bytes()];
+        for section in multi.sections.iter() {
+            let single = &bank.singles[section.single_number.into_inner() as usize];
+            let mut data = single.to_bytes();
+            data[11] = 0;  // the effect number may differ
+            data[130] = 0;  // and so may the checksum
+            result.push(data);
+            result.push(bank.effects[effect_index(single)].to_bytes());
+        }
+        result
+    }
+
+    #[test]
+    fn test_import_multi_into_empty_bank() {
+        let source = make_bank();
+        let mut destination = Bank::default();
+
+        let report = import(&source, Group::Multis, 3, &mut destination, &Slots::default()).unwrap();
+        assert_eq!(sound_of_multi(&destination, report.target), sound_of_multi(&source, 3));
+        assert_eq!(destination.multis[report.target].name, source.multis[3].name);
+        assert!(report.dangling.is_empty());
+    }
+
+    #[test]
+    fn test_import_multi_with_chosen_slots() {
+        let source = make_bank();
+        let mut destination = make_bank();
+        let first_single = source.multis[0].sections[0].single_number.into_inner() as usize;
+
+        let slots = Slots {
+            target: Some(10),
+            singles: BTreeMap::from([(first_single, 40)]),
+            effects: BTreeMap::new(),
+        };
+        destination.singles[40] = SinglePatch::default();  // make sure it is not identical
+        let report = import(&source, Group::Multis, 0, &mut destination, &slots).unwrap();
+        assert_eq!(report.target, 10);
+        assert_eq!(report.singles[&first_single], 40);
+        assert_eq!(destination.multis[10].sections[0].single_number.into_inner(), 40);
+        assert_eq!(sound_of_multi(&destination, 10), sound_of_multi(&source, 0));
+    }
+
+    #[test]
+    fn test_import_reuses_identical_patches() {
+        let source = make_bank();
+        let mut destination = make_bank();
+
+        let report = import(&source, Group::Singles, 5, &mut destination, &Slots::default()).unwrap();
+        assert_eq!(report.target, 5);
+        assert!(report.dangling.is_empty());
+    }
+
+    #[test]
+    fn test_import_single_prefers_free_slots() {
+        let source = make_bank();
+        let mut destination = make_bank();
+        destination.singles[20] = SinglePatch::default();
+        destination.singles[5].name = "Changed   ".to_string();
+
+        let report = import(&source, Group::Singles, 5, &mut destination, &Slots::default()).unwrap();
+        assert_eq!(report.target, 20);
+        assert_eq!(destination.singles[20].name, source.singles[5].name);
+    }
+
+    #[test]
+    fn test_slot_conflict() {
+        let source = make_bank();
+        let mut destination = Bank::default();
+        let sections = &source.multis[0].sections;
+        let first = sections[0].single_number.into_inner() as usize;
+        let other = sections.iter()
+            .map(|s| s.single_number.into_inner() as usize)
+            .find(|&s| s != first)
+            .unwrap();
+
+        let slots = Slots {
+            target: None,
+            singles: BTreeMap::from([(first, 7), (other, 7)]),
+            effects: BTreeMap::new(),
+        };
+        assert_eq!(import(&source, Group::Multis, 0, &mut destination, &slots),
+            Err(ImportError::SlotConflict(Group::Singles, 7)));
+    }
+
+    #[test]
+    fn test_chosen_slots_reserved_first() {
+        let source = make_bank();
+        let mut destination = Bank::default();
+
+        let slots = Slots {
+            target: None,
+            singles: BTreeMap::from([(63, 63)]),
+            effects: BTreeMap::new(),
+        };
+        let report = import(&source, Group::Multis, 0, &mut destination, &slots).unwrap();
+        assert_eq!(report.singles[&63], 63);
+        assert_eq!(report.singles.values().filter(|&&s| s == 63).count(), 1);
+        assert_eq!(sound_of_multi(&destination, report.target), sound_of_multi(&source, 0));
+    }
+}
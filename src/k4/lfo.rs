@@ -9,7 +9,9 @@ use num_enum::TryFromPrimitive;
 
 use crate::{
     SystemExclusiveData,
-    ParseError
+    ParseError,
+    check_length,
+    invalid_field
 };
 use crate::k4::{
     Level,
@@ -26,6 +28,8 @@ pub enum Shape {
     Random,
 }
 
+serialize_by_name!(Shape);
+
 impl fmt::Display for Shape {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f,
@@ -42,6 +46,7 @@ impl fmt::Display for Shape {
 
 /// LFO.
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Lfo {
     pub shape: Shape,
     pub speed: Level,  // 0~100
@@ -83,12 +88,13 @@ impl fmt::Display for Lfo {
 
 impl SystemExclusiveData for Lfo {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(Lfo {
-            shape: Shape::try_from(data[0] & 0x03).unwrap(),
-            speed: Level::try_new(data[1] & 0x7f).unwrap(),
-            delay: Level::try_new(data[2] & 0x7f).unwrap(),
-            depth: ModulationDepth::try_new(((data[3] & 0x7f) as i8) - 50).unwrap(), // 0~100 to ±50
-            pressure_depth: ModulationDepth::try_new(((data[4] & 0x7f) as i8) - 50).unwrap(), // 0~100 to ±50
+            shape: Shape::try_from(data[0] & 0x03).map_err(invalid_field("shape", data, 0))?,
+            speed: Level::try_new(data[1] & 0x7f).map_err(invalid_field("speed", data, 1))?,
+            delay: Level::try_new(data[2] & 0x7f).map_err(invalid_field("delay", data, 2))?,
+            depth: ModulationDepth::try_new(((data[3] & 0x7f) as i8) - 50).map_err(invalid_field("depth", data, 3))?, // 0~100 to ±50
+            pressure_depth: ModulationDepth::try_new(((data[4] & 0x7f) as i8) - 50).map_err(invalid_field("pressure_depth", data, 4))?, // 0~100 to ±50
         })
     }
 
@@ -112,6 +118,7 @@ impl SystemExclusiveData for Lfo {
 
 /// Vibrato settings.
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vibrato {
     pub shape: Shape,
     pub speed: Level,  // 0~100
@@ -150,11 +157,12 @@ impl fmt::Display for Vibrato {
 
 impl SystemExclusiveData for Vibrato {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(Vibrato {
-            shape: Shape::try_from((data[0] >> 4) & 0x03).unwrap(),
-            speed: Level::try_new(data[1] & 0x7f).unwrap(),
-            pressure: ModulationDepth::try_new(((data[2] & 0x7f) as i8) - 50).unwrap(), // 0~100 to ±50
-            depth: ModulationDepth::try_new(((data[3] & 0x7f) as i8) - 50).unwrap(), // 0~100 to ±50
+            shape: Shape::try_from((data[0] >> 4) & 0x03).map_err(invalid_field("shape", data, 0))?,
+            speed: Level::try_new(data[1] & 0x7f).map_err(invalid_field("speed", data, 1))?,
+            pressure: ModulationDepth::try_new(((data[2] & 0x7f) as i8) - 50).map_err(invalid_field("pressure", data, 2))?, // 0~100 to ±50
+            depth: ModulationDepth::try_new(((data[3] & 0x7f) as i8) - 50).map_err(invalid_field("depth", data, 3))?, // 0~100 to ±50
         })
     }
 
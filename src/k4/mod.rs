@@ -1,4 +1,6 @@
-use crate::{SystemExclusiveData, ParseError};
+use std::ops::RangeInclusive;
+
+use crate::{SystemExclusiveData, ParseError, check_length, invalid_field};
 
 pub mod amp;
 pub mod effect;
@@ -11,6 +13,11 @@ pub mod wave;
 pub mod drum;
 pub mod bank;
 pub mod sysex;
+pub mod parameter;
+pub mod edit;
+pub mod import;
+pub mod validate;
+pub mod registry;
 
 /// Length of patch name
 pub const NAME_LENGTH: usize = 10;
@@ -39,6 +46,27 @@ pub fn get_note_name(note_number: u8) -> String {
     format!("{}{}", name, octave)
 }
 
+/// Range of values of a domain type, for generic editors and validators.
+pub trait Ranged {
+    const MIN: i32;
+    const MAX: i32;
+
+    /// Gets the range of allowed values as an inclusive range.
+    fn range() -> RangeInclusive<i32> {
+        Self::MIN ..= Self::MAX
+    }
+}
+
+/// Implements `Ranged` for a nutype with the same bounds as its validation.
+macro_rules! ranged {
+    ($type:ty, $min:expr, $max:expr) => {
+        impl Ranged for $type {
+            const MIN: i32 = $min;
+            const MAX: i32 = $max;
+        }
+    };
+}
+
 // Domain types based on nutype.
 // We use the smallest possible inner type for the wrapped value, FWIW.
 
@@ -51,6 +79,9 @@ use nutype::nutype;
 )]
 pub struct EnvelopeTime(u8);
 
+serialize_validated!(EnvelopeTime, u8);
+ranged!(EnvelopeTime, 0, 100);
+
 /// Envelope level for DCA/DCF sustain
 type EnvelopeLevel = EnvelopeTime;
 
@@ -61,6 +92,9 @@ type EnvelopeLevel = EnvelopeTime;
 )]
 pub struct Level(u8);
 
+serialize_validated!(Level, u8);
+ranged!(Level, 0, 100);
+
 /// Depth used for DCA/DCF modulation values
 #[nutype(
     validate(
@@ -71,6 +105,9 @@ pub struct Level(u8);
 )]
 pub struct ModulationDepth(i8);  // note: signed inner type
 
+serialize_validated!(ModulationDepth, i8);
+ranged!(ModulationDepth, -50, 50);
+
 /// MIDI channel
 #[nutype(
     validate(greater_or_equal = 1, less_or_equal = 16),
@@ -78,6 +115,9 @@ pub struct ModulationDepth(i8);  // note: signed inner type
 )]
 pub struct Channel(u8);
 
+serialize_validated!(Channel, u8);
+ranged!(Channel, 1, 16);
+
 /// Drum source 1 and 2 decay
 #[nutype(
     validate(greater_or_equal = 1, less_or_equal = 100),
@@ -85,6 +125,9 @@ pub struct Channel(u8);
 )]
 pub struct Decay(u8);
 
+serialize_validated!(Decay, u8);
+ranged!(Decay, 1, 100);
+
 
 /// Small effect parameter
 #[nutype(
@@ -93,6 +136,9 @@ pub struct Decay(u8);
 )]
 pub struct SmallEffectParameter(i8);
 
+serialize_validated!(SmallEffectParameter, i8);
+ranged!(SmallEffectParameter, -7, 7);
+
 /// Big effect parameter
 #[nutype(
     validate(greater_or_equal = 0, less_or_equal = 31),
@@ -100,6 +146,9 @@ pub struct SmallEffectParameter(i8);
 )]
 pub struct BigEffectParameter(u8);
 
+serialize_validated!(BigEffectParameter, u8);
+ranged!(BigEffectParameter, 0, 31);
+
 /// Envelope level for DCF sustain
 #[nutype(
     validate(greater_or_equal = -50, less_or_equal = 50),
@@ -107,6 +156,9 @@ pub struct BigEffectParameter(u8);
 )]
 pub struct FilterEnvelopeLevel(i8);
 
+serialize_validated!(FilterEnvelopeLevel, i8);
+ranged!(FilterEnvelopeLevel, -50, 50);
+
 /// Filter cutoff
 #[nutype(
     validate(greater_or_equal = 0, less_or_equal = 100),
@@ -114,6 +166,9 @@ pub struct FilterEnvelopeLevel(i8);
 )]
 pub struct Cutoff(u8);
 
+serialize_validated!(Cutoff, u8);
+ranged!(Cutoff, 0, 100);
+
 /// Filter resonance
 #[nutype(
     validate(greater_or_equal = 0, less_or_equal = 7),
@@ -121,6 +176,9 @@ pub struct Cutoff(u8);
 )]
 pub struct Resonance(u8);
 
+serialize_validated!(Resonance, u8);
+ranged!(Resonance, 0, 7);
+
 /// Effect number
 #[nutype(
     validate(greater_or_equal = 1, less_or_equal = 32),
@@ -128,9 +186,13 @@ pub struct Resonance(u8);
 )]
 pub struct EffectNumber(u8);
 
+serialize_validated!(EffectNumber, u8);
+ranged!(EffectNumber, 1, 32);
+
 impl SystemExclusiveData for EffectNumber {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
-        Ok(Self::try_new(data[0] + 1).unwrap())  // adjust 0~31 to 1~32
+        check_length(data, Self::data_size())?;
+        Self::try_new((data[0] & 0x7f) + 1).map_err(invalid_field("", data, 0))  // adjust 0~31 to 1~32
     }
 
     fn to_bytes(&self) -> Vec<u8> {
@@ -147,6 +209,9 @@ impl SystemExclusiveData for EffectNumber {
 )]
 pub struct Curve(u8);
 
+serialize_validated!(Curve, u8);
+ranged!(Curve, 1, 8);
+
 /// DCO coarse tuning
 #[nutype(
     validate(greater_or_equal = -24, less_or_equal = 24),
@@ -154,6 +219,9 @@ pub struct Curve(u8);
 )]
 pub struct Coarse(i8);
 
+serialize_validated!(Coarse, i8);
+ranged!(Coarse, -24, 24);
+
 /// DCO fine tuning
 #[nutype(
     validate(greater_or_equal = -50, less_or_equal = 50),
@@ -161,6 +229,9 @@ pub struct Coarse(i8);
 )]
 pub struct Fine(i8);
 
+serialize_validated!(Fine, i8);
+ranged!(Fine, -50, 50);
+
 /// Wave number
 #[nutype(
     validate(greater_or_equal = 1, less_or_equal = 256),
@@ -168,6 +239,9 @@ pub struct Fine(i8);
 )]
 pub struct WaveNumber(u16);
 
+serialize_validated!(WaveNumber, u16);
+ranged!(WaveNumber, 1, 256);
+
 /// Patch number 0...63 (can be converted to A-1...D-16)
 #[nutype(
     validate(greater_or_equal = 0, less_or_equal = 63),
@@ -175,6 +249,9 @@ pub struct WaveNumber(u16);
 )]
 pub struct PatchNumber(u8);
 
+serialize_validated!(PatchNumber, u8);
+ranged!(PatchNumber, 0, 63);
+
 /// Transpose
 #[nutype(
     validate(greater_or_equal = -24, less_or_equal = 24), // +-24 (in SysEx 0~48)
@@ -182,9 +259,13 @@ pub struct PatchNumber(u8);
 )]
 pub struct Transpose(i8);
 
+serialize_validated!(Transpose, i8);
+ranged!(Transpose, -24, 24);
+
 impl SystemExclusiveData for Transpose {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
-        Ok(Self::try_new(data[0] as i8 - 24).unwrap())
+        check_length(data, Self::data_size())?;
+        Self::try_new((data[0] & 0x7f) as i8 - 24).map_err(invalid_field("", data, 0))
     }
 
     fn to_bytes(&self) -> Vec<u8> {
@@ -13,6 +13,8 @@ use crate::{
     ParseError,
     MIDIChannel,
     MIDINote,
+    check_length,
+    invalid_field,
 };
 use crate::k4::{
     Level,
@@ -26,7 +28,9 @@ pub const SECTION_COUNT: usize = 8;
 
 /// Multi patch.
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MultiPatch {
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "crate::serialize::fixed_name::<_, 10>"))]
     pub name: String,
     pub volume: Level,
     pub effect: EffectNumber,
@@ -71,27 +75,31 @@ impl fmt::Display for MultiPatch {
 
 impl SystemExclusiveData for MultiPatch {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
+
         let mut offset: usize = 0;
         let start: usize = 0;
 
         // name = M0 ... M9
         let end = start + crate::k4::NAME_LENGTH;
 
-        let name = String::from_utf8(data[start..end].to_vec()).expect("Found invalid UTF-8");
+        let name = String::from_utf8(data[start..end].to_vec()).map_err(invalid_field("name", data, start))?;
         let name = str::replace(&name, char::from(0), " ").to_string();
 
         offset += crate::k4::NAME_LENGTH + 2;  // skip over name, volume and effect to sections
 
         let mut sections: [Section; SECTION_COUNT] = [Default::default(); SECTION_COUNT];
-        for i in 0..SECTION_COUNT {
-            sections[i] = Section::from_bytes(&data[offset .. offset + 8])?;
+        for (i, section) in sections.iter_mut().enumerate() {
+            let start = offset;
+            *section = Section::from_bytes(&data[offset .. offset + 8])
+                .map_err(|e| e.within(format!("sections[{}]", i), start))?;
             offset += 8;
         }
 
         Ok(MultiPatch {
             name,
-            volume: Level::try_new(data[10]).unwrap(),
-            effect: EffectNumber::try_new(data[11]).unwrap(),
+            volume: Level::try_new(data[10]).map_err(invalid_field("volume", data, 10))?,
+            effect: EffectNumber::from_bytes(&data[11..12]).map_err(|e| e.within("effect", 11))?,
             sections,
         })
     }
@@ -118,6 +126,7 @@ impl Checksum for MultiPatch {
 
 /// Section of a multi patch.
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Section {
     pub single_number: PatchNumber,
     pub zone: Zone,
@@ -135,7 +144,10 @@ impl Section {
     pub fn new() -> Section {
         Section {
             single_number: PatchNumber::try_new(0).unwrap(),
-            zone: Zone { low_key: Key { note: 0 }, high_key: Key { note: 127 } },
+            zone: Zone {
+                low_key: Key { note: MIDINote::try_new(0).unwrap() },
+                high_key: Key { note: MIDINote::try_new(127).unwrap() }
+            },
             velocity_switch: VelocitySwitch::All,
             receive_channel: MIDIChannel::try_new(1).unwrap(),  // use 1...16 for MIDI channel here
             is_muted: false,
@@ -156,17 +168,18 @@ impl Default for Section {
 
 impl SystemExclusiveData for Section {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(Section {
-            single_number: PatchNumber::try_new(data[0]).unwrap(),
-            zone: Zone::from_bytes(&[data[1], data[2]])?,
-            velocity_switch: VelocitySwitch::try_from((data[3] >> 4) & 0b0000_0011).unwrap(),
-            receive_channel: MIDIChannel::from_bytes(&[data[3] & 0b0000_1111])?,  // adjust MIDI channel to 1...16
+            single_number: PatchNumber::try_new(data[0]).map_err(invalid_field("single_number", data, 0))?,
+            zone: Zone::from_bytes(&data[1..3]).map_err(|e| e.within("zone", 1))?,
+            velocity_switch: VelocitySwitch::try_from((data[3] >> 4) & 0b0000_0011).map_err(invalid_field("velocity_switch", data, 3))?,
+            receive_channel: MIDIChannel::from_bytes(&[data[3] & 0b0000_1111]).map_err(|e| e.within("receive_channel", 3))?,  // adjust MIDI channel to 1...16
             is_muted: data[3] >> 6 == 1,
             out_select: data[4] & 0b0000_0111,
-            play_mode: PlayMode::try_from((data[4] >> 3) & 0b0000_0011).unwrap(),
-            level: Level::try_new(data[5]).unwrap(),
-            transpose: Transpose::from_bytes(&[data[6]])?,
-            tune: (data[7] as i8) - 50,
+            play_mode: PlayMode::try_from((data[4] >> 3) & 0b0000_0011).map_err(invalid_field("play_mode", data, 4))?,
+            level: Level::try_new(data[5]).map_err(invalid_field("level", data, 5))?,
+            transpose: Transpose::from_bytes(&data[6..7]).map_err(|e| e.within("transpose", 6))?,
+            tune: ((data[7] & 0x7f) as i8) - 50,
         })
     }
 
@@ -196,6 +209,7 @@ impl SystemExclusiveData for Section {
 
 /// Key in a keyboard zone.
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct Key {
     /// MIDI note number for the key.
     pub note: MIDINote,
@@ -210,6 +224,7 @@ impl Key {
 
 /// Keyboard zone.
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Zone {
     pub low_key: Key,
     pub high_key: Key,
@@ -225,16 +240,17 @@ impl fmt::Display for Zone {
 
 impl SystemExclusiveData for Zone {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(
             Zone {
-                low_key: Key { note: MIDINote::try_new(data[0].into()) },
-                high_key: Key { note: MIDINote::try_new(data[1].into())? }
+                low_key: Key { note: MIDINote::try_new(data[0].into()).map_err(invalid_field("low_key", data, 0))? },
+                high_key: Key { note: MIDINote::try_new(data[1].into()).map_err(invalid_field("high_key", data, 1))? }
             }
         )
     }
 
     fn to_bytes(&self) -> Vec<u8> {
-        vec![self.low_key.note.value(), self.high_key.note]
+        vec![self.low_key.note.value() as u8, self.high_key.note.value() as u8]
     }
 
     fn data_size() -> usize { 2 }
@@ -249,6 +265,8 @@ pub enum VelocitySwitch {
     Loud,
 }
 
+serialize_by_name!(VelocitySwitch);
+
 impl fmt::Display for VelocitySwitch {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", match self {
@@ -268,6 +286,8 @@ pub enum PlayMode {
     Mix,
 }
 
+serialize_by_name!(PlayMode);
+
 impl fmt::Display for PlayMode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", match self {
@@ -289,9 +309,8 @@ mod tests {
         multi::MultiPatch,
     };
     
-    use super::{*};
 
-    static DATA: &'static [u8] = include_bytes!("A401.SYX");
+    static DATA: &[u8] = include_bytes!("A401.SYX");
 
     #[test]
     fn test_multi_patch_from_bytes() {
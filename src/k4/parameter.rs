@@ -0,0 +1,1080 @@
+//! Parameter send messages for real-time editing.
+//!
+//! A parameter send message changes one parameter of the patch
+//! that is currently being edited on the K4:
+//!
+//! F0H 40H 0nH 10H 00H 04H 0ppp pppp 0iii iiiv 0vvv vvvv F7H
+//!
+//! where `n` is the MIDI channel, `p` is the parameter number,
+//! `i` is the index of the source, filter, section, drum note or
+//! submix the parameter belongs to, and `v` is the value,
+//! with its most significant bit packed together with the index.
+//!
+//! The values are encoded like in the patch data, so for example
+//! a modulation depth of -50~+50 is sent as 0~100, and the source
+//! wave number 1~256 is sent as 0~255.
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use num_enum::TryFromPrimitive;
+
+use crate::{
+    SystemExclusiveData,
+    ParseError,
+    FieldError,
+    ValueError,
+    MIDIChannel,
+    MIDINote,
+    check_length,
+    invalid_field,
+};
+use crate::k4::{
+    Level,
+    ModulationDepth,
+    EffectNumber,
+    EnvelopeTime,
+    FilterEnvelopeLevel,
+    Cutoff,
+    Resonance,
+    Curve,
+    Coarse,
+    Fine,
+    WaveNumber,
+    PatchNumber,
+    Transpose,
+    Channel,
+    Decay,
+    SmallEffectParameter,
+    BigEffectParameter,
+    SOURCE_COUNT,
+    DRUM_NOTE_COUNT,
+    SUBMIX_COUNT,
+    single::{self, SinglePatch},
+    multi::{MultiPatch, VelocitySwitch, PlayMode},
+    drum::DrumPatch,
+    effect::{self, EffectPatch, Effect, Submix},
+    filter::Filter,
+    lfo::Shape,
+    sysex::{Header, Function},
+};
+
+/// Number of filters in a single patch.
+const FILTER_COUNT: usize = 2;
+
+/// Number of sections in a multi patch.
+const SECTION_COUNT: usize = 8;
+
+/// Offset of the low bits of the value in a parameter send message.
+const VALUE_OFFSET: usize = 8;
+
+/// Single patch parameter, numbered in the order it appears in the patch data.
+/// The source and amplifier parameters are indexed by source (0~3),
+/// and the filter parameters by filter (0~1).
+#[derive(Debug, Eq, PartialEq, Copy, Clone, TryFromPrimitive)]
+#[repr(u8)]
+pub enum SingleParameter {
+    Volume = 0,
+    Effect,
+    Submix,
+    SourceMode,
+    PolyphonyMode,
+    Am12,
+    Am34,
+    SourceMute,
+    VibratoShape,
+    BenderRange,
+    WheelAssign,
+    VibratoSpeed,
+    WheelDepth,
+    AutoBendTime,
+    AutoBendDepth,
+    AutoBendKeyScalingTime,
+    AutoBendVelocityDepth,
+    VibratoPressure,
+    VibratoDepth,
+    LfoShape,
+    LfoSpeed,
+    LfoDelay,
+    LfoDepth,
+    LfoPressureDepth,
+    PressFreq,
+
+    SourceDelay,
+    SourceWave,
+    SourceKeyScalingCurve,
+    SourceCoarse,
+    SourceKeyTrack,
+    SourceFixedKey,
+    SourceFine,
+    SourcePressFreq,
+    SourceVibrato,
+    SourceVelocityCurve,
+
+    AmpLevel,
+    AmpAttack,
+    AmpDecay,
+    AmpSustain,
+    AmpRelease,
+    AmpLevelVelocityDepth,
+    AmpLevelPressureDepth,
+    AmpLevelKeyScalingDepth,
+    AmpTimeAttackVelocity,
+    AmpTimeReleaseVelocity,
+    AmpTimeKeyScaling,
+
+    FilterCutoff,
+    FilterResonance,
+    FilterCutoffVelocityDepth,
+    FilterCutoffPressureDepth,
+    FilterCutoffKeyScalingDepth,
+    FilterLfoModulatesCutoff,
+    FilterEnvelopeDepth,
+    FilterEnvelopeVelocityDepth,
+    FilterAttack,
+    FilterDecay,
+    FilterSustain,
+    FilterRelease,
+    FilterTimeAttackVelocity,
+    FilterTimeReleaseVelocity,
+    FilterTimeKeyScaling,
+}
+
+impl SingleParameter {
+    /// Returns the number of indexes this parameter has.
+    pub fn index_count(&self) -> usize {
+        use SingleParameter::*;
+        match self {
+            SourceMute => SOURCE_COUNT,
+            p if (SourceDelay as u8..=AmpTimeKeyScaling as u8).contains(&(*p as u8)) => SOURCE_COUNT,
+            p if (FilterCutoff as u8..=FilterTimeKeyScaling as u8).contains(&(*p as u8)) => FILTER_COUNT,
+            _ => 1,
+        }
+    }
+
+    /// Returns the path of the patch field for this parameter at `index`.
+    pub fn path(&self, index: usize) -> String {
+        use SingleParameter::*;
+        let field = match self {
+            Volume => "volume",
+            Effect => "effect",
+            Submix => "submix",
+            SourceMode => "source_mode",
+            PolyphonyMode => "polyphony_mode",
+            Am12 => "am12",
+            Am34 => "am34",
+            SourceMute => return format!("source_mutes[{}]", index),
+            VibratoShape => "vibrato.shape",
+            BenderRange => "bender_range",
+            WheelAssign => "wheel_assign",
+            VibratoSpeed => "vibrato.speed",
+            WheelDepth => "wheel_depth",
+            AutoBendTime => "auto_bend.time",
+            AutoBendDepth => "auto_bend.depth",
+            AutoBendKeyScalingTime => "auto_bend.key_scaling_time",
+            AutoBendVelocityDepth => "auto_bend.velocity_depth",
+            VibratoPressure => "vibrato.pressure",
+            VibratoDepth => "vibrato.depth",
+            LfoShape => "lfo.shape",
+            LfoSpeed => "lfo.speed",
+            LfoDelay => "lfo.delay",
+            LfoDepth => "lfo.depth",
+            LfoPressureDepth => "lfo.pressure_depth",
+            PressFreq => "press_freq",
+            SourceDelay => "delay",
+            SourceWave => "wave",
+            SourceKeyScalingCurve => "ks_curve",
+            SourceCoarse => "coarse",
+            SourceKeyTrack => "key_track",
+            SourceFixedKey => "fixed_key",
+            SourceFine => "fine",
+            SourcePressFreq => "press_freq",
+            SourceVibrato => "vibrato",
+            SourceVelocityCurve => "velocity_curve",
+            AmpLevel => "level",
+            AmpAttack => "envelope.attack",
+            AmpDecay => "envelope.decay",
+            AmpSustain => "envelope.sustain",
+            AmpRelease => "envelope.release",
+            AmpLevelVelocityDepth => "level_modulation.velocity_depth",
+            AmpLevelPressureDepth => "level_modulation.pressure_depth",
+            AmpLevelKeyScalingDepth => "level_modulation.key_scaling_depth",
+            AmpTimeAttackVelocity => "time_modulation.attack_velocity",
+            AmpTimeReleaseVelocity => "time_modulation.release_velocity",
+            AmpTimeKeyScaling => "time_modulation.key_scaling",
+            FilterCutoff => "cutoff",
+            FilterResonance => "resonance",
+            FilterCutoffVelocityDepth => "cutoff_mod.velocity_depth",
+            FilterCutoffPressureDepth => "cutoff_mod.pressure_depth",
+            FilterCutoffKeyScalingDepth => "cutoff_mod.key_scaling_depth",
+            FilterLfoModulatesCutoff => "lfo_modulates_cutoff",
+            FilterEnvelopeDepth => "env_depth",
+            FilterEnvelopeVelocityDepth => "env_vel_depth",
+            FilterAttack => "envelope.attack",
+            FilterDecay => "envelope.decay",
+            FilterSustain => "envelope.sustain",
+            FilterRelease => "envelope.release",
+            FilterTimeAttackVelocity => "time_mod.attack_velocity",
+            FilterTimeReleaseVelocity => "time_mod.release_velocity",
+            FilterTimeKeyScaling => "time_mod.key_scaling",
+        };
+
+        match self.index_count() {
+            SOURCE_COUNT if (*self as u8) < SingleParameter::AmpLevel as u8 => format!("sources[{}].{}", index, field),
+            SOURCE_COUNT => format!("amplifiers[{}].{}", index, field),
+            FILTER_COUNT => format!("filter{}.{}", index + 1, field),
+            _ => field.to_string(),
+        }
+    }
+}
+
+/// Multi patch parameter. The section parameters are indexed by section (0~7).
+#[derive(Debug, Eq, PartialEq, Copy, Clone, TryFromPrimitive)]
+#[repr(u8)]
+pub enum MultiParameter {
+    Volume = 64,
+    Effect,
+    SingleNumber,
+    ZoneLow,
+    ZoneHigh,
+    VelocitySwitch,
+    ReceiveChannel,
+    Mute,
+    OutSelect,
+    PlayMode,
+    Level,
+    Transpose,
+    Tune,
+}
+
+impl MultiParameter {
+    /// Returns the number of indexes this parameter has.
+    pub fn index_count(&self) -> usize {
+        match self {
+            MultiParameter::Volume | MultiParameter::Effect => 1,
+            _ => SECTION_COUNT,
+        }
+    }
+
+    /// Returns the path of the patch field for this parameter at `index`.
+    pub fn path(&self, index: usize) -> String {
+        let field = match self {
+            MultiParameter::Volume => return "volume".to_string(),
+            MultiParameter::Effect => return "effect".to_string(),
+            MultiParameter::SingleNumber => "single_number",
+            MultiParameter::ZoneLow => "zone.low_key",
+            MultiParameter::ZoneHigh => "zone.high_key",
+            MultiParameter::VelocitySwitch => "velocity_switch",
+            MultiParameter::ReceiveChannel => "receive_channel",
+            MultiParameter::Mute => "is_muted",
+            MultiParameter::OutSelect => "out_select",
+            MultiParameter::PlayMode => "play_mode",
+            MultiParameter::Level => "level",
+            MultiParameter::Transpose => "transpose",
+            MultiParameter::Tune => "tune",
+        };
+        format!("sections[{}].{}", index, field)
+    }
+}
+
+/// Drum patch parameter. The note parameters are indexed by note (0~60).
+#[derive(Debug, Eq, PartialEq, Copy, Clone, TryFromPrimitive)]
+#[repr(u8)]
+pub enum DrumParameter {
+    Channel = 80,
+    Volume,
+    VelocityDepth,
+    Submix,
+    Wave1,
+    Wave2,
+    Decay1,
+    Decay2,
+    Tune1,
+    Tune2,
+    Level1,
+    Level2,
+}
+
+impl DrumParameter {
+    /// Returns the number of indexes this parameter has.
+    pub fn index_count(&self) -> usize {
+        match self {
+            DrumParameter::Channel | DrumParameter::Volume | DrumParameter::VelocityDepth => 1,
+            _ => DRUM_NOTE_COUNT,
+        }
+    }
+
+    /// Returns the path of the patch field for this parameter at `index`.
+    pub fn path(&self, index: usize) -> String {
+        let field = match self {
+            DrumParameter::Channel => return "common.channel".to_string(),
+            DrumParameter::Volume => return "common.volume".to_string(),
+            DrumParameter::VelocityDepth => return "common.velocity_depth".to_string(),
+            DrumParameter::Submix => "submix",
+            DrumParameter::Wave1 => "source1.wave",
+            DrumParameter::Wave2 => "source2.wave",
+            DrumParameter::Decay1 => "source1.decay",
+            DrumParameter::Decay2 => "source2.decay",
+            DrumParameter::Tune1 => "source1.tune",
+            DrumParameter::Tune2 => "source2.tune",
+            DrumParameter::Level1 => "source1.level",
+            DrumParameter::Level2 => "source2.level",
+        };
+        format!("notes[{}].{}", index, field)
+    }
+}
+
+/// Effect patch parameter. The submix parameters are indexed by submix (0~7).
+#[derive(Debug, Eq, PartialEq, Copy, Clone, TryFromPrimitive)]
+#[repr(u8)]
+pub enum EffectParameter {
+    Effect = 96,
+    Param1,
+    Param2,
+    Param3,
+    Pan,
+    Send1,
+    Send2,
+}
+
+impl EffectParameter {
+    /// Returns the number of indexes this parameter has.
+    pub fn index_count(&self) -> usize {
+        match self {
+            EffectParameter::Pan | EffectParameter::Send1 | EffectParameter::Send2 => SUBMIX_COUNT,
+            _ => 1,
+        }
+    }
+
+    /// Returns the path of the patch field for this parameter at `index`.
+    pub fn path(&self, index: usize) -> String {
+        match self {
+            EffectParameter::Effect => "effect".to_string(),
+            EffectParameter::Param1 => "param1".to_string(),
+            EffectParameter::Param2 => "param2".to_string(),
+            EffectParameter::Param3 => "param3".to_string(),
+            EffectParameter::Pan => format!("submixes[{}].pan", index),
+            EffectParameter::Send1 => format!("submixes[{}].send1", index),
+            EffectParameter::Send2 => format!("submixes[{}].send2", index),
+        }
+    }
+}
+
+/// Any editable K4 parameter.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Parameter {
+    Single(SingleParameter),
+    Multi(MultiParameter),
+    Drum(DrumParameter),
+    Effect(EffectParameter),
+}
+
+impl Parameter {
+    /// Returns the parameter number used in the parameter send message.
+    pub fn number(&self) -> u8 {
+        match self {
+            Parameter::Single(p) => *p as u8,
+            Parameter::Multi(p) => *p as u8,
+            Parameter::Drum(p) => *p as u8,
+            Parameter::Effect(p) => *p as u8,
+        }
+    }
+
+    /// Returns the parameter with the number `number`, if there is one.
+    pub fn from_number(number: u8) -> Option<Parameter> {
+        SingleParameter::try_from(number).map(Parameter::Single)
+            .or_else(|_| MultiParameter::try_from(number).map(Parameter::Multi))
+            .or_else(|_| DrumParameter::try_from(number).map(Parameter::Drum))
+            .or_else(|_| EffectParameter::try_from(number).map(Parameter::Effect))
+            .ok()
+    }
+
+    /// Returns the number of indexes this parameter has.
+    pub fn index_count(&self) -> usize {
+        match self {
+            Parameter::Single(p) => p.index_count(),
+            Parameter::Multi(p) => p.index_count(),
+            Parameter::Drum(p) => p.index_count(),
+            Parameter::Effect(p) => p.index_count(),
+        }
+    }
+
+    /// Returns the path of the patch field for this parameter at `index`.
+    pub fn path(&self, index: usize) -> String {
+        match self {
+            Parameter::Single(p) => p.path(index),
+            Parameter::Multi(p) => p.path(index),
+            Parameter::Drum(p) => p.path(index),
+            Parameter::Effect(p) => p.path(index),
+        }
+    }
+}
+
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Parameter::Single(p) => write!(f, "Single {:?}", p),
+            Parameter::Multi(p) => write!(f, "Multi {:?}", p),
+            Parameter::Drum(p) => write!(f, "Drum {:?}", p),
+            Parameter::Effect(p) => write!(f, "Effect {:?}", p),
+        }
+    }
+}
+
+/// Parameter send message.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct ParameterChange {
+    pub channel: MIDIChannel,
+    pub parameter: Parameter,
+    pub index: usize,
+    pub value: u8,
+}
+
+impl ParameterChange {
+    /// Makes a parameter change with the current value of
+    /// `parameter` at `index` in the single patch.
+    /// Fails if `index` is out of range for the parameter.
+    pub fn from_single(channel: MIDIChannel, patch: &SinglePatch, parameter: SingleParameter, index: usize) -> Result<Self, ParseError> {
+        let mut change = ParameterChange {
+            channel,
+            parameter: Parameter::Single(parameter),
+            index,
+            value: 0,
+        };
+        change.check_index()?;
+        change.value = single_value(patch, parameter, index);
+        Ok(change)
+    }
+
+    /// Makes a parameter change with the current value of
+    /// `parameter` at `index` in the multi patch.
+    /// Fails if `index` is out of range for the parameter.
+    pub fn from_multi(channel: MIDIChannel, patch: &MultiPatch, parameter: MultiParameter, index: usize) -> Result<Self, ParseError> {
+        let mut change = ParameterChange {
+            channel,
+            parameter: Parameter::Multi(parameter),
+            index,
+            value: 0,
+        };
+        change.check_index()?;
+        change.value = multi_value(patch, parameter, index);
+        Ok(change)
+    }
+
+    /// Makes a parameter change with the current value of
+    /// `parameter` at `index` in the drum patch.
+    /// Fails if `index` is out of range for the parameter.
+    pub fn from_drum(channel: MIDIChannel, patch: &DrumPatch, parameter: DrumParameter, index: usize) -> Result<Self, ParseError> {
+        let mut change = ParameterChange {
+            channel,
+            parameter: Parameter::Drum(parameter),
+            index,
+            value: 0,
+        };
+        change.check_index()?;
+        change.value = drum_value(patch, parameter, index);
+        Ok(change)
+    }
+
+    /// Makes a parameter change with the current value of
+    /// `parameter` at `index` in the effect patch.
+    /// Fails if `index` is out of range for the parameter.
+    pub fn from_effect(channel: MIDIChannel, patch: &EffectPatch, parameter: EffectParameter, index: usize) -> Result<Self, ParseError> {
+        let mut change = ParameterChange {
+            channel,
+            parameter: Parameter::Effect(parameter),
+            index,
+            value: 0,
+        };
+        change.check_index()?;
+        change.value = effect_value(patch, parameter, index);
+        Ok(change)
+    }
+
+    /// Applies this change to the single patch.
+    /// Fails if this is not a single parameter, or if the value is out of range.
+    pub fn apply_to_single(&self, patch: &mut SinglePatch) -> Result<(), ParseError> {
+        match self.parameter {
+            Parameter::Single(parameter) => {
+                self.check_index()?;
+                apply_single(patch, parameter, self.index, self.value)
+                    .map_err(|e| self.field_error(e))
+            },
+            _ => Err(self.wrong_kind("single")),
+        }
+    }
+
+    /// Applies this change to the multi patch.
+    /// Fails if this is not a multi parameter, or if the value is out of range.
+    pub fn apply_to_multi(&self, patch: &mut MultiPatch) -> Result<(), ParseError> {
+        match self.parameter {
+            Parameter::Multi(parameter) => {
+                self.check_index()?;
+                apply_multi(patch, parameter, self.index, self.value)
+                    .map_err(|e| self.field_error(e))
+            },
+            _ => Err(self.wrong_kind("multi")),
+        }
+    }
+
+    /// Applies this change to the drum patch.
+    /// Fails if this is not a drum parameter, or if the value is out of range.
+    pub fn apply_to_drum(&self, patch: &mut DrumPatch) -> Result<(), ParseError> {
+        match self.parameter {
+            Parameter::Drum(parameter) => {
+                self.check_index()?;
+                apply_drum(patch, parameter, self.index, self.value)
+                    .map_err(|e| self.field_error(e))
+            },
+            _ => Err(self.wrong_kind("drum")),
+        }
+    }
+
+    /// Applies this change to the effect patch.
+    /// Fails if this is not an effect parameter, or if the value is out of range.
+    pub fn apply_to_effect(&self, patch: &mut EffectPatch) -> Result<(), ParseError> {
+        match self.parameter {
+            Parameter::Effect(parameter) => {
+                self.check_index()?;
+                apply_effect(patch, parameter, self.index, self.value)
+                    .map_err(|e| self.field_error(e))
+            },
+            _ => Err(self.wrong_kind("effect")),
+        }
+    }
+
+    /// Returns the header of the parameter send message.
+    pub fn header(&self) -> Header {
+        Header {
+            channel: self.channel,
+            function: Function::ParameterSend,
+            substatus1: self.parameter.number(),
+            substatus2: ((self.index as u8) << 1) | (self.value >> 7),
+        }
+    }
+
+    fn check_index(&self) -> Result<(), ParseError> {
+        if self.index < self.parameter.index_count() {
+            Ok(())
+        } else {
+            Err(ParseError::InvalidField(FieldError {
+                path: self.parameter.path(self.index),
+                offset: VALUE_OFFSET - 1,
+                value: self.index as u8,
+                reason: ValueError(0, self.parameter.index_count() as i32 - 1, self.index as i32).to_string(),
+            }))
+        }
+    }
+
+    fn field_error(&self, reason: String) -> ParseError {
+        ParseError::InvalidField(FieldError {
+            path: self.parameter.path(self.index),
+            offset: VALUE_OFFSET,
+            value: self.value,
+            reason,
+        })
+    }
+
+    fn wrong_kind(&self, kind: &str) -> ParseError {
+        self.field_error(format!("{} is not a {} parameter", self.parameter, kind))
+    }
+}
+
+impl fmt::Display for ParameterChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} = {}, channel {}",
+            self.parameter.path(self.index), self.value, self.channel.value())
+    }
+}
+
+impl SystemExclusiveData for ParameterChange {
+    /// Parses a complete parameter send message, from F0H to F7H.
+    fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
+
+        if data[0] != 0xF0 {
+            return Err(invalid_field("initiator", data, 0)("expected F0H"));
+        }
+        if data[1] != 0x40 {
+            return Err(invalid_field("manufacturer", data, 1)("expected Kawai (40H)"));
+        }
+        let header = Header::from_bytes(&data[2..]).map_err(|e| e.offset_by(2))?;
+        if header.function != Function::ParameterSend {
+            return Err(invalid_field("function", data, 3)("expected parameter send (10H)"));
+        }
+        if data[4] != 0x00 || data[5] != 0x04 {
+            return Err(invalid_field("machine", data, 5)("expected K4 (00H 04H)"));
+        }
+        if data[9] != 0xF7 {
+            return Err(invalid_field("terminator", data, 9)("expected F7H"));
+        }
+
+        let parameter = Parameter::from_number(header.substatus1)
+            .ok_or_else(|| invalid_field("parameter", data, 6)("unknown parameter number"))?;
+
+        let change = ParameterChange {
+            channel: header.channel,
+            parameter,
+            index: ((header.substatus2 >> 1) & 0x3f) as usize,
+            value: ((header.substatus2 & 0x01) << 7) | (data[VALUE_OFFSET] & 0x7f),
+        };
+        change.check_index()?;
+        Ok(change)
+    }
+
+    /// Emits the complete parameter send message, from F0H to F7H.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut result = vec![0xF0, 0x40];
+        result.extend(self.header().to_bytes());
+        result.push(self.value & 0x7f);
+        result.push(0xF7);
+        result
+    }
+
+    fn data_size() -> usize { 10 }
+}
+
+/// Checks that `value` is in the range `min..=max`.
+fn ranged(value: u8, min: u8, max: u8) -> Result<u8, ValueError> {
+    if (min..=max).contains(&value) {
+        Ok(value)
+    } else {
+        Err(ValueError(min as i32, max as i32, value as i32))
+    }
+}
+
+/// Converts a value of 0~100 to a signed depth of -50~+50.
+fn depth(value: u8) -> Result<ModulationDepth, String> {
+    ModulationDepth::try_new(value as i8 - 50).map_err(|e| e.to_string())
+}
+
+fn filter(patch: &SinglePatch, index: usize) -> &Filter {
+    if index == 0 { &patch.filter1 } else { &patch.filter2 }
+}
+
+fn filter_mut(patch: &mut SinglePatch, index: usize) -> &mut Filter {
+    if index == 0 { &mut patch.filter1 } else { &mut patch.filter2 }
+}
+
+fn single_value(patch: &SinglePatch, parameter: SingleParameter, index: usize) -> u8 {
+    use SingleParameter::*;
+
+    let source = &patch.sources[index];
+    let amp = &patch.amplifiers[index];
+    let filter = filter(patch, index);
+
+    match parameter {
+        Volume => patch.volume.into_inner(),
+        Effect => patch.effect.into_inner() - 1,
+        Submix => patch.submix as u8,
+        SourceMode => patch.source_mode as u8,
+        PolyphonyMode => patch.polyphony_mode as u8,
+        Am12 => patch.am12 as u8,
+        Am34 => patch.am34 as u8,
+        SourceMute => !patch.source_mutes[index] as u8,  // 0/mute, 1/not mute
+        VibratoShape => patch.vibrato.shape as u8,
+        BenderRange => patch.bender_range,
+        WheelAssign => patch.wheel_assign as u8,
+        VibratoSpeed => patch.vibrato.speed.into_inner(),
+        WheelDepth => (patch.wheel_depth + 50) as u8,
+        AutoBendTime => patch.auto_bend.time.into_inner(),
+        AutoBendDepth => (patch.auto_bend.depth.into_inner() + 50) as u8,
+        AutoBendKeyScalingTime => (patch.auto_bend.key_scaling_time.into_inner() + 50) as u8,
+        AutoBendVelocityDepth => (patch.auto_bend.velocity_depth.into_inner() + 50) as u8,
+        VibratoPressure => (patch.vibrato.pressure.into_inner() + 50) as u8,
+        VibratoDepth => (patch.vibrato.depth.into_inner() + 50) as u8,
+        LfoShape => patch.lfo.shape as u8,
+        LfoSpeed => patch.lfo.speed.into_inner(),
+        LfoDelay => patch.lfo.delay.into_inner(),
+        LfoDepth => (patch.lfo.depth.into_inner() + 50) as u8,
+        LfoPressureDepth => (patch.lfo.pressure_depth.into_inner() + 50) as u8,
+        PressFreq => (patch.press_freq + 50) as u8,
+
+        SourceDelay => source.delay.into_inner(),
+        SourceWave => (source.wave.number.into_inner() - 1) as u8,
+        SourceKeyScalingCurve => source.ks_curve.into_inner() - 1,
+        SourceCoarse => (source.coarse.into_inner() + 24) as u8,
+        SourceKeyTrack => source.key_track as u8,
+        SourceFixedKey => source.fixed_key.value() as u8,
+        SourceFine => (source.fine.into_inner() + 50) as u8,
+        SourcePressFreq => source.press_freq as u8,
+        SourceVibrato => source.vibrato as u8,
+        SourceVelocityCurve => source.velocity_curve.into_inner() - 1,
+
+        AmpLevel => amp.level.into_inner(),
+        AmpAttack => amp.envelope.attack.into_inner(),
+        AmpDecay => amp.envelope.decay.into_inner(),
+        AmpSustain => amp.envelope.sustain.into_inner(),
+        AmpRelease => amp.envelope.release.into_inner(),
+        AmpLevelVelocityDepth => (amp.level_modulation.velocity_depth.into_inner() + 50) as u8,
+        AmpLevelPressureDepth => (amp.level_modulation.pressure_depth.into_inner() + 50) as u8,
+        AmpLevelKeyScalingDepth => (amp.level_modulation.key_scaling_depth.into_inner() + 50) as u8,
+        AmpTimeAttackVelocity => (amp.time_modulation.attack_velocity.into_inner() + 50) as u8,
+        AmpTimeReleaseVelocity => (amp.time_modulation.release_velocity.into_inner() + 50) as u8,
+        AmpTimeKeyScaling => (amp.time_modulation.key_scaling.into_inner() + 50) as u8,
+
+        FilterCutoff => filter.cutoff.into_inner(),
+        FilterResonance => filter.resonance.into_inner(),
+        FilterCutoffVelocityDepth => (filter.cutoff_mod.velocity_depth.into_inner() + 50) as u8,
+        FilterCutoffPressureDepth => (filter.cutoff_mod.pressure_depth.into_inner() + 50) as u8,
+        FilterCutoffKeyScalingDepth => (filter.cutoff_mod.key_scaling_depth.into_inner() + 50) as u8,
+        FilterLfoModulatesCutoff => filter.lfo_modulates_cutoff as u8,
+        FilterEnvelopeDepth => (filter.env_depth.into_inner() + 50) as u8,
+        FilterEnvelopeVelocityDepth => (filter.env_vel_depth.into_inner() + 50) as u8,
+        FilterAttack => filter.envelope.attack.into_inner(),
+        FilterDecay => filter.envelope.decay.into_inner(),
+        FilterSustain => (filter.envelope.sustain.into_inner() + 50) as u8,
+        FilterRelease => filter.envelope.release.into_inner(),
+        FilterTimeAttackVelocity => (filter.time_mod.attack_velocity.into_inner() + 50) as u8,
+        FilterTimeReleaseVelocity => (filter.time_mod.release_velocity.into_inner() + 50) as u8,
+        FilterTimeKeyScaling => (filter.time_mod.key_scaling.into_inner() + 50) as u8,
+    }
+}
+
+fn apply_single(patch: &mut SinglePatch, parameter: SingleParameter, index: usize, value: u8) -> Result<(), String> {
+    use SingleParameter::*;
+
+    let e = |e: ValueError| e.to_string();
+
+    match parameter {
+        Volume => patch.volume = Level::try_new(value).map_err(|e| e.to_string())?,
+        Effect => patch.effect = EffectNumber::try_new(value.saturating_add(1)).map_err(|e| e.to_string())?,
+        Submix => patch.submix = effect::Submix::try_from(value).map_err(|e| e.to_string())?,
+        SourceMode => patch.source_mode = single::SourceMode::try_from(value).map_err(|e| e.to_string())?,
+        PolyphonyMode => patch.polyphony_mode = single::PolyphonyMode::try_from(value).map_err(|e| e.to_string())?,
+        Am12 => patch.am12 = ranged(value, 0, 1).map_err(e)? == 1,
+        Am34 => patch.am34 = ranged(value, 0, 1).map_err(e)? == 1,
+        SourceMute => patch.source_mutes[index] = ranged(value, 0, 1).map_err(e)? == 0,
+        VibratoShape => patch.vibrato.shape = Shape::try_from(value).map_err(|e| e.to_string())?,
+        BenderRange => patch.bender_range = ranged(value, 0, 12).map_err(e)?,
+        WheelAssign => patch.wheel_assign = single::WheelAssign::try_from(value).map_err(|e| e.to_string())?,
+        VibratoSpeed => patch.vibrato.speed = Level::try_new(value).map_err(|e| e.to_string())?,
+        WheelDepth => patch.wheel_depth = ranged(value, 0, 100).map_err(e)? as i8 - 50,
+        AutoBendTime => patch.auto_bend.time = Level::try_new(value).map_err(|e| e.to_string())?,
+        AutoBendDepth => patch.auto_bend.depth = depth(value)?,
+        AutoBendKeyScalingTime => patch.auto_bend.key_scaling_time = depth(value)?,
+        AutoBendVelocityDepth => patch.auto_bend.velocity_depth = depth(value)?,
+        VibratoPressure => patch.vibrato.pressure = depth(value)?,
+        VibratoDepth => patch.vibrato.depth = depth(value)?,
+        LfoShape => patch.lfo.shape = Shape::try_from(value).map_err(|e| e.to_string())?,
+        LfoSpeed => patch.lfo.speed = Level::try_new(value).map_err(|e| e.to_string())?,
+        LfoDelay => patch.lfo.delay = Level::try_new(value).map_err(|e| e.to_string())?,
+        LfoDepth => patch.lfo.depth = depth(value)?,
+        LfoPressureDepth => patch.lfo.pressure_depth = depth(value)?,
+        PressFreq => patch.press_freq = ranged(value, 0, 100).map_err(e)? as i8 - 50,
+
+        SourceDelay => patch.sources[index].delay = Level::try_new(value).map_err(|e| e.to_string())?,
+        SourceWave => patch.sources[index].wave.number = WaveNumber::try_new(value as u16 + 1).map_err(|e| e.to_string())?,
+        SourceKeyScalingCurve => patch.sources[index].ks_curve = Curve::try_new(ranged(value, 0, 7).map_err(e)? + 1).map_err(|e| e.to_string())?,
+        SourceCoarse => patch.sources[index].coarse = Coarse::try_new(ranged(value, 0, 48).map_err(e)? as i8 - 24).map_err(|e| e.to_string())?,
+        SourceKeyTrack => patch.sources[index].key_track = ranged(value, 0, 1).map_err(e)? == 1,
+        SourceFixedKey => patch.sources[index].fixed_key = MIDINote::try_new(value as i32).map_err(e)?,
+        SourceFine => patch.sources[index].fine = Fine::try_new(ranged(value, 0, 100).map_err(e)? as i8 - 50).map_err(|e| e.to_string())?,
+        SourcePressFreq => patch.sources[index].press_freq = ranged(value, 0, 1).map_err(e)? == 1,
+        SourceVibrato => patch.sources[index].vibrato = ranged(value, 0, 1).map_err(e)? == 1,
+        SourceVelocityCurve => patch.sources[index].velocity_curve = Curve::try_new(ranged(value, 0, 7).map_err(e)? + 1).map_err(|e| e.to_string())?,
+
+        AmpLevel => patch.amplifiers[index].level = Level::try_new(value).map_err(|e| e.to_string())?,
+        AmpAttack => patch.amplifiers[index].envelope.attack = EnvelopeTime::try_new(value).map_err(|e| e.to_string())?,
+        AmpDecay => patch.amplifiers[index].envelope.decay = EnvelopeTime::try_new(value).map_err(|e| e.to_string())?,
+        AmpSustain => patch.amplifiers[index].envelope.sustain = EnvelopeTime::try_new(value).map_err(|e| e.to_string())?,
+        AmpRelease => patch.amplifiers[index].envelope.release = EnvelopeTime::try_new(value).map_err(|e| e.to_string())?,
+        AmpLevelVelocityDepth => patch.amplifiers[index].level_modulation.velocity_depth = depth(value)?,
+        AmpLevelPressureDepth => patch.amplifiers[index].level_modulation.pressure_depth = depth(value)?,
+        AmpLevelKeyScalingDepth => patch.amplifiers[index].level_modulation.key_scaling_depth = depth(value)?,
+        AmpTimeAttackVelocity => patch.amplifiers[index].time_modulation.attack_velocity = depth(value)?,
+        AmpTimeReleaseVelocity => patch.amplifiers[index].time_modulation.release_velocity = depth(value)?,
+        AmpTimeKeyScaling => patch.amplifiers[index].time_modulation.key_scaling = depth(value)?,
+
+        FilterCutoff => filter_mut(patch, index).cutoff = Cutoff::try_new(value).map_err(|e| e.to_string())?,
+        FilterResonance => filter_mut(patch, index).resonance = Resonance::try_new(value).map_err(|e| e.to_string())?,
+        FilterCutoffVelocityDepth => filter_mut(patch, index).cutoff_mod.velocity_depth = depth(value)?,
+        FilterCutoffPressureDepth => filter_mut(patch, index).cutoff_mod.pressure_depth = depth(value)?,
+        FilterCutoffKeyScalingDepth => filter_mut(patch, index).cutoff_mod.key_scaling_depth = depth(value)?,
+        FilterLfoModulatesCutoff => filter_mut(patch, index).lfo_modulates_cutoff = ranged(value, 0, 1).map_err(e)? == 1,
+        FilterEnvelopeDepth => filter_mut(patch, index).env_depth = depth(value)?,
+        FilterEnvelopeVelocityDepth => filter_mut(patch, index).env_vel_depth = depth(value)?,
+        FilterAttack => filter_mut(patch, index).envelope.attack = EnvelopeTime::try_new(value).map_err(|e| e.to_string())?,
+        FilterDecay => filter_mut(patch, index).envelope.decay = EnvelopeTime::try_new(value).map_err(|e| e.to_string())?,
+        FilterSustain => filter_mut(patch, index).envelope.sustain = FilterEnvelopeLevel::try_new(ranged(value, 0, 100).map_err(e)? as i8 - 50).map_err(|e| e.to_string())?,
+        FilterRelease => filter_mut(patch, index).envelope.release = EnvelopeTime::try_new(value).map_err(|e| e.to_string())?,
+        FilterTimeAttackVelocity => filter_mut(patch, index).time_mod.attack_velocity = depth(value)?,
+        FilterTimeReleaseVelocity => filter_mut(patch, index).time_mod.release_velocity = depth(value)?,
+        FilterTimeKeyScaling => filter_mut(patch, index).time_mod.key_scaling = depth(value)?,
+    }
+
+    Ok(())
+}
+
+fn multi_value(patch: &MultiPatch, parameter: MultiParameter, index: usize) -> u8 {
+    let section = &patch.sections[index];
+    match parameter {
+        MultiParameter::Volume => patch.volume.into_inner(),
+        MultiParameter::Effect => patch.effect.into_inner() - 1,
+        MultiParameter::SingleNumber => section.single_number.into_inner(),
+        MultiParameter::ZoneLow => section.zone.low_key.note.value() as u8,
+        MultiParameter::ZoneHigh => section.zone.high_key.note.value() as u8,
+        MultiParameter::VelocitySwitch => section.velocity_switch as u8,
+        MultiParameter::ReceiveChannel => section.receive_channel.value() as u8 - 1,
+        MultiParameter::Mute => section.is_muted as u8,
+        MultiParameter::OutSelect => section.out_select,
+        MultiParameter::PlayMode => section.play_mode as u8,
+        MultiParameter::Level => section.level.into_inner(),
+        MultiParameter::Transpose => (section.transpose.into_inner() + 24) as u8,
+        MultiParameter::Tune => (section.tune + 50) as u8,
+    }
+}
+
+fn apply_multi(patch: &mut MultiPatch, parameter: MultiParameter, index: usize, value: u8) -> Result<(), String> {
+    let e = |e: ValueError| e.to_string();
+
+    match parameter {
+        MultiParameter::Volume => patch.volume = Level::try_new(value).map_err(|e| e.to_string())?,
+        MultiParameter::Effect => patch.effect = EffectNumber::try_new(value.saturating_add(1)).map_err(|e| e.to_string())?,
+        _ => {
+            let section = &mut patch.sections[index];
+            match parameter {
+                MultiParameter::SingleNumber => section.single_number = PatchNumber::try_new(value).map_err(|e| e.to_string())?,
+                MultiParameter::ZoneLow => section.zone.low_key.note = MIDINote::try_new(value as i32).map_err(e)?,
+                MultiParameter::ZoneHigh => section.zone.high_key.note = MIDINote::try_new(value as i32).map_err(e)?,
+                MultiParameter::VelocitySwitch => section.velocity_switch = VelocitySwitch::try_from(value).map_err(|e| e.to_string())?,
+                MultiParameter::ReceiveChannel => section.receive_channel = MIDIChannel::try_new(value as i32 + 1).map_err(e)?,
+                MultiParameter::Mute => section.is_muted = ranged(value, 0, 1).map_err(e)? == 1,
+                MultiParameter::OutSelect => section.out_select = ranged(value, 0, 7).map_err(e)?,
+                MultiParameter::PlayMode => section.play_mode = PlayMode::try_from(value).map_err(|e| e.to_string())?,
+                MultiParameter::Level => section.level = Level::try_new(value).map_err(|e| e.to_string())?,
+                MultiParameter::Transpose => section.transpose = Transpose::try_new(ranged(value, 0, 48).map_err(e)? as i8 - 24).map_err(|e| e.to_string())?,
+                MultiParameter::Tune => section.tune = ranged(value, 0, 100).map_err(e)? as i8 - 50,
+                MultiParameter::Volume | MultiParameter::Effect => unreachable!(),
+            }
+        },
+    }
+
+    Ok(())
+}
+
+fn drum_value(patch: &DrumPatch, parameter: DrumParameter, index: usize) -> u8 {
+    let note = &patch.notes[index];
+    match parameter {
+        DrumParameter::Channel => patch.common.channel.into_inner() - 1,
+        DrumParameter::Volume => patch.common.volume.into_inner(),
+        DrumParameter::VelocityDepth => (patch.common.velocity_depth.into_inner() + 50) as u8,
+        DrumParameter::Submix => note.submix as u8,
+        DrumParameter::Wave1 => (note.source1.wave.number.into_inner() - 1) as u8,
+        DrumParameter::Wave2 => (note.source2.wave.number.into_inner() - 1) as u8,
+        DrumParameter::Decay1 => note.source1.decay.into_inner(),
+        DrumParameter::Decay2 => note.source2.decay.into_inner(),
+        DrumParameter::Tune1 => (note.source1.tune.into_inner() + 50) as u8,
+        DrumParameter::Tune2 => (note.source2.tune.into_inner() + 50) as u8,
+        DrumParameter::Level1 => note.source1.level.into_inner(),
+        DrumParameter::Level2 => note.source2.level.into_inner(),
+    }
+}
+
+fn apply_drum(patch: &mut DrumPatch, parameter: DrumParameter, index: usize, value: u8) -> Result<(), String> {
+    match parameter {
+        DrumParameter::Channel => patch.common.channel = Channel::try_new(value.saturating_add(1)).map_err(|e| e.to_string())?,
+        DrumParameter::Volume => patch.common.volume = Level::try_new(value).map_err(|e| e.to_string())?,
+        DrumParameter::VelocityDepth => patch.common.velocity_depth = depth(value)?,
+        _ => {
+            let note = &mut patch.notes[index];
+            match parameter {
+                DrumParameter::Submix => note.submix = Submix::try_from(value).map_err(|e| e.to_string())?,
+                DrumParameter::Wave1 => note.source1.wave.number = WaveNumber::try_new(value as u16 + 1).map_err(|e| e.to_string())?,
+                DrumParameter::Wave2 => note.source2.wave.number = WaveNumber::try_new(value as u16 + 1).map_err(|e| e.to_string())?,
+                DrumParameter::Decay1 => note.source1.decay = Decay::try_new(value).map_err(|e| e.to_string())?,
+                DrumParameter::Decay2 => note.source2.decay = Decay::try_new(value).map_err(|e| e.to_string())?,
+                DrumParameter::Tune1 => note.source1.tune = depth(value)?,
+                DrumParameter::Tune2 => note.source2.tune = depth(value)?,
+                DrumParameter::Level1 => note.source1.level = Level::try_new(value).map_err(|e| e.to_string())?,
+                DrumParameter::Level2 => note.source2.level = Level::try_new(value).map_err(|e| e.to_string())?,
+                DrumParameter::Channel | DrumParameter::Volume | DrumParameter::VelocityDepth => unreachable!(),
+            }
+        },
+    }
+
+    Ok(())
+}
+
+fn effect_value(patch: &EffectPatch, parameter: EffectParameter, index: usize) -> u8 {
+    let submix = &patch.submixes[index];
+    match parameter {
+        EffectParameter::Effect => patch.effect as u8 - 1,
+        EffectParameter::Param1 => (patch.param1.into_inner() + 7) as u8,
+        EffectParameter::Param2 => (patch.param2.into_inner() + 7) as u8,
+        EffectParameter::Param3 => patch.param3.into_inner(),
+        EffectParameter::Pan => (submix.pan + 7) as u8,
+        EffectParameter::Send1 => submix.send1.into_inner(),
+        EffectParameter::Send2 => submix.send2.into_inner(),
+    }
+}
+
+fn apply_effect(patch: &mut EffectPatch, parameter: EffectParameter, index: usize, value: u8) -> Result<(), String> {
+    let e = |e: ValueError| e.to_string();
+
+    match parameter {
+        EffectParameter::Effect => patch.effect = Effect::try_from(ranged(value, 0, 15).map_err(e)? + 1).map_err(|e| e.to_string())?,
+        EffectParameter::Param1 => patch.param1 = SmallEffectParameter::try_new(ranged(value, 0, 14).map_err(e)? as i8 - 7).map_err(|e| e.to_string())?,
+        EffectParameter::Param2 => patch.param2 = SmallEffectParameter::try_new(ranged(value, 0, 14).map_err(e)? as i8 - 7).map_err(|e| e.to_string())?,
+        EffectParameter::Param3 => patch.param3 = BigEffectParameter::try_new(value).map_err(|e| e.to_string())?,
+        EffectParameter::Pan => patch.submixes[index].pan = ranged(value, 0, 14).map_err(e)? as i32 - 7,
+        EffectParameter::Send1 => patch.submixes[index].send1 = Level::try_new(value).map_err(|e| e.to_string())?,
+        EffectParameter::Send2 => patch.submixes[index].send2 = Level::try_new(value).map_err(|e| e.to_string())?,
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{*};
+
+    use crate::k4::{
+        bank,
+        sysex::Header,
+    };
+
+    static DATA: &[u8] = include_bytes!("A401.SYX");
+
+    fn first_single() -> SinglePatch {
+        SinglePatch::from_bytes(&DATA[2 + Header::data_size()..]).unwrap()
+    }
+
+    #[test]
+    fn test_parameter_numbers() {
+        assert_eq!(Parameter::from_number(0), Some(Parameter::Single(SingleParameter::Volume)));
+        assert_eq!(Parameter::from_number(60), Some(Parameter::Single(SingleParameter::FilterTimeKeyScaling)));
+        assert_eq!(Parameter::from_number(61), None);
+        assert_eq!(Parameter::from_number(76), Some(Parameter::Multi(MultiParameter::Tune)));
+        assert_eq!(Parameter::from_number(91), Some(Parameter::Drum(DrumParameter::Level2)));
+        assert_eq!(Parameter::from_number(102), Some(Parameter::Effect(EffectParameter::Send2)));
+        for number in 0..=127 {
+            if let Some(parameter) = Parameter::from_number(number) {
+                assert_eq!(parameter.number(), number);
+            }
+        }
+    }
+
+    #[test]
+    fn test_paths() {
+        assert_eq!(SingleParameter::SourceCoarse.path(1), "sources[1].coarse");
+        assert_eq!(SingleParameter::AmpAttack.path(3), "amplifiers[3].envelope.attack");
+        assert_eq!(SingleParameter::FilterCutoff.path(1), "filter2.cutoff");
+        assert_eq!(SingleParameter::LfoSpeed.path(0), "lfo.speed");
+        assert_eq!(DrumParameter::Wave2.path(12), "notes[12].source2.wave");
+    }
+
+    #[test]
+    fn test_message_round_trip() {
+        let change = ParameterChange {
+            channel: MIDIChannel::try_new(3).unwrap(),
+            parameter: Parameter::Single(SingleParameter::SourceWave),
+            index: 2,
+            value: 200,
+        };
+        let data = change.to_bytes();
+        assert_eq!(data, vec![0xF0, 0x40, 0x02, 0x10, 0x00, 0x04, 26, 0b0000_0101, 200 & 0x7f, 0xF7]);
+        assert_eq!(ParameterChange::from_bytes(&data), Ok(change));
+    }
+
+    #[test]
+    fn test_rejects_bad_index() {
+        // Filter parameters only have indexes 0 and 1.
+        let data = [0xF0, 0x40, 0x00, 0x10, 0x00, 0x04, 46, 2 << 1, 0x10, 0xF7];
+        assert!(matches!(ParameterChange::from_bytes(&data), Err(ParseError::InvalidField(_))));
+    }
+
+    #[test]
+    fn test_from_patch_rejects_bad_index() {
+        let patch = first_single();
+        let channel = MIDIChannel::try_new(1).unwrap();
+        match ParameterChange::from_single(channel, &patch, SingleParameter::SourceCoarse, 4) {
+            Err(ParseError::InvalidField(error)) => {
+                assert_eq!(error.path, "sources[4].coarse");
+                assert_eq!(error.value, 4);
+            },
+            other => panic!("expected an invalid field error, got {:?}", other),
+        }
+        assert!(ParameterChange::from_single(channel, &patch, SingleParameter::Volume, 1).is_err());
+        assert!(ParameterChange::from_multi(channel, &MultiPatch::default(), MultiParameter::Transpose, 8).is_err());
+        assert!(ParameterChange::from_drum(channel, &DrumPatch::default(), DrumParameter::Wave1, 61).is_err());
+        assert!(ParameterChange::from_effect(channel, &EffectPatch::default(), EffectParameter::Pan, 8).is_err());
+    }
+
+    #[test]
+    fn test_every_single_value_applies_to_itself() {
+        let patch = first_single();
+        let channel = MIDIChannel::try_new(1).unwrap();
+        for number in 0..=SingleParameter::FilterTimeKeyScaling as u8 {
+            let parameter = SingleParameter::try_from(number).unwrap();
+            for index in 0..parameter.index_count() {
+                let change = ParameterChange::from_single(channel, &patch, parameter, index).unwrap();
+                let mut edited = patch.clone();
+                assert_eq!(change.apply_to_single(&mut edited), Ok(()), "{}", change);
+                assert_eq!(edited.to_bytes(), patch.to_bytes(), "{}", change);
+            }
+        }
+    }
+
+    #[test]
+    fn test_apply_to_single() {
+        let mut patch = first_single();
+        let channel = MIDIChannel::try_new(1).unwrap();
+        let data = [0xF0, 0x40, 0x00, 0x10, 0x00, 0x04, 28, 1 << 1, 36, 0xF7];
+        let change = ParameterChange::from_bytes(&data).unwrap();
+        change.apply_to_single(&mut patch).unwrap();
+        assert_eq!(patch.sources[1].coarse.into_inner(), 12);
+        assert_eq!(ParameterChange::from_single(channel, &patch, SingleParameter::SourceCoarse, 1), Ok(change));
+    }
+
+    #[test]
+    fn test_apply_out_of_range() {
+        let mut patch = first_single();
+        let data = [0xF0, 0x40, 0x00, 0x10, 0x00, 0x04, 28, 1 << 1, 60, 0xF7];
+        let change = ParameterChange::from_bytes(&data).unwrap();
+        match change.apply_to_single(&mut patch) {
+            Err(ParseError::InvalidField(error)) => {
+                assert_eq!(error.path, "sources[1].coarse");
+                assert_eq!(error.offset, 8);
+                assert_eq!(error.value, 60);
+            },
+            other => panic!("expected an invalid field error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_apply_to_wrong_kind() {
+        let mut patch = first_single();
+        let change = ParameterChange {
+            channel: MIDIChannel::try_new(1).unwrap(),
+            parameter: Parameter::Multi(MultiParameter::Volume),
+            index: 0,
+            value: 50,
+        };
+        assert!(change.apply_to_single(&mut patch).is_err());
+    }
+
+    #[test]
+    fn test_apply_to_multi_drum_and_effect() {
+        let bank = bank::Bank::from_bytes(&DATA[2 + Header::data_size()..]).unwrap();
+        let channel = MIDIChannel::try_new(1).unwrap();
+
+        let mut multi = bank.multis[0].clone();
+        let change = ParameterChange { channel, parameter: Parameter::Multi(MultiParameter::Transpose), index: 7, value: 36 };
+        change.apply_to_multi(&mut multi).unwrap();
+        assert_eq!(multi.sections[7].transpose.into_inner(), 12);
+        assert_eq!(ParameterChange::from_multi(channel, &multi, MultiParameter::Transpose, 7), Ok(change));
+
+        let mut drum = bank.drum;
+        let change = ParameterChange { channel, parameter: Parameter::Drum(DrumParameter::Wave1), index: 60, value: 255 };
+        change.apply_to_drum(&mut drum).unwrap();
+        assert_eq!(drum.notes[60].source1.wave.number.into_inner(), 256);
+        assert_eq!(ParameterChange::from_drum(channel, &drum, DrumParameter::Wave1, 60), Ok(change));
+
+        let mut effect = bank.effects[0].clone();
+        let change = ParameterChange { channel, parameter: Parameter::Effect(EffectParameter::Pan), index: 3, value: 0 };
+        change.apply_to_effect(&mut effect).unwrap();
+        assert_eq!(effect.submixes[3].pan, -7);
+        assert_eq!(ParameterChange::from_effect(channel, &effect, EffectParameter::Pan, 3), Ok(change));
+    }
+}
@@ -0,0 +1,441 @@
+//! Metadata of the K4 parameters.
+//!
+//! Every parameter of the single, multi, drum and effect patches is
+//! described with its name, range, unit and default, and where it is
+//! stored in the patch data. The parameters are the same as the ones
+//! in parameter send messages, listed in the order of their numbers,
+//! and the offsets are relative to the data of the patch as returned
+//! by `to_bytes`. The defaults are the values of a new patch.
+
+use std::ops::RangeInclusive;
+
+use crate::SystemExclusiveData;
+use crate::registry::{ParameterInfo, BitField, Unit, choices};
+use crate::k4::{
+    Ranged,
+    Level,
+    ModulationDepth,
+    EffectNumber,
+    EnvelopeTime,
+    FilterEnvelopeLevel,
+    Cutoff,
+    Resonance,
+    Curve,
+    Coarse,
+    Fine,
+    WaveNumber,
+    PatchNumber,
+    Transpose,
+    Channel,
+    Decay,
+    SmallEffectParameter,
+    BigEffectParameter,
+    SOURCE_COUNT,
+    single::{self, SinglePatch},
+    multi::{MultiPatch, VelocitySwitch, PlayMode},
+    drum::DrumPatch,
+    effect::{self, EffectPatch, Submix},
+    lfo::Shape,
+    wave::Wave,
+    parameter::{Parameter, SingleParameter, MultiParameter, DrumParameter, EffectParameter},
+};
+
+/// Offset of the first source byte in single patch data.
+const SOURCE_OFFSET: usize = 30;
+
+/// Offset of the first amplifier byte in single patch data.
+const AMP_OFFSET: usize = 58;
+
+/// Offset of the first filter byte in single patch data.
+const FILTER_OFFSET: usize = 102;
+
+/// Offset of the first section in multi patch data.
+const SECTION_OFFSET: usize = 12;
+
+/// Size of a section in multi patch data.
+const SECTION_SIZE: usize = 8;
+
+/// Size of the common data and of each note in drum patch data.
+const DRUM_NOTE_SIZE: usize = 11;
+
+/// Offset of the first submix in effect patch data.
+const SUBMIX_OFFSET: usize = 10;
+
+/// Size of a submix in effect patch data.
+const SUBMIX_SIZE: usize = 3;
+
+/// Lists the waves by number, for the wave parameters.
+fn wave_choices() -> Vec<(i32, String)> {
+    WaveNumber::range()
+        .map(|number| (number, Wave { number: WaveNumber::try_new(number as u16).unwrap() }.name()))
+        .collect()
+}
+
+/// Wave number stored with its high bit in bit 0 of the byte at `high`,
+/// and its low seven bits in the byte at `low`.
+fn wave(path: &str, name: &str, high: usize, low: usize) -> ParameterInfo {
+    ParameterInfo::new(path, name, WaveNumber::range(), Unit::Wave, low)
+        .fields(vec![BitField::new(high, 0b0000_0001), BitField::byte(low)])
+        .bias(-1)
+        .choices(wave_choices())
+}
+
+/// Sets the default of `info` to its value in the data of a new patch.
+fn with_default(mut info: ParameterInfo, data: &[u8]) -> ParameterInfo {
+    info.default = info.read(data);
+    info
+}
+
+/// Describes the single patch parameter at `index`, which is the source
+/// (0~3) of a source or amplifier parameter, or the filter (0~1).
+pub fn single_parameter(parameter: SingleParameter, index: usize) -> ParameterInfo {
+    with_default(single_info(parameter, index), &SinglePatch::default().to_bytes())
+}
+
+fn single_info(parameter: SingleParameter, index: usize) -> ParameterInfo {
+    use SingleParameter::*;
+
+    let path = parameter.path(index);
+    let info = |name: &str, range, unit, offset| ParameterInfo::new(&path, name, range, unit, offset);
+    let depth = |name: &str, offset| info(name, ModulationDepth::range(), Unit::Depth, offset).bias(50);
+    let switch = |name: &str, offset, mask| info(name, 0..=1, Unit::Switch, offset).bits(mask);
+
+    // The bytes of the sources and amplifiers are interleaved, as are the filters.
+    let source = SOURCE_OFFSET + index;
+    let amp = AMP_OFFSET + index;
+    let filter = FILTER_OFFSET + index;
+    let s = |name: &str| format!("S{} {}", index + 1, name);
+    let f = |name: &str| format!("F{} {}", index + 1, name);
+
+    match parameter {
+        Volume => info("Volume", Level::range(), Unit::Level, 10),
+        Effect => info("Effect", EffectNumber::range(), Unit::Number, 11).bits(0b0001_1111).bias(-1),
+        Submix => info("Out Select", 0..=7, Unit::Choice, 12).bits(0b0000_0111).choices(choices::<effect::Submix>(0..=7)),
+        SourceMode => info("Source Mode", 0..=2, Unit::Choice, 13).bits(0b0000_0011).choices(choices::<single::SourceMode>(0..=2)),
+        PolyphonyMode => info("Poly Mode", 0..=2, Unit::Choice, 13).bits(0b0000_1100).choices(choices::<single::PolyphonyMode>(0..=2)),
+        Am12 => switch("AM 1>2", 13, 0b0001_0000),
+        Am34 => switch("AM 3>4", 13, 0b0010_0000),
+        SourceMute => switch(&s("Mute"), 14, 1 << index).inverted(),
+        VibratoShape => info("Vibrato Shape", 0..=3, Unit::Choice, 14).bits(0b0011_0000).choices(choices::<Shape>(0..=3)),
+        BenderRange => info("Bender Range", 0..=12, Unit::Semitones, 15).bits(0b0000_1111),
+        WheelAssign => info("Wheel Assign", 0..=2, Unit::Choice, 15).bits(0b0011_0000).choices(choices::<single::WheelAssign>(0..=2)),
+        VibratoSpeed => info("Vibrato Speed", Level::range(), Unit::Level, 16),
+        WheelDepth => depth("Wheel Depth", 17),
+        AutoBendTime => info("Auto Bend Time", Level::range(), Unit::Time, 18),
+        AutoBendDepth => depth("Auto Bend Depth", 19),
+        AutoBendKeyScalingTime => depth("Auto Bend KS Time", 20),
+        AutoBendVelocityDepth => depth("Auto Bend Velocity Depth", 21),
+        VibratoPressure => depth("Vibrato Pressure", 22),
+        VibratoDepth => depth("Vibrato Depth", 23),
+        LfoShape => info("LFO Shape", 0..=3, Unit::Choice, 24).choices(choices::<Shape>(0..=3)),
+        LfoSpeed => info("LFO Speed", Level::range(), Unit::Level, 25),
+        LfoDelay => info("LFO Delay", Level::range(), Unit::Time, 26),
+        LfoDepth => depth("LFO Depth", 27),
+        LfoPressureDepth => depth("LFO Pressure Depth", 28),
+        PressFreq => depth("Pressure Freq", 29),
+
+        SourceDelay => info(&s("Delay"), Level::range(), Unit::Time, source),
+        SourceWave => wave(&path, &s("Wave"), source + SOURCE_COUNT, source + 2 * SOURCE_COUNT),
+        SourceKeyScalingCurve => info(&s("KS Curve"), Curve::range(), Unit::Number, source + SOURCE_COUNT).bits(0b0111_0000).bias(-1),
+        SourceCoarse => info(&s("Coarse"), Coarse::range(), Unit::Semitones, source + 3 * SOURCE_COUNT).bits(0b0011_1111).bias(24),
+        SourceKeyTrack => switch(&s("Key Track"), source + 3 * SOURCE_COUNT, 0b0100_0000),
+        SourceFixedKey => info(&s("Fixed Key"), 0..=127, Unit::Note, source + 4 * SOURCE_COUNT),
+        SourceFine => info(&s("Fine"), Fine::range(), Unit::Cents, source + 5 * SOURCE_COUNT).bias(50),
+        SourcePressFreq => switch(&s("Pressure Freq"), source + 6 * SOURCE_COUNT, 0b0000_0001),
+        SourceVibrato => switch(&s("Vibrato/Auto Bend"), source + 6 * SOURCE_COUNT, 0b0000_0010),
+        SourceVelocityCurve => info(&s("Velocity Curve"), Curve::range(), Unit::Number, source + 6 * SOURCE_COUNT).bits(0b0001_1100).bias(-1),
+
+        AmpLevel => info(&s("Level"), Level::range(), Unit::Level, amp),
+        AmpAttack => info(&s("Attack"), EnvelopeTime::range(), Unit::Time, amp + SOURCE_COUNT),
+        AmpDecay => info(&s("Decay"), EnvelopeTime::range(), Unit::Time, amp + 2 * SOURCE_COUNT),
+        AmpSustain => info(&s("Sustain"), EnvelopeTime::range(), Unit::Level, amp + 3 * SOURCE_COUNT),
+        AmpRelease => info(&s("Release"), EnvelopeTime::range(), Unit::Time, amp + 4 * SOURCE_COUNT),
+        AmpLevelVelocityDepth => depth(&s("Level Velocity Depth"), amp + 5 * SOURCE_COUNT),
+        AmpLevelPressureDepth => depth(&s("Level Pressure Depth"), amp + 6 * SOURCE_COUNT),
+        AmpLevelKeyScalingDepth => depth(&s("Level KS Depth"), amp + 7 * SOURCE_COUNT),
+        AmpTimeAttackVelocity => depth(&s("Time Attack Velocity"), amp + 8 * SOURCE_COUNT),
+        AmpTimeReleaseVelocity => depth(&s("Time Release Velocity"), amp + 9 * SOURCE_COUNT),
+        AmpTimeKeyScaling => depth(&s("Time KS"), amp + 10 * SOURCE_COUNT),
+
+        FilterCutoff => info(&f("Cutoff"), Cutoff::range(), Unit::Level, filter),
+        FilterResonance => info(&f("Resonance"), Resonance::range(), Unit::Level, filter + 2).bits(0b0000_0111),
+        FilterCutoffVelocityDepth => depth(&f("Cutoff Velocity Depth"), filter + 2 * 2),
+        FilterCutoffPressureDepth => depth(&f("Cutoff Pressure Depth"), filter + 3 * 2),
+        FilterCutoffKeyScalingDepth => depth(&f("Cutoff KS Depth"), filter + 4 * 2),
+        FilterLfoModulatesCutoff => switch(&f("LFO"), filter + 2, 0b0000_1000),
+        FilterEnvelopeDepth => depth(&f("Envelope Depth"), filter + 5 * 2),
+        FilterEnvelopeVelocityDepth => depth(&f("Envelope Velocity Depth"), filter + 6 * 2),
+        FilterAttack => info(&f("Attack"), EnvelopeTime::range(), Unit::Time, filter + 7 * 2),
+        FilterDecay => info(&f("Decay"), EnvelopeTime::range(), Unit::Time, filter + 8 * 2),
+        FilterSustain => info(&f("Sustain"), FilterEnvelopeLevel::range(), Unit::Depth, filter + 9 * 2).bias(50),
+        FilterRelease => info(&f("Release"), EnvelopeTime::range(), Unit::Time, filter + 10 * 2),
+        FilterTimeAttackVelocity => depth(&f("Time Attack Velocity"), filter + 11 * 2),
+        FilterTimeReleaseVelocity => depth(&f("Time Release Velocity"), filter + 12 * 2),
+        FilterTimeKeyScaling => depth(&f("Time KS"), filter + 13 * 2),
+    }
+}
+
+/// Describes the multi patch parameter at `index`, which is the section (0~7).
+pub fn multi_parameter(parameter: MultiParameter, index: usize) -> ParameterInfo {
+    with_default(multi_info(parameter, index), &MultiPatch::default().to_bytes())
+}
+
+fn multi_info(parameter: MultiParameter, index: usize) -> ParameterInfo {
+    let path = parameter.path(index);
+    let section = SECTION_OFFSET + index * SECTION_SIZE;
+    let info = |name: &str, range, unit, offset| {
+        ParameterInfo::new(&path, &format!("Section {} {}", index + 1, name), range, unit, section + offset)
+    };
+
+    match parameter {
+        MultiParameter::Volume => ParameterInfo::new(&path, "Volume", Level::range(), Unit::Level, 10),
+        MultiParameter::Effect => ParameterInfo::new(&path, "Effect", EffectNumber::range(), Unit::Number, 11).bias(-1),
+        MultiParameter::SingleNumber => info("Single", PatchNumber::range(), Unit::Number, 0),
+        MultiParameter::ZoneLow => info("Zone Low", 0..=127, Unit::Note, 1),
+        MultiParameter::ZoneHigh => info("Zone High", 0..=127, Unit::Note, 2),
+        MultiParameter::VelocitySwitch => info("Velocity Switch", 0..=2, Unit::Choice, 3).bits(0b0011_0000)
+            .choices(choices::<VelocitySwitch>(0..=2)),
+        MultiParameter::ReceiveChannel => info("Receive Channel", Channel::range(), Unit::Number, 3).bits(0b0000_1111).bias(-1),
+        MultiParameter::Mute => info("Mute", 0..=1, Unit::Switch, 3).bits(0b0100_0000),
+        MultiParameter::OutSelect => info("Out Select", 0..=7, Unit::Choice, 4).bits(0b0000_0111).choices(choices::<Submix>(0..=7)),
+        MultiParameter::PlayMode => info("Play Mode", 0..=2, Unit::Choice, 4).bits(0b0001_1000).choices(choices::<PlayMode>(0..=2)),
+        MultiParameter::Level => info("Level", Level::range(), Unit::Level, 5),
+        MultiParameter::Transpose => info("Transpose", Transpose::range(), Unit::Semitones, 6).bias(24),
+        MultiParameter::Tune => info("Tune", -50..=50, Unit::Cents, 7).bias(50),
+    }
+}
+
+/// Describes the drum patch parameter at `index`, which is the note (0~60).
+pub fn drum_parameter(parameter: DrumParameter, index: usize) -> ParameterInfo {
+    with_default(drum_info(parameter, index), &DrumPatch::default().to_bytes())
+}
+
+fn drum_info(parameter: DrumParameter, index: usize) -> ParameterInfo {
+    let path = parameter.path(index);
+    let info = |name: &str, range, unit, offset| ParameterInfo::new(&path, name, range, unit, offset);
+
+    // The common data comes first, then the notes. The bytes of the two sources
+    // of a note are interleaved, and the submix is in the first byte of source 1.
+    let note = DRUM_NOTE_SIZE + index * DRUM_NOTE_SIZE;
+    let n = |name: &str| format!("Note {} {}", index + 1, name);
+
+    match parameter {
+        DrumParameter::Channel => info("Receive Channel", Channel::range(), Unit::Number, 0).bias(-1),
+        DrumParameter::Volume => info("Volume", Level::range(), Unit::Level, 1),
+        DrumParameter::VelocityDepth => info("Velocity Depth", ModulationDepth::range(), Unit::Depth, 2).bias(50),
+        DrumParameter::Submix => info(&n("Out Select"), 0..=7, Unit::Choice, note).bits(0b0111_0000)
+            .choices(choices::<Submix>(0..=7)),
+        DrumParameter::Wave1 => wave(&path, &n("S1 Wave"), note, note + 2),
+        DrumParameter::Wave2 => wave(&path, &n("S2 Wave"), note + 1, note + 3),
+        DrumParameter::Decay1 => info(&n("S1 Decay"), Decay::range(), Unit::Time, note + 4),
+        DrumParameter::Decay2 => info(&n("S2 Decay"), Decay::range(), Unit::Time, note + 5),
+        DrumParameter::Tune1 => info(&n("S1 Tune"), ModulationDepth::range(), Unit::Depth, note + 6).bias(50),
+        DrumParameter::Tune2 => info(&n("S2 Tune"), ModulationDepth::range(), Unit::Depth, note + 7).bias(50),
+        DrumParameter::Level1 => info(&n("S1 Level"), Level::range(), Unit::Level, note + 8),
+        DrumParameter::Level2 => info(&n("S2 Level"), Level::range(), Unit::Level, note + 9),
+    }
+}
+
+/// Describes the effect patch parameter at `index`, which is the submix (0~7).
+pub fn effect_parameter(parameter: EffectParameter, index: usize) -> ParameterInfo {
+    with_default(effect_info(parameter, index), &EffectPatch::default().to_bytes())
+}
+
+fn effect_info(parameter: EffectParameter, index: usize) -> ParameterInfo {
+    let path = parameter.path(index);
+    let info = |name: &str, range, unit, offset| ParameterInfo::new(&path, name, range, unit, offset);
+    let submix = SUBMIX_OFFSET + index * SUBMIX_SIZE;
+    let s = |name: &str| format!("Submix {} {}", Submix::try_from(index as u8).unwrap(), name);
+
+    match parameter {
+        EffectParameter::Effect => info("Effect Type", 1..=16, Unit::Choice, 0).bits(0b0000_1111).bias(-1)
+            .choices(choices::<effect::Effect>(1..=16)),
+        EffectParameter::Param1 => info("Parameter 1", SmallEffectParameter::range(), Unit::Depth, 1).bias(7),
+        EffectParameter::Param2 => info("Parameter 2", SmallEffectParameter::range(), Unit::Depth, 2).bias(7),
+        EffectParameter::Param3 => info("Parameter 3", BigEffectParameter::range(), Unit::Level, 3),
+        EffectParameter::Pan => info(&s("Pan"), -7..=7, Unit::Depth, submix).bias(7),
+        EffectParameter::Send1 => info(&s("Send 1"), Level::range(), Unit::Level, submix + 1),
+        EffectParameter::Send2 => info(&s("Send 2"), Level::range(), Unit::Level, submix + 2),
+    }
+}
+
+/// Describes any K4 parameter at `index`.
+pub fn parameter(parameter: Parameter, index: usize) -> ParameterInfo {
+    match parameter {
+        Parameter::Single(p) => single_parameter(p, index),
+        Parameter::Multi(p) => multi_parameter(p, index),
+        Parameter::Drum(p) => drum_parameter(p, index),
+        Parameter::Effect(p) => effect_parameter(p, index),
+    }
+}
+
+/// Lists the parameters numbered `numbers` with all their indexes,
+/// with the defaults from `data`.
+fn list<P>(
+    numbers: RangeInclusive<u8>,
+    index_count: fn(&P) -> usize,
+    info: fn(P, usize) -> ParameterInfo,
+    data: &[u8]
+) -> Vec<ParameterInfo>
+where
+    P: TryFrom<u8> + Copy,
+{
+    numbers
+        .filter_map(|number| P::try_from(number).ok())
+        .flat_map(|p| (0..index_count(&p)).map(move |index| (p, index)))
+        .map(|(p, index)| with_default(info(p, index), data))
+        .collect()
+}
+
+/// Lists the parameters of a single patch.
+pub fn single_parameters() -> Vec<ParameterInfo> {
+    list(SingleParameter::Volume as u8..=SingleParameter::FilterTimeKeyScaling as u8,
+        SingleParameter::index_count, single_info, &SinglePatch::default().to_bytes())
+}
+
+/// Lists the parameters of a multi patch.
+pub fn multi_parameters() -> Vec<ParameterInfo> {
+    list(MultiParameter::Volume as u8..=MultiParameter::Tune as u8,
+        MultiParameter::index_count, multi_info, &MultiPatch::default().to_bytes())
+}
+
+/// Lists the parameters of a drum patch.
+pub fn drum_parameters() -> Vec<ParameterInfo> {
+    list(DrumParameter::Channel as u8..=DrumParameter::Level2 as u8,
+        DrumParameter::index_count, drum_info, &DrumPatch::default().to_bytes())
+}
+
+/// Lists the parameters of an effect patch.
+pub fn effect_parameters() -> Vec<ParameterInfo> {
+    list(EffectParameter::Effect as u8..=EffectParameter::Send2 as u8,
+        EffectParameter::index_count, effect_info, &EffectPatch::default().to_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{*};
+    use crate::registry::checks::{check_parameters, check_writes};
+    use crate::MIDIChannel;
+    use crate::k4::bank::Bank;
+    use crate::k4::parameter::ParameterChange;
+
+    static DATA: &[u8] = include_bytes!("A401.SYX");
+
+    fn bank() -> Bank {
+        Bank::from_bytes(&DATA[8..]).unwrap()
+    }
+
+    #[test]
+    fn test_ranges_match_validation() {
+        macro_rules! check {
+            ($($type:ty: $inner:ty),*) => {
+                $(
+                    assert!(<$type>::try_new(<$type>::MIN as $inner).is_ok(), stringify!($type));
+                    assert!(<$type>::try_new(<$type>::MAX as $inner).is_ok(), stringify!($type));
+                    if let Ok(below) = <$inner>::try_from(<$type>::MIN - 1) {
+                        assert!(<$type>::try_new(below).is_err(), stringify!($type));
+                    }
+                    assert!(<$type>::try_new((<$type>::MAX + 1) as $inner).is_err(), stringify!($type));
+                )*
+            };
+        }
+
+        check!(
+            EnvelopeTime: u8, Level: u8, ModulationDepth: i8, Channel: u8, Decay: u8,
+            SmallEffectParameter: i8, BigEffectParameter: u8, FilterEnvelopeLevel: i8,
+            Cutoff: u8, Resonance: u8, EffectNumber: u8, Curve: u8, Coarse: i8, Fine: i8,
+            WaveNumber: u16, PatchNumber: u8, Transpose: i8
+        );
+    }
+
+    #[test]
+    fn test_lists() {
+        let singles = single_parameters();
+        let count: usize = (SingleParameter::Volume as u8..=SingleParameter::FilterTimeKeyScaling as u8)
+            .map(|number| SingleParameter::try_from(number).unwrap().index_count())
+            .sum();
+        assert_eq!(singles.len(), count);
+        assert_eq!(singles[0].to_string(), "Volume (volume): 0...100 level, default 100");
+        assert_eq!(multi_parameters().len(), 2 + 11 * 8);
+        assert_eq!(drum_parameters().len(), 3 + 9 * 61);
+        assert_eq!(effect_parameters().len(), 4 + 3 * 8);
+
+        let coarse = single_parameter(SingleParameter::SourceCoarse, 1);
+        assert_eq!(coarse.name, "S2 Coarse");
+        assert_eq!(coarse.path, "sources[1].coarse");
+        assert_eq!(coarse.unit, Unit::Semitones);
+        assert_eq!(coarse.range, -24..=24);
+        assert_eq!(parameter(Parameter::Single(SingleParameter::SourceCoarse), 1), coarse);
+
+        let wave = single_parameter(SingleParameter::SourceWave, 0);
+        assert_eq!(wave.choice_name(1), Some("SIN 1ST"));
+    }
+
+    /// Checks the parameters with `data`, and that each one reads
+    /// the value that a parameter send message would carry.
+    fn check_encodings(parameters: &[ParameterInfo], data: &[u8], values: &[u8]) {
+        check_parameters(parameters, data);
+        for (info, value) in parameters.iter().zip(values) {
+            assert_eq!(info.encoding.read(data), *value as i32, "{}", info);
+        }
+        assert_eq!(parameters.len(), values.len());
+    }
+
+    #[test]
+    fn test_single_encodings() {
+        let patch = bank().singles[3].clone();
+        let channel = MIDIChannel::try_new(1).unwrap();
+        let values: Vec<u8> = (SingleParameter::Volume as u8..=SingleParameter::FilterTimeKeyScaling as u8)
+            .map(|number| SingleParameter::try_from(number).unwrap())
+            .flat_map(|p| (0..p.index_count()).map(move |index| (p, index)))
+            .map(|(p, index)| ParameterChange::from_single(channel, &patch, p, index).unwrap().value)
+            .collect();
+        check_encodings(&single_parameters(), &patch.to_bytes(), &values);
+    }
+
+    #[test]
+    fn test_multi_drum_and_effect_encodings() {
+        let bank = bank();
+        let channel = MIDIChannel::try_new(1).unwrap();
+
+        let multi = &bank.multis[0];
+        let values: Vec<u8> = (MultiParameter::Volume as u8..=MultiParameter::Tune as u8)
+            .map(|number| MultiParameter::try_from(number).unwrap())
+            .flat_map(|p| (0..p.index_count()).map(move |index| (p, index)))
+            .map(|(p, index)| ParameterChange::from_multi(channel, multi, p, index).unwrap().value)
+            .collect();
+        check_encodings(&multi_parameters(), &multi.to_bytes(), &values);
+
+        let values: Vec<u8> = (DrumParameter::Channel as u8..=DrumParameter::Level2 as u8)
+            .map(|number| DrumParameter::try_from(number).unwrap())
+            .flat_map(|p| (0..p.index_count()).map(move |index| (p, index)))
+            .map(|(p, index)| ParameterChange::from_drum(channel, &bank.drum, p, index).unwrap().value)
+            .collect();
+        check_encodings(&drum_parameters(), &bank.drum.to_bytes(), &values);
+
+        let effect = &bank.effects[5];
+        let values: Vec<u8> = (EffectParameter::Effect as u8..=EffectParameter::Send2 as u8)
+            .map(|number| EffectParameter::try_from(number).unwrap())
+            .flat_map(|p| (0..p.index_count()).map(move |index| (p, index)))
+            .map(|(p, index)| ParameterChange::from_effect(channel, effect, p, index).unwrap().value)
+            .collect();
+        check_encodings(&effect_parameters(), &effect.to_bytes(), &values);
+    }
+
+    #[test]
+    fn test_writes() {
+        let bank = bank();
+        check_writes::<SinglePatch>(&single_parameters(), &bank.singles[0].to_bytes());
+        check_writes::<MultiPatch>(&multi_parameters(), &bank.multis[0].to_bytes());
+        check_writes::<DrumPatch>(&drum_parameters(), &bank.drum.to_bytes());
+        check_writes::<EffectPatch>(&effect_parameters(), &bank.effects[0].to_bytes());
+    }
+
+    #[test]
+    fn test_write_out_of_range() {
+        let info = single_parameter(SingleParameter::SourceFine, 2);
+        let mut data = SinglePatch::default().to_bytes();
+        assert!(info.write(&mut data, 51).is_err());
+        assert_eq!(data, SinglePatch::default().to_bytes());
+    }
+}
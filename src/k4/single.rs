@@ -8,7 +8,7 @@ use std::fmt;
 use bit::BitIndex;
 use num_enum::TryFromPrimitive;
 
-use crate::{SystemExclusiveData, ParseError, Checksum, every_nth_byte};
+use crate::{SystemExclusiveData, ParseError, Checksum, every_nth_byte, check_length, invalid_field};
 use crate::k4::{
     Level, 
     ModulationDepth, 
@@ -31,6 +31,8 @@ pub enum SourceMode {
     Double,
 }
 
+serialize_by_name!(SourceMode);
+
 impl fmt::Display for SourceMode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -55,6 +57,8 @@ pub enum PolyphonyMode {
     Solo2,
 }
 
+serialize_by_name!(PolyphonyMode);
+
 impl fmt::Display for PolyphonyMode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -79,6 +83,8 @@ pub enum WheelAssign {
     Dcf,
 }
 
+serialize_by_name!(WheelAssign);
+
 impl fmt::Display for WheelAssign {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -95,6 +101,7 @@ impl fmt::Display for WheelAssign {
 
 /// Auto-bend setting.
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AutoBend {
     pub time: Level,
     pub depth: ModulationDepth,
@@ -134,11 +141,12 @@ impl fmt::Display for AutoBend {
 
 impl SystemExclusiveData for AutoBend {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(AutoBend {
-            time: Level::try_new(data[0] & 0x7f).unwrap(),
-            depth: ModulationDepth::try_new(((data[1] & 0x7f) as i8) - 50).unwrap(), // 0~100 to ±50
-            key_scaling_time: ModulationDepth::try_new(((data[2] & 0x7f) as i8) - 50).unwrap(), // 0~100 to ±50
-            velocity_depth: ModulationDepth::try_new(((data[3] & 0x7f) as i8) - 50).unwrap(), // 0~100 to ±50
+            time: Level::try_new(data[0] & 0x7f).map_err(invalid_field("time", data, 0))?,
+            depth: ModulationDepth::try_new(((data[1] & 0x7f) as i8) - 50).map_err(invalid_field("depth", data, 1))?, // 0~100 to ±50
+            key_scaling_time: ModulationDepth::try_new(((data[2] & 0x7f) as i8) - 50).map_err(invalid_field("key_scaling_time", data, 2))?, // 0~100 to ±50
+            velocity_depth: ModulationDepth::try_new(((data[3] & 0x7f) as i8) - 50).map_err(invalid_field("velocity_depth", data, 3))?, // 0~100 to ±50
         })
     }
 
@@ -161,10 +169,19 @@ impl SystemExclusiveData for AutoBend {
 
 /// Single patch.
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SinglePatch {
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "crate::serialize::fixed_name::<_, 10>"))]
     pub name: String,
     pub volume: Level,  // 0~100
     pub effect: EffectNumber,  // 1~32 (in SysEx 0~31)
+
+    /// Bits 5 and 6 of s11, above the effect number. They are not
+    /// documented, but the factory patches have bit 5 set, so they
+    /// are kept to write the patch back as it was read.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "crate::serialize::is_default"))]
+    pub effect_dummy_bits: u8,
+
     pub submix: Submix,
     pub source_mode: SourceMode,
     pub polyphony_mode: PolyphonyMode,
@@ -190,6 +207,7 @@ impl SinglePatch {
             name: "NewSound  ".to_string(),
             volume: Level::try_new(100).unwrap(),
             effect: EffectNumber::try_new(1).unwrap(),
+            effect_dummy_bits: 0,
             submix: Submix::A,
             source_mode: SourceMode::Normal,
             polyphony_mode: PolyphonyMode::Poly1,
@@ -210,21 +228,22 @@ impl SinglePatch {
         }
     }
 
+    #[allow(clippy::needless_range_loop)]
     fn collect_data(&self) -> Vec<u8> {
         let mut buf: Vec<u8> = Vec::new();
 
         buf.extend(self.name.as_bytes());
         buf.push(self.volume.into_inner());
-        buf.push(self.effect.into_inner() - 1);  // 1~32 to 0~31
+        buf.push((self.effect.into_inner() - 1) | ((self.effect_dummy_bits & 0x03) << 5));  // 1~32 to 0~31
         buf.push(self.submix as u8);
 
         let mut s13 = (self.polyphony_mode as u8) << 2;
         s13 |= self.source_mode as u8;
         if self.am12 {
-            s13 |= 0b0010_0000;  // set bit #5
+            s13 |= 0b0001_0000;  // set bit #4
         }
         if self.am34 {
-            s13 |= 0b0001_0000;  // set bit #4
+            s13 |= 0b0010_0000;  // set bit #5
         }
         buf.push(s13);
 
@@ -232,7 +251,7 @@ impl SinglePatch {
 
         let mut s14 = vibrato_bytes[0] << 4;
         for i in 0..4 {
-            s14.set_bit(i, self.source_mutes[i]);
+            s14.set_bit(i, !self.source_mutes[i]);  // 0/mute, 1/not mute
         }
         buf.push(s14);
 
@@ -283,6 +302,7 @@ impl SinglePatch {
         buf
     }
 
+    #[allow(clippy::needless_range_loop)]
     fn source_mute_string(&self) -> String {
         let mut s = String::new();
         let chars: [char; 4] = ['1', '2', '3', '4'];
@@ -332,41 +352,45 @@ impl fmt::Display for SinglePatch {
 }
 
 impl SystemExclusiveData for SinglePatch {
+    #[allow(clippy::needless_range_loop)]
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
+
         let mut offset: usize = 0;
         let mut start: usize = 0;
 
         // name = s00 ... s09
         let mut end = start + NAME_LENGTH;
 
-        let name = String::from_utf8(data[start..end].to_vec()).expect("Found invalid UTF-8");
+        let name = String::from_utf8(data[start..end].to_vec()).map_err(invalid_field("name", data, start))?;
         let name = str::replace(&name, char::from(0), " ").to_string();
 
         offset += NAME_LENGTH;
 
         let mut b: u8;
         b = data[offset];
+        let volume = Level::try_new(b & 0x7f).map_err(invalid_field("volume", data, offset))?;
         offset += 1;
-        let volume = b;
 
         // effect = s11 bits 0...4
         b = data[offset];
+        let effect = EffectNumber::try_new(get_effect_number(b)).map_err(invalid_field("effect", data, offset))?;
+        let effect_dummy_bits = (b >> 5) & 0x03;
         offset += 1;
-        let effect = get_effect_number(b);
 
         // output select = s12 bits 0...2
         b = data[offset];
-        offset += 1;
         let output_name_index = b & 0b00000111;
-        let submix = Submix::try_from(output_name_index).unwrap();
+        let submix = Submix::try_from(output_name_index).map_err(invalid_field("submix", data, offset))?;
+        offset += 1;
 
         // source mode = s13 bits 0...1
         b = data[offset];
-        offset += 1;
-        let source_mode = b & 0x03;
-        let polyphony_mode = (b >> 2) & 0x03;
+        let source_mode = SourceMode::try_from(b & 0x03).map_err(invalid_field("source_mode", data, offset))?;
+        let polyphony_mode = PolyphonyMode::try_from((b >> 2) & 0x03).map_err(invalid_field("polyphony_mode", data, offset))?;
         let am12 = ((b >> 4) & 0x01) == 1;
         let am34 = ((b >> 5) & 0x01) == 1;
+        offset += 1;
 
         b = data[offset];
         offset += 1;
@@ -383,12 +407,12 @@ impl SystemExclusiveData for SinglePatch {
         vibrato_bytes.push(b);
 
         b = data[offset];
-        offset += 1;
 
         // Pitch bend = s15 bits 0...3
         let bender_range = b & 0x0f;
         // Wheel assign = s15 bits 4...5
-        let wheel_assign = (b >> 4) & 0x03;
+        let wheel_assign = WheelAssign::try_from((b >> 4) & 0x03).map_err(invalid_field("wheel_assign", data, offset))?;
+        offset += 1;
 
         b = data[offset];
         offset += 1;
@@ -403,7 +427,8 @@ impl SystemExclusiveData for SinglePatch {
 
         start = offset;
         end = offset + 4;
-        let auto_bend = AutoBend::from_bytes(&data[start..end]);
+        let auto_bend = AutoBend::from_bytes(&data[start..end])
+            .map_err(|e| e.within("auto_bend", start))?;
         offset += 4;
 
         b = data[offset];
@@ -414,26 +439,32 @@ impl SystemExclusiveData for SinglePatch {
         offset += 1;
         vibrato_bytes.push(b);  // vib depth
 
-        let vibrato = Vibrato::from_bytes(&vibrato_bytes);
+        // The vibrato settings are collected from s14, s16, s22 and s23
+        let vibrato = Vibrato::from_bytes(&vibrato_bytes)
+            .map_err(|e| e.in_field("vibrato").map_offset(|o| [14, 16, 22, 23][o]))?;
 
         start = offset;
         end = start + 5;
-        let lfo = Lfo::from_bytes(&data[start..end]);
+        let lfo = Lfo::from_bytes(&data[start..end])
+            .map_err(|e| e.within("lfo", start))?;
         offset += 5;
 
         b = data[offset];
         offset += 1;
         let press_freq = ((b & 0x7f) as i8) - 50; // 0~100 to ±50
 
+        // The source, amplifier and filter data are interleaved,
+        // so the offsets of any errors need to be mapped back.
         let total_source_data_size = 4 * 7;
         start = offset;
         end = start + total_source_data_size;
         let all_source_data = &data[start..end];
 
-        let s1 = Source::from_bytes(&every_nth_byte(&all_source_data, 4, 0));
-        let s2 = Source::from_bytes(&every_nth_byte(&all_source_data, 4, 1));
-        let s3 = Source::from_bytes(&every_nth_byte(&all_source_data, 4, 2));
-        let s4 = Source::from_bytes(&every_nth_byte(&all_source_data, 4, 3));
+        let mut sources = [Source::default(); 4];
+        for (i, source) in sources.iter_mut().enumerate() {
+            *source = Source::from_bytes(&every_nth_byte(all_source_data, 4, i))
+                .map_err(|e| e.in_field(format!("sources[{}]", i)).map_offset(|o| start + o * 4 + i))?;
+        }
 
         offset += total_source_data_size;
 
@@ -442,10 +473,11 @@ impl SystemExclusiveData for SinglePatch {
         end = start + total_amp_data_size;
         let all_amp_data = &data[start..end];
 
-        let a1 = Amplifier::from_bytes(&every_nth_byte(&all_amp_data, 4, 0));
-        let a2 = Amplifier::from_bytes(&every_nth_byte(&all_amp_data, 4, 1));
-        let a3 = Amplifier::from_bytes(&every_nth_byte(&all_amp_data, 4, 2));
-        let a4 = Amplifier::from_bytes(&every_nth_byte(&all_amp_data, 4, 3));
+        let mut amplifiers = [Amplifier::default(); 4];
+        for (i, amplifier) in amplifiers.iter_mut().enumerate() {
+            *amplifier = Amplifier::from_bytes(&every_nth_byte(all_amp_data, 4, i))
+                .map_err(|e| e.in_field(format!("amplifiers[{}]", i)).map_offset(|o| start + o * 4 + i))?;
+        }
 
         offset += total_amp_data_size;
 
@@ -454,8 +486,10 @@ impl SystemExclusiveData for SinglePatch {
         end = start + total_filter_data_size;
         let all_filter_data = &data[start..end];
 
-        let f1 = Filter::from_bytes(&every_nth_byte(&all_filter_data, 2, 0));
-        let f2 = Filter::from_bytes(&every_nth_byte(&all_filter_data, 2, 1));
+        let f1 = Filter::from_bytes(&every_nth_byte(all_filter_data, 2, 0))
+            .map_err(|e| e.in_field("filter1").map_offset(|o| start + o * 2))?;
+        let f2 = Filter::from_bytes(&every_nth_byte(all_filter_data, 2, 1))
+            .map_err(|e| e.in_field("filter2").map_offset(|o| start + o * 2 + 1))?;
 
         //offset += total_filter_data_size;
 
@@ -465,25 +499,26 @@ impl SystemExclusiveData for SinglePatch {
 
         Ok(SinglePatch {
             name,
-            volume: Level::try_new(volume).unwrap(),
-            effect: EffectNumber::try_new(effect).unwrap(),
+            volume,
+            effect,
+            effect_dummy_bits,
             submix,
-            source_mode: SourceMode::try_from(source_mode).unwrap(),
-            polyphony_mode: PolyphonyMode::try_from(polyphony_mode).unwrap(),
+            source_mode,
+            polyphony_mode,
             am12,
             am34,
             source_mutes,
             bender_range,
-            wheel_assign: WheelAssign::try_from(wheel_assign).unwrap(),
+            wheel_assign,
             wheel_depth,
-            auto_bend: auto_bend?,
-            lfo: lfo?,
-            vibrato: vibrato?,
+            auto_bend,
+            lfo,
+            vibrato,
             press_freq,
-            sources: [s1?, s2?, s3?, s4?],
-            amplifiers: [a1?, a2?, a3?, a4?],
-            filter1: f1?,
-            filter2: f2?,
+            sources,
+            amplifiers,
+            filter1: f1,
+            filter2: f2,
         })
     }
 
@@ -516,7 +551,7 @@ mod tests {
         single::SinglePatch,
     };
 
-    static DATA: &'static [u8] = include_bytes!("A401.SYX");
+    static DATA: &[u8] = include_bytes!("A401.SYX");
 
     #[test]
     fn test_single_patch_from_bytes() {
@@ -525,4 +560,33 @@ mod tests {
         assert_eq!(patch.as_ref().unwrap().name, "Melo Vox 1");
         assert_eq!(patch.as_ref().unwrap().volume.into_inner(), 100);
     }
+
+    #[test]
+    fn test_amplitude_modulation_round_trip() {
+        let patch = SinglePatch { am12: true, ..Default::default() };
+        let data = patch.to_bytes();
+        assert_eq!(data[13] & 0b0011_0000, 0b0001_0000);
+        let patch = SinglePatch::from_bytes(&data).unwrap();
+        assert!(patch.am12);
+        assert!(!patch.am34);
+    }
+
+    #[test]
+    fn test_source_mutes_round_trip() {
+        let patch = SinglePatch { source_mutes: [false, true, false, true], ..Default::default() };
+        let data = patch.to_bytes();
+        assert_eq!(data[14] & 0x0f, 0b0101);  // a muted source has its bit cleared
+        let patch = SinglePatch::from_bytes(&data).unwrap();
+        assert_eq!(patch.source_mutes, [false, true, false, true]);
+    }
+
+    #[test]
+    fn test_single_patch_round_trip() {
+        let start: usize = 2 + Header::data_size();
+        for i in 0..64 {
+            let data = &DATA[start + i * SinglePatch::data_size()..start + (i + 1) * SinglePatch::data_size()];
+            let patch = SinglePatch::from_bytes(data).unwrap();
+            assert_eq!(patch.to_bytes(), data, "single {}", i);
+        }
+    }
 }
@@ -5,19 +5,21 @@ use std::fmt;
 
 use bit::BitIndex;
 
-use crate::{SystemExclusiveData, ParseError};
+use crate::{SystemExclusiveData, ParseError, MIDINote, check_length, invalid_field};
 use crate::k4::{Level, Curve, Coarse, Fine};
 use crate::k4::wave::Wave;
 
 
 /// Source in a single patch.
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Source {
     pub delay: Level,
     pub wave: Wave,
     pub ks_curve: Curve,
     pub coarse: Coarse,
-    pub key_track: KeyTrack,
+    pub key_track: bool,
+    pub fixed_key: MIDINote,  // used when key tracking is off, but kept in the patch anyway
     pub fine: Fine,
     pub press_freq: bool,
     pub vibrato: bool,
@@ -31,7 +33,8 @@ impl Source {
             wave: Default::default(),
             ks_curve: Curve::try_new(1).unwrap(),
             coarse: Coarse::try_new(0).unwrap(),
-            key_track: KeyTrack::On,
+            key_track: true,
+            fixed_key: MIDINote::try_new(60).unwrap(),
             fine: Fine::try_new(0).unwrap(),
             press_freq: true,
             vibrato: true,
@@ -56,7 +59,7 @@ impl fmt::Display for Source {
             self.ks_curve.into_inner(),
             self.coarse.into_inner(),
             self.fine.into_inner(),
-            self.key_track,
+            if self.key_track { "ON".to_string() } else { format!("fixed {}", self.fixed_key.name()) },
             self.press_freq,
             self.vibrato,
             self.velocity_curve.into_inner()
@@ -66,61 +69,58 @@ impl fmt::Display for Source {
 
 impl SystemExclusiveData for Source {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
+
         let mut offset: usize = 0;
 
         let mut b: u8;
         b = data[offset];
+        let delay = Level::try_new(b & 0x7f).map_err(invalid_field("delay", data, offset))?;
         offset += 1;
-        let delay = b & 0x7f;
 
         b = data[offset];
-        offset += 1;
         let wave_high = b & 0x01;
-        let ks_curve = ((b >> 4) & 0x07) + 1; // 0...7 to 1...8
+        let ks_curve = Curve::try_new(((b >> 4) & 0x07) + 1).map_err(invalid_field("ks_curve", data, offset))?; // 0...7 to 1...8
+        offset += 1;
         let wave_low = data[offset] & 0x7f;
         offset += 1;
 
-        let wave = Wave::from_bytes(&[wave_high, wave_low]);
+        let wave = Wave::from_bytes(&[wave_high, wave_low])
+            .map_err(|e| e.within("wave", 1))?;
 
         b = data[offset];
-        offset += 1;
 
         // Assuming that the low six bits are the coarse value,
         // and b6 is the key tracking bit (b7 is zero).
-        let is_key_track = b.bit(6);
+        let key_track = b.bit(6);
 
-        let coarse = ((b & 0x3f) as i8) - 24;  // 00 ~ 48 to ±24
+        let coarse = Coarse::try_new(((b & 0x3f) as i8) - 24).map_err(invalid_field("coarse", data, offset))?;  // 00 ~ 48 to ±24
+        offset += 1;
 
         b = data[offset];
+        let fixed_key = MIDINote::try_new((b & 0x7f).into()).map_err(invalid_field("fixed_key", data, offset))?;
         offset += 1;
-        let fixed_key = b & 0x7f;
-
-        let key_track = if is_key_track {
-            KeyTrack::On
-        }
-        else {
-            KeyTrack::FixedKey(fixed_key)
-        };
 
         b = data[offset];
+        let fine = Fine::try_new(((b & 0x7f) as i8) - 50).map_err(invalid_field("fine", data, offset))?;
         offset += 1;
-        let fine = ((b & 0x7f) as i8) - 50;
 
         b = data[offset];
         let press_freq = b.bit(0);
         let vibrato = b.bit(1);
-        let velocity_curve = ((b >> 2) & 0x07) + 1;  // 0...7 to 1...8
+        let velocity_curve = Curve::try_new(((b >> 2) & 0x07) + 1).map_err(invalid_field("velocity_curve", data, offset))?;  // 0...7 to 1...8
 
         Ok(Source {
-            delay: Level::try_new(delay).unwrap(),
-            wave: wave?,
-            ks_curve: Curve::try_new(ks_curve).unwrap(),
-            coarse: Coarse::try_new(coarse).unwrap(),
+            delay,
+            wave,
+            ks_curve,
+            coarse,
             key_track,
-            fine: Fine::try_new(fine).unwrap(),
+            fixed_key,
+            fine,
             press_freq,
             vibrato,
-            velocity_curve: Curve::try_new(velocity_curve).unwrap(),
+            velocity_curve,
         })
     }
 
@@ -138,24 +138,15 @@ impl SystemExclusiveData for Source {
         buf.push(wave_bytes[1]);
 
         let mut s42 = (self.coarse.into_inner() + 24) as u8;  // bring into 0~48
-        let mut key: u8 = 0;
-        match self.key_track {
-            KeyTrack::On => {
-                s42.set_bit(6, true);
-            },
-            KeyTrack::FixedKey(k) => {
-                key = k;
-            }
-        };
+        s42.set_bit(6, self.key_track);
         buf.push(s42);
-        buf.push(key);
+        buf.push(self.fixed_key.value() as u8);
 
         buf.push((self.fine.into_inner() + 50) as u8);  // bring into 0~100
 
         let mut s54 = (self.velocity_curve.into_inner() - 1) << 2;
-        if self.vibrato {
-            s54.set_bit(0, true);
-        }
+        s54.set_bit(0, self.press_freq);
+        s54.set_bit(1, self.vibrato);
         buf.push(s54);
 
         buf
@@ -164,22 +155,27 @@ impl SystemExclusiveData for Source {
     fn data_size() -> usize { 7 }
 }
 
-/// Key tracking setting.
-#[derive(Copy, Clone)]
-pub enum KeyTrack {
-    On,
-    FixedKey(u8),
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_switches_round_trip() {
+        let source = Source { press_freq: true, vibrato: false, ..Default::default() };
+        let data = source.to_bytes();
+        assert_eq!(data[6] & 0b0000_0011, 0b0000_0001);
+        let source = Source::from_bytes(&data).unwrap();
+        assert!(source.press_freq);
+        assert!(!source.vibrato);
+    }
 
-impl fmt::Display for KeyTrack {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                KeyTrack::On => "ON".to_string(),
-                KeyTrack::FixedKey(k) => format!("fixed {}", k),
-            }
-        )
+    #[test]
+    fn test_fixed_key_kept_with_key_tracking() {
+        let source = Source { key_track: true, fixed_key: MIDINote::try_new(48).unwrap(), ..Default::default() };
+        let data = source.to_bytes();
+        assert_eq!(data[4], 48);
+        let source = Source::from_bytes(&data).unwrap();
+        assert!(source.key_track);
+        assert_eq!(source.fixed_key.value(), 48);
     }
 }
@@ -7,8 +7,16 @@ use num_enum::TryFromPrimitive;
 use crate::{
     SystemExclusiveData,
     ParseError,
-    MIDIChannel
+    MIDIChannel,
+    check_length,
+    invalid_field
 };
+use crate::k4::{PatchNumber, EffectNumber};
+use crate::k4::single::SinglePatch;
+use crate::k4::multi::MultiPatch;
+use crate::k4::drum::DrumPatch;
+use crate::k4::effect::EffectPatch;
+use crate::k4::bank::{Bank, SINGLE_PATCH_COUNT, MULTI_PATCH_COUNT, EFFECT_PATCH_COUNT};
 
 const GROUP: u8 = 0x00;      // synth group
 const MACHINE_ID: u8 = 0x04; // K4/K4r ID
@@ -71,9 +79,10 @@ impl fmt::Display for Header {
 
 impl SystemExclusiveData for Header {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(Header {
-            channel: MIDIChannel::try_new(data[0] as i32 + 1).unwrap(),
-            function: Function::try_from(data[1]).unwrap(),
+            channel: MIDIChannel::from_bytes(&data[0..1]).map_err(|e| e.in_field("channel"))?,
+            function: Function::try_from(data[1]).map_err(invalid_field("function", data, 1))?,
             substatus1: data[4],
             substatus2: data[5],
         })
@@ -94,6 +103,7 @@ impl SystemExclusiveData for Header {
     fn data_size() -> usize { 6 }
 }
 
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum Locality {
     Internal,
     External,
@@ -108,6 +118,8 @@ impl fmt::Display for Locality {
     }
 }
 
+serialize_by_name!(Locality, [Locality::Internal, Locality::External].into_iter());
+
 pub struct Dump {
     pub kind: Kind,
     pub locality: Locality,
@@ -142,61 +154,524 @@ impl Dump {
     pub fn identify(payload: Vec<u8>) -> Result<Dump, ParseError> {
         // Extract the SysEx header from the message payload:
 
-        let header_data = &payload[0..Header::data_size() as usize];
-        let header = Header::from_bytes(header_data);
+        let header = Header::from_bytes(&payload)?;
 
         // The raw data is everything in the payload after the header.
-        let raw_data = &payload[Header::data_size() as usize..];
-
-        match (header.as_ref().unwrap().function, header.as_ref().unwrap().substatus1, header.as_ref().unwrap().substatus2) {
-            (Function::OnePatchDataDump, 0x00, number) if (0..=63).contains(&number) =>
-                Ok(Dump { kind: Kind::OneSingle(number), locality: Locality::Internal, payload: raw_data.to_vec() }),
-            (Function::OnePatchDataDump, 0x00, number) if (64..=127).contains(&number) =>
-                Ok(Dump { kind: Kind::OneMulti(number), locality: Locality::Internal, payload: raw_data.to_vec() }),
-            (Function::OnePatchDataDump, 0x02, number) if (0..=63).contains(&number) =>
-                Ok(Dump { kind: Kind::OneSingle(number), locality: Locality::External, payload: raw_data.to_vec() }),
-            (Function::OnePatchDataDump, 0x02, number) if (64..=127).contains(&number) =>
-                Ok(Dump { kind: Kind::OneMulti(number), locality: Locality::External, payload: raw_data.to_vec() }),
-            (Function::OnePatchDataDump, 0x01, number) if (0..=31).contains(&number) =>
-                Ok(Dump { kind: Kind::OneEffect(number), locality: Locality::Internal, payload: raw_data.to_vec() }),
-            (Function::OnePatchDataDump, 0x03, number) if (0..=31).contains(&number) =>
-                Ok(Dump { kind: Kind::OneEffect(number), locality: Locality::External, payload: raw_data.to_vec() }),
-            (Function::OnePatchDataDump, 0x01, 32) =>
-                Ok(Dump { kind: Kind::Drum, locality: Locality::Internal, payload: raw_data.to_vec() }),
-            (Function::OnePatchDataDump, 0x03, 32) =>
-                Ok(Dump { kind: Kind::Drum, locality: Locality::External, payload: raw_data.to_vec() }),
-            (Function::BlockPatchDataDump, 0x00, 0x00) =>
-                Ok(Dump { kind: Kind::BlockSingle, locality: Locality::Internal, payload: raw_data.to_vec() }),
-            (Function::BlockPatchDataDump, 0x00, 0x40) =>
-                Ok(Dump { kind: Kind::BlockMulti, locality: Locality::Internal, payload: raw_data.to_vec() }),
-            (Function::BlockPatchDataDump, 0x02, 0x00) =>
-                Ok(Dump { kind: Kind::BlockSingle, locality: Locality::External, payload: raw_data.to_vec() }),
-            (Function::BlockPatchDataDump, 0x02, 0x40) =>
-                Ok(Dump { kind: Kind::BlockMulti, locality: Locality::External, payload: raw_data.to_vec() }),
-            (Function::BlockPatchDataDump, 0x01, 0x00) =>
-                Ok(Dump { kind: Kind::BlockEffect, locality: Locality::Internal, payload: raw_data.to_vec() }),
-            (Function::BlockPatchDataDump, 0x03, 0x00) =>
-                Ok(Dump { kind: Kind::BlockEffect, locality: Locality::External, payload: raw_data.to_vec() }),
-            (Function::AllPatchDataDump, 0x00, 0x00) =>
-                Ok(Dump { kind: Kind::All, locality: Locality::Internal, payload: raw_data.to_vec() }),
-            (Function::AllPatchDataDump, 0x02, 0x00) =>
-                Ok(Dump { kind: Kind::All, locality: Locality::External, payload: raw_data.to_vec() }),
-            _ => Err(ParseError::Unidentified),
+        let raw_data = &payload[Header::data_size()..];
+
+        let cardinality = match header.function {
+            Function::OnePatchDataDump => Cardinality::One,
+            Function::BlockPatchDataDump => Cardinality::Block,
+            Function::AllPatchDataDump => Cardinality::All,
+            _ => return Err(ParseError::Unidentified),
+        };
+
+        match identify_kind(cardinality, header.substatus1, header.substatus2) {
+            Some((kind, locality)) => Ok(Dump { kind, locality, payload: raw_data.to_vec() }),
+            None => Err(ParseError::Unidentified),
+        }
+    }
+
+    /// Parses the raw data of this dump according to its kind.
+    /// Error offsets are relative to the start of the raw data.
+    pub fn data(&self) -> Result<DumpData, ParseError> {
+        DumpData::parse(self.kind, &self.payload)
+    }
+}
+
+/// The patches in a K4 dump, parsed according to the kind of the dump.
+/// Singles and multis are numbered 0~63, effects 1~32.
+pub enum DumpData {
+    OneSingle(PatchNumber, SinglePatch),
+    OneMulti(PatchNumber, MultiPatch),
+    Drum(DrumPatch),
+    OneEffect(EffectNumber, EffectPatch),
+    BlockSingle(Vec<SinglePatch>),
+    BlockMulti(Vec<MultiPatch>),
+    BlockEffect(Vec<EffectPatch>),
+    All(Bank),
+}
+
+impl DumpData {
+    /// Parses the raw data of a dump of `kind`.
+    /// Error offsets are relative to the start of `data`.
+    /// A patch number out of range for the kind is invalid data at offset 0.
+    pub fn parse(kind: Kind, data: &[u8]) -> Result<DumpData, ParseError> {
+        Ok(match kind {
+            Kind::OneSingle(number) => {
+                let number = PatchNumber::try_new(number).map_err(bad_number)?;
+                DumpData::OneSingle(number, SinglePatch::from_bytes(data)?)
+            },
+            Kind::OneMulti(number) => {
+                let number = PatchNumber::try_new(number.wrapping_sub(64)).map_err(bad_number)?;
+                DumpData::OneMulti(number, MultiPatch::from_bytes(data)?)
+            },
+            Kind::Drum => DumpData::Drum(DrumPatch::from_bytes(data)?),
+            Kind::OneEffect(number) => {
+                let number = EffectNumber::try_new(number.wrapping_add(1)).map_err(bad_number)?;
+                DumpData::OneEffect(number, EffectPatch::from_bytes(data)?)
+            },
+            Kind::BlockSingle => DumpData::BlockSingle(parse_block(data, SINGLE_PATCH_COUNT, "singles")?),
+            Kind::BlockMulti => DumpData::BlockMulti(parse_block(data, MULTI_PATCH_COUNT, "multis")?),
+            Kind::BlockEffect => DumpData::BlockEffect(parse_block(data, EFFECT_PATCH_COUNT, "effects")?),
+            Kind::All => DumpData::All(Bank::from_bytes(data)?),
+        })
+    }
+
+    /// Returns the kind of dump that carries this data.
+    pub fn kind(&self) -> Kind {
+        match self {
+            DumpData::OneSingle(number, _) => Kind::OneSingle(number.into_inner()),
+            DumpData::OneMulti(number, _) => Kind::OneMulti(number.into_inner() + 64),
+            DumpData::Drum(_) => Kind::Drum,
+            DumpData::OneEffect(number, _) => Kind::OneEffect(number.into_inner() - 1),
+            DumpData::BlockSingle(_) => Kind::BlockSingle,
+            DumpData::BlockMulti(_) => Kind::BlockMulti,
+            DumpData::BlockEffect(_) => Kind::BlockEffect,
+            DumpData::All(_) => Kind::All,
+        }
+    }
+
+    /// Returns the raw data of the dump, without the header and the terminator.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            DumpData::OneSingle(_, patch) => patch.to_bytes(),
+            DumpData::OneMulti(_, patch) => patch.to_bytes(),
+            DumpData::Drum(patch) => patch.to_bytes(),
+            DumpData::OneEffect(_, patch) => patch.to_bytes(),
+            DumpData::BlockSingle(patches) => patches.iter().flat_map(|p| p.to_bytes()).collect(),
+            DumpData::BlockMulti(patches) => patches.iter().flat_map(|p| p.to_bytes()).collect(),
+            DumpData::BlockEffect(patches) => patches.iter().flat_map(|p| p.to_bytes()).collect(),
+            DumpData::All(bank) => bank.to_bytes(),
+        }
+    }
+}
+
+/// Makes the error for a patch number out of range for the kind of dump.
+fn bad_number(error: impl fmt::Display) -> ParseError {
+    ParseError::InvalidData(0, format!("bad patch number: {}", error))
+}
+
+/// Parses `count` consecutive patches of type `T` from a block dump.
+fn parse_block<T: SystemExclusiveData>(data: &[u8], count: usize, field: &str) -> Result<Vec<T>, ParseError> {
+    check_length(data, count * T::data_size())?;
+    (0..count)
+        .map(|i| {
+            let offset = i * T::data_size();
+            T::from_bytes(&data[offset..offset + T::data_size()])
+                .map_err(|e| e.within(format!("{}[{}]", field, i), offset))
+        })
+        .collect()
+}
+
+/// A complete K4 dump message with its parsed patches.
+pub struct PatchDump {
+    pub channel: MIDIChannel,
+    pub locality: Locality,
+    pub data: DumpData,
+}
+
+impl PatchDump {
+    /// Returns the header of this dump.
+    pub fn header(&self) -> Header {
+        let (cardinality, substatus1, substatus2) = kind_substatus(self.data.kind(), self.locality);
+        let function = match cardinality {
+            Cardinality::One => Function::OnePatchDataDump,
+            Cardinality::Block => Function::BlockPatchDataDump,
+            Cardinality::All => Function::AllPatchDataDump,
+        };
+        Header { channel: self.channel, function, substatus1, substatus2 }
+    }
+}
+
+impl fmt::Display for PatchDump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Dump of {:?} {}, channel {}", self.data.kind(), self.locality, self.channel.value())
+    }
+}
+
+impl SystemExclusiveData for PatchDump {
+    /// Parses a complete dump message, from F0H to F7H.
+    /// Error offsets are relative to the start of the message.
+    fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, 9)?;  // F0H, manufacturer, header, F7H
+
+        if data[0] != 0xF0 {
+            return Err(invalid_field("initiator", data, 0)("expected F0H"));
+        }
+        if data[1] != 0x40 {
+            return Err(invalid_field("manufacturer", data, 1)("expected Kawai (40H)"));
+        }
+        if data[4] != GROUP {
+            return Err(invalid_field("group", data, 4)("expected synthesizer group (00H)"));
+        }
+        if data[5] != MACHINE_ID {
+            return Err(invalid_field("machine", data, 5)("expected K4 (04H)"));
+        }
+        let last = data.len() - 1;
+        if data[last] != 0xF7 {
+            return Err(invalid_field("terminator", data, last)("expected F7H"));
+        }
+
+        let header = Header::from_bytes(&data[2..]).map_err(|e| e.offset_by(2))?;
+        let dump = Dump::identify(data[2..last].to_vec()).map_err(|e| e.offset_by(2))?;
+        let data = dump.data().map_err(|e| e.offset_by(2 + Header::data_size()))?;
+
+        Ok(PatchDump { channel: header.channel, locality: dump.locality, data })
+    }
+
+    /// Emits the complete dump message, from F0H to F7H.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut result = vec![0xF0, 0x40];
+        result.extend(self.header().to_bytes());
+        result.extend(self.data.to_bytes());
+        result.push(0xF7);
+        result
+    }
+
+    /// Returns the size of a one effect dump, the smallest K4 dump.
+    fn data_size() -> usize {
+        2 + Header::data_size() + EffectPatch::data_size() + 1
+    }
+}
+
+/// Cardinality of a dump or a dump request.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+enum Cardinality {
+    One,
+    Block,
+    All,
+}
+
+/// Identifies the kind and locality of a dump or a dump request
+/// from the substatus bytes.
+fn identify_kind(cardinality: Cardinality, substatus1: u8, substatus2: u8) -> Option<(Kind, Locality)> {
+    match (cardinality, substatus1, substatus2) {
+        (Cardinality::One, 0x00, number) if (0..=63).contains(&number) =>
+            Some((Kind::OneSingle(number), Locality::Internal)),
+        (Cardinality::One, 0x00, number) if (64..=127).contains(&number) =>
+            Some((Kind::OneMulti(number), Locality::Internal)),
+        (Cardinality::One, 0x02, number) if (0..=63).contains(&number) =>
+            Some((Kind::OneSingle(number), Locality::External)),
+        (Cardinality::One, 0x02, number) if (64..=127).contains(&number) =>
+            Some((Kind::OneMulti(number), Locality::External)),
+        (Cardinality::One, 0x01, number) if (0..=31).contains(&number) =>
+            Some((Kind::OneEffect(number), Locality::Internal)),
+        (Cardinality::One, 0x03, number) if (0..=31).contains(&number) =>
+            Some((Kind::OneEffect(number), Locality::External)),
+        (Cardinality::One, 0x01, 32) =>
+            Some((Kind::Drum, Locality::Internal)),
+        (Cardinality::One, 0x03, 32) =>
+            Some((Kind::Drum, Locality::External)),
+        (Cardinality::Block, 0x00, 0x00) =>
+            Some((Kind::BlockSingle, Locality::Internal)),
+        (Cardinality::Block, 0x00, 0x40) =>
+            Some((Kind::BlockMulti, Locality::Internal)),
+        (Cardinality::Block, 0x02, 0x00) =>
+            Some((Kind::BlockSingle, Locality::External)),
+        (Cardinality::Block, 0x02, 0x40) =>
+            Some((Kind::BlockMulti, Locality::External)),
+        (Cardinality::Block, 0x01, 0x00) =>
+            Some((Kind::BlockEffect, Locality::Internal)),
+        (Cardinality::Block, 0x03, 0x00) =>
+            Some((Kind::BlockEffect, Locality::External)),
+        (Cardinality::All, 0x00, 0x00) =>
+            Some((Kind::All, Locality::Internal)),
+        (Cardinality::All, 0x02, 0x00) =>
+            Some((Kind::All, Locality::External)),
+        _ => None,
+    }
+}
+
+/// Returns the cardinality and the substatus bytes
+/// of a dump or a dump request for `kind` in `locality`.
+/// This is the inverse of `identify_kind`.
+fn kind_substatus(kind: Kind, locality: Locality) -> (Cardinality, u8, u8) {
+    let external = if locality == Locality::External { 0x02 } else { 0x00 };
+    match kind {
+        Kind::OneSingle(number) | Kind::OneMulti(number) => (Cardinality::One, external, number),
+        Kind::OneEffect(number) => (Cardinality::One, external | 0x01, number),
+        Kind::Drum => (Cardinality::One, external | 0x01, 32),
+        Kind::BlockSingle => (Cardinality::Block, external, 0x00),
+        Kind::BlockMulti => (Cardinality::Block, external, 0x40),
+        Kind::BlockEffect => (Cardinality::Block, external | 0x01, 0x00),
+        Kind::All => (Cardinality::All, external, 0x00),
+    }
+}
+
+/// Request message sent to the K4.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Request {
+    /// Dump request for the patch or patches of `Kind`.
+    Dump(MIDIChannel, Locality, Kind),
+
+    /// Program change to a single (0~63) or a multi (64~127).
+    ProgramChange(MIDIChannel, Locality, u8),
+}
+
+impl Request {
+    /// Makes a request for one single patch.
+    pub fn one_single(channel: MIDIChannel, locality: Locality, number: PatchNumber) -> Self {
+        Request::Dump(channel, locality, Kind::OneSingle(number.into_inner()))
+    }
+
+    /// Makes a request for one multi patch.
+    pub fn one_multi(channel: MIDIChannel, locality: Locality, number: PatchNumber) -> Self {
+        Request::Dump(channel, locality, Kind::OneMulti(number.into_inner() + 64))
+    }
+
+    /// Makes a request for one effect patch.
+    pub fn one_effect(channel: MIDIChannel, locality: Locality, number: EffectNumber) -> Self {
+        Request::Dump(channel, locality, Kind::OneEffect(number.into_inner() - 1))
+    }
+
+    /// Makes a request for the drum patch.
+    pub fn drum(channel: MIDIChannel, locality: Locality) -> Self {
+        Request::Dump(channel, locality, Kind::Drum)
+    }
+
+    /// Makes a request for the block of all single patches.
+    pub fn block_single(channel: MIDIChannel, locality: Locality) -> Self {
+        Request::Dump(channel, locality, Kind::BlockSingle)
+    }
+
+    /// Makes a request for the block of all multi patches.
+    pub fn block_multi(channel: MIDIChannel, locality: Locality) -> Self {
+        Request::Dump(channel, locality, Kind::BlockMulti)
+    }
 
+    /// Makes a request for the block of all effect patches.
+    pub fn block_effect(channel: MIDIChannel, locality: Locality) -> Self {
+        Request::Dump(channel, locality, Kind::BlockEffect)
+    }
+
+    /// Makes a request for all the patches.
+    pub fn all(channel: MIDIChannel, locality: Locality) -> Self {
+        Request::Dump(channel, locality, Kind::All)
+    }
+
+    /// Makes a program change to a single patch.
+    pub fn single_program_change(channel: MIDIChannel, locality: Locality, number: PatchNumber) -> Self {
+        Request::ProgramChange(channel, locality, number.into_inner())
+    }
+
+    /// Makes a program change to a multi patch.
+    pub fn multi_program_change(channel: MIDIChannel, locality: Locality, number: PatchNumber) -> Self {
+        Request::ProgramChange(channel, locality, number.into_inner() + 64)
+    }
+
+    /// Returns the header of this request.
+    pub fn header(&self) -> Header {
+        match *self {
+            Request::Dump(channel, locality, kind) => {
+                let (cardinality, substatus1, substatus2) = kind_substatus(kind, locality);
+                let function = match cardinality {
+                    Cardinality::One => Function::OnePatchDumpRequest,
+                    Cardinality::Block => Function::BlockPatchDumpRequest,
+                    Cardinality::All => Function::AllPatchDumpRequest,
+                };
+                Header { channel, function, substatus1, substatus2 }
+            },
+            Request::ProgramChange(channel, locality, number) => {
+                let substatus1 = if locality == Locality::External { 0x02 } else { 0x00 };
+                Header { channel, function: Function::ProgramChange, substatus1, substatus2: number }
+            },
         }
     }
 }
 
+impl fmt::Display for Request {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Request::Dump(channel, locality, kind) =>
+                write!(f, "Dump request for {:?} {}, channel {}", kind, locality, channel.value()),
+            Request::ProgramChange(channel, locality, number) =>
+                write!(f, "Program change to {} {}, channel {}", number, locality, channel.value()),
+        }
+    }
+}
+
+impl SystemExclusiveData for Request {
+    /// Parses a complete request message, from F0H to F7H.
+    fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
+
+        if data[0] != 0xF0 {
+            return Err(invalid_field("initiator", data, 0)("expected F0H"));
+        }
+        if data[1] != 0x40 {
+            return Err(invalid_field("manufacturer", data, 1)("expected Kawai (40H)"));
+        }
+        let header = Header::from_bytes(&data[2..]).map_err(|e| e.offset_by(2))?;
+        if data[4] != GROUP {
+            return Err(invalid_field("group", data, 4)("expected synthesizer group (00H)"));
+        }
+        if data[5] != MACHINE_ID {
+            return Err(invalid_field("machine", data, 5)("expected K4 (04H)"));
+        }
+        if data[8] != 0xF7 {
+            return Err(invalid_field("terminator", data, 8)("expected F7H"));
+        }
+
+        let locality = if header.substatus1 & 0x02 != 0 { Locality::External } else { Locality::Internal };
+        let cardinality = match header.function {
+            Function::OnePatchDumpRequest => Cardinality::One,
+            Function::BlockPatchDumpRequest => Cardinality::Block,
+            Function::AllPatchDumpRequest => Cardinality::All,
+            Function::ProgramChange => {
+                if header.substatus1 & !0x02 != 0 {
+                    return Err(invalid_field("substatus1", data, 6)("expected 00H or 02H"));
+                }
+                if header.substatus2 > 127 {
+                    return Err(invalid_field("substatus2", data, 7)("expected 0~127"));
+                }
+                return Ok(Request::ProgramChange(header.channel, locality, header.substatus2));
+            },
+            _ => return Err(invalid_field("function", data, 3)("expected a request")),
+        };
+
+        match identify_kind(cardinality, header.substatus1, header.substatus2) {
+            Some((kind, locality)) => Ok(Request::Dump(header.channel, locality, kind)),
+            None => Err(ParseError::Unidentified),
+        }
+    }
+
+    /// Emits the complete request message, from F0H to F7H.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut result = vec![0xF0, 0x40];
+        result.extend(self.header().to_bytes());
+        result.push(0xF7);
+        result
+    }
+
+    fn data_size() -> usize { 9 }
+}
+
+/// Reason why the K4 could not write the data it received.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum WriteError {
+    General,
+    Protect,
+    NoCard,
+}
+
+impl WriteError {
+    fn function(&self) -> Function {
+        match self {
+            WriteError::General => Function::WriteError,
+            WriteError::Protect => Function::WriteErrorProtect,
+            WriteError::NoCard => Function::WriteErrorNoCard,
+        }
+    }
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match self {
+            WriteError::General => "Write error",
+            WriteError::Protect => "Write error: memory is protected",
+            WriteError::NoCard => "Write error: no card inserted",
+        })
+    }
+}
+
+impl std::error::Error for WriteError { }
+
+/// Response sent by the K4 after receiving a dump:
+/// F0H 40H 0nH <function> 00H 04H F7H.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct Response {
+    pub channel: MIDIChannel,
+    pub result: Result<(), WriteError>,
+}
+
+impl Response {
+    /// Makes a write complete response.
+    pub fn complete(channel: MIDIChannel) -> Self {
+        Response { channel, result: Ok(()) }
+    }
+
+    /// Makes a write error response.
+    pub fn error(channel: MIDIChannel, error: WriteError) -> Self {
+        Response { channel, result: Err(error) }
+    }
+
+    /// Returns the function of this response.
+    pub fn function(&self) -> Function {
+        match self.result {
+            Ok(()) => Function::WriteComplete,
+            Err(error) => error.function(),
+        }
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.result {
+            Ok(()) => write!(f, "Write complete, channel {}", self.channel.value()),
+            Err(error) => write!(f, "{}, channel {}", error, self.channel.value()),
+        }
+    }
+}
+
+impl SystemExclusiveData for Response {
+    /// Parses a complete response message, from F0H to F7H.
+    fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
+
+        if data[0] != 0xF0 {
+            return Err(invalid_field("initiator", data, 0)("expected F0H"));
+        }
+        if data[1] != 0x40 {
+            return Err(invalid_field("manufacturer", data, 1)("expected Kawai (40H)"));
+        }
+        let channel = MIDIChannel::from_bytes(&data[2..3]).map_err(|e| e.within("channel", 2))?;
+        let function = Function::try_from(data[3]).map_err(invalid_field("function", data, 3))?;
+        if data[4] != GROUP {
+            return Err(invalid_field("group", data, 4)("expected synthesizer group (00H)"));
+        }
+        if data[5] != MACHINE_ID {
+            return Err(invalid_field("machine", data, 5)("expected K4 (04H)"));
+        }
+        if data[6] != 0xF7 {
+            return Err(invalid_field("terminator", data, 6)("expected F7H"));
+        }
+
+        let result = match function {
+            Function::WriteComplete => Ok(()),
+            Function::WriteError => Err(WriteError::General),
+            Function::WriteErrorProtect => Err(WriteError::Protect),
+            Function::WriteErrorNoCard => Err(WriteError::NoCard),
+            _ => return Err(invalid_field("function", data, 3)("expected a write response")),
+        };
+
+        Ok(Response { channel, result })
+    }
+
+    /// Emits the complete response message, from F0H to F7H.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut result = vec![0xF0, 0x40];
+        result.extend(self.channel.to_bytes());
+        result.push(self.function() as u8);
+        result.push(GROUP);
+        result.push(MACHINE_ID);
+        result.push(0xF7);
+        result
+    }
+
+    fn data_size() -> usize { 7 }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{*};
     use syxpack::Message;
 
-    static DATA: &'static [u8] = include_bytes!("A401.SYX");
+    static DATA: &[u8] = include_bytes!("A401.SYX");
 
     #[test]
     fn test_dump_identify_all() {
-        match Message::from_bytes(&DATA.to_vec()) {
+        match Message::from_bytes(DATA) {
             Ok(Message::ManufacturerSpecific { manufacturer: _, payload }) => {
                 match Dump::identify(payload) {
                     Ok(dump) => {
@@ -228,4 +703,190 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_patch_dump_round_trip() {
+        let dump = PatchDump::from_bytes(DATA).unwrap();
+        assert_eq!(dump.locality, Locality::Internal);
+        match &dump.data {
+            DumpData::All(bank) => assert_eq!(bank.singles[0].name, "Melo Vox 1"),
+            _ => panic!("expected a full bank"),
+        }
+
+        assert_eq!(dump.to_bytes(), DATA);
+    }
+
+    #[test]
+    fn test_patch_dump_one_single() {
+        let payload: [u8; 137] = include!("intsingle.in");
+        let mut data = vec![0xF0, 0x40];
+        data.extend(payload);
+        data.push(0xF7);
+
+        let dump = PatchDump::from_bytes(&data).unwrap();
+        assert!(matches!(dump.data, DumpData::OneSingle(number, _) if number.into_inner() == 0));
+        let emitted = dump.to_bytes();
+        assert_eq!(emitted.len(), data.len());
+        assert_eq!(emitted[..8], data[..8]);
+        assert!(matches!(PatchDump::from_bytes(&emitted).unwrap().data, DumpData::OneSingle(number, _) if number.into_inner() == 0));
+    }
+
+    #[test]
+    fn test_patch_dump_block_and_multi() {
+        let bank = match PatchDump::from_bytes(DATA).unwrap().data {
+            DumpData::All(bank) => bank,
+            _ => panic!("expected a full bank"),
+        };
+        let channel = MIDIChannel::try_new(3).unwrap();
+
+        let dump = PatchDump {
+            channel,
+            locality: Locality::External,
+            data: DumpData::BlockEffect(bank.effects.clone()),
+        };
+        let data = dump.to_bytes();
+        assert_eq!(data[..8], [0xF0, 0x40, 0x02, 0x21, 0x00, 0x04, 0x03, 0x00]);
+        assert_eq!(data.len(), 8 + 32 * EffectPatch::data_size() + 1);
+        let parsed = PatchDump::from_bytes(&data).unwrap();
+        assert_eq!(parsed.data.kind(), Kind::BlockEffect);
+        assert_eq!(parsed.to_bytes(), data);
+
+        let dump = PatchDump {
+            channel,
+            locality: Locality::Internal,
+            data: DumpData::OneMulti(PatchNumber::try_new(5).unwrap(), bank.multis[5].clone()),
+        };
+        let data = dump.to_bytes();
+        assert_eq!(data[..8], [0xF0, 0x40, 0x02, 0x20, 0x00, 0x04, 0x00, 0x45]);
+        assert!(matches!(PatchDump::from_bytes(&data).unwrap().data, DumpData::OneMulti(number, _) if number.into_inner() == 5));
+
+        let dump = PatchDump {
+            channel,
+            locality: Locality::Internal,
+            data: DumpData::OneEffect(EffectNumber::try_new(32).unwrap(), bank.effects[31].clone()),
+        };
+        let data = dump.to_bytes();
+        assert_eq!(data[..8], [0xF0, 0x40, 0x02, 0x20, 0x00, 0x04, 0x01, 0x1F]);
+        assert!(matches!(PatchDump::from_bytes(&data).unwrap().data, DumpData::OneEffect(number, _) if number.into_inner() == 32));
+    }
+
+    #[test]
+    fn test_dump_data_bad_number() {
+        let data = vec![0; MultiPatch::data_size()];
+        assert!(matches!(DumpData::parse(Kind::OneMulti(0x20), &data), Err(ParseError::InvalidData(0, _))));
+        let data = vec![0; SinglePatch::data_size()];
+        assert!(matches!(DumpData::parse(Kind::OneSingle(0x40), &data), Err(ParseError::InvalidData(0, _))));
+        let data = vec![0; EffectPatch::data_size()];
+        assert!(matches!(DumpData::parse(Kind::OneEffect(0x20), &data), Err(ParseError::InvalidData(0, _))));
+    }
+
+    #[test]
+    fn test_patch_dump_error_offset() {
+        let mut data = DATA.to_vec();
+        let offset = 8 + 31;  // singles[0].sources[1].delay
+        data[offset] = 0x7F;
+        match PatchDump::from_bytes(&data) {
+            Err(ParseError::InvalidField(error)) => {
+                assert_eq!(error.path, "singles[0].sources[1].delay");
+                assert_eq!(error.offset, offset);
+            },
+            _ => panic!("expected an invalid field error"),
+        }
+    }
+
+    #[test]
+    fn test_one_single_request() {
+        let channel = MIDIChannel::try_new(2).unwrap();
+        let request = Request::one_single(channel, Locality::Internal, PatchNumber::try_new(5).unwrap());
+        let data = request.to_bytes();
+        assert_eq!(data, vec![0xF0, 0x40, 0x01, 0x00, 0x00, 0x04, 0x00, 0x05, 0xF7]);
+        assert_eq!(Request::from_bytes(&data), Ok(request));
+    }
+
+    #[test]
+    fn test_one_multi_request() {
+        let channel = MIDIChannel::try_new(1).unwrap();
+        let request = Request::one_multi(channel, Locality::External, PatchNumber::try_new(63).unwrap());
+        let data = request.to_bytes();
+        assert_eq!(data, vec![0xF0, 0x40, 0x00, 0x00, 0x00, 0x04, 0x02, 0x7F, 0xF7]);
+        assert_eq!(Request::from_bytes(&data), Ok(request));
+    }
+
+    #[test]
+    fn test_effect_and_drum_requests() {
+        let channel = MIDIChannel::try_new(1).unwrap();
+        let request = Request::one_effect(channel, Locality::External, EffectNumber::try_new(32).unwrap());
+        assert_eq!(request.to_bytes(), vec![0xF0, 0x40, 0x00, 0x00, 0x00, 0x04, 0x03, 0x1F, 0xF7]);
+        assert_eq!(Request::from_bytes(&request.to_bytes()), Ok(request));
+
+        let request = Request::drum(channel, Locality::Internal);
+        assert_eq!(request.to_bytes(), vec![0xF0, 0x40, 0x00, 0x00, 0x00, 0x04, 0x01, 0x20, 0xF7]);
+        assert_eq!(Request::from_bytes(&request.to_bytes()), Ok(request));
+    }
+
+    #[test]
+    fn test_block_and_all_requests() {
+        let channel = MIDIChannel::try_new(16).unwrap();
+        let requests = [
+            (Request::block_single(channel, Locality::Internal), [0x01, 0x00, 0x00]),
+            (Request::block_multi(channel, Locality::External), [0x01, 0x02, 0x40]),
+            (Request::block_effect(channel, Locality::Internal), [0x01, 0x01, 0x00]),
+            (Request::all(channel, Locality::External), [0x02, 0x02, 0x00]),
+        ];
+        for (request, [function, substatus1, substatus2]) in requests {
+            let data = request.to_bytes();
+            assert_eq!(data, vec![0xF0, 0x40, 0x0F, function, 0x00, 0x04, substatus1, substatus2, 0xF7]);
+            assert_eq!(Request::from_bytes(&data), Ok(request));
+        }
+    }
+
+    #[test]
+    fn test_program_change() {
+        let channel = MIDIChannel::try_new(1).unwrap();
+        let request = Request::multi_program_change(channel, Locality::Internal, PatchNumber::try_new(0).unwrap());
+        let data = request.to_bytes();
+        assert_eq!(data, vec![0xF0, 0x40, 0x00, 0x30, 0x00, 0x04, 0x00, 0x40, 0xF7]);
+        assert_eq!(Request::from_bytes(&data), Ok(request));
+    }
+
+    #[test]
+    fn test_dump_is_not_request() {
+        let data = [0xF0, 0x40, 0x00, 0x20, 0x00, 0x04, 0x00, 0x00, 0xF7];
+        assert!(Request::from_bytes(&data).is_err());
+    }
+
+    #[test]
+    fn test_write_complete() {
+        let channel = MIDIChannel::try_new(1).unwrap();
+        let data = [0xF0, 0x40, 0x00, 0x40, 0x00, 0x04, 0xF7];
+        let response = Response::from_bytes(&data).unwrap();
+        assert_eq!(response, Response::complete(channel));
+        assert_eq!(response.result, Ok(()));
+        assert_eq!(response.to_bytes(), data.to_vec());
+    }
+
+    #[test]
+    fn test_write_errors() {
+        let channel = MIDIChannel::try_new(5).unwrap();
+        let errors = [
+            (WriteError::General, 0x41),
+            (WriteError::Protect, 0x42),
+            (WriteError::NoCard, 0x43),
+        ];
+        for (error, function) in errors {
+            let data = vec![0xF0, 0x40, 0x04, function, 0x00, 0x04, 0xF7];
+            let response = Response::error(channel, error);
+            assert_eq!(response.to_bytes(), data);
+            assert_eq!(Response::from_bytes(&data), Ok(response));
+        }
+
+        let error: Box<dyn std::error::Error> = Box::new(WriteError::Protect);
+        assert_eq!(error.to_string(), "Write error: memory is protected");
+    }
+
+    #[test]
+    fn test_request_is_not_response() {
+        let data = [0xF0, 0x40, 0x00, 0x00, 0x00, 0x04, 0xF7];
+        assert!(Response::from_bytes(&data).is_err());
+    }
 }
@@ -0,0 +1,426 @@
+//! Consistency checks for K4 banks.
+//!
+//! The checks find things that parse fine but are probably mistakes,
+//! like multis that play initialized singles, or overlapping zones.
+//! Each problem is reported as a finding with a severity and a location,
+//! so that the results can be shown in a CI job or an editor.
+//!
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::{SystemExclusiveData, Checksum, ParseError};
+use crate::k4::DRUM_NOTE_COUNT;
+use crate::k4::bank::{Bank, SINGLE_PATCH_COUNT, MULTI_PATCH_COUNT, EFFECT_PATCH_COUNT};
+use crate::k4::drum::{self, DrumPatch};
+use crate::k4::edit::Group;
+use crate::k4::effect::EffectPatch;
+use crate::k4::multi::{MultiPatch, Section, VelocitySwitch};
+use crate::k4::single::SinglePatch;
+use crate::k4::wave::WaveCategory;
+
+/// Severity of a finding.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
+pub enum Severity {
+    /// Worth knowing, but often intentional.
+    Info,
+
+    /// Probably a mistake.
+    Warning,
+
+    /// The data is broken.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// Location of a finding in a bank. The indexes are zero-based.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Location {
+    Bank,
+    Single(usize),
+    Multi(usize),
+    Section(usize, usize),  // multi, section
+    Drum,
+    DrumNote(usize),
+    Effect(usize),
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Location::Bank => write!(f, "bank"),
+            Location::Single(i) => write!(f, "singles[{}]", i),
+            Location::Multi(i) => write!(f, "multis[{}]", i),
+            Location::Section(m, s) => write!(f, "multis[{}].sections[{}]", m, s),
+            Location::Drum => write!(f, "drum"),
+            Location::DrumNote(i) => write!(f, "drum.notes[{}]", i),
+            Location::Effect(i) => write!(f, "effects[{}]", i),
+        }
+    }
+}
+
+/// A problem found in a bank.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Finding {
+    pub severity: Severity,
+    pub location: Location,
+    pub message: String,
+}
+
+impl Finding {
+    fn new<S: Into<String>>(severity: Severity, location: Location, message: S) -> Self {
+        Finding { severity, location, message: message.into() }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.severity, self.location, self.message)
+    }
+}
+
+/// The findings of a validation run.
+#[derive(Debug, Default, Eq, PartialEq, Clone)]
+pub struct Report {
+    pub findings: Vec<Finding>,
+}
+
+impl Report {
+    /// Returns true if there are no warnings or errors.
+    pub fn is_clean(&self) -> bool {
+        self.findings.iter().all(|f| f.severity == Severity::Info)
+    }
+
+    /// Returns the process exit code for this report:
+    /// zero if the bank is clean, one if it is not.
+    pub fn exit_code(&self) -> i32 {
+        if self.is_clean() { 0 } else { 1 }
+    }
+
+    /// Returns the most severe finding level, if there are any findings.
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.findings.iter().map(|f| f.severity).max()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for finding in self.findings.iter() {
+            writeln!(f, "{}", finding)?;
+        }
+        Ok(())
+    }
+}
+
+/// Validates a bank.
+pub fn validate(bank: &Bank) -> Report {
+    let mut report = Report::default();
+    check_references(bank, &mut report);
+    check_zones(bank, &mut report);
+    check_names(bank, &mut report);
+    check_drum(&bank.drum, &mut report);
+    report
+}
+
+/// Validates the data of an all patch dump, without the header
+/// and the terminator. The checksums are verified against the data
+/// as it was received, and if the data parses, the bank is validated too.
+pub fn validate_data(data: &[u8]) -> Report {
+    let mut report = Report::default();
+    if let Err(e) = check_checksums(data, &mut report) {
+        report.findings.push(Finding::new(Severity::Error, Location::Bank, e.to_string()));
+        return report;
+    }
+
+    match Bank::from_bytes(data) {
+        Ok(bank) => report.findings.extend(validate(&bank).findings),
+        Err(ParseError::InvalidField(e)) => {
+            let location = location_of(&e.path);
+            report.findings.push(Finding::new(Severity::Error, location, e.to_string()));
+        },
+        Err(e) => report.findings.push(Finding::new(Severity::Error, Location::Bank, e.to_string())),
+    }
+    report
+}
+
+fn check_references(bank: &Bank, report: &mut Report) {
+    for (m, multi) in bank.multis.iter().enumerate() {
+        if bank.is_free(Group::Multis, m) {
+            continue;
+        }
+        for (s, section) in multi.sections.iter().enumerate() {
+            let single = section.single_number.into_inner() as usize;
+            if !section.is_muted && bank.is_free(Group::Singles, single) {
+                report.findings.push(Finding::new(Severity::Warning, Location::Section(m, s),
+                    format!("plays single {}, which is initialized", single + 1)));
+            }
+        }
+    }
+
+    for (i, single) in bank.singles.iter().enumerate() {
+        if bank.is_free(Group::Singles, i) {
+            continue;
+        }
+        let effect = single.effect.into_inner() as usize - 1;
+        if bank.is_free(Group::Effects, effect) {
+            report.findings.push(Finding::new(Severity::Warning, Location::Single(i),
+                format!("uses effect {}, which is initialized", effect + 1)));
+        }
+    }
+}
+
+fn check_zones(bank: &Bank, report: &mut Report) {
+    for (m, multi) in bank.multis.iter().enumerate() {
+        for (s, section) in multi.sections.iter().enumerate() {
+            if section.zone.low_key.note.value() > section.zone.high_key.note.value() {
+                report.findings.push(Finding::new(Severity::Error, Location::Section(m, s),
+                    format!("zone {} is inverted", section.zone)));
+            }
+        }
+
+        for (s, section) in multi.sections.iter().enumerate() {
+            for (t, other) in multi.sections.iter().enumerate().skip(s + 1) {
+                if overlaps(section, other) {
+                    report.findings.push(Finding::new(Severity::Warning, Location::Section(m, t),
+                        format!("zone {} overlaps section {} zone {}", other.zone, s + 1, section.zone)));
+                }
+            }
+        }
+    }
+}
+
+/// Returns true if two unmuted sections play on the same channel,
+/// for the same velocities and in overlapping zones.
+fn overlaps(a: &Section, b: &Section) -> bool {
+    let velocities_overlap = a.velocity_switch == VelocitySwitch::All
+        || b.velocity_switch == VelocitySwitch::All
+        || a.velocity_switch == b.velocity_switch;
+    !a.is_muted && !b.is_muted
+        && a.receive_channel == b.receive_channel
+        && velocities_overlap
+        && a.zone.low_key.note.value() <= b.zone.high_key.note.value()
+        && b.zone.low_key.note.value() <= a.zone.high_key.note.value()
+}
+
+fn check_names(bank: &Bank, report: &mut Report) {
+    let single_names = bank.singles.iter().enumerate()
+        .filter(|(i, _)| !bank.is_free(Group::Singles, *i))
+        .map(|(i, p)| (i, p.name.as_str()));
+    for (i, first) in duplicates(single_names) {
+        report.findings.push(Finding::new(Severity::Warning, Location::Single(i),
+            format!("has the same name as single {}", first + 1)));
+    }
+
+    let multi_names = bank.multis.iter().enumerate()
+        .filter(|(i, _)| !bank.is_free(Group::Multis, *i))
+        .map(|(i, p)| (i, p.name.as_str()));
+    for (i, first) in duplicates(multi_names) {
+        report.findings.push(Finding::new(Severity::Warning, Location::Multi(i),
+            format!("has the same name as multi {}", first + 1)));
+    }
+}
+
+/// Returns the index of each patch whose name was seen before,
+/// with the index of the first patch that had the name.
+fn duplicates<'a, I: Iterator<Item = (usize, &'a str)>>(names: I) -> Vec<(usize, usize)> {
+    let mut seen = BTreeMap::<&str, usize>::new();
+    let mut result = Vec::new();
+    for (i, name) in names {
+        match seen.get(name.trim_end()) {
+            Some(&first) => result.push((i, first)),
+            None => { seen.insert(name.trim_end(), i); },
+        }
+    }
+    result
+}
+
+/// Drum sources can use any wave, but waves outside of the drum and
+/// percussion group are reported as warnings, since they are often
+/// left over from copying a single.
+fn check_drum(drum: &DrumPatch, report: &mut Report) {
+    let percussion = WaveCategory::Drum.range();
+    for (i, note) in drum.notes.iter().enumerate() {
+        for (n, source) in [note.source1, note.source2].iter().enumerate() {
+            let number = source.wave.number.into_inner();
+            if source.level.into_inner() > 0 && !percussion.contains(&number) {
+                report.findings.push(Finding::new(Severity::Warning, Location::DrumNote(i),
+                    format!("source {} uses wave {}, which is not a drum or percussion wave", n + 1, source.wave)));
+            }
+        }
+    }
+}
+
+/// Returns the location of the field at `path` in a bank,
+/// like `drum.notes[3].source1.wave`.
+fn location_of(path: &str) -> Location {
+    let indexes: Vec<usize> = path.split('[')
+        .skip(1)
+        .filter_map(|part| part.split(']').next()?.parse().ok())
+        .collect();
+    let index = |i: usize| indexes.get(i).copied().unwrap_or(0);
+    if path.starts_with("singles[") {
+        Location::Single(index(0))
+    } else if path.starts_with("multis[") && path.contains(".sections[") {
+        Location::Section(index(0), index(1))
+    } else if path.starts_with("multis[") {
+        Location::Multi(index(0))
+    } else if path.starts_with("drum.notes[") {
+        Location::DrumNote(index(0))
+    } else if path.starts_with("drum") {
+        Location::Drum
+    } else if path.starts_with("effects[") {
+        Location::Effect(index(0))
+    } else {
+        Location::Bank
+    }
+}
+
+/// Checks the checksum of each patch in the data of an all patch dump.
+fn check_checksums(data: &[u8], report: &mut Report) -> Result<(), ParseError> {
+    crate::check_length(data, Bank::data_size())?;
+
+    let mut offset = 0;
+    for i in 0..SINGLE_PATCH_COUNT {
+        check_checksum::<SinglePatch>(data, &mut offset, Location::Single(i), report);
+    }
+    for i in 0..MULTI_PATCH_COUNT {
+        check_checksum::<MultiPatch>(data, &mut offset, Location::Multi(i), report);
+    }
+    check_checksum::<drum::Common>(data, &mut offset, Location::Drum, report);
+    for i in 0..DRUM_NOTE_COUNT {
+        check_checksum::<drum::Note>(data, &mut offset, Location::DrumNote(i), report);
+    }
+    for i in 0..EFFECT_PATCH_COUNT {
+        check_checksum::<EffectPatch>(data, &mut offset, Location::Effect(i), report);
+    }
+    Ok(())
+}
+
+/// Checks the checksum byte at the end of the patch at `offset` against
+/// the checksum of the parsed patch, and advances `offset` past the patch.
+/// A patch that does not parse is left for the bank parser to report.
+fn check_checksum<T: SystemExclusiveData + Checksum>(data: &[u8], offset: &mut usize, location: Location, report: &mut Report) {
+    let patch_data = &data[*offset..*offset + T::data_size()];
+    *offset += T::data_size();
+    if let Ok(patch) = T::from_bytes(patch_data) {
+        let (actual, expected) = (patch_data[T::data_size() - 1], patch.checksum());
+        if actual != expected {
+            report.findings.push(Finding::new(Severity::Error, location,
+                format!("checksum is {:02X}H, but the data sums to {:02X}H", actual, expected)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{*};
+    use crate::{MIDINote, MIDIChannel};
+    use crate::k4::{PatchNumber, WaveNumber};
+    use crate::k4::multi::Key;
+    use crate::k4::wave::Wave;
+
+    static DATA: &[u8] = include_bytes!("A401.SYX");
+
+    fn payload() -> &'static [u8] {
+        &DATA[8..DATA.len() - 1]
+    }
+
+    #[test]
+    fn test_factory_bank_checksums() {
+        let report = validate_data(payload());
+        assert!(report.findings.iter().all(|f| !f.message.starts_with("checksum")), "{}", report);
+    }
+
+    #[test]
+    fn test_bad_checksum() {
+        let mut data = payload().to_vec();
+        data[SinglePatch::data_size() + 3] ^= 0x01;  // fourth character of the name of single 2
+        let report = validate_data(&data);
+        let errors: Vec<&Finding> = report.findings.iter()
+            .filter(|f| f.severity == Severity::Error)
+            .collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].location, Location::Single(1));
+        assert!(errors[0].message.starts_with("checksum is"));
+        assert!(!report.is_clean());
+        assert_eq!(report.exit_code(), 1);
+    }
+
+    #[test]
+    fn test_section_plays_initialized_single() {
+        let mut bank = Bank::from_bytes(payload()).unwrap();
+        bank.singles[10] = SinglePatch::default();
+        bank.multis[0].sections[3].single_number = PatchNumber::try_new(10).unwrap();
+        bank.multis[0].sections[3].is_muted = false;
+
+        let report = validate(&bank);
+        assert!(report.findings.iter().any(|f| f.location == Location::Section(0, 3) && f.severity == Severity::Warning));
+    }
+
+    #[test]
+    fn test_inverted_zone() {
+        let mut bank = Bank::from_bytes(payload()).unwrap();
+        bank.multis[2].sections[1].zone.low_key = Key { note: MIDINote::try_new(80).unwrap() };
+        bank.multis[2].sections[1].zone.high_key = Key { note: MIDINote::try_new(40).unwrap() };
+
+        let report = validate(&bank);
+        assert!(report.findings.iter().any(|f| f.location == Location::Section(2, 1) && f.severity == Severity::Error));
+        assert_eq!(report.max_severity(), Some(Severity::Error));
+    }
+
+    #[test]
+    fn test_overlapping_zones() {
+        let mut bank = Bank::from_bytes(payload()).unwrap();
+        for section in bank.multis[4].sections.iter_mut().take(2) {
+            section.is_muted = false;
+            section.receive_channel = MIDIChannel::try_new(1).unwrap();
+            section.velocity_switch = VelocitySwitch::All;
+            section.zone.low_key = Key { note: MIDINote::try_new(40).unwrap() };
+            section.zone.high_key = Key { note: MIDINote::try_new(80).unwrap() };
+        }
+
+        let report = validate(&bank);
+        assert!(report.findings.iter().any(|f| f.location == Location::Section(4, 1) && f.severity == Severity::Warning));
+        assert!(!report.is_clean());
+    }
+
+    #[test]
+    fn test_duplicate_names() {
+        let mut bank = Bank::from_bytes(payload()).unwrap();
+        bank.singles[7].name = bank.singles[3].name.clone();
+
+        let report = validate(&bank);
+        assert!(report.findings.contains(&Finding::new(Severity::Warning, Location::Single(7), "has the same name as single 4")));
+    }
+
+    #[test]
+    fn test_drum_wave() {
+        let mut bank = Bank::from_bytes(payload()).unwrap();
+        bank.drum.notes[5].source1.wave = Wave { number: WaveNumber::try_new(1).unwrap() };
+
+        let report = validate(&bank);
+        assert!(report.findings.iter().any(|f| f.location == Location::DrumNote(5)));
+        assert!(format!("{}", report).contains("warning: drum.notes[5]: source 1 uses wave 1 SIN 1ST"));
+        assert!(!report.is_clean());
+    }
+
+    #[test]
+    fn test_invalid_field_location() {
+        let mut data = payload().to_vec();
+        let offset = 64 * SinglePatch::data_size() + 3 * MultiPatch::data_size() + 12 + 8 + 5;
+        data[offset] = 0x7F;  // level of section 2 in multi 4
+        let report = validate_data(&data);
+        assert!(report.findings.iter().any(|f| f.location == Location::Section(3, 1) && f.severity == Severity::Error));
+        assert_eq!(location_of("drum.notes[12].source2.wave"), Location::DrumNote(12));
+        assert_eq!(location_of("drum.common.volume"), Location::Drum);
+    }
+}
@@ -3,9 +3,12 @@
 
 use std::fmt;
 use std::convert::TryInto;
+use std::ops::RangeInclusive;
+
+use strum::IntoEnumIterator;
 
 use crate::k4::WaveNumber;
-use crate::{SystemExclusiveData, ParseError};
+use crate::{SystemExclusiveData, ParseError, check_length, invalid_field};
 
 static WAVE_NAMES: &[&str] = &[
     "(not used)",  // just to bring the index in line with the one-based wave number
@@ -278,6 +281,51 @@ static WAVE_NAMES: &[&str] = &[
     "LOOP 12"
 ];
 
+/// Wave category, following the groups of the K4 wave list.
+#[derive(Debug, Eq, PartialEq, Copy, Clone, strum_macros::Display, strum_macros::EnumIter)]
+pub enum WaveCategory {
+    #[strum(to_string = "Cyclic")]
+    Cyclic,
+
+    #[strum(to_string = "Drum & Percussion")]
+    Drum,
+
+    #[strum(to_string = "Multi")]
+    Multi,
+
+    #[strum(to_string = "Block")]
+    Block,
+
+    #[strum(to_string = "Reverse")]
+    Reverse,
+
+    #[strum(to_string = "Loop")]
+    Loop,
+}
+
+serialize_by_name!(WaveCategory, WaveCategory::iter());
+
+impl WaveCategory {
+    /// Returns the range of wave numbers in this category.
+    pub fn range(&self) -> RangeInclusive<u16> {
+        match self {
+            WaveCategory::Cyclic => 1..=96,
+            WaveCategory::Drum => 97..=139,
+            WaveCategory::Multi => 140..=192,
+            WaveCategory::Block => 193..=233,
+            WaveCategory::Reverse => 234..=244,
+            WaveCategory::Loop => 245..=256,
+        }
+    }
+
+    /// Returns the waves in this category.
+    pub fn waves(&self) -> Vec<Wave> {
+        self.range()
+            .map(|n| Wave { number: WaveNumber::try_new(n).unwrap() })
+            .collect()
+    }
+}
+
 /// Wave in a source of a single patch DCO.
 #[derive(Copy, Clone)]
 pub struct Wave {
@@ -300,6 +348,24 @@ impl Wave {
     pub fn name(&self) -> String {
         WAVE_NAMES[self.number.into_inner() as usize].to_string()
     }
+
+    /// Returns the category of this wave.
+    pub fn category(&self) -> WaveCategory {
+        let number = self.number.into_inner();
+        WaveCategory::iter()
+            .find(|c| c.range().contains(&number))
+            .unwrap()  // the categories cover all the wave numbers
+    }
+
+    /// Finds a wave by its name, ignoring case. Some names
+    /// (like "MARIMBA") appear in more than one category,
+    /// and then the wave with the lowest number is returned.
+    pub fn from_name(name: &str) -> Option<Wave> {
+        WAVE_NAMES.iter()
+            .skip(1)
+            .position(|n| n.eq_ignore_ascii_case(name))
+            .map(|index| Wave { number: WaveNumber::try_new(index as u16 + 1).unwrap() })
+    }
 }
 
 impl fmt::Display for Wave {
@@ -313,19 +379,55 @@ impl fmt::Display for Wave {
     }
 }
 
+/// Waves are serialized by name. A name that belongs to more than one wave
+/// is written with the number, like "97 MARIMBA", and either form is read.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Wave {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match Wave::from_name(&self.name()) {
+            Some(wave) if wave.number == self.number => serializer.collect_str(&self.name()),
+            _ => serializer.collect_str(self),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Wave {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let text = <String as serde::Deserialize>::deserialize(deserializer)?;
+        let numbered = text.split_once(' ')
+            .and_then(|(number, name)| number.parse::<u16>().ok().map(|number| (number, name)));
+        match numbered {
+            Some((number, name)) => {
+                let wave = Wave { number: WaveNumber::try_new(number).map_err(D::Error::custom)? };
+                if wave.name().eq_ignore_ascii_case(name) {
+                    Ok(wave)
+                } else {
+                    Err(D::Error::custom(format!("wave {} is \"{}\", not \"{}\"", number, wave.name(), name)))
+                }
+            },
+            None => Wave::from_name(&text)
+                .ok_or_else(|| D::Error::custom(format!("unknown wave \"{}\"", text))),
+        }
+    }
+}
+
 impl SystemExclusiveData for Wave {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         let high = data[0] & 0x01;  // `wave select h` is b0 of s34/s35/s36/s37
         let low = data[1] & 0x7f;   // `wave select l` is bits 0...6 of s38/s39/s40/s41
         Ok(Wave {
-            number: WaveNumber::try_new((((high as u16) << 7) | low as u16) + 1).unwrap(),
+            number: WaveNumber::try_new((((high as u16) << 7) | low as u16) + 1).map_err(invalid_field("", data, 0))?,
         })
     }
 
     fn to_bytes(&self) -> Vec<u8> {
         let n = self.number.into_inner() - 1;
         vec![
-            (n & 0b0000_0001).try_into().unwrap(),
+            ((n >> 7) & 0b0000_0001).try_into().unwrap(),
             (n & 0x7f).try_into().unwrap(),
         ]
     }
@@ -359,5 +461,36 @@ mod tests {
         };
 
         assert_eq!(wave.to_bytes(), vec![0x01, 0x7f]);
+
+        let wave = Wave {
+            number: WaveNumber::try_new(130).unwrap(),
+        };
+        assert_eq!(wave.to_bytes(), vec![0x01, 0x01]);
+    }
+
+    #[test]
+    fn test_all_wave_names() {
+        assert_eq!(WAVE_NAMES.len(), 257);
+        let wave = Wave { number: WaveNumber::try_new(256).unwrap() };
+        assert_eq!(wave.name(), "LOOP 12");
+    }
+
+    #[test]
+    fn test_wave_category() {
+        let wave = Wave { number: WaveNumber::try_new(97).unwrap() };
+        assert_eq!(wave.name(), "KICK");
+        assert_eq!(wave.category(), WaveCategory::Drum);
+
+        let count: usize = WaveCategory::iter().map(|c| c.waves().len()).sum();
+        assert_eq!(count, 256);
+        assert_eq!(WaveCategory::Block.waves()[0].name(), "PIANO 1");
+        assert_eq!(WaveCategory::Reverse.waves().last().unwrap().name(), "REVERSE 11");
+    }
+
+    #[test]
+    fn test_wave_from_name() {
+        assert_eq!(Wave::from_name("loop 1").unwrap().number.into_inner(), 245);
+        assert_eq!(Wave::from_name("MARIMBA").unwrap().category(), WaveCategory::Cyclic);
+        assert!(Wave::from_name("NO SUCH WAVE").is_none());
     }
 }
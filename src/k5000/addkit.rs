@@ -4,7 +4,8 @@
 use crate::{
     SystemExclusiveData,
     ParseError,
-    Checksum
+    Checksum,
+    check_length
 };
 use crate::k5000::formant::FormantFilter;
 use crate::k5000::harmonic::{
@@ -23,11 +24,13 @@ pub const HARMONIC_COUNT: usize = 64;
 pub const BAND_COUNT: usize = 128;
 
 /// Additive kit.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AdditiveKit {
     pub common: HarmonicCommon,
     pub morf: MorfHarmonic,
     pub formant_filter: FormantFilter,
     pub levels: Levels,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::big_array"))]
     pub bands: [u8; BAND_COUNT],
     pub envelopes: Vec::<HarmonicEnvelope>,
 }
@@ -54,41 +57,40 @@ impl AdditiveKit {
 
 impl SystemExclusiveData for AdditiveKit {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         let mut offset = 0;
-        let checksum = data[offset];
-        eprintln!("{:#04X}: additive kit checksum = {:#02x}", offset, checksum);
+        let _checksum = data[offset];
         offset += 1;
 
         let hc_data = &data[1..7];
-        let common = HarmonicCommon::from_bytes(hc_data)?;
-        eprintln!("{:#04X}: harmonic common = {}", offset, common);
+        let common = HarmonicCommon::from_bytes(hc_data)
+            .map_err(|e| e.within("common", 1))?;
         offset += HarmonicCommon::data_size();
 
         let morf_data = &data[7..20];
-        let morf = MorfHarmonic::from_bytes(morf_data)?;
-        eprintln!("{:#04X}: MORF harmonic = {}", offset, morf);
+        let morf = MorfHarmonic::from_bytes(morf_data)
+            .map_err(|e| e.within("morf", 7))?;
         offset += MorfHarmonic::data_size();
 
         let ff_data = &data[20..37];
-        let formant_filter = FormantFilter::from_bytes(ff_data)?;
-        eprintln!("{:#04X}: FF = {}", offset, formant_filter);
+        let formant_filter = FormantFilter::from_bytes(ff_data)
+            .map_err(|e| e.within("formant_filter", 20))?;
         offset += FormantFilter::data_size();
 
         let levels_data = &data[37..165];
-        let levels = Levels::from_bytes(levels_data)?;
+        let levels = Levels::from_bytes(levels_data)
+            .map_err(|e| e.within("levels", 37))?;
         offset += Levels::data_size();
 
-        eprintln!("{:#04X}: FF bands start here", offset);
         let mut bands: [u8; BAND_COUNT] = [0; BAND_COUNT];
-        for i in 0..BAND_COUNT {
-            bands[i] = data[offset];
-            offset += 1;
-        }
-
-        eprintln!("{:#04X}: Harmonic envelopes start here", offset);
-        let mut envelopes: Vec::<HarmonicEnvelope> = vec![HarmonicEnvelope::new(); HARMONIC_COUNT];
-        for _ in 0..HARMONIC_COUNT {
-            envelopes.push(HarmonicEnvelope::from_bytes(&data[offset..offset + 8])?);
+        bands.copy_from_slice(&data[offset..offset + BAND_COUNT]);
+        offset += BAND_COUNT;
+
+        let mut envelopes = Vec::<HarmonicEnvelope>::new();
+        for i in 0..HARMONIC_COUNT {
+            let start = offset;
+            envelopes.push(HarmonicEnvelope::from_bytes(&data[offset..offset + 8])
+                .map_err(|e| e.within(format!("envelopes[{}]", i), start))?);
             offset += 8;
         }
 
@@ -135,10 +137,13 @@ impl SystemExclusiveData for AdditiveKit {
         + BAND_COUNT
 
         + HARMONIC_COUNT * HarmonicEnvelope::data_size()
+
+        + 1  // loud sens select
     }
 }
 
 impl Checksum for AdditiveKit {
+    #[allow(clippy::identity_op)]
     fn checksum(&self) -> u8 {
         // Additive kit checksum:
         // {(HCKIT sum) + (HCcode1 sum) + (HCcode2 sum)
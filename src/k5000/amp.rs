@@ -6,7 +6,10 @@ use std::fmt;
 
 use crate::{
     SystemExclusiveData,
-    ParseError
+    ParseError,
+    ValueError,
+    check_length,
+    invalid_field
 };
 use crate::k5000::{
     RangedInteger,
@@ -27,6 +30,7 @@ type EnvelopeLevelValue = RangedInteger::<0, 127>;
 
 /// Wrapper for envelope level parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct EnvelopeLevel {
     value: EnvelopeLevelValue,  // private field to prevent accidental range violations
 }
@@ -69,9 +73,11 @@ impl Default for EnvelopeLevel {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for EnvelopeLevel {
-    fn from(value: u8) -> EnvelopeLevel {
-        EnvelopeLevel::new(value as i32)
+impl TryFrom<u8> for EnvelopeLevel {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: EnvelopeLevelValue::try_new(value as i32)? })
     }
 }
 
@@ -89,6 +95,7 @@ impl fmt::Display for EnvelopeLevel {
 
 /// Amplifier envelope.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Envelope {
     pub attack_time: EnvelopeTime,
     pub decay1_time: EnvelopeTime,
@@ -128,13 +135,14 @@ impl fmt::Display for Envelope {
 
 impl SystemExclusiveData for Envelope {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(Envelope {
-            attack_time: EnvelopeTime::from(data[0]),
-            decay1_time: EnvelopeTime::from(data[1]),
-            decay1_level: EnvelopeLevel::from(data[2]),
-            decay2_time: EnvelopeTime::from(data[3]),
-            decay2_level: EnvelopeLevel::from(data[4]),
-            release_time: EnvelopeTime::from(data[5]),
+            attack_time: EnvelopeTime::try_from(data[0]).map_err(invalid_field("attack_time", data, 0))?,
+            decay1_time: EnvelopeTime::try_from(data[1]).map_err(invalid_field("decay1_time", data, 1))?,
+            decay1_level: EnvelopeLevel::try_from(data[2]).map_err(invalid_field("decay1_level", data, 2))?,
+            decay2_time: EnvelopeTime::try_from(data[3]).map_err(invalid_field("decay2_time", data, 3))?,
+            decay2_level: EnvelopeLevel::try_from(data[4]).map_err(invalid_field("decay2_level", data, 4))?,
+            release_time: EnvelopeTime::try_from(data[5]).map_err(invalid_field("release_time", data, 5))?,
         })
     }
 
@@ -154,6 +162,7 @@ impl SystemExclusiveData for Envelope {
 
 /// Amplifier key scaling control.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct KeyScalingControl {
     pub level: KeyScaling,
     pub attack_time: ControlTime,
@@ -182,11 +191,12 @@ impl fmt::Display for KeyScalingControl {
 
 impl SystemExclusiveData for KeyScalingControl {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(KeyScalingControl {
-            level: KeyScaling::from(data[0]),
-            attack_time: ControlTime::from(data[1]),
-            decay1_time: ControlTime::from(data[2]),
-            release: ControlTime::from(data[3]),
+            level: KeyScaling::try_from(data[0]).map_err(invalid_field("level", data, 0))?,
+            attack_time: ControlTime::try_from(data[1]).map_err(invalid_field("attack_time", data, 1))?,
+            decay1_time: ControlTime::try_from(data[2]).map_err(invalid_field("decay1_time", data, 2))?,
+            release: ControlTime::try_from(data[3]).map_err(invalid_field("release", data, 3))?,
         })
     }
 
@@ -204,6 +214,7 @@ impl SystemExclusiveData for KeyScalingControl {
 
 /// Amplifier velocity control.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VelocityControl {
     pub level: VelocityControlLevel,
     pub attack_time: ControlTime,
@@ -232,11 +243,12 @@ impl fmt::Display for VelocityControl {
 
 impl SystemExclusiveData for VelocityControl {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(VelocityControl {
-            level: VelocityControlLevel::from(data[0]),
-            attack_time: ControlTime::from(data[1]),
-            decay1_time: ControlTime::from(data[2]),
-            release: ControlTime::from(data[3]),
+            level: VelocityControlLevel::try_from(data[0]).map_err(invalid_field("level", data, 0))?,
+            attack_time: ControlTime::try_from(data[1]).map_err(invalid_field("attack_time", data, 1))?,
+            decay1_time: ControlTime::try_from(data[2]).map_err(invalid_field("decay1_time", data, 2))?,
+            release: ControlTime::try_from(data[3]).map_err(invalid_field("release", data, 3))?,
         })
     }
 
@@ -254,6 +266,7 @@ impl SystemExclusiveData for VelocityControl {
 
 /// Modulation settings for the amplifier section.
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Modulation {
     pub ks_to_env: KeyScalingControl,
     pub vel_sens: VelocityControl,
@@ -269,9 +282,10 @@ impl fmt::Display for Modulation {
 
 impl SystemExclusiveData for Modulation {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(Modulation {
-            ks_to_env: KeyScalingControl::from_bytes(&data[..4])?,
-            vel_sens: VelocityControl::from_bytes(&data[4..8])?,
+            ks_to_env: KeyScalingControl::from_bytes(&data[..4]).map_err(|e| e.in_field("ks_to_env"))?,
+            vel_sens: VelocityControl::from_bytes(&data[4..8]).map_err(|e| e.within("vel_sens", 4))?,
         })
     }
 
@@ -292,6 +306,7 @@ impl SystemExclusiveData for Modulation {
 
 /// Amplifier.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Amplifier {
     pub velocity_curve: VelocityCurve,  // 1...12 (stored as 0~11)
     pub envelope: Envelope,
@@ -318,10 +333,11 @@ impl fmt::Display for Amplifier {
 
 impl SystemExclusiveData for Amplifier {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(Amplifier {
-            velocity_curve: VelocityCurve::try_from(data[0]).unwrap(),  // 0-11 to enum
-            envelope: Envelope::from_bytes(&data[1..7])?,
-            modulation: Modulation::from_bytes(&data[7..15])?,
+            velocity_curve: VelocityCurve::try_from(data[0]).map_err(invalid_field("velocity_curve", data, 0))?,  // 0-11 to enum
+            envelope: Envelope::from_bytes(&data[1..7]).map_err(|e| e.within("envelope", 1))?,
+            modulation: Modulation::from_bytes(&data[7..15]).map_err(|e| e.within("modulation", 7))?,
         })
     }
 
@@ -0,0 +1,375 @@
+//! Data model for a bank of single patches.
+//!
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::{
+    SystemExclusiveData,
+    ParseError,
+    MIDIChannel,
+    check_length
+};
+use crate::k5000::single::{SinglePatch, Common};
+use crate::k5000::source::Source;
+use crate::k5000::sysex::{
+    Message,
+    Function,
+    PatchKind,
+    BankIdentifier,
+    ToneMap,
+    ToneNumber,
+    MAX_TONE_COUNT,
+};
+
+pub const PCM_PATCH_COUNT: usize = MAX_TONE_COUNT as usize;  // number of patches in the PCM bank B
+
+/// Bank of single patches, as found in a block single dump.
+/// The patches are keyed by their tone number (0~127),
+/// and only the patches that are present are stored.
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bank {
+    pub patches: BTreeMap<ToneNumber, SinglePatch>,
+}
+
+impl Bank {
+    /// Makes a new empty bank.
+    pub fn new() -> Self {
+        Bank { patches: BTreeMap::new() }
+    }
+
+    /// Returns a tone map of the patches in this bank.
+    pub fn tone_map(&self) -> ToneMap {
+        let mut tone_map = ToneMap::new();
+        for tone_number in self.patches.keys() {
+            tone_map.set_included(*tone_number, true);
+        }
+        tone_map
+    }
+
+    /// Makes a bank from a block single dump message.
+    /// Error offsets are relative to the start of the message.
+    pub fn from_message(message: &Message) -> Result<Self, ParseError> {
+        let start = 6;  // F0, manufacturer, channel, function, group, machine
+        if message.function != Function::AllBlockDump {
+            return Err(ParseError::InvalidData(3, "expected a block dump".to_string()));
+        }
+        if message.function_data.first() != Some(&(PatchKind::Single as u8)) {
+            return Err(ParseError::InvalidData(start as u32, "expected single patches".to_string()));
+        }
+        if message.function_data.get(1) == Some(&(BankIdentifier::B as u8)) {
+            return Err(ParseError::InvalidData(start as u32 + 1, "PCM bank B has no tone map".to_string()));
+        }
+
+        let mut data = message.subdata.clone();
+        data.extend(&message.patch_data);
+        Bank::from_bytes(&data)
+            .map_err(|e| e.offset_by(start + message.function_data.len()))
+    }
+
+    /// Makes a block single dump message of this bank.
+    pub fn to_message(&self, channel: MIDIChannel, bank: BankIdentifier) -> Message {
+        let mut patch_data = Vec::<u8>::new();
+        for patch in self.patches.values() {
+            patch_data.extend(patch.to_bytes());
+        }
+
+        Message {
+            channel,
+            function: Function::AllBlockDump,
+            function_data: vec![PatchKind::Single.into(), bank.into()],
+            subdata: self.tone_map().to_bytes(),
+            patch_data,
+        }
+    }
+}
+
+/// The PCM bank B, as found in a block PCM bank B dump.
+/// Unlike the other banks, the dump has no tone map,
+/// and always contains all the patches.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PCMBank {
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "crate::serialize::fixed_count::<_, _, PCM_PATCH_COUNT>"))]
+    pub patches: Vec<SinglePatch>,
+}
+
+impl PCMBank {
+    /// Makes a new bank of default PCM patches.
+    pub fn new() -> Self {
+        PCMBank {
+            patches: (0..PCM_PATCH_COUNT).map(|_| SinglePatch::new(2, 0)).collect(),
+        }
+    }
+
+    /// Makes a bank from a block PCM bank B dump message.
+    /// Error offsets are relative to the start of the message.
+    pub fn from_message(message: &Message) -> Result<Self, ParseError> {
+        let start = 6;  // F0, manufacturer, channel, function, group, machine
+        if message.function != Function::AllBlockDump {
+            return Err(ParseError::InvalidData(3, "expected a block dump".to_string()));
+        }
+        if message.function_data != [u8::from(PatchKind::Single), u8::from(BankIdentifier::B)] {
+            return Err(ParseError::InvalidData(start as u32, "expected PCM bank B".to_string()));
+        }
+
+        PCMBank::from_bytes(&message.patch_data)
+            .map_err(|e| e.offset_by(start + message.function_data.len()))
+    }
+
+    /// Makes a block PCM bank B dump message of this bank.
+    pub fn to_message(&self, channel: MIDIChannel) -> Message {
+        Message {
+            channel,
+            function: Function::AllBlockDump,
+            function_data: vec![PatchKind::Single.into(), BankIdentifier::B.into()],
+            subdata: vec![],
+            patch_data: self.to_bytes(),
+        }
+    }
+}
+
+impl Default for PCMBank {
+    fn default() -> Self {
+        PCMBank::new()
+    }
+}
+
+impl fmt::Display for PCMBank {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (tone_number, patch) in self.patches.iter().enumerate() {
+            writeln!(f, "{:03} {}", tone_number + 1, patch.common.name)?;
+        }
+        Ok(())
+    }
+}
+
+impl SystemExclusiveData for PCMBank {
+    /// Parses all the patches of the bank, one after another.
+    fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        let mut patches = Vec::<SinglePatch>::new();
+        let tone_numbers: Vec<ToneNumber> = ToneNumber::all().collect();
+        let patch_data = SinglePatch::split(data, &tone_numbers)?;
+        for (tone_number, (start, patch_data)) in patch_data.into_iter().enumerate() {
+            let patch = SinglePatch::pcm_from_bytes(patch_data)
+                .map_err(|e| e.within(format!("patches[{}]", tone_number), start))?;
+            patches.push(patch);
+        }
+
+        Ok(PCMBank { patches })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::<u8>::new();
+        for patch in self.patches.iter() {
+            result.extend(patch.to_bytes());
+        }
+        result
+    }
+
+    /// Returns the size of a bank where every patch has
+    /// the minimum of two sources, since the actual size
+    /// depends on the patches.
+    fn data_size() -> usize {
+        MAX_TONE_COUNT as usize * (1 + Common::data_size() + 2 * Source::data_size())
+    }
+}
+
+impl fmt::Display for Bank {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (tone_number, patch) in self.patches.iter() {
+            writeln!(f, "{:03} {}", tone_number.value() + 1, patch.common.name)?;
+        }
+        Ok(())
+    }
+}
+
+impl SystemExclusiveData for Bank {
+    /// Parses the tone map and then the patches it lists.
+    fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, ToneMap::data_size())?;
+
+        let tone_map = ToneMap::from_bytes(&data[..ToneMap::data_size()])
+            .map_err(|e| e.in_field("tone_map"))?;
+        let offset = ToneMap::data_size();
+
+        let tone_numbers = tone_map.tone_numbers();
+        let patch_data = SinglePatch::split(&data[offset..], &tone_numbers)
+            .map_err(|e| match e {
+                ParseError::InvalidLength(actual, expected) =>
+                    ParseError::InvalidLength(offset + actual, offset + expected),
+                other => other.offset_by(offset),
+            })?;
+
+        let mut patches = BTreeMap::<ToneNumber, SinglePatch>::new();
+        for (tone_number, (start, patch_data)) in tone_numbers.into_iter().zip(patch_data) {
+            let patch = SinglePatch::from_bytes(patch_data)
+                .map_err(|e| e.within(format!("patches[{}]", tone_number), offset + start))?;
+            patches.insert(tone_number, patch);
+        }
+
+        Ok(Bank { patches })
+    }
+
+    /// Emits the regenerated tone map followed by the patches.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut result = self.tone_map().to_bytes();
+        for patch in self.patches.values() {
+            result.extend(patch.to_bytes());
+        }
+        result
+    }
+
+    /// Returns the size of an empty bank, since the actual size
+    /// depends on the patches.
+    fn data_size() -> usize {
+        ToneMap::data_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{*};
+    use crate::Checksum;
+
+    static DATA: &[u8] = include_bytes!("WizooIni.syx");
+
+    fn wizoo() -> SinglePatch {
+        SinglePatch::from_bytes(&DATA[9..DATA.len() - 1]).unwrap()
+    }
+
+    fn tone(n: u8) -> ToneNumber {
+        ToneNumber::try_new(n).unwrap()
+    }
+
+    #[test]
+    fn test_tone_map() {
+        let mut bank = Bank::new();
+        bank.patches.insert(tone(0), wizoo());
+        bank.patches.insert(tone(9), wizoo());
+        bank.patches.insert(tone(127), wizoo());
+        assert_eq!(bank.tone_map().tone_numbers(), vec![tone(0), tone(9), tone(127)]);
+    }
+
+    #[test]
+    fn test_tone_number_range() {
+        assert_eq!(ToneNumber::try_new(127).unwrap().value(), 127);
+        assert!(ToneNumber::try_new(128).is_err());
+    }
+
+    #[test]
+    fn test_bank_round_trip() {
+        let mut bank = Bank::new();
+        bank.patches.insert(tone(3), wizoo());
+        bank.patches.insert(tone(64), wizoo());
+
+        let channel = MIDIChannel::try_new(1).unwrap();
+        let data = bank.to_message(channel, BankIdentifier::A).to_bytes();
+        assert_eq!(&data[..8], &[0xF0, 0x40, 0x00, 0x21, 0x00, 0x0A, 0x00, 0x00]);
+
+        let message = Message::from_bytes(&data).unwrap();
+        assert_eq!(message.subdata.len(), ToneMap::data_size());
+
+        let parsed = Bank::from_message(&message).unwrap();
+        assert_eq!(parsed.patches.keys().copied().collect::<Vec<ToneNumber>>(), vec![tone(3), tone(64)]);
+        assert_eq!(parsed.patches[&tone(64)].common.name, "WizooIni");
+        assert_eq!(parsed.to_message(channel, BankIdentifier::A).to_bytes(), data);
+    }
+
+    #[test]
+    fn test_classic_kaa_tone_map() {
+        // Tone map of the Classic.KAA block dump documented in ClassicKAA.md
+        let data = [
+            0x7f, 0x7f, 0x7f, 0x7f, 0x7f, 0x7f, 0x7f, 0x7f,
+            0x7f, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00
+        ];
+        let tone_map = ToneMap::from_bytes(&data).unwrap();
+        assert_eq!(tone_map.included_count(), 64);
+
+        // The tone map says 64 patches, but none follow
+        let result = Bank::from_bytes(&data);
+        assert!(matches!(result, Err(ParseError::InvalidLength(_, _))));
+    }
+
+    #[test]
+    fn test_wrong_message() {
+        let message = Message {
+            channel: MIDIChannel::try_new(1).unwrap(),
+            function: Function::AllBlockDump,
+            function_data: vec![PatchKind::Multi.into()],
+            subdata: vec![],
+            patch_data: vec![],
+        };
+        assert!(Bank::from_message(&message).is_err());
+    }
+
+    #[test]
+    fn test_max_tone_count() {
+        let mut bank = Bank::new();
+        for tone_number in 0..MAX_TONE_COUNT {
+            bank.patches.insert(tone(tone_number), SinglePatch::default());
+        }
+        let parsed = Bank::from_bytes(&bank.to_bytes()).unwrap();
+        assert_eq!(parsed.patches.len(), MAX_TONE_COUNT as usize);
+    }
+
+    #[test]
+    fn test_pcm_bank_round_trip() {
+        let mut bank = PCMBank::new();
+        bank.patches[5].common.name = "PCMTest ".to_string();
+
+        let channel = MIDIChannel::try_new(1).unwrap();
+        let data = bank.to_message(channel).to_bytes();
+        assert_eq!(&data[..8], &[0xF0, 0x40, 0x00, 0x21, 0x00, 0x0A, 0x00, 0x01]);
+
+        let message = Message::from_bytes(&data).unwrap();
+        assert!(message.subdata.is_empty());
+        assert!(Bank::from_message(&message).is_err());
+
+        let parsed = PCMBank::from_message(&message).unwrap();
+        assert_eq!(parsed.patches.len(), MAX_TONE_COUNT as usize);
+        assert_eq!(parsed.patches[5].common.name, "PCMTest ");
+        assert_eq!(parsed.to_message(channel).to_bytes(), data);
+    }
+
+    #[test]
+    fn test_pcm_bank_checksum() {
+        let mut data = PCMBank::new().to_bytes();
+        let patch_size = data.len() / MAX_TONE_COUNT as usize;
+        let offset = 3 * patch_size;
+        let checksum = data[offset];
+        data[offset] = (checksum + 1) & 0x7f;
+        assert_eq!(
+            PCMBank::from_bytes(&data).err(),
+            Some(ParseError::InvalidChecksum(checksum, (checksum + 1) & 0x7f))
+        );
+    }
+
+    #[test]
+    fn test_pcm_bank_rejects_additive() {
+        let mut bank = PCMBank::new();
+        bank.patches[1] = SinglePatch::new(1, 1);
+        let result = PCMBank::from_bytes(&bank.to_bytes());
+        match result {
+            Err(ParseError::InvalidField(error)) => {
+                assert_eq!(error.path, "patches[1].sources[1].oscillator.wave");
+            },
+            _ => panic!("expected an invalid field error"),
+        }
+    }
+
+    #[test]
+    fn test_one_pcm_patch() {
+        let patch = SinglePatch::new(3, 0);
+        assert!(patch.is_pcm_only());
+        let channel = MIDIChannel::try_new(2).unwrap();
+        let data = patch.to_message(channel, BankIdentifier::B, ToneNumber::try_new(17).unwrap()).to_bytes();
+        let message = Message::from_bytes(&data).unwrap();
+        let (bank, tone_number, parsed) = SinglePatch::from_message(&message).unwrap();
+        assert_eq!(bank, BankIdentifier::B);
+        assert_eq!(tone_number.value(), 17);
+        assert_eq!(parsed.sources.len(), 3);
+        assert_eq!(parsed.checksum(), data[9]);
+    }
+}
@@ -10,7 +10,9 @@ use strum_macros;
 
 use crate::{
     SystemExclusiveData,
-    ParseError
+    ParseError,
+    check_length,
+    invalid_field
 };
 use crate::k5000::{
     MacroParameterDepth,
@@ -30,8 +32,11 @@ pub enum VelocitySwitch {
     Unknown,
 }
 
+serialize_by_name!(VelocitySwitch);
+
 /// Velocity switch settings.
 #[derive(Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VelocitySwitchSettings {
     pub switch_type: VelocitySwitch,
     pub threshold: u8,
@@ -70,11 +75,11 @@ impl fmt::Display for VelocitySwitchSettings {
 
 impl SystemExclusiveData for VelocitySwitchSettings {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         let vs = data[0].bit_range(5..7) & 0b11;  // bits 5-6
         let t = data[0].bit_range(0..5); // bits 0-4
-        eprintln!("VelocitySwitchSettings: vs = 0b{:b} ({}), t = 0b{:b} ({})", vs, vs, t, t);
         Ok(VelocitySwitchSettings {
-            switch_type: VelocitySwitch::try_from(vs).unwrap(),
+            switch_type: VelocitySwitch::try_from(vs).map_err(invalid_field("switch_type", data, 0))?,
             threshold: VelocitySwitchSettings::threshold_from(t as usize),
         })
     }
@@ -143,6 +148,8 @@ pub enum ControlSource {
     GeneralController8,
 }
 
+serialize_by_name!(ControlSource);
+
 /// Control destination.
 #[derive(
     Debug, Eq, PartialEq,
@@ -215,8 +222,11 @@ pub enum ControlDestination {
     HarmonicOddOffset,
 }
 
+serialize_by_name!(ControlDestination);
+
 /// Macro controller.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MacroController {
     pub destination1: ControlDestination,
     pub depth1: MacroParameterDepth,
@@ -245,13 +255,12 @@ impl fmt::Display for MacroController {
 
 impl SystemExclusiveData for MacroController {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
-        eprintln!("MacroController from bytes {:?}", data);
-
+        check_length(data, Self::data_size())?;
         Ok(MacroController {
-            destination1: ControlDestination::try_from(data[0]).unwrap(),
-            depth1: MacroParameterDepth::from(data[1]),
-            destination2: ControlDestination::try_from(data[2]).unwrap(),
-            depth2: MacroParameterDepth::from(data[3]),
+            destination1: ControlDestination::try_from(data[0]).map_err(invalid_field("destination1", data, 0))?,
+            depth1: MacroParameterDepth::try_from(data[1]).map_err(invalid_field("depth1", data, 1))?,
+            destination2: ControlDestination::try_from(data[2]).map_err(invalid_field("destination2", data, 2))?,
+            depth2: MacroParameterDepth::try_from(data[3]).map_err(invalid_field("depth2", data, 3))?,
         })
     }
 
@@ -269,6 +278,7 @@ impl SystemExclusiveData for MacroController {
 
 /// Assignable controller.
 #[derive(Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AssignableController {
     pub source: ControlSource,
     pub destination: ControlDestination,
@@ -277,10 +287,11 @@ pub struct AssignableController {
 
 impl SystemExclusiveData for AssignableController {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(AssignableController {
-            source: ControlSource::try_from(data[0]).unwrap(),
-            destination: ControlDestination::try_from(data[1]).unwrap(),
-            depth: ControlDepth::from(data[2]),
+            source: ControlSource::try_from(data[0]).map_err(invalid_field("source", data, 0))?,
+            destination: ControlDestination::try_from(data[1]).map_err(invalid_field("destination", data, 1))?,
+            depth: ControlDepth::try_from(data[2]).map_err(invalid_field("depth", data, 2))?,
         })
     }
 
@@ -293,6 +304,7 @@ impl SystemExclusiveData for AssignableController {
 
 /// Modulation settings.
 #[derive(Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ModulationSettings {
     pub pressure: MacroController,
     pub wheel: MacroController,
@@ -303,12 +315,13 @@ pub struct ModulationSettings {
 
 impl SystemExclusiveData for ModulationSettings {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(ModulationSettings {
-            pressure: MacroController::from_bytes(&data[..4])?,
-            wheel: MacroController::from_bytes(&data[4..8])?,
-            expression: MacroController::from_bytes(&data[8..12])?,
-            assignable1: AssignableController::from_bytes(&data[12..15])?,  // NOTE: only three bytes
-            assignable2: AssignableController::from_bytes(&data[15..18])?,  // not four like macros
+            pressure: MacroController::from_bytes(&data[..4]).map_err(|e| e.in_field("pressure"))?,
+            wheel: MacroController::from_bytes(&data[4..8]).map_err(|e| e.within("wheel", 4))?,
+            expression: MacroController::from_bytes(&data[8..12]).map_err(|e| e.within("expression", 8))?,
+            assignable1: AssignableController::from_bytes(&data[12..15]).map_err(|e| e.within("assignable1", 12))?,  // NOTE: only three bytes
+            assignable2: AssignableController::from_bytes(&data[15..18]).map_err(|e| e.within("assignable2", 15))?,  // not four like macros
         })
     }
 
@@ -353,8 +366,11 @@ pub enum PanKind {
     NegativeKeyScale,
 }
 
+serialize_by_name!(PanKind);
+
 /// Pan settings.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PanSettings {
     pub pan_type: PanKind,
     pub pan_value: Pan,
@@ -371,9 +387,10 @@ impl Default for PanSettings {
 
 impl SystemExclusiveData for PanSettings {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(PanSettings {
-            pan_type: PanKind::try_from(data[0]).unwrap(),
-            pan_value: Pan::from(data[1]),
+            pan_type: PanKind::try_from(data[0]).map_err(invalid_field("pan_type", data, 0))?,
+            pan_value: Pan::try_from(data[1]).map_err(invalid_field("pan_value", data, 1))?,
         })
     }
 
@@ -409,6 +426,8 @@ pub enum Switch {
     FfComb2,
 }
 
+serialize_by_name!(Switch);
+
 impl fmt::Display for Switch {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", match self {
@@ -435,6 +454,7 @@ impl fmt::Display for Switch {
 
 /// Switch control settings.
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SwitchControl {
     pub switch1: Switch,
     pub switch2: Switch,
@@ -444,11 +464,12 @@ pub struct SwitchControl {
 
 impl SystemExclusiveData for SwitchControl {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(SwitchControl {
-            switch1: Switch::try_from(data[0]).unwrap(),
-            switch2: Switch::try_from(data[1]).unwrap(),
-            footswitch1: Switch::try_from(data[2]).unwrap(),
-            footswitch2: Switch::try_from(data[3]).unwrap(),
+            switch1: Switch::try_from(data[0]).map_err(invalid_field("switch1", data, 0))?,
+            switch2: Switch::try_from(data[1]).map_err(invalid_field("switch2", data, 1))?,
+            footswitch1: Switch::try_from(data[2]).map_err(invalid_field("footswitch1", data, 2))?,
+            footswitch2: Switch::try_from(data[3]).map_err(invalid_field("footswitch2", data, 3))?,
         })
     }
 
@@ -468,6 +489,8 @@ pub enum Polyphony {
     Solo2,
 }
 
+serialize_by_name!(Polyphony);
+
 impl fmt::Display for Polyphony {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", match self {
@@ -492,6 +515,8 @@ pub enum AmplitudeModulation {
     Source6,
 }
 
+serialize_by_name!(AmplitudeModulation);
+
 impl fmt::Display for AmplitudeModulation {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", match self {
@@ -523,6 +548,8 @@ pub enum VelocityCurve {
     Curve12,
 }
 
+serialize_by_name!(VelocityCurve);
+
 impl fmt::Display for VelocityCurve {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", match self {
@@ -0,0 +1,654 @@
+//! Data models for the drum kit and the user drum instruments.
+//!
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::{
+    SystemExclusiveData,
+    ParseError,
+    Checksum,
+    MIDIChannel,
+    check_length,
+    invalid_field
+};
+use crate::k5000::effect::{EffectSettings, EffectControl};
+use crate::k5000::source::Source;
+use crate::k5000::addkit::AdditiveKit;
+use crate::k5000::sysex::{Message, Function, PatchKind, InstrumentNumber};
+use crate::k5000::Volume;
+
+/// Number of keys in the drum kit.
+pub const KEY_COUNT: usize = 64;
+
+/// The MIDI note number of the first key in the drum kit (C1).
+pub const FIRST_KEY: u8 = 36;
+
+/// Number of user drum instruments.
+pub const USER_INSTRUMENT_COUNT: usize = 32;
+
+/// Drum kit common settings.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Common {
+    pub effects: EffectSettings,
+    pub geq: [i8; 7],
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "crate::serialize::fixed_name::<_, 8>"))]
+    pub name: String,
+    pub volume: Volume,
+    pub effect_control: EffectControl,
+}
+
+impl Default for Common {
+    fn default() -> Self {
+        Common {
+            effects: Default::default(),
+            geq: [0; 7],
+            name: "NewDrums".to_string(),
+            volume: Volume::new(127),
+            effect_control: Default::default(),
+        }
+    }
+}
+
+impl fmt::Display for Common {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\nVolume: {}\n\nEffect settings:\n{}\n",
+            self.name, self.volume, self.effects)
+    }
+}
+
+impl SystemExclusiveData for Common {
+    fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
+
+        let mut offset = 0;
+        let mut size = EffectSettings::data_size();
+        let effects = EffectSettings::from_bytes(&data[offset..offset + size])
+            .map_err(|e| e.in_field("effects"))?;
+        offset += size;
+
+        size = 7;
+        let mut geq = [0i8; 7];
+        for (i, value) in geq.iter_mut().enumerate() {
+            *value = (data[offset + i] & 0x7f) as i8 - 64;  // 58(-6) ~ 70(+6), so 64 is zero
+        }
+        offset += size;
+
+        size = 8;
+        let start = offset;
+        let name = String::from_utf8(data[start..start + size].to_vec())
+            .map_err(invalid_field("name", data, start))?;
+        offset += size;
+
+        let volume = Volume::try_from(data[offset]).map_err(invalid_field("volume", data, offset))?;
+        offset += 1;
+
+        let effect_control = EffectControl::from_bytes(&data[offset..offset + 6])
+            .map_err(|e| e.within("effect_control", offset))?;
+
+        Ok(Common {
+            effects,
+            geq,
+            name,
+            volume,
+            effect_control,
+        })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut result: Vec<u8> = Vec::new();
+
+        result.extend(self.effects.to_bytes());
+        result.extend(self.geq.iter().map(|n| (n + 64) as u8));
+        result.extend(self.name.clone().into_bytes());
+        result.push(self.volume.into());
+        result.extend(self.effect_control.to_bytes());
+
+        result
+    }
+
+    fn data_size() -> usize { 53 }
+}
+
+/// Settings of one key in the drum kit.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Key {
+    pub instrument: u16,  // inst no.
+    pub volume: Volume,
+    pub pan: u8,
+    pub effect_path: u8,
+}
+
+impl Default for Key {
+    fn default() -> Self {
+        Key {
+            instrument: 0,
+            volume: Volume::new(100),
+            pan: 64,
+            effect_path: 0,
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Instrument={} Volume={} Pan={} Effect path={}",
+            self.instrument, self.volume, self.pan, self.effect_path)
+    }
+}
+
+impl SystemExclusiveData for Key {
+    fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
+
+        Ok(Key {
+            instrument: ((data[0] as u16 & 0x03) << 7) | (data[1] as u16 & 0x7f),
+            volume: Volume::try_from(data[2]).map_err(invalid_field("volume", data, 2))?,
+            pan: data[3],
+            effect_path: data[4],
+        })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        vec![
+            ((self.instrument >> 7) & 0x03) as u8,
+            (self.instrument & 0x7f) as u8,
+            self.volume.into(),
+            self.pan,
+            self.effect_path,
+        ]
+    }
+
+    fn data_size() -> usize { 5 }
+}
+
+/// Drum kit with common settings and the instrument assignments of the keys.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DrumKit {
+    pub common: Common,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::big_array"))]
+    pub keys: [Key; KEY_COUNT],
+}
+
+impl Default for DrumKit {
+    fn default() -> Self {
+        DrumKit {
+            common: Default::default(),
+            keys: std::array::from_fn(|_| Default::default()),
+        }
+    }
+}
+
+impl DrumKit {
+    /// Makes a drum kit from a one drum kit dump message.
+    /// Error offsets are relative to the start of the message.
+    pub fn from_message(message: &Message) -> Result<Self, ParseError> {
+        let start = check_message(message, Function::OneBlockDump, PatchKind::DrumKit)?;
+        DrumKit::from_bytes(&message.patch_data)
+            .map_err(|e| e.offset_by(start))
+    }
+
+    /// Makes a one drum kit dump message of this drum kit.
+    pub fn to_message(&self, channel: MIDIChannel) -> Message {
+        Message {
+            channel,
+            function: Function::OneBlockDump,
+            function_data: vec![PatchKind::DrumKit.into()],
+            subdata: vec![],
+            patch_data: self.to_bytes(),
+        }
+    }
+
+    /// Returns the settings of the key with the MIDI note number `note`,
+    /// or `None` if the key is not in the drum kit.
+    pub fn key(&self, note: u8) -> Option<&Key> {
+        self.keys.get(note.checked_sub(FIRST_KEY)? as usize)
+    }
+
+    fn collect_data(&self) -> Vec<u8> {
+        let mut result = self.common.to_bytes();
+        for key in self.keys.iter() {
+            result.extend(key.to_bytes());
+        }
+        result
+    }
+}
+
+impl Checksum for DrumKit {
+    fn checksum(&self) -> u8 {
+        // check sum = {(common sum) + (key sum) + 0xa5} & 0x7f
+        let data = self.collect_data();
+        let mut total = data.iter().fold(0, |acc, x| acc + ((*x as u32) & 0xFF));
+        total += 0xA5;
+        (total & 0x7F) as u8
+    }
+}
+
+impl fmt::Display for DrumKit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.common)?;
+        for (i, key) in self.keys.iter().enumerate() {
+            writeln!(f, "Key {}: {}", FIRST_KEY as usize + i, key)?;
+        }
+        Ok(())
+    }
+}
+
+impl SystemExclusiveData for DrumKit {
+    fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
+
+        let _checksum = data[0];
+        let mut offset = 1;
+
+        let common = Common::from_bytes(&data[offset..offset + Common::data_size()])
+            .map_err(|e| e.within("common", offset))?;
+        offset += Common::data_size();
+
+        let mut keys: [Key; KEY_COUNT] = std::array::from_fn(|_| Default::default());
+        for (i, key) in keys.iter_mut().enumerate() {
+            let start = offset;
+            *key = Key::from_bytes(&data[start..start + Key::data_size()])
+                .map_err(|e| e.within(format!("keys[{}]", i), start))?;
+            offset += Key::data_size();
+        }
+
+        Ok(DrumKit { common, keys })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut result: Vec<u8> = Vec::new();
+
+        result.push(self.checksum());
+        result.extend(self.collect_data());
+
+        result
+    }
+
+    fn data_size() -> usize {
+        1 + Common::data_size() + KEY_COUNT * Key::data_size()
+    }
+}
+
+/// Drum instrument common settings.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InstrumentCommon {
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "crate::serialize::fixed_name::<_, 8>"))]
+    pub name: String,
+    pub volume: Volume,
+    pub gate_time: u8,
+    pub exclusive_group: u8,  // 0 = off
+}
+
+impl Default for InstrumentCommon {
+    fn default() -> Self {
+        InstrumentCommon {
+            name: "NewInst ".to_string(),
+            volume: Volume::new(100),
+            gate_time: 0,
+            exclusive_group: 0,
+        }
+    }
+}
+
+impl fmt::Display for InstrumentCommon {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\nVolume: {}  Gate time: {}  Exclusive group: {}",
+            self.name, self.volume, self.gate_time, self.exclusive_group)
+    }
+}
+
+impl SystemExclusiveData for InstrumentCommon {
+    fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
+
+        Ok(InstrumentCommon {
+            name: String::from_utf8(data[..8].to_vec()).map_err(invalid_field("name", data, 0))?,
+            volume: Volume::try_from(data[8]).map_err(invalid_field("volume", data, 8))?,
+            gate_time: data[9],
+            exclusive_group: data[10],
+        })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut result = self.name.clone().into_bytes();
+        result.push(self.volume.into());
+        result.push(self.gate_time);
+        result.push(self.exclusive_group);
+        result
+    }
+
+    fn data_size() -> usize { 11 }
+}
+
+/// User drum instrument, with one source. If the source is ADD,
+/// its additive kit follows the source data.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DrumInstrument {
+    pub common: InstrumentCommon,
+    pub source: Source,
+    pub additive_kit: Option<AdditiveKit>,
+}
+
+impl Default for DrumInstrument {
+    fn default() -> Self {
+        DrumInstrument {
+            common: Default::default(),
+            source: Source::pcm(),
+            additive_kit: None,
+        }
+    }
+}
+
+impl DrumInstrument {
+    /// Makes a drum instrument from a one drum instrument dump message.
+    /// Returns the instrument number with the instrument.
+    /// Error offsets are relative to the start of the message.
+    pub fn from_message(message: &Message) -> Result<(InstrumentNumber, DrumInstrument), ParseError> {
+        let start = check_message(message, Function::OneBlockDump, PatchKind::DrumInstrument)?;
+        check_length(&message.subdata, 1).map_err(|e| e.offset_by(start))?;
+        let number = InstrumentNumber::try_new(message.subdata[0])
+            .map_err(invalid_field("number", &message.subdata, 0))
+            .map_err(|e| e.offset_by(start))?;
+        let instrument = DrumInstrument::from_bytes(&message.patch_data)
+            .map_err(|e| e.offset_by(start + message.subdata.len()))?;
+        Ok((number, instrument))
+    }
+
+    /// Makes a one drum instrument dump message of this instrument,
+    /// to be stored as user instrument `number`.
+    pub fn to_message(&self, channel: MIDIChannel, number: InstrumentNumber) -> Message {
+        Message {
+            channel,
+            function: Function::OneBlockDump,
+            function_data: vec![PatchKind::DrumInstrument.into()],
+            subdata: vec![number.into()],
+            patch_data: self.to_bytes(),
+        }
+    }
+
+    /// Returns the size of this instrument in bytes, including the checksum.
+    pub fn size(&self) -> usize {
+        let kit_size = if self.additive_kit.is_some() { AdditiveKit::data_size() } else { 0 };
+        Self::data_size() + kit_size
+    }
+}
+
+impl Checksum for DrumInstrument {
+    fn checksum(&self) -> u8 {
+        // check sum = {(common sum) + (source sum) + 0xa5} & 0x7f
+        let mut total = 0;
+        for data in [self.common.to_bytes(), self.source.to_bytes()] {
+            let sum = data.iter().fold(0, |acc, x| acc + ((*x as u32) & 0xFF));
+            total += sum & 0xFF;
+        }
+        total += 0xA5;
+        (total & 0x7F) as u8
+    }
+}
+
+impl fmt::Display for DrumInstrument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\n\nSource:\n{}", self.common, self.source)
+    }
+}
+
+impl SystemExclusiveData for DrumInstrument {
+    fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
+
+        let _checksum = data[0];
+        let mut offset = 1;
+
+        let common = InstrumentCommon::from_bytes(&data[offset..offset + InstrumentCommon::data_size()])
+            .map_err(|e| e.within("common", offset))?;
+        offset += InstrumentCommon::data_size();
+
+        let source = Source::from_bytes(&data[offset..offset + Source::data_size()])
+            .map_err(|e| e.within("source", offset))?;
+        offset += Source::data_size();
+
+        let additive_kit = if source.is_additive() {
+            check_length(data, offset + AdditiveKit::data_size())?;
+            let kit = AdditiveKit::from_bytes(&data[offset..offset + AdditiveKit::data_size()])
+                .map_err(|e| e.within("additive_kit", offset))?;
+            Some(kit)
+        } else {
+            None
+        };
+
+        Ok(DrumInstrument { common, source, additive_kit })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut result: Vec<u8> = Vec::new();
+
+        result.push(self.checksum());
+        result.extend(self.common.to_bytes());
+        result.extend(self.source.to_bytes());
+        if let Some(kit) = &self.additive_kit {
+            result.extend(kit.to_bytes());
+        }
+
+        result
+    }
+
+    /// Returns the size of an instrument with a PCM source.
+    /// Use `size` to get the size of a particular instrument.
+    fn data_size() -> usize {
+        1 + InstrumentCommon::data_size() + Source::data_size()
+    }
+}
+
+/// All the user drum instruments, as found in a block drum instrument dump.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InstrumentBank {
+    pub instruments: Vec<DrumInstrument>,
+}
+
+impl InstrumentBank {
+    /// Makes a new bank of default drum instruments.
+    pub fn new() -> Self {
+        InstrumentBank {
+            instruments: (0..USER_INSTRUMENT_COUNT).map(|_| Default::default()).collect(),
+        }
+    }
+
+    /// Makes a bank from a block drum instrument dump message.
+    /// Error offsets are relative to the start of the message.
+    pub fn from_message(message: &Message) -> Result<Self, ParseError> {
+        let start = check_message(message, Function::AllBlockDump, PatchKind::DrumInstrument)?;
+        InstrumentBank::from_bytes(&message.patch_data)
+            .map_err(|e| e.offset_by(start))
+    }
+
+    /// Makes a block drum instrument dump message of this bank.
+    pub fn to_message(&self, channel: MIDIChannel) -> Message {
+        Message {
+            channel,
+            function: Function::AllBlockDump,
+            function_data: vec![PatchKind::DrumInstrument.into()],
+            subdata: vec![],
+            patch_data: self.to_bytes(),
+        }
+    }
+}
+
+impl Default for InstrumentBank {
+    fn default() -> Self {
+        InstrumentBank::new()
+    }
+}
+
+impl fmt::Display for InstrumentBank {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, instrument) in self.instruments.iter().enumerate() {
+            writeln!(f, "U{:02} {}", i + 1, instrument.common.name)?;
+        }
+        Ok(())
+    }
+}
+
+impl SystemExclusiveData for InstrumentBank {
+    fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        let mut offset = 0;
+        let mut instruments = Vec::<DrumInstrument>::new();
+        for i in 0..USER_INSTRUMENT_COUNT {
+            let instrument = DrumInstrument::from_bytes(&data[offset.min(data.len())..])
+                .map_err(|e| match e {
+                    ParseError::InvalidLength(actual, expected) =>
+                        ParseError::InvalidLength(offset + actual, offset + expected),
+                    other => other.within(format!("instruments[{}]", i), offset),
+                })?;
+            offset += instrument.size();
+            instruments.push(instrument);
+        }
+
+        Ok(InstrumentBank { instruments })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::<u8>::new();
+        for instrument in self.instruments.iter() {
+            result.extend(instrument.to_bytes());
+        }
+        result
+    }
+
+    /// Returns the size of a bank where every instrument has
+    /// a PCM source, since the actual size depends on the instruments.
+    fn data_size() -> usize {
+        USER_INSTRUMENT_COUNT * DrumInstrument::data_size()
+    }
+}
+
+/// Checks that `message` is a dump of `function` with patches of `kind`.
+/// Returns the offset of the data after the function data in the message.
+fn check_message(message: &Message, function: Function, kind: PatchKind) -> Result<usize, ParseError> {
+    let start = 6;  // F0, manufacturer, channel, function, group, machine
+    if message.function != function {
+        return Err(ParseError::InvalidData(3, format!("expected function {:?}", function)));
+    }
+    if message.function_data.first() != Some(&(kind as u8)) {
+        return Err(ParseError::InvalidData(start as u32, format!("expected {}", kind)));
+    }
+    Ok(start + message.function_data.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{*};
+    use crate::k5000::sysex::{Header, Cardinality};
+
+    fn make_drum_kit() -> DrumKit {
+        let mut kit = DrumKit::default();
+        kit.common.name = "TestKit ".to_string();
+        kit.keys[0].instrument = 300;
+        kit.keys[2].volume = Volume::new(80);
+        kit
+    }
+
+    #[test]
+    fn test_key_from_bytes() {
+        let data = [0x02, 0x2c, 0x64, 0x40, 0x01];
+        let key = Key::from_bytes(&data).unwrap();
+        assert_eq!(key.instrument, 300);
+        assert_eq!(key.volume.value(), 100);
+        assert_eq!(key.to_bytes(), data.to_vec());
+    }
+
+    #[test]
+    fn test_drum_kit_round_trip() {
+        let kit = make_drum_kit();
+        let data = kit.to_bytes();
+        assert_eq!(data.len(), DrumKit::data_size());
+        assert_eq!(data[0], kit.checksum());
+
+        let parsed = DrumKit::from_bytes(&data).unwrap();
+        assert_eq!(parsed.common.name, "TestKit ");
+        assert_eq!(parsed.key(FIRST_KEY).unwrap().instrument, 300);
+        assert_eq!(parsed.key(FIRST_KEY + 2).unwrap().volume.value(), 80);
+        assert!(parsed.key(FIRST_KEY - 1).is_none());
+        assert_eq!(parsed.to_bytes(), data);
+    }
+
+    #[test]
+    fn test_one_drum_kit_dump() {
+        let channel = MIDIChannel::try_new(1).unwrap();
+        let data = make_drum_kit().to_message(channel).to_bytes();
+
+        let header = Header::identify_vec(&data[2..]).unwrap();
+        assert_eq!(header.cardinality, Cardinality::One);
+        assert_eq!(header.kind, PatchKind::DrumKit);
+        let kit = DrumKit::from_bytes(&data[2 + header.size()..]).unwrap();
+        assert_eq!(kit.common.name, "TestKit ");
+
+        let message = Message::from_bytes(&data).unwrap();
+        assert_eq!(DrumKit::from_message(&message).unwrap().keys[0].instrument, 300);
+    }
+
+    #[test]
+    fn test_one_drum_instrument_dump() {
+        let mut instrument = DrumInstrument::default();
+        instrument.common.name = "Kick 1  ".to_string();
+        instrument.common.exclusive_group = 2;
+
+        let channel = MIDIChannel::try_new(1).unwrap();
+        let data = instrument.to_message(channel, InstrumentNumber::try_new(7).unwrap()).to_bytes();
+        assert_eq!(data.len(), 9 + instrument.size());  // header, kind, number and F7H
+
+        let header = Header::identify_vec(&data[2..]).unwrap();
+        assert_eq!(header.kind, PatchKind::DrumInstrument);
+        assert_eq!(header.sub_bytes, vec![7]);
+
+        let message = Message::from_bytes(&data).unwrap();
+        let (number, parsed) = DrumInstrument::from_message(&message).unwrap();
+        assert_eq!(number.value(), 7);
+        assert_eq!(parsed.common.name, "Kick 1  ");
+        assert_eq!(parsed.common.exclusive_group, 2);
+        assert!(parsed.additive_kit.is_none());
+        assert!(DrumKit::from_message(&message).is_err());
+    }
+
+    #[test]
+    fn test_block_drum_instrument_dump() {
+        let mut bank = InstrumentBank::new();
+        bank.instruments[1] = DrumInstrument {
+            common: Default::default(),
+            source: Source::additive(),
+            additive_kit: Some(AdditiveKit::new()),
+        };
+        bank.instruments[31].common.name = "Last one".to_string();
+
+        let channel = MIDIChannel::try_new(1).unwrap();
+        let data = bank.to_message(channel).to_bytes();
+        let header = Header::identify_vec(&data[2..]).unwrap();
+        assert_eq!(header.cardinality, Cardinality::Block);
+        assert_eq!(header.kind, PatchKind::DrumInstrument);
+
+        let message = Message::from_bytes(&data).unwrap();
+        let parsed = InstrumentBank::from_message(&message).unwrap();
+        assert_eq!(parsed.instruments.len(), USER_INSTRUMENT_COUNT);
+        assert!(parsed.instruments[1].additive_kit.is_some());
+        assert_eq!(parsed.instruments[31].common.name, "Last one");
+        assert_eq!(parsed.to_message(channel).to_bytes(), data);
+    }
+
+    #[test]
+    fn test_error_path_in_drum_kit() {
+        let mut data = make_drum_kit().to_bytes();
+        let offset = 1 + Common::data_size() + 3 * Key::data_size() + 2;
+        data[offset] = 0xff;
+        match DrumKit::from_bytes(&data) {
+            Err(ParseError::InvalidField(error)) => {
+                assert_eq!(error.path, "keys[3].volume");
+                assert_eq!(error.offset, offset);
+            },
+            _ => panic!("expected an invalid field error"),
+        }
+    }
+}
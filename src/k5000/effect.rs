@@ -10,12 +10,15 @@ use lazy_static::lazy_static;
 
 use crate::{
     SystemExclusiveData,
-    ParseError
+    ParseError,
+    check_length,
+    invalid_field
 };
 use crate::k5000::control;
 use crate::k5000::{
     EffectParameter,
-    Depth
+    Depth,
+    MacroParameterDepth
 };
 
 static EFFECT_NAMES: &[&str] = &[
@@ -125,6 +128,8 @@ pub enum Effect {
     DistortionAndDelay,
 }
 
+serialize_by_name!(Effect);
+
 impl fmt::Display for Effect {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", EFFECT_NAMES[*self as usize])
@@ -196,6 +201,8 @@ pub enum EffectAlgorithm {
     Algorithm4,
 }
 
+serialize_by_name!(EffectAlgorithm);
+
 impl fmt::Display for EffectAlgorithm {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", match self {
@@ -209,6 +216,7 @@ impl fmt::Display for EffectAlgorithm {
 
 /// Effect definition.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EffectDefinition {
     pub effect: Effect,  // reverb = 0~10, others = 11~47
     pub depth: Depth,  // 0~100
@@ -248,14 +256,14 @@ impl Default for EffectDefinition {
 
 impl SystemExclusiveData for EffectDefinition {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
-        eprintln!("EffectDefinition, data = {:02X?}", data);
+        check_length(data, Self::data_size())?;
         Ok(EffectDefinition {
-            effect: Effect::try_from(data[0]).unwrap(),  // 11~47
-            depth: Depth::from(data[1]),
-            parameter1: EffectParameter::from(data[2]),
-            parameter2: EffectParameter::from(data[3]),
-            parameter3: EffectParameter::from(data[4]),
-            parameter4: EffectParameter::from(data[5]),
+            effect: Effect::try_from(data[0]).map_err(invalid_field("effect", data, 0))?,  // 11~47
+            depth: Depth::try_from(data[1]).map_err(invalid_field("depth", data, 1))?,
+            parameter1: EffectParameter::try_from(data[2]).map_err(invalid_field("parameter1", data, 2))?,
+            parameter2: EffectParameter::try_from(data[3]).map_err(invalid_field("parameter2", data, 3))?,
+            parameter3: EffectParameter::try_from(data[4]).map_err(invalid_field("parameter3", data, 4))?,
+            parameter4: EffectParameter::try_from(data[5]).map_err(invalid_field("parameter4", data, 5))?,
         })
     }
 
@@ -275,6 +283,7 @@ impl SystemExclusiveData for EffectDefinition {
 
 /// Effect settings.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EffectSettings {
     pub algorithm: EffectAlgorithm,  // 0~3
     pub reverb: EffectDefinition,
@@ -306,14 +315,14 @@ impl Default for EffectSettings {
 
 impl SystemExclusiveData for EffectSettings {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
-        eprintln!("EffectSettings, data = {:02X?}", data);
+        check_length(data, Self::data_size())?;
         Ok(EffectSettings {
-            algorithm: EffectAlgorithm::try_from(data[0]).unwrap(),  // 0~3 to enum
-            reverb: EffectDefinition::from_bytes(&data[1..7])?,
-            effect1: EffectDefinition::from_bytes(&data[7..13])?,
-            effect2: EffectDefinition::from_bytes(&data[13..19])?,
-            effect3: EffectDefinition::from_bytes(&data[19..25])?,
-            effect4: EffectDefinition::from_bytes(&data[25..31])?,
+            algorithm: EffectAlgorithm::try_from(data[0]).map_err(invalid_field("algorithm", data, 0))?,  // 0~3 to enum
+            reverb: EffectDefinition::from_bytes(&data[1..7]).map_err(|e| e.within("reverb", 1))?,
+            effect1: EffectDefinition::from_bytes(&data[7..13]).map_err(|e| e.within("effect1", 7))?,
+            effect2: EffectDefinition::from_bytes(&data[13..19]).map_err(|e| e.within("effect2", 13))?,
+            effect3: EffectDefinition::from_bytes(&data[19..25]).map_err(|e| e.within("effect3", 19))?,
+            effect4: EffectDefinition::from_bytes(&data[25..31]).map_err(|e| e.within("effect4", 25))?,
         })
     }
 
@@ -335,35 +344,60 @@ impl SystemExclusiveData for EffectSettings {
 }
 
 /// Effect destinations.
-#[derive(Debug, Eq, PartialEq, Copy, Clone, TryFromPrimitive, Default)]
+#[derive(
+    Debug,
+    Eq, PartialEq,
+    Copy, Clone,
+    TryFromPrimitive,
+    Default,
+    strum_macros::Display
+)]
 #[repr(u8)]
 pub enum EffectDestination {
     #[default]
+    #[strum(to_string = "Effect 1 dry/wet")]
     Effect1DryWet,
 
+    #[strum(to_string = "Effect 1 parameter")]
     Effect1Parameter,
+
+    #[strum(to_string = "Effect 2 dry/wet")]
     Effect2DryWet,
+
+    #[strum(to_string = "Effect 2 parameter")]
     Effect2Parameter,
+
+    #[strum(to_string = "Effect 3 dry/wet")]
     Effect3DryWet,
+
+    #[strum(to_string = "Effect 3 parameter")]
     Effect3Parameter,
+
+    #[strum(to_string = "Effect 4 dry/wet")]
     Effect4DryWet,
+
+    #[strum(to_string = "Effect 4 parameter")]
     Effect4Parameter,
 }
 
+serialize_by_name!(EffectDestination);
+
 /// Effect control source.
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ControlSource {
     pub source: control::ControlSource,  // 0~13
     pub destination: EffectDestination,  // 0~9
-    pub depth: Depth, // (-31)33~(+31)95
+    pub depth: MacroParameterDepth, // (-31)33~(+31)95
 }
 
 impl SystemExclusiveData for ControlSource {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(ControlSource {
-            source: control::ControlSource::try_from(data[0]).unwrap(),
-            destination: EffectDestination::try_from(data[1]).unwrap(),
-            depth: Depth::from(data[2]),
+            source: control::ControlSource::try_from(data[0]).map_err(invalid_field("source", data, 0))?,
+            destination: EffectDestination::try_from(data[1]).map_err(invalid_field("destination", data, 1))?,
+            depth: MacroParameterDepth::try_from(data[2]).map_err(invalid_field("depth", data, 2))?,
         })
     }
 
@@ -376,6 +410,7 @@ impl SystemExclusiveData for ControlSource {
 
 /// Effect control with two sources.
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EffectControl {
     pub source1: ControlSource,
     pub source2: ControlSource,
@@ -383,9 +418,10 @@ pub struct EffectControl {
 
 impl SystemExclusiveData for EffectControl {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(EffectControl {
-            source1: ControlSource::from_bytes(&data[0..3])?,
-            source2: ControlSource::from_bytes(&data[3..6])?,
+            source1: ControlSource::from_bytes(&data[0..3]).map_err(|e| e.in_field("source1"))?,
+            source2: ControlSource::from_bytes(&data[3..6]).map_err(|e| e.within("source2", 3))?,
         })
     }
 
@@ -447,4 +483,13 @@ mod tests {
         let effect_settings = EffectSettings::from_bytes(&data);
         assert_eq!(effect_settings.unwrap().effect4.parameter3.value(), 0x63);
     }
+
+    #[test]
+    fn test_control_source_depth() {
+        let source = ControlSource::from_bytes(&[0x01, 0x02, 0x5f]).unwrap();
+        assert_eq!(source.depth.value(), 31);
+        assert_eq!(source.to_bytes(), vec![0x01, 0x02, 0x5f]);
+
+        assert!(ControlSource::from_bytes(&[0x01, 0x02, 0x00]).is_err());
+    }
 }
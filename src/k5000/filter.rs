@@ -8,7 +8,9 @@ use num_enum::TryFromPrimitive;
 
 use crate::{
     SystemExclusiveData,
-    ParseError
+    ParseError,
+    check_length,
+    invalid_field
 };
 use crate::k5000::{
     EnvelopeTime,
@@ -29,6 +31,8 @@ pub enum FilterMode {
     HighPass = 1,
 }
 
+serialize_by_name!(FilterMode);
+
 impl fmt::Display for FilterMode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", match self {
@@ -40,6 +44,7 @@ impl fmt::Display for FilterMode {
 
 /// Filter envelope.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Envelope {
     pub attack_time: EnvelopeTime,
     pub decay1_time: EnvelopeTime,
@@ -79,13 +84,14 @@ impl fmt::Display for Envelope {
 
 impl SystemExclusiveData for Envelope {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(Envelope {
-            attack_time: EnvelopeTime::from(data[0]),
-            decay1_time: EnvelopeTime::from(data[1]),
-            decay1_level: EnvelopeLevel::from(data[2]),
-            decay2_time: EnvelopeTime::from(data[3]),
-            decay2_level: EnvelopeLevel::from(data[4]),
-            release_time: EnvelopeTime::from(data[5]),
+            attack_time: EnvelopeTime::try_from(data[0]).map_err(invalid_field("attack_time", data, 0))?,
+            decay1_time: EnvelopeTime::try_from(data[1]).map_err(invalid_field("decay1_time", data, 1))?,
+            decay1_level: EnvelopeLevel::try_from(data[2]).map_err(invalid_field("decay1_level", data, 2))?,
+            decay2_time: EnvelopeTime::try_from(data[3]).map_err(invalid_field("decay2_time", data, 3))?,
+            decay2_level: EnvelopeLevel::try_from(data[4]).map_err(invalid_field("decay2_level", data, 4))?,
+            release_time: EnvelopeTime::try_from(data[5]).map_err(invalid_field("release_time", data, 5))?,
         })
     }
 
@@ -105,6 +111,7 @@ impl SystemExclusiveData for Envelope {
 
 /// Filter key scaling control.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct KeyScalingControl {
     pub attack_time: ControlTime,
     pub decay1_time: ControlTime,
@@ -127,9 +134,10 @@ impl fmt::Display for KeyScalingControl {
 
 impl SystemExclusiveData for KeyScalingControl {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(KeyScalingControl {
-            attack_time: ControlTime::from(data[0]),
-            decay1_time: ControlTime::from(data[1]),
+            attack_time: ControlTime::try_from(data[0]).map_err(invalid_field("attack_time", data, 0))?,
+            decay1_time: ControlTime::try_from(data[1]).map_err(invalid_field("decay1_time", data, 1))?,
         })
     }
 
@@ -145,6 +153,7 @@ impl SystemExclusiveData for KeyScalingControl {
 
 /// Filter velocity control.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VelocityControl {
     pub depth: EnvelopeDepth,
     pub attack_time: ControlTime,
@@ -170,10 +179,11 @@ impl fmt::Display for VelocityControl {
 
 impl SystemExclusiveData for VelocityControl {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(VelocityControl {
-            depth: EnvelopeDepth::from(data[0]),
-            attack_time: ControlTime::from(data[1]),
-            decay1_time: ControlTime::from(data[2]),
+            depth: EnvelopeDepth::try_from(data[0]).map_err(invalid_field("depth", data, 0))?,
+            attack_time: ControlTime::try_from(data[1]).map_err(invalid_field("attack_time", data, 1))?,
+            decay1_time: ControlTime::try_from(data[2]).map_err(invalid_field("decay1_time", data, 2))?,
         })
     }
 
@@ -190,6 +200,7 @@ impl SystemExclusiveData for VelocityControl {
 
 /// Modulation settings for the filter.
 #[derive(Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Modulation {
     pub ks_to_env: KeyScalingControl,
     pub vel_to_env: VelocityControl,
@@ -203,9 +214,10 @@ impl fmt::Display for Modulation {
 
 impl SystemExclusiveData for Modulation {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(Modulation {
-            ks_to_env: KeyScalingControl::from_bytes(&data[..2])?,
-            vel_to_env: VelocityControl::from_bytes(&data[2..5])?,
+            ks_to_env: KeyScalingControl::from_bytes(&data[..2]).map_err(|e| e.in_field("ks_to_env"))?,
+            vel_to_env: VelocityControl::from_bytes(&data[2..5]).map_err(|e| e.within("vel_to_env", 2))?,
         })
     }
 
@@ -226,6 +238,7 @@ impl SystemExclusiveData for Modulation {
 
 /// Filter settings.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Filter {
     pub is_active: bool,
     pub mode: FilterMode,
@@ -277,18 +290,19 @@ impl fmt::Display for Filter {
 
 impl SystemExclusiveData for Filter {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(Filter {
             is_active: data[0] != 1,  // value of 1 means filter is bypassed
-            mode: FilterMode::try_from(data[1]).unwrap(),
-            velocity_curve: VelocityCurve::try_from(data[2]).unwrap(),  // from 0 ~ 11 to enum
-            resonance: Resonance::from(data[3]),
-            level: Level::from(data[4]),
-            cutoff: Cutoff::from(data[5]),
-            ks_to_cutoff: EnvelopeDepth::from(data[6]),
-            vel_to_cutoff: EnvelopeDepth::from(data[7]),
-            envelope_depth: EnvelopeDepth::from(data[8]),
-            envelope: Envelope::from_bytes(&data[9..15])?,
-            modulation: Modulation::from_bytes(&data[15..20])?,
+            mode: FilterMode::try_from(data[1]).map_err(invalid_field("mode", data, 1))?,
+            velocity_curve: VelocityCurve::try_from(data[2]).map_err(invalid_field("velocity_curve", data, 2))?,  // from 0 ~ 11 to enum
+            resonance: Resonance::try_from(data[3]).map_err(invalid_field("resonance", data, 3))?,
+            level: Level::try_from(data[4]).map_err(invalid_field("level", data, 4))?,
+            cutoff: Cutoff::try_from(data[5]).map_err(invalid_field("cutoff", data, 5))?,
+            ks_to_cutoff: EnvelopeDepth::try_from(data[6]).map_err(invalid_field("ks_to_cutoff", data, 6))?,
+            vel_to_cutoff: EnvelopeDepth::try_from(data[7]).map_err(invalid_field("vel_to_cutoff", data, 7))?,
+            envelope_depth: EnvelopeDepth::try_from(data[8]).map_err(invalid_field("envelope_depth", data, 8))?,
+            envelope: Envelope::from_bytes(&data[9..15]).map_err(|e| e.within("envelope", 9))?,
+            modulation: Modulation::from_bytes(&data[15..20]).map_err(|e| e.within("modulation", 15))?,
         })
     }
 
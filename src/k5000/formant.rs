@@ -8,7 +8,9 @@ use num_enum::TryFromPrimitive;
 
 use crate::{
     SystemExclusiveData,
-    ParseError
+    ParseError,
+    check_length,
+    invalid_field
 };
 use crate::k5000::morf::Loop;
 use crate::k5000::{
@@ -21,17 +23,22 @@ use crate::k5000::{
 };
 
 /// Formant filter envelope mode.
-#[derive(Debug, Eq, PartialEq, Copy, Clone, TryFromPrimitive, Default)]
+#[derive(Debug, Eq, PartialEq, Copy, Clone, TryFromPrimitive, Default, strum_macros::Display)]
 #[repr(u8)]
 pub enum Mode {
     #[default]
+    #[strum(to_string = "ENV")]
     Envelope,
 
+    #[strum(to_string = "LFO")]
     Lfo,
 }
 
+serialize_by_name!(Mode);
+
 /// Envelope segment.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EnvelopeSegment {
     pub rate: EnvelopeRate,  // 0~127
     pub level: EnvelopeLevel, // -63(1)~+63(127)
@@ -48,9 +55,10 @@ impl Default for EnvelopeSegment {
 
 impl SystemExclusiveData for EnvelopeSegment {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(EnvelopeSegment {
-            rate: EnvelopeRate::from(data[0]),
-            level: EnvelopeLevel::from(data[1]),
+            rate: EnvelopeRate::try_from(data[0]).map_err(invalid_field("rate", data, 0))?,
+            level: EnvelopeLevel::try_from(data[1]).map_err(invalid_field("level", data, 1))?,
         })
     }
 
@@ -63,6 +71,7 @@ impl SystemExclusiveData for EnvelopeSegment {
 
 /// Formant filter envelope.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Envelope {
     pub attack: EnvelopeSegment,
     pub decay1: EnvelopeSegment,
@@ -89,14 +98,15 @@ impl Default for Envelope {
 
 impl SystemExclusiveData for Envelope {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(Envelope {
-            attack: EnvelopeSegment::from_bytes(&data[..2])?,
-            decay1: EnvelopeSegment::from_bytes(&data[2..4])?,
-            decay2: EnvelopeSegment::from_bytes(&data[4..6])?,
-            release: EnvelopeSegment::from_bytes(&data[6..8])?,
-            decay_loop: Loop::try_from(data[8]).unwrap(),
-            velocity_depth: EnvelopeDepth::from(data[9]),
-            ks_depth: EnvelopeDepth::from(data[10]),
+            attack: EnvelopeSegment::from_bytes(&data[..2]).map_err(|e| e.in_field("attack"))?,
+            decay1: EnvelopeSegment::from_bytes(&data[2..4]).map_err(|e| e.within("decay1", 2))?,
+            decay2: EnvelopeSegment::from_bytes(&data[4..6]).map_err(|e| e.within("decay2", 4))?,
+            release: EnvelopeSegment::from_bytes(&data[6..8]).map_err(|e| e.within("release", 6))?,
+            decay_loop: Loop::try_from(data[8]).map_err(invalid_field("decay_loop", data, 8))?,
+            velocity_depth: EnvelopeDepth::try_from(data[9]).map_err(invalid_field("velocity_depth", data, 9))?,
+            ks_depth: EnvelopeDepth::try_from(data[10]).map_err(invalid_field("ks_depth", data, 10))?,
         })
     }
 
@@ -125,18 +135,25 @@ impl SystemExclusiveData for Envelope {
 }
 
 /// Formant filter LFO shape.
-#[derive(Debug, Eq, PartialEq, Copy, Clone, TryFromPrimitive, Default)]
+#[derive(Debug, Eq, PartialEq, Copy, Clone, TryFromPrimitive, Default, strum_macros::Display)]
 #[repr(u8)]
 pub enum LFOShape {
     #[default]
+    #[strum(to_string = "TRI")]
     Triangle,
 
+    #[strum(to_string = "SAW")]
     Sawtooth,
+
+    #[strum(to_string = "RND")]
     Random,
 }
 
+serialize_by_name!(LFOShape);
+
 /// Formant filter LFO.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Lfo {
     pub speed: LFOSpeed,
     pub shape: LFOShape,
@@ -155,10 +172,11 @@ impl Default for Lfo {
 
 impl SystemExclusiveData for Lfo {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(Lfo {
-            speed: LFOSpeed::from(data[0]),
-            shape: LFOShape::try_from(data[1]).unwrap(),
-            depth: LFODepth::from(data[2]),
+            speed: LFOSpeed::try_from(data[0]).map_err(invalid_field("speed", data, 0))?,
+            shape: LFOShape::try_from(data[1]).map_err(invalid_field("shape", data, 1))?,
+            depth: LFODepth::try_from(data[2]).map_err(invalid_field("depth", data, 2))?,
         })
     }
 
@@ -170,6 +188,7 @@ impl SystemExclusiveData for Lfo {
 }
 
 /// Formant filter settings.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FormantFilter {
     pub bias: Bias,
     pub mode: Mode,
@@ -200,12 +219,13 @@ impl fmt::Display for FormantFilter {
 
 impl SystemExclusiveData for FormantFilter {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(FormantFilter {
-            bias: Bias::from(data[0]),
-            mode: Mode::try_from(data[1]).unwrap(),
-            envelope_depth: EnvelopeDepth::from(data[2]),
-            envelope: Envelope::from_bytes(&data[3..14])?,
-            lfo: Lfo::from_bytes(&data[14..])?,
+            bias: Bias::try_from(data[0]).map_err(invalid_field("bias", data, 0))?,
+            mode: Mode::try_from(data[1]).map_err(invalid_field("mode", data, 1))?,
+            envelope_depth: EnvelopeDepth::try_from(data[2]).map_err(invalid_field("envelope_depth", data, 2))?,
+            envelope: Envelope::from_bytes(&data[3..14]).map_err(|e| e.within("envelope", 3))?,
+            lfo: Lfo::from_bytes(&data[14..]).map_err(|e| e.within("lfo", 14))?,
         })
     }
 
@@ -5,7 +5,9 @@ use bit::BitIndex;
 
 use crate::{
     SystemExclusiveData,
-    ParseError
+    ParseError,
+    check_length,
+    invalid_field
 };
 use crate::k5000::morf::Loop;
 use crate::k5000::addkit::HARMONIC_COUNT;
@@ -17,8 +19,11 @@ use crate::k5000::{
 pub type Level = u8;
 
 /// Harmonic levels (soft and loud).
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Levels {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::big_array"))]
     pub soft: [Level; HARMONIC_COUNT],
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::big_array"))]
     pub loud: [Level; HARMONIC_COUNT],
 }
 
@@ -32,7 +37,9 @@ impl Default for Levels {
 }
 
 impl SystemExclusiveData for Levels {
+    #[allow(clippy::needless_range_loop)]
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         let mut offset = 0;
 
         let mut soft: [u8; HARMONIC_COUNT] = [0; HARMONIC_COUNT];
@@ -64,6 +71,7 @@ impl SystemExclusiveData for Levels {
 
 /// Harmonic envelope segment.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EnvelopeSegment {
     pub rate: EnvelopeRate,
     pub level: HarmonicEnvelopeLevel,
@@ -80,9 +88,10 @@ impl Default for EnvelopeSegment {
 
 impl SystemExclusiveData for EnvelopeSegment {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(EnvelopeSegment {
-            rate: EnvelopeRate::from(data[0]),
-            level: HarmonicEnvelopeLevel::from(data[1]),
+            rate: EnvelopeRate::try_from(data[0]).map_err(invalid_field("rate", data, 0))?,
+            level: HarmonicEnvelopeLevel::try_from(data[1]).map_err(invalid_field("level", data, 1))?,
         })
     }
 
@@ -95,12 +104,17 @@ impl SystemExclusiveData for EnvelopeSegment {
 
 /// Harmonic envelope with four segments and loop type.
 #[derive(Debug, Copy, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Envelope {
     pub attack: EnvelopeSegment,
     pub decay1: EnvelopeSegment,
     pub decay2: EnvelopeSegment,
     pub release: EnvelopeSegment,
     pub loop_type: Loop,
+
+    // Bit 6 of the decay1 level is sometimes set even when
+    // the loop is off. Keep it so that the data round-trips.
+    decay1_level_bit6: bool,
 }
 
 impl Envelope {
@@ -117,24 +131,26 @@ impl Envelope {
             decay2: zero_segment,
             release: zero_segment,
             loop_type: Loop::Off,
+            decay1_level_bit6: false,
         }
     }
 }
 
 impl SystemExclusiveData for Envelope {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
-        let segment0_rate = EnvelopeRate::from(data[0]);
-        let segment0_level = HarmonicEnvelopeLevel::from(data[1] & 0b0011_1111);
-        let segment1_rate = EnvelopeRate::from(data[2]);
-        let segment1_level = HarmonicEnvelopeLevel::from(data[3] & 0b0011_1111);
+        check_length(data, Self::data_size())?;
+        let segment0_rate = EnvelopeRate::try_from(data[0]).map_err(invalid_field("attack.rate", data, 0))?;
+        let segment0_level = HarmonicEnvelopeLevel::try_from(data[1] & 0b0011_1111).map_err(invalid_field("attack.level", data, 1))?;
+        let segment1_rate = EnvelopeRate::try_from(data[2]).map_err(invalid_field("decay1.rate", data, 2))?;
+        let segment1_level = HarmonicEnvelopeLevel::try_from(data[3] & 0b0011_1111).map_err(invalid_field("decay1.level", data, 3))?;
         let segment1_level_bit6 = data[3].bit(6);
-        let segment2_rate = EnvelopeRate::from(data[4]);
+        let segment2_rate = EnvelopeRate::try_from(data[4]).map_err(invalid_field("decay2.rate", data, 4))?;
         let mut segment2_level_byte = data[5];
         let segment2_level_bit6 = data[5].bit(6);
         segment2_level_byte.set_bit(6, false);
-        let segment2_level = HarmonicEnvelopeLevel::from(segment2_level_byte & 0b0011_1111);
-        let segment3_rate = EnvelopeRate::from(data[6]);
-        let segment3_level = HarmonicEnvelopeLevel::from(data[7] & 0b0011_1111);
+        let segment2_level = HarmonicEnvelopeLevel::try_from(segment2_level_byte & 0b0011_1111).map_err(invalid_field("decay2.level", data, 5))?;
+        let segment3_rate = EnvelopeRate::try_from(data[6]).map_err(invalid_field("release.rate", data, 6))?;
+        let segment3_level = HarmonicEnvelopeLevel::try_from(data[7] & 0b0011_1111).map_err(invalid_field("release.level", data, 7))?;
 
         Ok(Envelope {
             attack: EnvelopeSegment {
@@ -160,7 +176,8 @@ impl SystemExclusiveData for Envelope {
                     (false, true) => Loop::Loop2,
                     (false, false) => Loop::Off,
                 }
-            }
+            },
+            decay1_level_bit6: segment1_level_bit6,
         })
     }
 
@@ -185,7 +202,7 @@ impl SystemExclusiveData for Envelope {
                 decay2_level_byte.set_bit(6, true);
             },
             Loop::Off => {
-                decay1_level_byte.set_bit(6, false);
+                decay1_level_byte.set_bit(6, self.decay1_level_bit6);
                 decay2_level_byte.set_bit(6, false);
             }
         }
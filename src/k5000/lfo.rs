@@ -8,7 +8,9 @@ use num_enum::TryFromPrimitive;
 
 use crate::{
     SystemExclusiveData,
-    ParseError
+    ParseError,
+    check_length,
+    invalid_field
 };
 use crate::k5000::{
     LFOSpeed,
@@ -29,6 +31,8 @@ pub enum Waveform {
     Random,
 }
 
+serialize_by_name!(Waveform);
+
 impl fmt::Display for Waveform {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", match self {
@@ -43,6 +47,7 @@ impl fmt::Display for Waveform {
 
 /// LFO control settings.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Control {
     pub depth: Depth,
     pub key_scaling: KeyScaling,
@@ -65,9 +70,10 @@ impl fmt::Display for Control {
 
 impl SystemExclusiveData for Control {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(Control {
-            depth: Depth::from(data[0]),
-            key_scaling: KeyScaling::from(data[1]),
+            depth: Depth::try_from(data[0]).map_err(invalid_field("depth", data, 0))?,
+            key_scaling: KeyScaling::try_from(data[1]).map_err(invalid_field("key_scaling", data, 1))?,
         })
     }
 
@@ -80,6 +86,7 @@ impl SystemExclusiveData for Control {
 
 /// LFO settings.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Lfo {
     pub waveform: Waveform,
     pub speed: LFOSpeed,
@@ -117,23 +124,24 @@ impl fmt::Display for Lfo {
 
 impl SystemExclusiveData for Lfo {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(Lfo {
-            waveform: Waveform::try_from(data[0]).unwrap(),
-            speed: LFOSpeed::from(data[1]),
-            fade_in_time: LFOSpeed::from(data[2]),
-            fade_in_to_speed: Depth::from(data[3]),
-            delay_onset: LFOSpeed::from(data[4]),
+            waveform: Waveform::try_from(data[0]).map_err(invalid_field("waveform", data, 0))?,
+            speed: LFOSpeed::try_from(data[1]).map_err(invalid_field("speed", data, 1))?,
+            delay_onset: LFOSpeed::try_from(data[2]).map_err(invalid_field("delay_onset", data, 2))?,
+            fade_in_time: LFOSpeed::try_from(data[3]).map_err(invalid_field("fade_in_time", data, 3))?,
+            fade_in_to_speed: Depth::try_from(data[4]).map_err(invalid_field("fade_in_to_speed", data, 4))?,
             vibrato: Control {
-                depth: Depth::from(data[5]),
-                key_scaling: KeyScaling::from(data[6]),
+                depth: Depth::try_from(data[5]).map_err(invalid_field("depth", data, 5))?,
+                key_scaling: KeyScaling::try_from(data[6]).map_err(invalid_field("key_scaling", data, 6))?,
             },
             growl: Control {
-                depth: Depth::from(data[7]),
-                key_scaling: KeyScaling::from(data[8]),
+                depth: Depth::try_from(data[7]).map_err(invalid_field("depth", data, 7))?,
+                key_scaling: KeyScaling::try_from(data[8]).map_err(invalid_field("key_scaling", data, 8))?,
             },
             tremolo: Control {
-                depth: Depth::from(data[9]),
-                key_scaling: KeyScaling::from(data[10]),
+                depth: Depth::try_from(data[9]).map_err(invalid_field("depth", data, 9))?,
+                key_scaling: KeyScaling::try_from(data[10]).map_err(invalid_field("key_scaling", data, 10))?,
             },
         })
     }
@@ -157,3 +165,19 @@ impl SystemExclusiveData for Lfo {
 
     fn data_size() -> usize { 11 }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{*};
+
+    #[test]
+    fn test_lfo_round_trip() {
+        let data = [0x01, 0x10, 0x20, 0x30, 0x40, 0x05, 0x45, 0x06, 0x46, 0x07, 0x47];
+        let lfo = Lfo::from_bytes(&data).unwrap();
+        assert_eq!(lfo.speed.value(), 0x10);
+        assert_eq!(lfo.delay_onset.value(), 0x20);
+        assert_eq!(lfo.fade_in_time.value(), 0x30);
+        assert_eq!(lfo.fade_in_to_speed.value(), 0x40);
+        assert_eq!(lfo.to_bytes(), data);
+    }
+}
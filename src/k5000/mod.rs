@@ -1,7 +1,10 @@
 use std::fmt;
+use std::convert::TryFrom;
 use rand::Rng;
 use std::ops::RangeInclusive;
 
+use crate::ValueError;
+
 pub mod filter;
 pub mod amp;
 pub mod osc;
@@ -11,12 +14,17 @@ pub mod control;
 pub mod source;
 pub mod effect;
 pub mod single;
+pub mod multi;
+pub mod drum;
 pub mod morf;
 pub mod harmonic;
 pub mod formant;
 pub mod addkit;
 pub mod wave;
 pub mod sysex;
+pub mod parameter;
+pub mod bank;
+pub mod registry;
 
 /// Length of patch name
 pub const NAME_LENGTH: usize = 8;
@@ -24,6 +32,7 @@ pub const NAME_LENGTH: usize = 8;
 /// A simple struct for wrapping an `i32` with const generic parameters to limit
 /// the range of allowed values.
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(try_from = "i32", into = "i32"))]
 pub struct RangedInteger<const MIN: i32, const MAX: i32> {
     value: i32,
 }
@@ -40,6 +49,17 @@ impl <const MIN: i32, const MAX: i32> RangedInteger<MIN, MAX> {
         }
     }
 
+    /// Makes a new ranged integer if the value is in the allowed range,
+    /// otherwise returns an error.
+    pub fn try_new(value: i32) -> Result<Self, ValueError> {
+        if Self::range().contains(&value) {
+            Ok(Self { value })
+        }
+        else {
+            Err(ValueError(MIN, MAX, value))
+        }
+    }
+
     /// Gets the range of allowed values as an inclusive range,
     /// constructed from the generic parameters.
     pub fn range() -> RangeInclusive<i32> {
@@ -54,8 +74,22 @@ impl <const MIN: i32, const MAX: i32> RangedInteger<MIN, MAX> {
     }
 }
 
+impl <const MIN: i32, const MAX: i32> TryFrom<i32> for RangedInteger<MIN, MAX> {
+    type Error = ValueError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        Self::try_new(value)
+    }
+}
+
+impl <const MIN: i32, const MAX: i32> From<RangedInteger<MIN, MAX>> for i32 {
+    fn from(val: RangedInteger<MIN, MAX>) -> Self {
+        val.value
+    }
+}
+
 /// Trait for a synth parameter.
-trait Parameter {
+pub trait Parameter {
     fn name(&self) -> String;
     fn minimum_value() -> i32;
     fn maximum_value() -> i32;
@@ -74,6 +108,7 @@ type VolumeValue = RangedInteger::<0, 127>;
 
 /// Wrapper for volume parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct Volume {
     value: VolumeValue,  // private field to prevent accidental range violations
 }
@@ -116,9 +151,11 @@ impl Default for Volume {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for Volume {
-    fn from(value: u8) -> Volume {
-        Volume::new(value as i32)
+impl TryFrom<u8> for Volume {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: VolumeValue::try_new(value as i32)? })
     }
 }
 
@@ -138,6 +175,7 @@ type BenderPitchValue = RangedInteger::<0, 24>;
 
 /// Wrapper for bender pitch parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct BenderPitch {
     value: BenderPitchValue,  // private field to prevent accidental range violations
 }
@@ -180,9 +218,11 @@ impl Default for BenderPitch {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for BenderPitch {
-    fn from(value: u8) -> BenderPitch {
-        BenderPitch::new(value as i32)
+impl TryFrom<u8> for BenderPitch {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: BenderPitchValue::try_new(value as i32)? })
     }
 }
 
@@ -202,6 +242,7 @@ type BenderCutoffValue = RangedInteger::<0, 31>;
 
 /// Wrapper for bender pitch parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct BenderCutoff {
     value: BenderCutoffValue,  // private field to prevent accidental range violations
 }
@@ -244,9 +285,11 @@ impl Default for BenderCutoff {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for BenderCutoff {
-    fn from(value: u8) -> BenderCutoff {
-        BenderCutoff::new(value as i32)
+impl TryFrom<u8> for BenderCutoff {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: BenderCutoffValue::try_new(value as i32)? })
     }
 }
 
@@ -266,6 +309,7 @@ type EnvelopeTimeValue = RangedInteger::<0, 127>;
 
 /// Wrapper for envelope time parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct EnvelopeTime {
     value: EnvelopeTimeValue,  // private field to prevent accidental range violations
 }
@@ -308,9 +352,11 @@ impl Default for EnvelopeTime {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for EnvelopeTime {
-    fn from(value: u8) -> EnvelopeTime {
-        EnvelopeTime::new(value as i32)
+impl TryFrom<u8> for EnvelopeTime {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: EnvelopeTimeValue::try_new(value as i32)? })
     }
 }
 
@@ -330,6 +376,7 @@ type EnvelopeLevelValue = RangedInteger::<-63, 63>;
 
 /// Wrapper for envelope level parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct EnvelopeLevel {
     value: EnvelopeLevelValue,  // private field to prevent accidental range violations
 }
@@ -372,9 +419,11 @@ impl Default for EnvelopeLevel {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for EnvelopeLevel {
-    fn from(value: u8) -> EnvelopeLevel {
-        EnvelopeLevel::new((value as i32) - 64)
+impl TryFrom<u8> for EnvelopeLevel {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: EnvelopeLevelValue::try_new((value as i32) - 64)? })
     }
 }
 
@@ -394,6 +443,7 @@ type EnvelopeRateValue = RangedInteger::<0, 127>;
 
 /// Wrapper for envelope rate parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct EnvelopeRate {
     value: EnvelopeRateValue,  // private field to prevent accidental range violations
 }
@@ -436,9 +486,11 @@ impl Default for EnvelopeRate {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for EnvelopeRate {
-    fn from(value: u8) -> EnvelopeRate {
-        EnvelopeRate::new(value as i32)
+impl TryFrom<u8> for EnvelopeRate {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: EnvelopeRateValue::try_new(value as i32)? })
     }
 }
 
@@ -458,6 +510,7 @@ type HarmonicEnvelopeLevelValue = RangedInteger::<0, 63>;
 
 /// Wrapper for harmonic envelope level parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct HarmonicEnvelopeLevel {
     value: HarmonicEnvelopeLevelValue,  // private field to prevent accidental range violations
 }
@@ -500,9 +553,11 @@ impl Default for HarmonicEnvelopeLevel {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for HarmonicEnvelopeLevel {
-    fn from(value: u8) -> HarmonicEnvelopeLevel {
-        HarmonicEnvelopeLevel::new(value as i32)
+impl TryFrom<u8> for HarmonicEnvelopeLevel {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: HarmonicEnvelopeLevelValue::try_new(value as i32)? })
     }
 }
 
@@ -522,6 +577,7 @@ type BiasValue = RangedInteger::<-63, 63>;
 
 /// Wrapper for bias parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct Bias {
     value: BiasValue,  // private field to prevent accidental range violations
 }
@@ -564,15 +620,17 @@ impl Default for Bias {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for Bias {
-    fn from(value: u8) -> Bias {
-        Bias::new(value as i32)
+impl TryFrom<u8> for Bias {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: BiasValue::try_new((value as i32) - 64)? })
     }
 }
 
 impl From<Bias> for u8 {
     fn from(val: Bias) -> Self {
-        val.value() as u8 // value can be used as such in SysEx
+        (val.value() + 64) as u8 // value needs adjustment for SysEx
     }
 }
 
@@ -586,6 +644,7 @@ type ControlTimeValue = RangedInteger::<-63, 63>;
 
 /// Wrapper for control time parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct ControlTime {
     value: ControlTimeValue,  // private field to prevent accidental range violations
 }
@@ -628,9 +687,11 @@ impl Default for ControlTime {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for ControlTime {
-    fn from(value: u8) -> ControlTime {
-        ControlTime::new((value as i32) - 64)
+impl TryFrom<u8> for ControlTime {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: ControlTimeValue::try_new((value as i32) - 64)? })
     }
 }
 
@@ -650,6 +711,7 @@ type EnvelopeDepthValue = RangedInteger::<-63, 63>;
 
 /// Wrapper for envelope depth parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct EnvelopeDepth {
     value: EnvelopeDepthValue,  // private field to prevent accidental range violations
 }
@@ -692,9 +754,11 @@ impl Default for EnvelopeDepth {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for EnvelopeDepth {
-    fn from(value: u8) -> EnvelopeDepth {
-        EnvelopeDepth::new((value as i32) - 64)
+impl TryFrom<u8> for EnvelopeDepth {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: EnvelopeDepthValue::try_new((value as i32) - 64)? })
     }
 }
 
@@ -714,6 +778,7 @@ type LFOSpeedValue = RangedInteger::<0, 127>;
 
 /// Wrapper for LFO speed parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct LFOSpeed {
     value: LFOSpeedValue,  // private field to prevent accidental range violations
 }
@@ -756,9 +821,11 @@ impl Default for LFOSpeed {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for LFOSpeed {
-    fn from(value: u8) -> LFOSpeed {
-        LFOSpeed::new(value as i32)
+impl TryFrom<u8> for LFOSpeed {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: LFOSpeedValue::try_new(value as i32)? })
     }
 }
 
@@ -778,6 +845,7 @@ type LFODepthValue = RangedInteger::<0, 63>;
 
 /// Wrapper for LFO depth parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct LFODepth {
     value: LFODepthValue,  // private field to prevent accidental range violations
 }
@@ -820,9 +888,11 @@ impl Default for LFODepth {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for LFODepth {
-    fn from(value: u8) -> LFODepth {
-        LFODepth::new(value as i32)
+impl TryFrom<u8> for LFODepth {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: LFODepthValue::try_new(value as i32)? })
     }
 }
 
@@ -843,6 +913,7 @@ type KeyScalingValue = RangedInteger::<-63, 63>;
 
 /// Wrapper for key scaling parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct KeyScaling {
     value: KeyScalingValue,  // private field to prevent accidental range violations
 }
@@ -885,9 +956,11 @@ impl Default for KeyScaling {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for KeyScaling {
-    fn from(value: u8) -> KeyScaling {
-        KeyScaling::new((value as i32) - 64)
+impl TryFrom<u8> for KeyScaling {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: KeyScalingValue::try_new((value as i32) - 64)? })
     }
 }
 
@@ -908,6 +981,7 @@ type EffectParameterValue = RangedInteger::<0, 127>;
 
 /// Wrapper for effect parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct EffectParameter {
     value: EffectParameterValue,  // private field to prevent accidental range violations
 }
@@ -950,9 +1024,11 @@ impl Default for EffectParameter {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for EffectParameter {
-    fn from(value: u8) -> EffectParameter {
-        EffectParameter::new(value as i32)
+impl TryFrom<u8> for EffectParameter {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: EffectParameterValue::try_new(value as i32)? })
     }
 }
 
@@ -973,6 +1049,7 @@ type CutoffValue = RangedInteger::<0, 127>;
 
 /// Wrapper for cutoff parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct Cutoff {
     value: CutoffValue,  // private field to prevent accidental range violations
 }
@@ -1015,9 +1092,11 @@ impl Default for Cutoff {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for Cutoff {
-    fn from(value: u8) -> Cutoff {
-        Cutoff::new(value as i32)
+impl TryFrom<u8> for Cutoff {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: CutoffValue::try_new(value as i32)? })
     }
 }
 
@@ -1037,6 +1116,7 @@ type ResonanceValue = RangedInteger::<0, 31>;
 
 /// Wrapper for resonance parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct Resonance {
     value: ResonanceValue,  // private field to prevent accidental range violations
 }
@@ -1079,9 +1159,11 @@ impl Default for Resonance {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for Resonance {
-    fn from(value: u8) -> Resonance {
-        Resonance::new(value as i32)
+impl TryFrom<u8> for Resonance {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: ResonanceValue::try_new(value as i32)? })
     }
 }
 
@@ -1101,6 +1183,7 @@ type LevelValue = RangedInteger::<0, 31>;
 
 /// Wrapper for level parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct Level {
     value: LevelValue,  // private field to prevent accidental range violations
 }
@@ -1143,9 +1226,11 @@ impl Default for Level {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for Level {
-    fn from(value: u8) -> Level {
-        Level::new(value as i32)
+impl TryFrom<u8> for Level {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: LevelValue::try_new(value as i32)? })
     }
 }
 
@@ -1166,6 +1251,7 @@ type PitchEnvelopeLevelValue = RangedInteger::<-63, 63>;
 
 /// Wrapper for pitch envelope level parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct PitchEnvelopeLevel {
     value: PitchEnvelopeLevelValue,  // private field to prevent accidental range violations
 }
@@ -1208,15 +1294,17 @@ impl Default for PitchEnvelopeLevel {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for PitchEnvelopeLevel {
-    fn from(value: u8) -> PitchEnvelopeLevel {
-        PitchEnvelopeLevel::new((value as i32) - 64)
+impl TryFrom<u8> for PitchEnvelopeLevel {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: PitchEnvelopeLevelValue::try_new((value as i32) - 64)? })
     }
 }
 
 impl From<PitchEnvelopeLevel> for u8 {
     fn from(val: PitchEnvelopeLevel) -> Self {
-        val.value() as u8 // value can be used as such in SysEx
+        (val.value() + 64) as u8 // value needs adjustment for SysEx
     }
 }
 
@@ -1230,6 +1318,7 @@ type PitchEnvelopeTimeValue = RangedInteger::<0, 127>;
 
 /// Wrapper for pitch envelope time parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct PitchEnvelopeTime {
     value: PitchEnvelopeTimeValue,  // private field to prevent accidental range violations
 }
@@ -1272,9 +1361,11 @@ impl Default for PitchEnvelopeTime {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for PitchEnvelopeTime {
-    fn from(value: u8) -> PitchEnvelopeTime {
-        PitchEnvelopeTime::new(value as i32)
+impl TryFrom<u8> for PitchEnvelopeTime {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: PitchEnvelopeTimeValue::try_new(value as i32)? })
     }
 }
 
@@ -1294,6 +1385,7 @@ type VelocityDepthValue = RangedInteger::<0, 127>;
 
 /// Wrapper for velocity depth parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct VelocityDepth {
     value: VelocityDepthValue,  // private field to prevent accidental range violations
 }
@@ -1336,9 +1428,11 @@ impl Default for VelocityDepth {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for VelocityDepth {
-    fn from(value: u8) -> VelocityDepth {
-        VelocityDepth::new(value as i32)
+impl TryFrom<u8> for VelocityDepth {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: VelocityDepthValue::try_new(value as i32)? })
     }
 }
 
@@ -1358,6 +1452,7 @@ type VelocityControlLevelValue = RangedInteger::<0, 127>;
 
 /// Wrapper for velocity control level parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct VelocityControlLevel {
     value: VelocityControlLevelValue,  // private field to prevent accidental range violations
 }
@@ -1400,9 +1495,11 @@ impl Default for VelocityControlLevel {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for VelocityControlLevel {
-    fn from(value: u8) -> VelocityControlLevel {
-        VelocityControlLevel::new(value as i32)
+impl TryFrom<u8> for VelocityControlLevel {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: VelocityControlLevelValue::try_new(value as i32)? })
     }
 }
 
@@ -1423,6 +1520,7 @@ type PortamentoLevelValue = RangedInteger::<0, 127>;
 
 /// Wrapper for portamento level parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct PortamentoLevel {
     value: PortamentoLevelValue,  // private field to prevent accidental range violations
 }
@@ -1465,9 +1563,11 @@ impl Default for PortamentoLevel {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for PortamentoLevel {
-    fn from(value: u8) -> PortamentoLevel {
-        PortamentoLevel::new(value as i32)
+impl TryFrom<u8> for PortamentoLevel {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: PortamentoLevelValue::try_new(value as i32)? })
     }
 }
 
@@ -1487,6 +1587,7 @@ type KeyOnDelayValue = RangedInteger::<0, 127>;
 
 /// Wrapper for key on delay parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct KeyOnDelay {
     value: KeyOnDelayValue,  // private field to prevent accidental range violations
 }
@@ -1529,9 +1630,11 @@ impl Default for KeyOnDelay {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for KeyOnDelay {
-    fn from(value: u8) -> KeyOnDelay {
-        KeyOnDelay::new(value as i32)
+impl TryFrom<u8> for KeyOnDelay {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: KeyOnDelayValue::try_new(value as i32)? })
     }
 }
 
@@ -1552,6 +1655,7 @@ type VelocitySensitivityValue = RangedInteger::<-63, 63>;
 
 /// Wrapper for velocity sensitivity parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct VelocitySensitivity {
     value: VelocitySensitivityValue,  // private field to prevent accidental range violations
 }
@@ -1594,9 +1698,11 @@ impl Default for VelocitySensitivity {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for VelocitySensitivity {
-    fn from(value: u8) -> VelocitySensitivity {
-        VelocitySensitivity::new((value as i32) - 64)
+impl TryFrom<u8> for VelocitySensitivity {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: VelocitySensitivityValue::try_new((value as i32) - 64)? })
     }
 }
 
@@ -1616,6 +1722,7 @@ type ControlDepthValue  = RangedInteger::<-63, 63>;
 
 /// Wrapper for control depth parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct ControlDepth {
     value: ControlDepthValue,  // private field to prevent accidental range violations
 }
@@ -1658,9 +1765,11 @@ impl Default for ControlDepth {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for ControlDepth {
-    fn from(value: u8) -> ControlDepth {
-        ControlDepth::new((value as i32) - 64)
+impl TryFrom<u8> for ControlDepth {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: ControlDepthValue::try_new((value as i32) - 64)? })
     }
 }
 
@@ -1680,6 +1789,7 @@ type DepthValue = RangedInteger::<0, 100>;
 
 /// Wrapper for depth parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct Depth {
     value: DepthValue,  // private field to prevent accidental range violations
 }
@@ -1722,9 +1832,11 @@ impl Default for Depth {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for Depth {
-    fn from(value: u8) -> Depth {
-        Depth::new(value as i32)
+impl TryFrom<u8> for Depth {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: DepthValue::try_new(value as i32)? })
     }
 }
 
@@ -1744,6 +1856,7 @@ type PanValue = RangedInteger::<-63, 63>;
 
 /// Wrapper for pan parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct Pan {
     value: PanValue,  // private field to prevent accidental range violations
 }
@@ -1786,9 +1899,11 @@ impl Default for Pan {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for Pan {
-    fn from(value: u8) -> Pan {
-        Pan::new((value as i32) - 64)
+impl TryFrom<u8> for Pan {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: PanValue::try_new((value as i32) - 64)? })
     }
 }
 
@@ -1808,6 +1923,7 @@ type KeyScalingToGainValue = RangedInteger::<-63, 63>;
 
 /// Wrapper for key scaling to gain parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct KeyScalingToGain {
     value: KeyScalingToGainValue,  // private field to prevent accidental range violations
 }
@@ -1850,9 +1966,11 @@ impl Default for KeyScalingToGain {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for KeyScalingToGain {
-    fn from(value: u8) -> KeyScalingToGain {
-        KeyScalingToGain::new((value as i32) - 64)
+impl TryFrom<u8> for KeyScalingToGain {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: KeyScalingToGainValue::try_new((value as i32) - 64)? })
     }
 }
 
@@ -1872,6 +1990,7 @@ type CoarseValue = RangedInteger::<-24, 24>;
 
 /// Wrapper for coarse parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct Coarse {
     value: CoarseValue,  // private field to prevent accidental range violations
 }
@@ -1914,9 +2033,11 @@ impl Default for Coarse {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for Coarse {
-    fn from(value: u8) -> Coarse {
-        Coarse::new((value as i32) - 64)
+impl TryFrom<u8> for Coarse {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: CoarseValue::try_new((value as i32) - 64)? })
     }
 }
 
@@ -1937,6 +2058,7 @@ type FineValue = RangedInteger::<-63, 63>;
 
 /// Wrapper for fine parameter.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct Fine {
     value: FineValue,  // private field to prevent accidental range violations
 }
@@ -1979,15 +2101,17 @@ impl Default for Fine {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for Fine {
-    fn from(value: u8) -> Fine {
-        Fine::new((value as i32) - 64)
+impl TryFrom<u8> for Fine {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: FineValue::try_new((value as i32) - 64)? })
     }
 }
 
 impl From<Fine> for u8 {
     fn from(val: Fine) -> Self {
-        (val.value() as u8) + 64
+        (val.value() + 64) as u8
     }
 }
 
@@ -2001,6 +2125,7 @@ type MacroParameterDepthValue = RangedInteger::<-31, 31>;
 
 /// Wrapper for macro parameter depth.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct MacroParameterDepth {
     value: MacroParameterDepthValue,  // private field to prevent accidental range violations
 }
@@ -2043,15 +2168,17 @@ impl Default for MacroParameterDepth {
     fn default() -> Self { Self::new(0) }
 }
 
-impl From<u8> for MacroParameterDepth {
-    fn from(value: u8) -> MacroParameterDepth {
-        MacroParameterDepth::new((value as i32) - 64)  // (-31)33~(+31)95 (K5000W=64)
+impl TryFrom<u8> for MacroParameterDepth {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self { value: MacroParameterDepthValue::try_new((value as i32) - 64)? })  // (-31)33~(+31)95 (K5000W=64)
     }
 }
 
 impl From<MacroParameterDepth> for u8 {
     fn from(val: MacroParameterDepth) -> Self {
-        (val.value() as u8) + 64
+        (val.value() + 64) as u8
     }
 }
 
@@ -2073,10 +2200,12 @@ use nutype::nutype;
 #[nutype(
     sanitize(with = |s: String| format!("{:<8}", s)),
     validate(not_empty, len_char_max = 8),
-    derive(Debug, PartialEq)
+    derive(Debug, Clone, PartialEq)
 )]
 pub struct PatchName(String);
 
+serialize_validated!(PatchName, String);
+
 
 #[cfg(test)]
 mod tests {
@@ -8,7 +8,9 @@ use num_enum::TryFromPrimitive;
 
 use crate::{
     SystemExclusiveData,
-    ParseError
+    ParseError,
+    check_length,
+    invalid_field
 };
 use crate::k5000::{
     VelocityDepth,
@@ -27,6 +29,8 @@ pub enum HarmonicGroup {
     High
 }
 
+serialize_by_name!(HarmonicGroup);
+
 impl fmt::Display for HarmonicGroup {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", if *self == HarmonicGroup::Low { "LO" } else { "HI" })
@@ -34,6 +38,7 @@ impl fmt::Display for HarmonicGroup {
 }
 
 /// Harmonic common settings.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HarmonicCommon {
     pub morf_enabled: bool,
     pub total_gain: u8,
@@ -66,13 +71,14 @@ impl fmt::Display for HarmonicCommon {
 
 impl SystemExclusiveData for HarmonicCommon {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(HarmonicCommon {
             morf_enabled: data[0] == 1,
             total_gain: data[1],
-            group: HarmonicGroup::try_from(data[2]).unwrap(),
-            ks_to_gain: KeyScalingToGain::from(data[3]),
-            velocity_curve: VelocityCurve::try_from(data[4]).unwrap(), // 0~11 maps to enum
-            velocity_depth: VelocityDepth::from(data[5]),
+            group: HarmonicGroup::try_from(data[2]).map_err(invalid_field("group", data, 2))?,
+            ks_to_gain: KeyScalingToGain::try_from(data[3]).map_err(invalid_field("ks_to_gain", data, 3))?,
+            velocity_curve: VelocityCurve::try_from(data[4]).map_err(invalid_field("velocity_curve", data, 4))?, // 0~11 maps to enum
+            velocity_depth: VelocityDepth::try_from(data[5]).map_err(invalid_field("velocity_depth", data, 5))?,
         })
     }
 
@@ -92,6 +98,7 @@ impl SystemExclusiveData for HarmonicCommon {
 
 /// MORF harmonic copy parameters.
 #[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MorfHarmonicCopyParameters {
     pub patch_number: u8,
     pub source_number: u8,
@@ -106,6 +113,7 @@ impl fmt::Display for MorfHarmonicCopyParameters {
 
 impl SystemExclusiveData for MorfHarmonicCopyParameters {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(MorfHarmonicCopyParameters {
             patch_number: data[0],
             source_number: data[1],
@@ -130,6 +138,8 @@ pub enum Loop {
     Loop2,
 }
 
+serialize_by_name!(Loop);
+
 impl fmt::Display for Loop {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", match *self {
@@ -141,6 +151,7 @@ impl fmt::Display for Loop {
 }
 
 /// MORF harmonic envelope.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MorfHarmonicEnvelope {
     pub time1: EnvelopeTime,
     pub time2: EnvelopeTime,
@@ -171,12 +182,13 @@ impl fmt::Display for MorfHarmonicEnvelope {
 
 impl SystemExclusiveData for MorfHarmonicEnvelope {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(MorfHarmonicEnvelope {
-            time1: EnvelopeTime::from(data[0]),
-            time2: EnvelopeTime::from(data[1]),
-            time3: EnvelopeTime::from(data[2]),
-            time4: EnvelopeTime::from(data[3]),
-            loop_type: Loop::try_from(data[4]).unwrap(),
+            time1: EnvelopeTime::try_from(data[0]).map_err(invalid_field("time1", data, 0))?,
+            time2: EnvelopeTime::try_from(data[1]).map_err(invalid_field("time2", data, 1))?,
+            time3: EnvelopeTime::try_from(data[2]).map_err(invalid_field("time3", data, 2))?,
+            time4: EnvelopeTime::try_from(data[3]).map_err(invalid_field("time4", data, 3))?,
+            loop_type: Loop::try_from(data[4]).map_err(invalid_field("loop_type", data, 4))?,
         })
     }
 
@@ -195,6 +207,7 @@ impl SystemExclusiveData for MorfHarmonicEnvelope {
 
 /// MORF harmonic settings.
 #[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MorfHarmonic {
     pub copy1: MorfHarmonicCopyParameters,
     pub copy2: MorfHarmonicCopyParameters,
@@ -213,12 +226,13 @@ impl fmt::Display for MorfHarmonic {
 
 impl SystemExclusiveData for MorfHarmonic {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(MorfHarmonic {
-            copy1: MorfHarmonicCopyParameters::from_bytes(&data[..2])?,
-            copy2: MorfHarmonicCopyParameters::from_bytes(&data[2..4])?,
-            copy3: MorfHarmonicCopyParameters::from_bytes(&data[4..6])?,
-            copy4: MorfHarmonicCopyParameters::from_bytes(&data[6..8])?,
-            envelope: MorfHarmonicEnvelope::from_bytes(&data[8..])?,
+            copy1: MorfHarmonicCopyParameters::from_bytes(&data[..2]).map_err(|e| e.in_field("copy1"))?,
+            copy2: MorfHarmonicCopyParameters::from_bytes(&data[2..4]).map_err(|e| e.within("copy2", 2))?,
+            copy3: MorfHarmonicCopyParameters::from_bytes(&data[4..6]).map_err(|e| e.within("copy3", 4))?,
+            copy4: MorfHarmonicCopyParameters::from_bytes(&data[6..8]).map_err(|e| e.within("copy4", 6))?,
+            envelope: MorfHarmonicEnvelope::from_bytes(&data[8..]).map_err(|e| e.within("envelope", 8))?,
         })
     }
 
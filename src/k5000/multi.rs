@@ -2,27 +2,36 @@
 //!
 
 use std::convert::TryFrom;
-use std::convert::TryInto;
 use std::fmt;
-use std::collections::BTreeMap;
+
 use bit::BitIndex;
-use crate::{SystemExclusiveData, ParseError, Checksum};
-use crate::k5000::control::{
-    Polyphony, AmplitudeModulation, MacroController, SwitchControl,
-    ControlDestination, Switch,
+
+use crate::{
+    SystemExclusiveData,
+    ParseError,
+    Checksum,
+    MIDIChannel,
+    check_length,
+    invalid_field
 };
+use crate::k5000::control::VelocitySwitchSettings;
 use crate::k5000::effect::{EffectSettings, EffectControl};
-use crate::k5000::addkit::AdditiveKit;
-use crate::k5000::source::Source;
+use crate::k5000::source::{Key, Zone};
+use crate::k5000::sysex::{Message, Function, PatchKind, MultiNumber};
+use crate::k5000::{Volume, Coarse, Fine};
 
 pub const SECTION_COUNT: usize = 4; // number of sections in a multi patch
+pub const MULTI_COUNT: usize = 64; // number of multi (or combi) patches in memory
 
 /// Multi patch common settings.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Common {
     pub effects: EffectSettings,
     pub geq: [i8; 7],
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "crate::serialize::fixed_name::<_, 8>"))]
     pub name: String,
-    pub volume: UnsignedLevel,
+    pub volume: Volume,
     pub section_mutes: [bool; SECTION_COUNT],
     pub effect_control: EffectControl,
 }
@@ -33,7 +42,8 @@ impl Default for Common {
             effects: Default::default(),
             geq: [0; 7],
             name: "NewMulti".to_string(),
-            section_mutes: 0x00,  // all sections muted by default
+            volume: Volume::new(127),
+            section_mutes: [false; SECTION_COUNT],
             effect_control: Default::default(),
         }
     }
@@ -41,55 +51,46 @@ impl Default for Common {
 
 impl fmt::Display for Common {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.name)
+        write!(f, "{}\nVolume: {}\n\nEffect settings:\n{}\n",
+            self.name, self.volume, self.effects)
     }
 }
 
 impl SystemExclusiveData for Common {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
-        eprintln!("Multi/combi common data ({} bytes): {:?}", data.len(), data);
+        check_length(data, Self::data_size())?;
 
         let mut offset = 0;
-        let mut size = 31;
-        let mut start = offset;
-        let mut end = offset + size;
-
-        let effects_data = &data[start..end];
-        let effects = EffectSettings::from_bytes(effects_data);
+        let mut size = EffectSettings::data_size();
+        let effects = EffectSettings::from_bytes(&data[offset..offset + size])
+            .map_err(|e| e.in_field("effects"))?;
         offset += size;
 
         size = 7;
-        end = start + size;
-        let geq_data = data[start..end];
-        let geq_values = geq_data.iter().map(|n| *n as i8 - 64).collect();  // 58(-6) ~ 70(+6), so 64 is zero
+        let mut geq = [0i8; 7];
+        for (i, value) in geq.iter_mut().enumerate() {
+            *value = (data[offset + i] & 0x7f) as i8 - 64;  // 58(-6) ~ 70(+6), so 64 is zero
+        }
         offset += size;
 
         size = 8;
-        start = offset;
-        end = offset + size;
-        let name_data = data[start..end].to_vec();
-        let name = String::from_utf8(name_data).unwrap();
-        eprintln!("Name = {}", name);
+        let start = offset;
+        let name = String::from_utf8(data[start..start + size].to_vec())
+            .map_err(invalid_field("name", data, start))?;
         offset += size;
 
+        let volume = Volume::try_from(data[offset]).map_err(invalid_field("volume", data, offset))?;
+        offset += 1;
+
         let mutes_byte = data[offset];
-        let mut section_mutes: [bool; SECTION_COUNT] = [false; SECTION_COUNT];
-        for i in 0..SECTION_COUNT {
-            section_mutes[i] = mutes_byte.bit(i);
+        let mut section_mutes = [false; SECTION_COUNT];
+        for (i, mute) in section_mutes.iter_mut().enumerate() {
+            *mute = mutes_byte.bit(i);
         }
         offset += 1;
 
-        let volume = UnsignedLevel::from(data[offset]);
-        eprintln!("Volume = {}", volume);
-        offset += 1;
-
-        size = 6;
-        start = offset;
-        end = start + size;
-        let effect_control_data = data[start..end];
-        let effect_control = EffectControl::from_bytes(effect_control_data);
-        eprintln!("Effect control = {:?}", effect_control);
-        offset += size;
+        let effect_control = EffectControl::from_bytes(&data[offset..offset + 6])
+            .map_err(|e| e.within("effect_control", offset))?;
 
         Ok(Common {
             effects,
@@ -105,11 +106,11 @@ impl SystemExclusiveData for Common {
         let mut result: Vec<u8> = Vec::new();
 
         result.extend(self.effects.to_bytes());
-        result.extend(self.geq.to_vec().iter().map(|n| (n + 64) as u8));
+        result.extend(self.geq.iter().map(|n| (n + 64) as u8));
         result.extend(self.name.clone().into_bytes());  // note the use of clone() here
-        result.push(self.volume as u8);
+        result.push(self.volume.into());
 
-        let mut mute_byte = 0x00;
+        let mut mute_byte = 0x00u8;
         for i in 0..SECTION_COUNT {
             if self.section_mutes[i] {
                 mute_byte.set_bit(i, true);
@@ -121,24 +122,64 @@ impl SystemExclusiveData for Common {
 
         result
     }
+
+    fn data_size() -> usize { 54 }
 }
 
 /// Multi section.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Section {
-    pub single: u32,  // inst no.
-    pub volume: u32,
-    pub pan: u32,
-    pub effect_path: u32,
-    pub transpose: i32,
-    pub tune: i32,
+    pub single: u16,  // inst no.
+    pub volume: Volume,
+    pub pan: u8,
+    pub effect_path: u8,
+    pub transpose: Coarse,  // -24~+24
+    pub tune: Fine,  // -63~+63
     pub zone: Zone,
     pub vel_switch: VelocitySwitchSettings,
-    pub receive_channel: u32,
+    pub receive_channel: Option<MIDIChannel>,  // `None` in a K5000W combi
+}
+
+impl Section {
+    /// Parses a section of a K5000W combi, which has no receive channel.
+    pub fn combi_from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        Section::parse(data, false)
+    }
+
+    fn parse(data: &[u8], has_channel: bool) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
+
+        let receive_channel = if has_channel {
+            // Stored as 0...15, scaled to 1...16
+            Some(MIDIChannel::from_bytes(&data[11..12]).map_err(|e| e.within("receive_channel", 11))?)
+        } else {
+            None
+        };
+
+        Ok(Section {
+            single: ((data[0] as u16 & 0x03) << 7) | (data[1] as u16 & 0x7f),
+            volume: Volume::try_from(data[2]).map_err(invalid_field("volume", data, 2))?,
+            pan: data[3],
+            effect_path: data[4],
+            transpose: Coarse::try_from(data[5]).map_err(invalid_field("transpose", data, 5))?,  // 40~88 to -24~+24
+            tune: Fine::try_from(data[6]).map_err(invalid_field("tune", data, 6))?,  // 1~127 to -63~+63
+            zone: Zone { low: Key { note: data[7] }, high: Key { note: data[8] } },
+            vel_switch: VelocitySwitchSettings::from_bytes(&data[9..10]).map_err(|e| e.within("vel_switch", 9))?,
+            // byte 10 is not used
+            receive_channel,
+        })
+    }
 }
 
 impl fmt::Display for Section {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.single)
+        write!(f, "Single={} Volume={} Pan={} Transpose={} Tune={} Zone={} Vel. switch: {}",
+            self.single, self.volume, self.pan, self.transpose, self.tune, self.zone, self.vel_switch)?;
+        if let Some(channel) = self.receive_channel {
+            write!(f, " Channel={}", channel.value())?;
+        }
+        Ok(())
     }
 }
 
@@ -146,165 +187,405 @@ impl Default for Section {
     fn default() -> Self {
         Section {
             single: 0,
-            volume: 127,
-            pan: 0,
+            volume: Volume::new(127),
+            pan: 64,
             effect_path: 0,
-            transpose: 0,
-            tune: 0,
-            zone: Default::default(),
+            transpose: Default::default(),
+            tune: Default::default(),
+            zone: Zone { low: Key { note: 0 }, high: Key { note: 127 } },
             vel_switch: Default::default(),
-            receive_channel: 0,
+            receive_channel: Some(MIDIChannel::try_new(1).unwrap()),
         }
     }
 }
 
 impl SystemExclusiveData for Section {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
-        eprintln!("Multi section data, {} bytes", data.len());
+        Section::parse(data, true)
+    }
 
-        let mut offset = 0;
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut result: Vec<u8> = vec![
+            ((self.single >> 7) & 0x03) as u8,
+            (self.single & 0x7f) as u8,
+            self.volume.into(),
+            self.pan,
+            self.effect_path,
+            self.transpose.into(),
+            self.tune.into(),
+        ];
 
-        let bit_str = format!("{:02b}{:07b}", data[offset], data[offset + 1]);
-        let single = u32::from_str_radix(&bit_str, 2).unwrap();
-        offset += 2;
+        result.extend(self.zone.to_bytes());
+        result.extend(self.vel_switch.to_bytes());
+        result.push(0);  // not used
 
-        let volume = data[offset] as u32;
-        eprintln!("Volume = {}", volume);
-        offset += 1;
+        match self.receive_channel {
+            Some(channel) => result.extend(channel.to_bytes()),
+            None => result.push(0),
+        }
 
-        let pan = data[offset] as u32;
-        eprintln!("Pan = {}", pan);
-        offset += 1;
+        result
+    }
 
-        let effect_path = data[offset] as u32;
-        eprintln!("Effect path = {}", effect_path);
-        offset += 1;
+    fn data_size() -> usize { 12 }
+}
 
-        let transpose = data[offset] as i32 - 64;  // stored as 40...88, scale to -24...+24
-        eprintln!("Transpose = {}", transpose);
-        offset += 1;
+/// Multi patch with common settings and sections.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MultiPatch {
+    pub common: Common,
+    pub sections: [Section; SECTION_COUNT],
+}
 
-        let tune = data[offset] as i32 - 64; // stored as 1...127, scale to -63...+63
-        eprintln!("Tune = {}", tune);
-        offset += 1;
+impl MultiPatch {
+    /// Parses a K5000W combi patch. The data is laid out
+    /// like a multi patch, but the sections have no receive channel.
+    pub fn combi_from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        MultiPatch::parse(data, false)
+    }
 
-        let zone = Zone { 
-            low: Key { note: data[offset] }, 
-            high: Key { note: data[offset + 1] } 
-        };
-        offset += 2;
+    /// Returns true if this is a K5000W combi patch.
+    pub fn is_combi(&self) -> bool {
+        self.sections.iter().all(|s| s.receive_channel.is_none())
+    }
 
-        let vel_switch = VelocitySwitchSettings::from_bytes(vec![data[offset]]);
-        offset += 2;
+    /// Makes a multi patch from a one multi dump message.
+    /// Returns the multi number with the patch.
+    /// Error offsets are relative to the start of the message.
+    pub fn from_message(message: &Message) -> Result<(MultiNumber, MultiPatch), ParseError> {
+        let start = 6;  // F0, manufacturer, channel, function, group, machine
+        if message.function != Function::OneBlockDump {
+            return Err(ParseError::InvalidData(3, "expected a one block dump".to_string()));
+        }
+        if message.function_data.first() != Some(&(PatchKind::Multi as u8)) {
+            return Err(ParseError::InvalidData(start as u32, "expected a multi patch".to_string()));
+        }
 
-        // Stored as 0...15, scale to 1...16, but on the K50000W it is zero.
-        // FIXME: Do we need to deal with this?
-        let receive_channel = data[offset] + 1;
+        let offset = start + message.function_data.len();
+        check_length(&message.subdata, 1).map_err(|e| e.offset_by(offset))?;
+        let number = MultiNumber::try_new(message.subdata[0])
+            .map_err(invalid_field("number", &message.subdata, 0))
+            .map_err(|e| e.offset_by(offset))?;
+        let patch = MultiPatch::from_bytes(&message.patch_data)
+            .map_err(|e| e.offset_by(offset + message.subdata.len()))?;
+        Ok((number, patch))
+    }
 
-        Ok(Section {
-            single,
-            volume,
-            pan,
-            effect_path,
-            transpose,
-            tune,
-            zone,
-            vel_switch,
-            receive_channel,
-        })
+    /// Makes a one multi dump message of this patch,
+    /// to be stored as multi `number`.
+    pub fn to_message(&self, channel: MIDIChannel, number: MultiNumber) -> Message {
+        Message {
+            channel,
+            function: Function::OneBlockDump,
+            function_data: vec![PatchKind::Multi.into()],
+            subdata: vec![number.into()],
+            patch_data: self.to_bytes(),
+        }
     }
 
-    fn to_bytes(&self) -> Vec<u8> {
-        let mut result: Vec<u8> = Vec::new();
+    fn parse(data: &[u8], has_channel: bool) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
 
-        let bit_str = format!("{:09b}", self.single);
-        let msb = u8::from_str_radix(&bit_str[..2], 2).unwrap();
-        let lsb = u8::from_str_radix(&bit_str[2..9], 2).unwrap();
-        result.extend(vec![msb, lsb]);
+        let _checksum = data[0];
+        let mut offset = 1;
 
-        result.push(self.volume as u8);
-        result.push(self.pan as u8);
-        result.push(self.effect_path as u8);
-        result.push((self.transpose + 64) as u8);
-        result.push((self.tune + 64) as u8);
+        let common = Common::from_bytes(&data[offset..offset + Common::data_size()])
+            .map_err(|e| e.within("common", offset))?;
+        offset += Common::data_size();
 
-        result.extend(self.zone.to_bytes());
-        result.extend(self.vel_switch.to_bytes());
+        let mut sections: [Section; SECTION_COUNT] = Default::default();
+        for (i, section) in sections.iter_mut().enumerate() {
+            let start = offset;
+            *section = Section::parse(&data[start..start + Section::data_size()], has_channel)
+                .map_err(|e| e.within(format!("sections[{}]", i), start))?;
+            offset += Section::data_size();
+        }
 
-        result.push(self.receive_channel as u8);
+        Ok(MultiPatch { common, sections })
+    }
 
+    fn collect_data(&self) -> Vec<u8> {
+        let mut result = self.common.to_bytes();
+        for section in self.sections.iter() {
+            result.extend(section.to_bytes());
+        }
         result
     }
 }
 
-/// Multi patch with common settings and sections.
-pub struct MultiPatch {
-    pub checksum: u8,
-    pub common: Common,
-    pub sections: [Section; SECTION_COUNT],
+impl Checksum for MultiPatch {
+    fn checksum(&self) -> u8 {
+        // check sum = {(common sum) + (section1 sum) + ... + (section4 sum) + 0xa5} & 0x7f
+        let data = self.collect_data();
+        let mut total = data.iter().fold(0, |acc, x| acc + ((*x as u32) & 0xFF));
+        total += 0xA5;
+        (total & 0x7F) as u8
+    }
 }
 
-impl Default for MultiPatch {
-    fn default() -> Self {
-        MultiPatch {
-            checksum: 0x00,
-            common: Default::default(),
-            sections: [Default::default(), Default::default(), Default::default(), Default::default()]
+impl fmt::Display for MultiPatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.common)?;
+        for (i, section) in self.sections.iter().enumerate() {
+            writeln!(f, "Section {}: {}", i + 1, section)?;
         }
+        Ok(())
     }
 }
 
 impl SystemExclusiveData for MultiPatch {
-    fn from_bytes(data: &[u8]) -> Self {
-        eprintln!("Multi");
-
-        MultiPatch {
-            checksum: data[0],
-            common: Common::from_bytes(data[1..55]),
-            sections: [
-                Section::from_bytes(data[55..67]),
-                Section::from_bytes(data[67..79]),
-                Section::from_bytes(data[79..91]),
-                Section::from_bytes(data[91..103]),
-            ]
-        }
+    fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        MultiPatch::parse(data, true)
     }
 
     fn to_bytes(&self) -> Vec<u8> {
         let mut result: Vec<u8> = Vec::new();
 
-        result.push(0x00);  // FIXME: emit actual checksum
+        result.push(self.checksum());
+        result.extend(self.collect_data());
 
-        result.extend(self.common.to_bytes());
+        result
+    }
 
-        for section in self.sections {
-            result.extend(section.to_bytes());
+    fn data_size() -> usize {
+        1 + Common::data_size() + SECTION_COUNT * Section::data_size()
+    }
+}
+
+/// Bank of multi (or combi) patches, as found in a block multi dump.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MultiBank {
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "crate::serialize::fixed_count::<_, _, MULTI_COUNT>"))]
+    pub patches: Vec<MultiPatch>,
+}
+
+impl MultiBank {
+    /// Makes a new bank of default multi patches.
+    pub fn new() -> Self {
+        MultiBank {
+            patches: (0..MULTI_COUNT).map(|_| Default::default()).collect(),
+        }
+    }
+
+    /// Parses a bank of K5000W combi patches.
+    pub fn combi_from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        MultiBank::parse(data, false)
+    }
+
+    /// Returns true if this is a bank of K5000W combi patches.
+    pub fn is_combi(&self) -> bool {
+        !self.patches.is_empty() && self.patches.iter().all(|p| p.is_combi())
+    }
+
+    /// Makes a bank from a block multi dump message. The dump does not
+    /// tell a K5000W combi from a multi, so the patches are parsed as combis
+    /// if they do not parse as multis, and `is_combi` tells which they are.
+    /// Error offsets are relative to the start of the message.
+    pub fn from_message(message: &Message) -> Result<Self, ParseError> {
+        let start = 6;  // F0, manufacturer, channel, function, group, machine
+        if message.function != Function::AllBlockDump {
+            return Err(ParseError::InvalidData(3, "expected a block dump".to_string()));
+        }
+        if message.function_data.first() != Some(&(PatchKind::Multi as u8)) {
+            return Err(ParseError::InvalidData(start as u32, "expected multi patches".to_string()));
+        }
+
+        MultiBank::from_bytes(&message.patch_data)
+            .or_else(|e| MultiBank::combi_from_bytes(&message.patch_data).map_err(|_| e))
+            .map_err(|e| e.offset_by(start + message.function_data.len()))
+    }
+
+    /// Makes a block multi dump message of this bank.
+    pub fn to_message(&self, channel: MIDIChannel) -> Message {
+        Message {
+            channel,
+            function: Function::AllBlockDump,
+            function_data: vec![PatchKind::Multi.into()],
+            subdata: vec![],
+            patch_data: self.to_bytes(),
+        }
+    }
+
+    fn parse(data: &[u8], has_channel: bool) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
+
+        let mut patches = Vec::<MultiPatch>::new();
+        for (i, chunk) in data.chunks(MultiPatch::data_size()).take(MULTI_COUNT).enumerate() {
+            let patch = MultiPatch::parse(chunk, has_channel)
+                .map_err(|e| e.within(format!("patches[{}]", i), i * MultiPatch::data_size()))?;
+            patches.push(patch);
+        }
+
+        Ok(MultiBank { patches })
+    }
+}
+
+impl Default for MultiBank {
+    fn default() -> Self {
+        MultiBank::new()
+    }
+}
+
+impl fmt::Display for MultiBank {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, patch) in self.patches.iter().enumerate() {
+            writeln!(f, "{:02} {}", i + 1, patch.common.name)?;
         }
+        Ok(())
+    }
+}
 
+impl SystemExclusiveData for MultiBank {
+    fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        MultiBank::parse(data, true)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::<u8>::new();
+        for patch in self.patches.iter() {
+            result.extend(patch.to_bytes());
+        }
         result
     }
+
+    fn data_size() -> usize {
+        MULTI_COUNT * MultiPatch::data_size()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{*};
+    use crate::k5000::sysex::{Header, Cardinality, PatchKind};
+
+    fn make_multi() -> MultiPatch {
+        let mut multi = MultiPatch::default();
+        multi.common.name = "TestMult".to_string();
+        multi.common.section_mutes = [false, false, true, true];
+        multi.sections[1].single = 300;
+        multi.sections[1].transpose = Coarse::new(-12);
+        multi.sections[1].receive_channel = Some(MIDIChannel::try_new(2).unwrap());
+        multi
+    }
 
     #[test]
     fn test_common_from_bytes() {
-        let data = vec![
-
-        ];
+        let common = Common::default();
+        let data = common.to_bytes();
+        assert_eq!(data.len(), Common::data_size());
+        assert_eq!(Common::from_bytes(&data).unwrap().name, "NewMulti");
     }
 
     #[test]
     fn test_section_from_bytes() {
+        let data = [0x02, 0x2c, 0x64, 0x40, 0x01, 0x34, 0x40, 0x24, 0x60, 0x00, 0x00, 0x03];
+        let section = Section::from_bytes(&data).unwrap();
+        assert_eq!(section.single, 300);
+        assert_eq!(section.volume.value(), 100);
+        assert_eq!(section.transpose.value(), -12);
+        assert_eq!(section.zone.low.note, 0x24);
+        assert_eq!(section.receive_channel, Some(MIDIChannel::try_new(4).unwrap()));
+        assert_eq!(section.to_bytes(), data.to_vec());
+    }
+
+    #[test]
+    fn test_combi_section_has_no_channel() {
+        let data = [0x00, 0x05, 0x7f, 0x40, 0x00, 0x40, 0x40, 0x00, 0x7f, 0x00, 0x00, 0x00];
+        let section = Section::combi_from_bytes(&data).unwrap();
+        assert_eq!(section.receive_channel, None);
+        assert_eq!(section.to_bytes(), data.to_vec());
+    }
+
+    #[test]
+    fn test_multi_patch_round_trip() {
+        let multi = make_multi();
+        let data = multi.to_bytes();
+        assert_eq!(data.len(), MultiPatch::data_size());
+        assert_eq!(data[0], multi.checksum());
+
+        let parsed = MultiPatch::from_bytes(&data).unwrap();
+        assert_eq!(parsed.common.name, "TestMult");
+        assert_eq!(parsed.common.section_mutes, [false, false, true, true]);
+        assert_eq!(parsed.sections[1].single, 300);
+        assert!(!parsed.is_combi());
+        assert_eq!(parsed.to_bytes(), data);
+    }
+
+    #[test]
+    fn test_combi_patch() {
+        let mut combi = make_multi();
+        for section in combi.sections.iter_mut() {
+            section.receive_channel = None;
+        }
+        let data = combi.to_bytes();
+        let parsed = MultiPatch::combi_from_bytes(&data).unwrap();
+        assert!(parsed.is_combi());
+        assert_eq!(parsed.to_bytes(), data);
+    }
 
+    #[test]
+    fn test_one_multi_dump() {
+        let mut data = vec![0x00, 0x20, 0x00, 0x0a, 0x20, 0x05];  // One Multi, number 6
+        data.extend(make_multi().to_bytes());
+
+        let header = Header::identify_vec(&data).unwrap();
+        assert_eq!(header.cardinality, Cardinality::One);
+        assert_eq!(header.kind, PatchKind::Multi);
+        let multi = MultiPatch::from_bytes(&data[header.size()..]).unwrap();
+        assert_eq!(multi.common.name, "TestMult");
+    }
+
+    #[test]
+    fn test_one_multi_message_round_trip() {
+        let channel = MIDIChannel::try_new(1).unwrap();
+        let message = make_multi().to_message(channel, MultiNumber::try_new(5).unwrap());
+        let mut data = message.to_bytes();
+        assert_eq!(data[..8], [0xF0, 0x40, 0x00, 0x20, 0x00, 0x0A, 0x20, 0x05]);
+
+        let (number, multi) = MultiPatch::from_message(&Message::from_bytes(&data).unwrap()).unwrap();
+        assert_eq!(number.value(), 5);
+        assert_eq!(multi.common.name, "TestMult");
+        assert_eq!(multi.to_message(channel, number).to_bytes(), data);
+
+        data[7] = MULTI_COUNT as u8;
+        match MultiPatch::from_message(&Message::from_bytes(&data).unwrap()) {
+            Err(ParseError::InvalidField(error)) => {
+                assert_eq!(error.path, "number");
+                assert_eq!(error.offset, 7);
+            },
+            _ => panic!("expected an invalid field error"),
+        }
+    }
+
+    #[test]
+    fn test_block_multi_dump() {
+        let mut bank = MultiBank::new();
+        bank.patches[1] = make_multi();
+        let channel = MIDIChannel::try_new(1).unwrap();
+        let data = bank.to_message(channel).to_bytes();
+
+        let header = Header::identify_vec(&data[2..]).unwrap();
+        assert_eq!(header.cardinality, Cardinality::Block);
+        assert_eq!(header.kind, PatchKind::Multi);
+
+        let bank = MultiBank::from_message(&Message::from_bytes(&data).unwrap()).unwrap();
+        assert!(!bank.is_combi());
+        assert_eq!(bank.patches.len(), MULTI_COUNT);
+        assert_eq!(bank.patches[1].sections[1].single, 300);
+        assert_eq!(bank.to_message(channel).to_bytes(), data);
     }
 
     #[test]
-    fn test_multi_patch_from_bytes() {
-        let data: [u8; 1070] = include!("WizooIni.in");
-        let multi_patch = MultiPatch::from_bytes(data[9..].to_vec());  // skip sysex header but not checksum
-        assert_eq!(multi_patch.common.name, "WizooIni");
+    fn test_block_combi_dump() {
+        let mut data = MultiBank::new().to_bytes();
+        let channel_offset = 1 + Common::data_size() + Section::data_size() - 1;
+        data[channel_offset] = 0x7f;  // not a receive channel
+
+        let message = MultiBank::new().to_message(MIDIChannel::try_new(1).unwrap());
+        let message = Message { patch_data: data, ..message };
+        let bank = MultiBank::from_message(&message).unwrap();
+        assert!(bank.is_combi());
+        assert_eq!(bank.patches.len(), MULTI_COUNT);
     }
 }
@@ -5,11 +5,12 @@ use std::convert::TryFrom;
 use std::fmt;
 
 use num_enum::TryFromPrimitive;
-use pretty_hex::*;
 
 use crate::{
     SystemExclusiveData,
-    ParseError
+    ParseError,
+    check_length,
+    invalid_field
 };
 use crate::k5000::pitch::Envelope as PitchEnvelope;
 use crate::k5000::{
@@ -21,6 +22,7 @@ use crate::k5000::source::Key;
 
 /// Fixed key for oscillator.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FixedKey {
     Off,
     On(Key)
@@ -38,11 +40,11 @@ impl fmt::Display for FixedKey {
 
 impl SystemExclusiveData for FixedKey {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
-        if data[0] == 0x00 {
-            Ok(FixedKey::Off)
-        }
-        else {
-            Ok(FixedKey::On(Key { note: data[0] - 21 }))
+        check_length(data, Self::data_size())?;
+        match data[0] {
+            0x00 => Ok(FixedKey::Off),
+            21..=108 => Ok(FixedKey::On(Key { note: data[0] - 21 })),
+            value => Err(invalid_field("", data, 0)(format!("expected 0 or 21...108, got {}", value))),
         }
     }
 
@@ -58,6 +60,7 @@ impl SystemExclusiveData for FixedKey {
 
 /// PCM oscillator.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Oscillator {
     pub wave: Wave,
     pub coarse: Coarse,
@@ -108,14 +111,14 @@ impl fmt::Display for Oscillator {
 
 impl SystemExclusiveData for Oscillator {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
-        eprintln!("OSC data = {}", simple_hex(&data));
+        check_length(data, Self::data_size())?;
         Ok(Oscillator {
-            wave: Wave::from_bytes(&[data[0], data[1]])?,
-            coarse: Coarse::from(data[2]),
-            fine: Fine::from(data[3]),
-            fixed_key: FixedKey::from_bytes(&[data[4]])?,
-            ks_to_pitch: KeyScaling::try_from(data[5]).unwrap(),
-            pitch_envelope: PitchEnvelope::from_bytes(&data[6..])?,
+            wave: Wave::from_bytes(&data[0..2]).map_err(|e| e.in_field("wave"))?,
+            coarse: Coarse::try_from(data[2]).map_err(invalid_field("coarse", data, 2))?,
+            fine: Fine::try_from(data[3]).map_err(invalid_field("fine", data, 3))?,
+            fixed_key: FixedKey::from_bytes(&data[4..5]).map_err(|e| e.within("fixed_key", 4))?,
+            ks_to_pitch: KeyScaling::try_from(data[5]).map_err(invalid_field("ks_to_pitch", data, 5))?,
+            pitch_envelope: PitchEnvelope::from_bytes(&data[6..]).map_err(|e| e.within("pitch_envelope", 6))?,
         })
     }
 
@@ -149,6 +152,8 @@ pub enum KeyScaling {
     FiftyCent = 3,
 }
 
+serialize_by_name!(KeyScaling);
+
 impl fmt::Display for KeyScaling {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", match self {
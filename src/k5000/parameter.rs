@@ -0,0 +1,649 @@
+//! Parameter send and track control messages for real-time editing.
+//!
+//! A parameter send message changes one parameter of the single patch
+//! that is currently being edited on the K5000:
+//!
+//! F0H 40H 0nH 10H 00H 0AH gg ss ph pl vh vl F7H
+//!
+//! where `n` is the MIDI channel, `g` is the parameter group, `s` is the
+//! source (0~5) for the source, harmonic and formant groups and zero
+//! for the common group, `ph pl` is the parameter number and `vh vl`
+//! is the value, both as two seven-bit bytes, most significant first.
+//! The harmonic and formant groups are in the additive kit of an ADD source.
+//!
+//! A track control message changes one parameter of a section
+//! of the multi patch that is currently being edited:
+//!
+//! F0H 40H 0nH 11H 00H 0AH tt pp vh vl F7H
+//!
+//! where `t` is the section (0~3) and `p` is the parameter number.
+//!
+//! The parameters of each group are numbered in the order they are listed
+//! in `k5000::registry`, and the values are encoded like in the patch data,
+//! so for example a coarse tuning of -24~+24 is sent as 40~88.
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use num_enum::TryFromPrimitive;
+
+use crate::{
+    SystemExclusiveData,
+    ParseError,
+    FieldError,
+    ValueError,
+    MIDIChannel,
+    invalid_field,
+};
+use crate::registry::ParameterInfo;
+use crate::k5000::registry;
+use crate::k5000::addkit::AdditiveKit;
+use crate::k5000::multi::{MultiPatch, Section, SECTION_COUNT};
+use crate::k5000::single::{Common, SinglePatch};
+use crate::k5000::source::Source;
+use crate::k5000::sysex::{Message, Function};
+
+/// Maximum number of sources in a single patch.
+const MAX_SOURCE_COUNT: usize = 6;
+
+/// Offset of the function data in a message.
+const START: usize = 6;
+
+/// Number of bytes of function data in a parameter send message.
+const PARAMETER_SEND_SIZE: usize = 6;
+
+/// Number of bytes of function data in a track control message.
+const TRACK_CONTROL_SIZE: usize = 4;
+
+/// Group of single patch parameters.
+#[derive(Debug, Eq, PartialEq, Copy, Clone, TryFromPrimitive)]
+#[repr(u8)]
+pub enum Group {
+    Common = 0x00,
+    Source = 0x01,
+    Harmonic = 0x02,  // harmonic common, MORF, levels and envelopes
+    Formant = 0x03,  // formant filter settings and bands
+}
+
+impl Group {
+    /// Returns true if the parameters of this group are in the additive kit of an ADD source.
+    pub fn is_additive(&self) -> bool {
+        matches!(self, Group::Harmonic | Group::Formant)
+    }
+
+    /// Lists the parameters of this group by parameter number.
+    /// The offsets are relative to the data of the common settings,
+    /// of a source, or of an additive kit.
+    pub fn parameters(&self) -> Vec<ParameterInfo> {
+        match self {
+            Group::Common => registry::common_parameters(),
+            Group::Source => registry::source_definition(),
+            Group::Harmonic | Group::Formant => registry::harmonic_definition().into_iter()
+                .filter(|info| is_formant(info) == (*self == Group::Formant))
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for Group {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match self {
+            Group::Common => "common",
+            Group::Source => "source",
+            Group::Harmonic => "harmonic",
+            Group::Formant => "formant",
+        })
+    }
+}
+
+/// Tells if `info` is a formant filter setting or band of an additive kit.
+fn is_formant(info: &ParameterInfo) -> bool {
+    info.path.starts_with("formant_filter.") || info.path.starts_with("bands[")
+}
+
+/// Returns the number stored for the parameter in `data`.
+fn stored_number(info: &ParameterInfo, data: &[u8]) -> u16 {
+    info.encoding.read(data) as u16
+}
+
+/// Writes the stored number `number` of the parameter to `data`.
+fn write_number(info: &ParameterInfo, data: &mut [u8], number: u16) -> Result<(), ValueError> {
+    let number = number as i32;
+    let number = if info.encoding.inverted { 1 - number } else { number };
+    info.write(data, number - info.encoding.bias)
+}
+
+/// Splits `value` into two seven-bit bytes, most significant first.
+fn split(value: usize) -> [u8; 2] {
+    [((value >> 7) & 0x7f) as u8, (value & 0x7f) as u8]
+}
+
+/// Joins two seven-bit bytes, most significant first.
+fn join(high: u8, low: u8) -> usize {
+    (((high & 0x7f) as usize) << 7) | (low & 0x7f) as usize
+}
+
+/// Returns the key of the additive kit of the source at `index`,
+/// if it is an ADD source. The kits are numbered in the order of
+/// the ADD sources, like when the patch is parsed.
+fn kit_key(patch: &SinglePatch, index: usize) -> Option<String> {
+    let source = patch.sources.get(index)?;
+    if !source.is_additive() {
+        return None;
+    }
+    let number = patch.sources[..index].iter().filter(|s| s.is_additive()).count() + 1;
+    let key = format!("s{}", number);
+    if patch.additive_kits.contains_key(&key) { Some(key) } else { None }
+}
+
+/// Parameter send message for the single patch being edited.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct ParameterSend {
+    pub channel: MIDIChannel,
+    pub group: Group,
+    pub source: usize,  // 0~5, zero for the common group
+    pub number: usize,
+    pub value: u16,
+}
+
+impl ParameterSend {
+    /// Makes a parameter send with the current value of parameter `number`
+    /// of `group` in the single patch. Fails if there is no such parameter,
+    /// or no such source, or if the source has no additive kit
+    /// for a harmonic or formant parameter.
+    pub fn from_single(channel: MIDIChannel, patch: &SinglePatch, group: Group, source: usize, number: usize) -> Result<Self, ParseError> {
+        let mut send = ParameterSend { channel, group, source, number, value: 0 };
+        let info = send.check()?;
+        let (data, _) = send.group_data(patch, &info)?;
+        send.value = stored_number(&info, &data);
+        Ok(send)
+    }
+
+    /// Applies this change to the single patch, by writing the value
+    /// to the patch data and parsing the changed group again.
+    /// A change cannot add or remove sources, or switch a source
+    /// between PCM and ADD, since that needs a full dump.
+    /// Error offsets are relative to the start of the message.
+    pub fn apply_to_single(&self, patch: &mut SinglePatch) -> Result<(), ParseError> {
+        let info = self.check()?;
+        let (mut data, path) = self.group_data(patch, &info)?;
+        write_number(&info, &mut data, self.value)
+            .map_err(|e| self.field_error(&path, e.to_string()))?;
+
+        match self.group {
+            Group::Common => {
+                let common = Common::from_bytes(&data)
+                    .map_err(|e| self.field_error(&path, e.to_string()))?;
+                if common.source_count != patch.common.source_count {
+                    return Err(self.field_error(&path, "cannot change the number of sources".to_string()));
+                }
+                patch.common = common;
+            },
+            Group::Source => {
+                let source = Source::from_bytes(&data)
+                    .map_err(|e| self.field_error(&path, e.to_string()))?;
+                if source.is_additive() != patch.sources[self.source].is_additive() {
+                    return Err(self.field_error(&path, "cannot switch between PCM and ADD".to_string()));
+                }
+                patch.sources[self.source] = source;
+            },
+            Group::Harmonic | Group::Formant => {
+                let kit = AdditiveKit::from_bytes(&data)
+                    .map_err(|e| self.field_error(&path, e.to_string()))?;
+                if let Some(key) = kit_key(patch, self.source) {
+                    patch.additive_kits.insert(key, kit);
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Makes the parameter send message of this change.
+    pub fn to_message(&self) -> Message {
+        let mut function_data = vec![self.group as u8, self.source as u8];
+        function_data.extend(split(self.number));
+        function_data.extend(split(self.value as usize));
+        Message {
+            channel: self.channel,
+            function: Function::ParameterSend,
+            function_data,
+            subdata: vec![],
+            patch_data: vec![],
+        }
+    }
+
+    /// Parses a parameter send message.
+    /// Error offsets are relative to the start of the message.
+    pub fn from_message(message: &Message) -> Result<Self, ParseError> {
+        if message.function != Function::ParameterSend {
+            return Err(ParseError::InvalidData(3, "expected a parameter send".to_string()));
+        }
+        let data = &message.function_data;
+        if data.len() != PARAMETER_SEND_SIZE {
+            return Err(ParseError::InvalidLength(START + data.len() + 1, START + PARAMETER_SEND_SIZE + 1));
+        }
+
+        let group = Group::try_from(data[0])
+            .map_err(|_| invalid_field("group", data, 0)("unknown parameter group").offset_by(START))?;
+        let send = ParameterSend {
+            channel: message.channel,
+            group,
+            source: data[1] as usize,
+            number: join(data[2], data[3]),
+            value: join(data[4], data[5]) as u16,
+        };
+        send.check()?;
+        Ok(send)
+    }
+
+    /// Returns the parameter of this change, if the source
+    /// and the parameter number are in range.
+    fn check(&self) -> Result<ParameterInfo, ParseError> {
+        let source_count = if self.group.is_additive() || self.group == Group::Source { MAX_SOURCE_COUNT } else { 1 };
+        if self.source >= source_count {
+            return Err(ParseError::InvalidField(FieldError {
+                path: "source".to_string(),
+                offset: START + 1,
+                value: self.source as u8,
+                reason: ValueError(0, source_count as i32 - 1, self.source as i32).to_string(),
+            }));
+        }
+
+        let parameters = self.group.parameters();
+        let count = parameters.len();
+        parameters.into_iter().nth(self.number).ok_or_else(|| ParseError::InvalidField(FieldError {
+            path: "number".to_string(),
+            offset: START + 3,
+            value: (self.number & 0x7f) as u8,
+            reason: ValueError(0, count as i32 - 1, self.number as i32).to_string(),
+        }))
+    }
+
+    /// Returns the data of the group of this change in the patch,
+    /// with the path of the parameter.
+    fn group_data(&self, patch: &SinglePatch, info: &ParameterInfo) -> Result<(Vec<u8>, String), ParseError> {
+        match self.group {
+            Group::Common => Ok((patch.common.to_bytes(), info.path.clone())),
+            Group::Source => {
+                let path = format!("sources[{}].{}", self.source, info.path);
+                let source = patch.sources.get(self.source)
+                    .ok_or_else(|| self.field_error(&path, "no such source".to_string()))?;
+                Ok((source.to_bytes(), path))
+            },
+            Group::Harmonic | Group::Formant => {
+                let key = kit_key(patch, self.source).ok_or_else(|| {
+                    self.field_error(&format!("sources[{}]", self.source), "not an ADD source".to_string())
+                })?;
+                Ok((patch.additive_kits[&key].to_bytes(), format!("additive_kits[{}].{}", key, info.path)))
+            },
+        }
+    }
+
+    fn field_error(&self, path: &str, reason: String) -> ParseError {
+        ParseError::InvalidField(FieldError {
+            path: path.to_string(),
+            offset: START + PARAMETER_SEND_SIZE - 1,
+            value: (self.value & 0x7f) as u8,
+            reason,
+        })
+    }
+}
+
+impl fmt::Display for ParameterSend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = self.group.parameters().into_iter().nth(self.number)
+            .map(|info| info.name)
+            .unwrap_or_else(|| format!("{} parameter {}", self.group, self.number));
+        if self.group == Group::Common {
+            write!(f, "{} = {}, channel {}", name, self.value, self.channel.value())
+        } else {
+            write!(f, "S{} {} = {}, channel {}", self.source + 1, name, self.value, self.channel.value())
+        }
+    }
+}
+
+/// Appends a parameter send for every parameter that differs between `old` and `new`.
+fn diff_group(channel: MIDIChannel, group: Group, source: usize, parameters: &[ParameterInfo],
+    old: &[u8], new: &[u8], sends: &mut Vec<ParameterSend>) {
+    for (number, info) in parameters.iter().enumerate() {
+        let value = stored_number(info, new);
+        if stored_number(info, old) != value {
+            sends.push(ParameterSend { channel, group, source, number, value });
+        }
+    }
+}
+
+impl SinglePatch {
+    /// Returns the parameter send messages that turn this patch into `other`,
+    /// one for each parameter that differs. Returns `None` if `other` can
+    /// only be sent as a full dump: if the patches have different sources,
+    /// or differ in something that has no parameter, like the name.
+    pub fn changes(&self, other: &SinglePatch, channel: MIDIChannel) -> Option<Vec<Message>> {
+        if self.sources.len() != other.sources.len()
+            || self.sources.iter().zip(other.sources.iter()).any(|(a, b)| a.is_additive() != b.is_additive()) {
+            return None;
+        }
+
+        let mut sends = Vec::new();
+
+        diff_group(channel, Group::Common, 0, &Group::Common.parameters(),
+            &self.common.to_bytes(), &other.common.to_bytes(), &mut sends);
+
+        let parameters = Group::Source.parameters();
+        for (index, (a, b)) in self.sources.iter().zip(other.sources.iter()).enumerate() {
+            diff_group(channel, Group::Source, index, &parameters, &a.to_bytes(), &b.to_bytes(), &mut sends);
+        }
+
+        for group in [Group::Harmonic, Group::Formant] {
+            let parameters = group.parameters();
+            for index in 0..self.sources.len() {
+                if let (Some(key), Some(other_key)) = (kit_key(self, index), kit_key(other, index)) {
+                    diff_group(channel, group, index, &parameters,
+                        &self.additive_kits[&key].to_bytes(), &other.additive_kits[&other_key].to_bytes(), &mut sends);
+                }
+            }
+        }
+
+        // Some of the patch data has no parameter, so check that the changes make `other`.
+        let mut patch = SinglePatch::from_bytes(&self.to_bytes()).ok()?;
+        for send in sends.iter() {
+            send.apply_to_single(&mut patch).ok()?;
+        }
+        if patch.to_bytes() != other.to_bytes() {
+            return None;
+        }
+
+        Some(sends.iter().map(|send| send.to_message()).collect())
+    }
+}
+
+/// Track control message for a section of the multi patch being edited.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct TrackControl {
+    pub channel: MIDIChannel,
+    pub section: usize,  // 0~3
+    pub number: usize,
+    pub value: u16,
+}
+
+impl TrackControl {
+    /// Makes a track control with the current value of parameter `number`
+    /// of `section` in the multi patch. Fails if there is no such parameter.
+    pub fn from_multi(channel: MIDIChannel, patch: &MultiPatch, section: usize, number: usize) -> Result<Self, ParseError> {
+        let mut control = TrackControl { channel, section, number, value: 0 };
+        let info = control.check()?;
+        control.value = stored_number(&info, &patch.sections[section].to_bytes());
+        Ok(control)
+    }
+
+    /// Applies this change to the multi patch, by writing the value
+    /// to the section data and parsing the section again.
+    /// Error offsets are relative to the start of the message.
+    pub fn apply_to_multi(&self, patch: &mut MultiPatch) -> Result<(), ParseError> {
+        let info = self.check()?;
+        let is_combi = patch.is_combi();
+        if is_combi && info.path.ends_with(".receive_channel") {
+            return Err(self.field_error(&info.path, "not in a K5000W combi".to_string()));
+        }
+
+        let mut data = patch.sections[self.section].to_bytes();
+        write_number(&info, &mut data, self.value)
+            .map_err(|e| self.field_error(&info.path, e.to_string()))?;
+        let section = if is_combi {
+            Section::combi_from_bytes(&data)
+        } else {
+            Section::from_bytes(&data)
+        };
+        patch.sections[self.section] = section
+            .map_err(|e| self.field_error(&info.path, e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Makes the track control message of this change.
+    pub fn to_message(&self) -> Message {
+        let mut function_data = vec![self.section as u8, self.number as u8];
+        function_data.extend(split(self.value as usize));
+        Message {
+            channel: self.channel,
+            function: Function::TrackControl,
+            function_data,
+            subdata: vec![],
+            patch_data: vec![],
+        }
+    }
+
+    /// Parses a track control message.
+    /// Error offsets are relative to the start of the message.
+    pub fn from_message(message: &Message) -> Result<Self, ParseError> {
+        if message.function != Function::TrackControl {
+            return Err(ParseError::InvalidData(3, "expected a track control".to_string()));
+        }
+        let data = &message.function_data;
+        if data.len() != TRACK_CONTROL_SIZE {
+            return Err(ParseError::InvalidLength(START + data.len() + 1, START + TRACK_CONTROL_SIZE + 1));
+        }
+
+        let control = TrackControl {
+            channel: message.channel,
+            section: data[0] as usize,
+            number: data[1] as usize,
+            value: join(data[2], data[3]) as u16,
+        };
+        control.check()?;
+        Ok(control)
+    }
+
+    /// Returns the parameter of this change, if the section
+    /// and the parameter number are in range.
+    fn check(&self) -> Result<ParameterInfo, ParseError> {
+        if self.section >= SECTION_COUNT {
+            return Err(ParseError::InvalidField(FieldError {
+                path: "section".to_string(),
+                offset: START,
+                value: self.section as u8,
+                reason: ValueError(0, SECTION_COUNT as i32 - 1, self.section as i32).to_string(),
+            }));
+        }
+
+        let parameters = registry::section_parameters(self.section);
+        let count = parameters.len();
+        parameters.into_iter().nth(self.number).ok_or_else(|| ParseError::InvalidField(FieldError {
+            path: "number".to_string(),
+            offset: START + 1,
+            value: self.number as u8,
+            reason: ValueError(0, count as i32 - 1, self.number as i32).to_string(),
+        }))
+    }
+
+    fn field_error(&self, path: &str, reason: String) -> ParseError {
+        ParseError::InvalidField(FieldError {
+            path: path.to_string(),
+            offset: START + TRACK_CONTROL_SIZE - 1,
+            value: (self.value & 0x7f) as u8,
+            reason,
+        })
+    }
+}
+
+impl fmt::Display for TrackControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = registry::section_parameters(self.section).into_iter().nth(self.number)
+            .map(|info| info.name)
+            .unwrap_or_else(|| format!("Section {} parameter {}", self.section + 1, self.number));
+        write!(f, "{} = {}, channel {}", name, self.value, self.channel.value())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{*};
+
+    static DATA: &[u8] = include_bytes!("WizooIni.syx");
+
+    fn wizoo() -> SinglePatch {
+        SinglePatch::from_bytes(&DATA[9..DATA.len() - 1]).unwrap()
+    }
+
+    fn channel() -> MIDIChannel {
+        MIDIChannel::try_new(3).unwrap()
+    }
+
+    fn number_of(group: Group, path: &str) -> usize {
+        group.parameters().iter().position(|info| info.path == path).unwrap()
+    }
+
+    #[test]
+    fn test_groups() {
+        assert_eq!(Group::Harmonic.parameters().len() + Group::Formant.parameters().len(),
+            registry::harmonic_definition().len());
+        assert!(Group::Formant.parameters().iter().all(is_formant));
+        assert_eq!(Group::Formant.parameters().len(), 17 + 128);  // settings and bands
+    }
+
+    #[test]
+    fn test_parameter_send_round_trip() {
+        let patch = wizoo();
+        let number = number_of(Group::Source, "oscillator.wave");
+        let send = ParameterSend::from_single(channel(), &patch, Group::Source, 0, number).unwrap();
+        assert_eq!(send.value, 512);  // ADD
+
+        let data = send.to_message().to_bytes();
+        assert_eq!(data, vec![0xF0, 0x40, 0x02, 0x10, 0x00, 0x0A, 0x01, 0x00,
+            (number >> 7) as u8, (number & 0x7f) as u8, 0x04, 0x00, 0xF7]);
+        let parsed = ParameterSend::from_message(&Message::from_bytes(&data).unwrap()).unwrap();
+        assert_eq!(parsed, send);
+
+        let number = number_of(Group::Harmonic, "envelopes[63].loop_type");
+        assert!(number > 127);
+        let send = ParameterSend { channel: channel(), group: Group::Harmonic, source: 5, number, value: 3 };
+        let message = Message::from_bytes(&send.to_message().to_bytes()).unwrap();
+        assert_eq!(ParameterSend::from_message(&message).unwrap(), send);
+    }
+
+    #[test]
+    fn test_bad_parameter_send() {
+        let count = Group::Common.parameters().len();
+        let send = ParameterSend { channel: channel(), group: Group::Common, source: 0, number: count, value: 0 };
+        match ParameterSend::from_message(&send.to_message()) {
+            Err(ParseError::InvalidField(error)) => {
+                assert_eq!(error.path, "number");
+                assert_eq!(error.offset, 9);
+            },
+            other => panic!("expected an invalid field error, got {:?}", other),
+        }
+
+        let send = ParameterSend { channel: channel(), group: Group::Common, source: 1, number: 0, value: 0 };
+        assert!(ParameterSend::from_message(&send.to_message()).is_err());
+
+        let mut message = send.to_message();
+        message.function_data[0] = 0x04;
+        match ParameterSend::from_message(&message) {
+            Err(ParseError::InvalidField(error)) => {
+                assert_eq!(error.path, "group");
+                assert_eq!(error.offset, 6);
+            },
+            other => panic!("expected an invalid field error, got {:?}", other),
+        }
+
+        message.function_data.pop();
+        assert!(matches!(ParameterSend::from_message(&message), Err(ParseError::InvalidLength(12, 13))));
+    }
+
+    #[test]
+    fn test_apply_parameter_send() {
+        let mut patch = wizoo();
+        let number = number_of(Group::Source, "oscillator.coarse");
+        let send = ParameterSend { channel: channel(), group: Group::Source, source: 1, number, value: 64 + 12 };
+        send.apply_to_single(&mut patch).unwrap();
+        assert_eq!(patch.sources[1].oscillator.coarse.value(), 12);
+
+        let send = ParameterSend { value: 200, ..send };
+        assert!(send.apply_to_single(&mut patch).is_err());
+
+        // The second source of WizooIni is PCM, so it has no additive kit.
+        let send = ParameterSend { channel: channel(), group: Group::Formant, source: 1, number: 0, value: 64 };
+        assert!(send.apply_to_single(&mut patch).is_err());
+    }
+
+    #[test]
+    fn test_cannot_change_source_count() {
+        let mut patch = wizoo();
+        let number = number_of(Group::Common, "common.source_count");
+        let send = ParameterSend { channel: channel(), group: Group::Common, source: 0, number, value: 1 };
+        match send.apply_to_single(&mut patch) {
+            Err(ParseError::InvalidField(error)) => assert_eq!(error.path, "common.source_count"),
+            other => panic!("expected an invalid field error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_no_changes() {
+        assert_eq!(wizoo().changes(&wizoo(), channel()), Some(vec![]));
+    }
+
+    #[test]
+    fn test_changes_apply() {
+        let old = wizoo();
+        let mut new = wizoo();
+        new.common.volume = crate::k5000::Volume::new(80);
+        new.sources[1].control.zone.low.note = 36;
+        let key = new.additive_kits.keys().next().unwrap().clone();
+        let kit = new.additive_kits.get_mut(&key).unwrap();
+        kit.bands[10] = 99;
+        kit.levels.soft[3] = 42;
+
+        let messages = old.changes(&new, channel()).unwrap();
+        assert_eq!(messages.len(), 4);
+        let sends: Vec<ParameterSend> = messages.iter()
+            .map(|message| ParameterSend::from_message(message).unwrap())
+            .collect();
+        assert!(sends.contains(&ParameterSend {
+            channel: channel(),
+            group: Group::Formant,
+            source: 0,
+            number: number_of(Group::Formant, "bands[10]"),
+            value: 99,
+        }));
+
+        let mut patch = wizoo();
+        for send in sends {
+            send.apply_to_single(&mut patch).unwrap();
+        }
+        assert_eq!(patch.to_bytes(), new.to_bytes());
+    }
+
+    #[test]
+    fn test_changes_need_dump() {
+        let old = SinglePatch::new(2, 0);
+        assert!(old.changes(&SinglePatch::new(3, 0), channel()).is_none());
+        assert!(old.changes(&SinglePatch::new(1, 1), channel()).is_none());
+
+        let mut renamed = wizoo();
+        renamed.common.name = "Renamed!".to_string();
+        assert!(wizoo().changes(&renamed, channel()).is_none());
+    }
+
+    #[test]
+    fn test_track_control() {
+        let mut multi = MultiPatch::default();
+        multi.sections[2].receive_channel = Some(MIDIChannel::try_new(3).unwrap());
+        let number = registry::section_parameters(2).iter()
+            .position(|info| info.path == "sections[2].volume")
+            .unwrap();
+        let control = TrackControl { channel: channel(), section: 2, number, value: 100 };
+        control.apply_to_multi(&mut multi).unwrap();
+        assert_eq!(multi.sections[2].volume.value(), 100);
+        assert_eq!(TrackControl::from_multi(channel(), &multi, 2, number).unwrap(), control);
+
+        let data = control.to_message().to_bytes();
+        assert_eq!(data, vec![0xF0, 0x40, 0x02, 0x11, 0x00, 0x0A, 0x02, number as u8, 0x00, 0x64, 0xF7]);
+        let parsed = TrackControl::from_message(&Message::from_bytes(&data).unwrap()).unwrap();
+        assert_eq!(parsed, control);
+
+        let control = TrackControl { section: SECTION_COUNT, ..control };
+        assert!(TrackControl::from_message(&control.to_message()).is_err());
+        assert!(control.apply_to_multi(&mut multi).is_err());
+    }
+}
@@ -5,7 +5,9 @@ use std::fmt;
 
 use crate::{
     SystemExclusiveData,
-    ParseError
+    ParseError,
+    check_length,
+    invalid_field
 };
 use crate::k5000::{
     PitchEnvelopeLevel,
@@ -15,6 +17,7 @@ use crate::k5000::{
 
 /// Pitch envelope.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Envelope {
     /// Envelope start level.
     pub start: PitchEnvelopeLevel,
@@ -65,13 +68,14 @@ impl fmt::Display for Envelope {
 
 impl SystemExclusiveData for Envelope {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(Envelope {
-            start: PitchEnvelopeLevel::from(data[0]),
-            attack_time: PitchEnvelopeTime::from(data[1]),
-            attack_level: PitchEnvelopeLevel::from(data[2]),
-            decay_time: PitchEnvelopeTime::from(data[3]),
-            time_vel_sens: VelocitySensitivity::from(data[4]),
-            level_vel_sens: VelocitySensitivity::from(data[5]),
+            start: PitchEnvelopeLevel::try_from(data[0]).map_err(invalid_field("start", data, 0))?,
+            attack_time: PitchEnvelopeTime::try_from(data[1]).map_err(invalid_field("attack_time", data, 1))?,
+            attack_level: PitchEnvelopeLevel::try_from(data[2]).map_err(invalid_field("attack_level", data, 2))?,
+            decay_time: PitchEnvelopeTime::try_from(data[3]).map_err(invalid_field("decay_time", data, 3))?,
+            time_vel_sens: VelocitySensitivity::try_from(data[4]).map_err(invalid_field("time_vel_sens", data, 4))?,
+            level_vel_sens: VelocitySensitivity::try_from(data[5]).map_err(invalid_field("level_vel_sens", data, 5))?,
         })
     }
 
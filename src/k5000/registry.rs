@@ -0,0 +1,683 @@
+//! Metadata of the K5000 patch parameters.
+//!
+//! The parameters are listed by group: the common settings and the sources
+//! of a single patch, the common settings and the sections of a multi patch,
+//! the common settings and the keys of a drum kit, the common settings and
+//! the source of a drum instrument, and the harmonics of an additive kit.
+//! Each one is described with its name, range, unit and default, and where
+//! it is stored. The offsets are relative to the data of one group as
+//! written by its `to_bytes`, like one `Source` or one `AdditiveKit`
+//! (which starts with its checksum). The defaults are the values
+//! of a new group, with a PCM source.
+//!
+//! Patch names are not listed.
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::SystemExclusiveData;
+use crate::registry::{ParameterInfo, BitField, Unit, choices};
+use crate::k5000::{
+    Parameter,
+    Volume,
+    BenderPitch,
+    BenderCutoff,
+    EnvelopeTime,
+    EnvelopeLevel,
+    ControlTime,
+    EnvelopeDepth,
+    LFOSpeed,
+    KeyScaling,
+    EffectParameter,
+    Cutoff,
+    Resonance,
+    Level,
+    PitchEnvelopeLevel,
+    PitchEnvelopeTime,
+    VelocityControlLevel,
+    PortamentoLevel,
+    KeyOnDelay,
+    VelocitySensitivity,
+    ControlDepth,
+    Depth,
+    Pan,
+    Coarse,
+    Fine,
+    MacroParameterDepth,
+    KeyScalingToGain,
+    VelocityDepth,
+    Bias,
+    LFODepth,
+};
+use crate::k5000::amp;
+use crate::k5000::control::{
+    VelocitySwitch,
+    VelocitySwitchSettings,
+    ControlSource,
+    ControlDestination,
+    PanKind,
+    Switch,
+    Polyphony,
+    AmplitudeModulation,
+    VelocityCurve,
+};
+use crate::k5000::effect::{Effect, EffectAlgorithm, EffectDestination};
+use crate::k5000::filter::FilterMode;
+use crate::k5000::lfo::Waveform;
+use crate::k5000::osc::{self, FixedKey};
+use crate::k5000::single::Common;
+use crate::k5000::source::Source;
+use crate::k5000::wave::Wave;
+use crate::k5000::multi::{self, Section};
+use crate::k5000::drum::{self, InstrumentCommon};
+use crate::k5000::addkit::{AdditiveKit, HARMONIC_COUNT, BAND_COUNT};
+use crate::k5000::harmonic;
+use crate::k5000::morf::{HarmonicGroup, Loop};
+use crate::k5000::formant;
+use crate::k5000::{EnvelopeRate, HarmonicEnvelopeLevel};
+
+/// Number of bytes in the definition of the reverb or an effect.
+const EFFECT_SIZE: usize = 6;
+
+/// Value of type `T` stored in the byte at `offset`, with the bias
+/// that makes the lowest stored number the minimum value of `T`.
+fn value<T: Parameter + TryFrom<u8>>(path: &str, name: &str, unit: Unit, offset: usize) -> ParameterInfo {
+    let lowest = (0..=127u8).find(|number| T::try_from(*number).is_ok()).unwrap_or_default();
+    ParameterInfo::new(path, name, T::minimum_value()..=T::maximum_value(), unit, offset)
+        .bias(lowest as i32 - T::minimum_value())
+}
+
+/// One of the values of enum `T`, stored in the byte at `offset`.
+fn choice<T: TryFrom<u8> + fmt::Display>(path: &str, name: &str, offset: usize) -> ParameterInfo {
+    let choices = choices::<T>(0..=127);
+    let range = choices[0].0..=choices[choices.len() - 1].0;
+    ParameterInfo::new(path, name, range, Unit::Choice, offset).choices(choices)
+}
+
+/// On or off, stored in the bits of `mask` of the byte at `offset`.
+fn switch(path: &str, name: &str, offset: usize, mask: u8) -> ParameterInfo {
+    ParameterInfo::new(path, name, 0..=1, Unit::Switch, offset).bits(mask)
+}
+
+/// Sets the default of `info` to its value in the data of a new patch.
+fn with_default(mut info: ParameterInfo, data: &[u8]) -> ParameterInfo {
+    info.default = info.read(data);
+    info
+}
+
+/// Adds `prefix` to the paths of `parameters` and sets their defaults
+/// from `data`, the data of a new group.
+fn finish(parameters: Vec<ParameterInfo>, prefix: &str, data: &[u8]) -> Vec<ParameterInfo> {
+    parameters.into_iter()
+        .map(|mut info| {
+            info.path = format!("{}.{}", prefix, info.path);
+            with_default(info, data)
+        })
+        .collect()
+}
+
+/// Parameters of the effect settings and the GEQ at the start
+/// of the single, multi and drum kit common settings.
+fn effects_and_geq() -> Vec<ParameterInfo> {
+    let mut parameters = vec![choice::<EffectAlgorithm>("effects.algorithm", "Effect Algorithm", 0)];
+
+    let effects = ["reverb", "effect1", "effect2", "effect3", "effect4"];
+    for (i, field) in effects.iter().enumerate() {
+        let prefix = if i == 0 { "Reverb".to_string() } else { format!("Effect {}", i) };
+        parameters.extend(effect_definition().into_iter()
+            .map(|info| info.within(&format!("effects.{}", field), &prefix).offset_by(1 + i * EFFECT_SIZE)));
+    }
+
+    for i in 0..7 {
+        parameters.push(ParameterInfo::new(&format!("geq[{}]", i), &format!("GEQ {}", i + 1), -6..=6, Unit::Level, 31 + i)
+            .bias(64));
+    }
+    parameters
+}
+
+/// Parameters of the two effect controls, starting at `offset`.
+fn effect_control(offset: usize) -> Vec<ParameterInfo> {
+    let mut parameters = Vec::new();
+    for i in 0..2 {
+        let offset = offset + i * 3;
+        let path = |field: &str| format!("effect_control.source{}.{}", i + 1, field);
+        let name = |field: &str| format!("Effect Control {} {}", i + 1, field);
+        parameters.push(choice::<ControlSource>(&path("source"), &name("Source"), offset));
+        parameters.push(choice::<EffectDestination>(&path("destination"), &name("Destination"), offset + 1));
+        parameters.push(value::<MacroParameterDepth>(&path("depth"), &name("Depth"), Unit::Depth, offset + 2));
+    }
+    parameters
+}
+
+/// Parameters of a velocity switch stored in the byte at `offset`.
+fn velocity_switch(path: &str, offset: usize) -> Vec<ParameterInfo> {
+    let threshold_choices = (0..32)
+        .map(|number| (number, VelocitySwitchSettings::from_bytes(&[number as u8]).unwrap().threshold.to_string()))
+        .collect();
+
+    vec![
+        choice::<VelocitySwitch>(&format!("{}.switch_type", path), "Velocity Switch", offset).bits(0b0110_0000),
+        ParameterInfo::new(&format!("{}.threshold", path), "Velocity Threshold", 0..=31, Unit::Choice, offset)
+            .bits(0b0001_1111)
+            .choices(threshold_choices),
+    ]
+}
+
+/// Instrument or single number stored in the low two bits of the byte
+/// at `offset` and the seven bits of the next byte.
+fn patch_number(path: &str, name: &str, offset: usize) -> ParameterInfo {
+    ParameterInfo::new(path, name, 0..=511, Unit::Number, offset)
+        .fields(vec![BitField::new(offset, 0b0000_0011), BitField::byte(offset + 1)])
+}
+
+/// Lists the parameters of the single patch common settings.
+pub fn common_parameters() -> Vec<ParameterInfo> {
+    let mut parameters = effects_and_geq();
+
+    parameters.push(value::<Volume>("volume", "Volume", Unit::Level, 47));
+    parameters.push(choice::<Polyphony>("polyphony", "Polyphony", 48));
+    parameters.push(ParameterInfo::new("source_count", "Sources", 1..=6, Unit::Number, 50));
+    for i in 0..6 {
+        parameters.push(switch(&format!("source_mutes[{}]", i), &format!("S{} Mute", i + 1), 51, 1 << i));
+    }
+    parameters.push(choice::<AmplitudeModulation>("amplitude_modulation", "AM", 52));
+    parameters.extend(effect_control(53));
+
+    // The speed byte is kept also when portamento is off.
+    parameters.push(switch("portamento", "Portamento", 59, 0b0000_0001));
+    parameters.push(value::<PortamentoLevel>("portamento.On", "Portamento Speed", Unit::Time, 60));
+
+    // The eight macro destinations come first, then the eight depths.
+    for i in 0..4 {
+        for j in 0..2 {
+            let path = |field: &str| format!("macros[{}].{}{}", i, field, j + 1);
+            let name = |field: &str| format!("Macro {} {} {}", i + 1, field, j + 1);
+            parameters.push(choice::<ControlDestination>(&path("destination"), &name("Destination"), 61 + i * 2 + j));
+            parameters.push(value::<MacroParameterDepth>(&path("depth"), &name("Depth"), Unit::Depth, 69 + i * 2 + j));
+        }
+    }
+
+    let switches = [("switch1", "Switch 1"), ("switch2", "Switch 2"), ("footswitch1", "Foot Switch 1"), ("footswitch2", "Foot Switch 2")];
+    for (i, (field, name)) in switches.iter().enumerate() {
+        parameters.push(choice::<Switch>(&format!("switches.{}", field), name, 77 + i));
+    }
+
+    finish(parameters, "common", &Common::default().to_bytes())
+}
+
+/// Parameters of the reverb or an effect, relative to its definition.
+fn effect_definition() -> Vec<ParameterInfo> {
+    let mut parameters = vec![
+        choice::<Effect>("effect", "Type", 0),
+        value::<Depth>("depth", "Depth", Unit::Level, 1),
+    ];
+    for i in 1..=4 {
+        parameters.push(value::<EffectParameter>(&format!("parameter{}", i), &format!("Param {}", i), Unit::Number, 1 + i));
+    }
+    parameters
+}
+
+/// Parameters of a macro controller with two destinations,
+/// relative to its data.
+fn macro_controller() -> Vec<ParameterInfo> {
+    vec![
+        choice::<ControlDestination>("destination1", "Destination 1", 0),
+        value::<MacroParameterDepth>("depth1", "Depth 1", Unit::Depth, 1),
+        choice::<ControlDestination>("destination2", "Destination 2", 2),
+        value::<MacroParameterDepth>("depth2", "Depth 2", Unit::Depth, 3),
+    ]
+}
+
+/// Lists the parameters of the source at `index` (0~5).
+pub fn source_parameters(index: usize) -> Vec<ParameterInfo> {
+    let data = Source::pcm().to_bytes();
+    source_definition().into_iter()
+        .map(|info| with_default(info.within(&format!("sources[{}]", index), &format!("S{}", index + 1)), &data))
+        .collect()
+}
+
+/// Parameters of a source, relative to its data.
+pub(crate) fn source_definition() -> Vec<ParameterInfo> {
+    let mut parameters = vec![
+        ParameterInfo::new("control.zone.low", "Zone Low", 0..=127, Unit::Note, 0),
+        ParameterInfo::new("control.zone.high", "Zone High", 0..=127, Unit::Note, 1),
+    ];
+    parameters.extend(velocity_switch("control.vel_sw", 2));
+    parameters.extend([
+        ParameterInfo::new("control.effect_path", "Effect Path", 0..=3, Unit::Number, 3),
+        value::<Volume>("control.volume", "Volume", Unit::Level, 4),
+        value::<BenderPitch>("control.bender_pitch", "Bender Pitch", Unit::Semitones, 5),
+        value::<BenderCutoff>("control.bender_cutoff", "Bender Cutoff", Unit::Level, 6),
+    ]);
+
+    let controllers = [("pressure", "Pressure"), ("wheel", "Wheel"), ("expression", "Expression")];
+    for (i, (field, name)) in controllers.iter().enumerate() {
+        parameters.extend(macro_controller().into_iter()
+            .map(|info| info.within(&format!("control.modulation.{}", field), name).offset_by(7 + i * 4)));
+    }
+    for i in 0..2 {
+        let offset = 19 + i * 3;
+        let path = |field: &str| format!("control.modulation.assignable{}.{}", i + 1, field);
+        let name = |field: &str| format!("Assignable {} {}", i + 1, field);
+        parameters.push(choice::<ControlSource>(&path("source"), &name("Source"), offset));
+        parameters.push(choice::<ControlDestination>(&path("destination"), &name("Destination"), offset + 1));
+        parameters.push(value::<ControlDepth>(&path("depth"), &name("Depth"), Unit::Depth, offset + 2));
+    }
+
+    parameters.extend([
+        value::<KeyOnDelay>("control.key_on_delay", "Key On Delay", Unit::Time, 25),
+        choice::<PanKind>("control.pan.pan_type", "Pan Type", 26),
+        value::<Pan>("control.pan.pan_value", "Pan", Unit::Depth, 27),
+
+        // The wave is the stored number: one less than the PCM wave number, or 512 for ADD.
+        ParameterInfo::new("oscillator.wave", "Wave", 0..=512, Unit::Wave, 29)
+            .fields(vec![BitField::new(28, 0b0000_0111), BitField::byte(29)])
+            .choices(wave_choices()),
+        value::<Coarse>("oscillator.coarse", "Coarse", Unit::Semitones, 30),
+        value::<Fine>("oscillator.fine", "Fine", Unit::Cents, 31),
+        ParameterInfo::new("oscillator.fixed_key", "Fixed Key", 0..=108, Unit::Note, 32)
+            .choices(fixed_key_choices()),
+        choice::<osc::KeyScaling>("oscillator.ks_to_pitch", "KS to Pitch", 33),
+        value::<PitchEnvelopeLevel>("oscillator.pitch_envelope.start", "Pitch Env Start", Unit::Depth, 34),
+        value::<PitchEnvelopeTime>("oscillator.pitch_envelope.attack_time", "Pitch Env Attack Time", Unit::Time, 35),
+        value::<PitchEnvelopeLevel>("oscillator.pitch_envelope.attack_level", "Pitch Env Attack Level", Unit::Depth, 36),
+        value::<PitchEnvelopeTime>("oscillator.pitch_envelope.decay_time", "Pitch Env Decay Time", Unit::Time, 37),
+        value::<VelocitySensitivity>("oscillator.pitch_envelope.time_vel_sens", "Pitch Env Time Vel Sens", Unit::Depth, 38),
+        value::<VelocitySensitivity>("oscillator.pitch_envelope.level_vel_sens", "Pitch Env Level Vel Sens", Unit::Depth, 39),
+
+        // Stored as 1 when the filter is bypassed.
+        switch("filter.is_active", "Filter Active", 40, 0b0000_0001).inverted(),
+        choice::<FilterMode>("filter.mode", "Filter Mode", 41),
+        choice::<VelocityCurve>("filter.velocity_curve", "Filter Velocity Curve", 42),
+        value::<Resonance>("filter.resonance", "Filter Resonance", Unit::Level, 43),
+        value::<Level>("filter.level", "Filter Level", Unit::Level, 44),
+        value::<Cutoff>("filter.cutoff", "Filter Cutoff", Unit::Level, 45),
+        value::<EnvelopeDepth>("filter.ks_to_cutoff", "Filter KS to Cutoff", Unit::Depth, 46),
+        value::<EnvelopeDepth>("filter.vel_to_cutoff", "Filter Velocity to Cutoff", Unit::Depth, 47),
+        value::<EnvelopeDepth>("filter.envelope_depth", "Filter Envelope Depth", Unit::Depth, 48),
+        value::<EnvelopeTime>("filter.envelope.attack_time", "Filter Attack Time", Unit::Time, 49),
+        value::<EnvelopeTime>("filter.envelope.decay1_time", "Filter Decay 1 Time", Unit::Time, 50),
+        value::<EnvelopeLevel>("filter.envelope.decay1_level", "Filter Decay 1 Level", Unit::Level, 51),
+        value::<EnvelopeTime>("filter.envelope.decay2_time", "Filter Decay 2 Time", Unit::Time, 52),
+        value::<EnvelopeLevel>("filter.envelope.decay2_level", "Filter Decay 2 Level", Unit::Level, 53),
+        value::<EnvelopeTime>("filter.envelope.release_time", "Filter Release Time", Unit::Time, 54),
+        value::<ControlTime>("filter.modulation.ks_to_env.attack_time", "Filter KS Attack Time", Unit::Depth, 55),
+        value::<ControlTime>("filter.modulation.ks_to_env.decay1_time", "Filter KS Decay 1 Time", Unit::Depth, 56),
+        value::<EnvelopeDepth>("filter.modulation.vel_to_env.depth", "Filter Velocity Depth", Unit::Depth, 57),
+        value::<ControlTime>("filter.modulation.vel_to_env.attack_time", "Filter Velocity Attack Time", Unit::Depth, 58),
+        value::<ControlTime>("filter.modulation.vel_to_env.decay1_time", "Filter Velocity Decay 1 Time", Unit::Depth, 59),
+
+        choice::<VelocityCurve>("amplifier.velocity_curve", "Amp Velocity Curve", 60),
+        value::<EnvelopeTime>("amplifier.envelope.attack_time", "Amp Attack Time", Unit::Time, 61),
+        value::<EnvelopeTime>("amplifier.envelope.decay1_time", "Amp Decay 1 Time", Unit::Time, 62),
+        value::<amp::EnvelopeLevel>("amplifier.envelope.decay1_level", "Amp Decay 1 Level", Unit::Level, 63),
+        value::<EnvelopeTime>("amplifier.envelope.decay2_time", "Amp Decay 2 Time", Unit::Time, 64),
+        value::<amp::EnvelopeLevel>("amplifier.envelope.decay2_level", "Amp Decay 2 Level", Unit::Level, 65),
+        value::<EnvelopeTime>("amplifier.envelope.release_time", "Amp Release Time", Unit::Time, 66),
+        value::<KeyScaling>("amplifier.modulation.ks_to_env.level", "Amp KS Level", Unit::Depth, 67),
+        value::<ControlTime>("amplifier.modulation.ks_to_env.attack_time", "Amp KS Attack Time", Unit::Depth, 68),
+        value::<ControlTime>("amplifier.modulation.ks_to_env.decay1_time", "Amp KS Decay 1 Time", Unit::Depth, 69),
+        value::<ControlTime>("amplifier.modulation.ks_to_env.release", "Amp KS Release Time", Unit::Depth, 70),
+        value::<VelocityControlLevel>("amplifier.modulation.vel_sens.level", "Amp Velocity Level", Unit::Level, 71),
+        value::<ControlTime>("amplifier.modulation.vel_sens.attack_time", "Amp Velocity Attack Time", Unit::Depth, 72),
+        value::<ControlTime>("amplifier.modulation.vel_sens.decay1_time", "Amp Velocity Decay 1 Time", Unit::Depth, 73),
+        value::<ControlTime>("amplifier.modulation.vel_sens.release", "Amp Velocity Release Time", Unit::Depth, 74),
+
+        choice::<Waveform>("lfo.waveform", "LFO Waveform", 75),
+        value::<LFOSpeed>("lfo.speed", "LFO Speed", Unit::Level, 76),
+        value::<LFOSpeed>("lfo.delay_onset", "LFO Delay Onset", Unit::Time, 77),
+        value::<LFOSpeed>("lfo.fade_in_time", "LFO Fade In Time", Unit::Time, 78),
+        value::<Depth>("lfo.fade_in_to_speed", "LFO Fade In to Speed", Unit::Level, 79),
+    ]);
+
+    let controls = [("vibrato", "Vibrato"), ("growl", "Growl"), ("tremolo", "Tremolo")];
+    for (i, (field, name)) in controls.iter().enumerate() {
+        let offset = 80 + i * 2;
+        parameters.push(value::<Depth>(&format!("lfo.{}.depth", field), &format!("{} Depth", name), Unit::Level, offset));
+        parameters.push(value::<KeyScaling>(&format!("lfo.{}.key_scaling", field), &format!("{} KS", name), Unit::Depth, offset + 1));
+    }
+    parameters
+}
+
+/// Lists the parameters of the multi patch common settings.
+pub fn multi_common_parameters() -> Vec<ParameterInfo> {
+    let mut parameters = effects_and_geq();
+    parameters.push(value::<Volume>("volume", "Volume", Unit::Level, 46));
+    for i in 0..multi::SECTION_COUNT {
+        parameters.push(switch(&format!("section_mutes[{}]", i), &format!("Section {} Mute", i + 1), 47, 1 << i));
+    }
+    parameters.extend(effect_control(48));
+    finish(parameters, "common", &multi::Common::default().to_bytes())
+}
+
+/// Lists the parameters of the multi section at `index` (0~3).
+/// The receive channel is not in a K5000W combi.
+pub fn section_parameters(index: usize) -> Vec<ParameterInfo> {
+    let mut parameters = vec![
+        patch_number("single", "Single", 0),
+        value::<Volume>("volume", "Volume", Unit::Level, 2),
+        ParameterInfo::new("pan", "Pan", 0..=127, Unit::Number, 3),
+        ParameterInfo::new("effect_path", "Effect Path", 0..=3, Unit::Number, 4),
+        value::<Coarse>("transpose", "Transpose", Unit::Semitones, 5),
+        value::<Fine>("tune", "Tune", Unit::Cents, 6),
+        ParameterInfo::new("zone.low", "Zone Low", 0..=127, Unit::Note, 7),
+        ParameterInfo::new("zone.high", "Zone High", 0..=127, Unit::Note, 8),
+    ];
+    parameters.extend(velocity_switch("vel_switch", 9));
+    parameters.push(ParameterInfo::new("receive_channel", "Receive Channel", 1..=16, Unit::Number, 11).bias(-1));
+
+    let data = Section::default().to_bytes();
+    parameters.into_iter()
+        .map(|info| with_default(info.within(&format!("sections[{}]", index), &format!("Section {}", index + 1)), &data))
+        .collect()
+}
+
+/// Lists the parameters of the drum kit common settings.
+pub fn drum_kit_common_parameters() -> Vec<ParameterInfo> {
+    let mut parameters = effects_and_geq();
+    parameters.push(value::<Volume>("volume", "Volume", Unit::Level, 46));
+    parameters.extend(effect_control(47));
+    finish(parameters, "common", &drum::Common::default().to_bytes())
+}
+
+/// Lists the parameters of the drum kit key at `index` (0~63),
+/// which is the key with the note number `drum::FIRST_KEY + index`.
+pub fn drum_key_parameters(index: usize) -> Vec<ParameterInfo> {
+    let parameters = vec![
+        patch_number("instrument", "Instrument", 0),
+        value::<Volume>("volume", "Volume", Unit::Level, 2),
+        ParameterInfo::new("pan", "Pan", 0..=127, Unit::Number, 3),
+        ParameterInfo::new("effect_path", "Effect Path", 0..=3, Unit::Number, 4),
+    ];
+
+    let data = drum::Key::default().to_bytes();
+    parameters.into_iter()
+        .map(|info| with_default(info.within(&format!("keys[{}]", index), &format!("Key {}", index + 1)), &data))
+        .collect()
+}
+
+/// Lists the parameters of the drum instrument common settings.
+pub fn instrument_common_parameters() -> Vec<ParameterInfo> {
+    let parameters = vec![
+        value::<Volume>("volume", "Volume", Unit::Level, 8),
+        ParameterInfo::new("gate_time", "Gate Time", 0..=127, Unit::Time, 9),
+        ParameterInfo::new("exclusive_group", "Exclusive Group", 0..=127, Unit::Number, 10),
+    ];
+    finish(parameters, "common", &InstrumentCommon::default().to_bytes())
+}
+
+/// Lists the parameters of the source of a drum instrument.
+pub fn instrument_source_parameters() -> Vec<ParameterInfo> {
+    let data = Source::pcm().to_bytes();
+    source_definition().into_iter()
+        .map(|info| with_default(info.within("source", "Source"), &data))
+        .collect()
+}
+
+/// Lists the parameters of the harmonics of the additive kit `kit` (0~5)
+/// of a single patch, the kit of the ADD source `kit + 1` in source order.
+pub fn additive_kit_parameters(kit: usize) -> Vec<ParameterInfo> {
+    let data = AdditiveKit::new().to_bytes();
+    harmonic_definition().into_iter()
+        .map(|info| with_default(info.within(&format!("additive_kits[s{}]", kit + 1), &format!("Kit {}", kit + 1)), &data))
+        .collect()
+}
+
+/// Lists the parameters of the harmonics of the additive kit
+/// of a drum instrument with an ADD source.
+pub fn instrument_additive_kit_parameters() -> Vec<ParameterInfo> {
+    let data = AdditiveKit::new().to_bytes();
+    harmonic_definition().into_iter()
+        .map(|info| with_default(info.within("additive_kit", "Kit"), &data))
+        .collect()
+}
+
+/// Parameters of the harmonics of an additive kit, relative to its data.
+pub(crate) fn harmonic_definition() -> Vec<ParameterInfo> {
+    let levels_offset = 37;  // checksum, harmonic common, MORF and formant filter
+    let bands_offset = levels_offset + 2 * HARMONIC_COUNT;
+    let envelopes_offset = bands_offset + BAND_COUNT;
+
+    // The harmonic common settings follow the checksum.
+    let mut parameters = vec![
+        switch("common.morf_enabled", "MORF", 1, 0b0000_0001),
+        ParameterInfo::new("common.total_gain", "Total Gain", 0..=127, Unit::Level, 2),
+        choice::<HarmonicGroup>("common.group", "Harmonic Group", 3),
+        value::<KeyScalingToGain>("common.ks_to_gain", "KS to Gain", Unit::Depth, 4),
+        choice::<VelocityCurve>("common.velocity_curve", "Velocity Curve", 5),
+        value::<VelocityDepth>("common.velocity_depth", "Velocity Depth", Unit::Depth, 6),
+    ];
+
+    for i in 0..4 {
+        let offset = 7 + i * 2;
+        parameters.push(ParameterInfo::new(&format!("morf.copy{}.patch_number", i + 1),
+            &format!("MORF Copy {} Patch", i + 1), 0..=127, Unit::Number, offset));
+        parameters.push(ParameterInfo::new(&format!("morf.copy{}.source_number", i + 1),
+            &format!("MORF Copy {} Source", i + 1), 0..=127, Unit::Number, offset + 1));
+    }
+    for i in 0..4 {
+        parameters.push(value::<EnvelopeTime>(&format!("morf.envelope.time{}", i + 1),
+            &format!("MORF Time {}", i + 1), Unit::Time, 15 + i));
+    }
+    parameters.push(choice::<Loop>("morf.envelope.loop_type", "MORF Loop", 19));
+
+    parameters.extend([
+        value::<Bias>("formant_filter.bias", "Formant Bias", Unit::Depth, 20),
+        choice::<formant::Mode>("formant_filter.mode", "Formant Mode", 21),
+        value::<EnvelopeDepth>("formant_filter.envelope_depth", "Formant Envelope Depth", Unit::Depth, 22),
+    ]);
+    let segments = [("attack", "Attack"), ("decay1", "Decay 1"), ("decay2", "Decay 2"), ("release", "Release")];
+    for (i, (field, name)) in segments.iter().enumerate() {
+        let offset = 23 + i * 2;
+        parameters.push(value::<EnvelopeRate>(&format!("formant_filter.envelope.{}.rate", field),
+            &format!("Formant {} Rate", name), Unit::Time, offset));
+        parameters.push(value::<EnvelopeLevel>(&format!("formant_filter.envelope.{}.level", field),
+            &format!("Formant {} Level", name), Unit::Depth, offset + 1));
+    }
+    parameters.extend([
+        choice::<Loop>("formant_filter.envelope.decay_loop", "Formant Loop", 31),
+        value::<EnvelopeDepth>("formant_filter.envelope.velocity_depth", "Formant Velocity Depth", Unit::Depth, 32),
+        value::<EnvelopeDepth>("formant_filter.envelope.ks_depth", "Formant KS Depth", Unit::Depth, 33),
+        value::<LFOSpeed>("formant_filter.lfo.speed", "Formant LFO Speed", Unit::Level, 34),
+        choice::<formant::LFOShape>("formant_filter.lfo.shape", "Formant LFO Shape", 35),
+        value::<LFODepth>("formant_filter.lfo.depth", "Formant LFO Depth", Unit::Level, 36),
+    ]);
+
+    for i in 0..HARMONIC_COUNT {
+        parameters.push(ParameterInfo::new(&format!("levels.soft[{}]", i), &format!("Soft Level {}", i + 1),
+            0..=127, Unit::Level, levels_offset + i));
+    }
+    for i in 0..HARMONIC_COUNT {
+        parameters.push(ParameterInfo::new(&format!("levels.loud[{}]", i), &format!("Loud Level {}", i + 1),
+            0..=127, Unit::Level, levels_offset + HARMONIC_COUNT + i));
+    }
+    for i in 0..BAND_COUNT {
+        parameters.push(ParameterInfo::new(&format!("bands[{}]", i), &format!("Band {}", i + 1),
+            0..=127, Unit::Level, bands_offset + i));
+    }
+
+    // Bit 6 of the decay 2 level turns the loop on, and bit 6 of the decay 1
+    // level picks loop 1 over loop 2. The decay 1 bit is kept when the loop
+    // is off, so the loop type is off when stored as either 0 or 1.
+    let loop_choices = vec![
+        (0, Loop::Off.to_string()),
+        (1, Loop::Off.to_string()),
+        (2, Loop::Loop2.to_string()),
+        (3, Loop::Loop1.to_string()),
+    ];
+    for i in 0..HARMONIC_COUNT {
+        let offset = envelopes_offset + i * harmonic::Envelope::data_size();
+        for (j, (field, name)) in segments.iter().enumerate() {
+            let path = |item: &str| format!("envelopes[{}].{}.{}", i, field, item);
+            let name = |item: &str| format!("Harmonic {} {} {}", i + 1, name, item);
+            parameters.push(value::<EnvelopeRate>(&path("rate"), &name("Rate"), Unit::Time, offset + j * 2));
+            parameters.push(value::<HarmonicEnvelopeLevel>(&path("level"), &name("Level"), Unit::Level, offset + j * 2 + 1)
+                .bits(0b0011_1111));
+        }
+        parameters.push(ParameterInfo::new(&format!("envelopes[{}].loop_type", i), &format!("Harmonic {} Loop", i + 1),
+            0..=3, Unit::Choice, offset + 5)
+            .fields(vec![BitField::new(offset + 5, 0b0100_0000), BitField::new(offset + 3, 0b0100_0000)])
+            .choices(loop_choices.clone()));
+    }
+    parameters
+}
+
+/// Lists the stored wave numbers with the wave names.
+fn wave_choices() -> Vec<(i32, String)> {
+    (0..=512)
+        .filter_map(|number: i32| Wave::from_bytes(&[(number >> 7) as u8, (number & 0x7f) as u8]).ok()
+            .map(|wave| (number, wave.name())))
+        .collect()
+}
+
+/// Lists the stored fixed key numbers with the key names.
+fn fixed_key_choices() -> Vec<(i32, String)> {
+    (0..=108)
+        .filter_map(|number: i32| FixedKey::from_bytes(&[number as u8]).ok()
+            .map(|key| (number, key.to_string())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{*};
+    use crate::registry::checks::{check_parameters, check_writes};
+    use crate::k5000::single::SinglePatch;
+
+    static DATA: &[u8] = include_bytes!("WizooIni.syx");
+
+    fn single() -> SinglePatch {
+        SinglePatch::from_bytes(&DATA[9..]).unwrap()
+    }
+
+    #[test]
+    fn test_common_parameters() {
+        let parameters = common_parameters();
+        let data = single().common.to_bytes();
+        check_parameters(&parameters, &data);
+
+        let volume = parameters.iter().find(|info| info.path == "common.volume").unwrap();
+        assert_eq!(volume.read(&data), single().common.volume.value());
+        assert_eq!(volume.default, 99);
+
+        check_writes::<Common>(&parameters, &data);
+    }
+
+    #[test]
+    fn test_source_parameters() {
+        let parameters = source_parameters(1);
+        let patch = single();
+        let data = patch.sources[1].to_bytes();
+        check_parameters(&parameters, &data);
+        check_writes::<Source>(&parameters, &data);
+
+        let coarse = parameters.iter().find(|info| info.name == "S2 Coarse").unwrap();
+        assert_eq!(coarse.path, "sources[1].oscillator.coarse");
+        assert_eq!(coarse.encoding.bias, 64);
+        assert_eq!(coarse.read(&data), patch.sources[1].oscillator.coarse.value());
+
+        let wave = parameters.iter().find(|info| info.path == "sources[1].oscillator.wave").unwrap();
+        assert_eq!(wave.default, 383);
+        assert_eq!(wave.choice_name(512), Some("ADD"));
+        assert!(!wave.is_valid(511));
+    }
+
+    #[test]
+    fn test_multi_parameters() {
+        let parameters = multi_common_parameters();
+        let data = multi::Common::default().to_bytes();
+        check_parameters(&parameters, &data);
+        check_writes::<multi::Common>(&parameters, &data);
+
+        let parameters = section_parameters(2);
+        let mut section = Section { single: 300, ..Default::default() };
+        section.receive_channel = Some(crate::MIDIChannel::try_new(5).unwrap());
+        let data = section.to_bytes();
+        check_parameters(&parameters, &data);
+        check_writes::<Section>(&parameters, &data);
+
+        let single = parameters.iter().find(|info| info.name == "Section 3 Single").unwrap();
+        assert_eq!(single.path, "sections[2].single");
+        assert_eq!(single.read(&data), 300);
+        let channel = parameters.iter().find(|info| info.path == "sections[2].receive_channel").unwrap();
+        assert_eq!(channel.read(&data), 5);
+        assert_eq!(channel.default, 1);
+    }
+
+    #[test]
+    fn test_drum_parameters() {
+        let parameters = drum_kit_common_parameters();
+        let data = drum::Common::default().to_bytes();
+        check_parameters(&parameters, &data);
+        check_writes::<drum::Common>(&parameters, &data);
+
+        let parameters = drum_key_parameters(10);
+        let data = drum::Key { instrument: 260, ..Default::default() }.to_bytes();
+        check_parameters(&parameters, &data);
+        check_writes::<drum::Key>(&parameters, &data);
+        let instrument = parameters.iter().find(|info| info.path == "keys[10].instrument").unwrap();
+        assert_eq!(instrument.read(&data), 260);
+
+        let parameters = instrument_common_parameters();
+        let data = InstrumentCommon::default().to_bytes();
+        check_parameters(&parameters, &data);
+        check_writes::<InstrumentCommon>(&parameters, &data);
+        assert_eq!(parameters[0].path, "common.volume");
+        assert_eq!(parameters[0].default, 100);
+
+        let parameters = instrument_source_parameters();
+        let data = Source::pcm().to_bytes();
+        check_parameters(&parameters, &data);
+        assert!(parameters.iter().any(|info| info.path == "source.oscillator.coarse" && info.name == "Source Coarse"));
+    }
+
+    #[test]
+    fn test_additive_kit_parameters() {
+        let parameters = additive_kit_parameters(1);
+        let mut kit = AdditiveKit::new();
+        kit.levels.loud[3] = 99;
+        kit.bands[127] = 12;
+        let data = kit.to_bytes();
+        check_parameters(&parameters, &data);
+        check_writes::<AdditiveKit>(&parameters, &data);
+
+        let loud = parameters.iter().find(|info| info.path == "additive_kits[s2].levels.loud[3]").unwrap();
+        assert_eq!(loud.read(&data), 99);
+        let band = parameters.iter().find(|info| info.name == "Kit 2 Band 128").unwrap();
+        assert_eq!(band.read(&data), 12);
+        assert_eq!(instrument_additive_kit_parameters().len(), parameters.len());
+
+        let patch = single();
+        let data = patch.additive_kits["s1"].to_bytes();
+        let parameters = additive_kit_parameters(0);
+        check_parameters(&parameters, &data);
+        check_writes::<AdditiveKit>(&parameters, &data);
+    }
+
+    #[test]
+    fn test_additive_kit_settings() {
+        let parameters = additive_kit_parameters(0);
+        let find = |path: &str| parameters.iter().find(|info| info.path == format!("additive_kits[s1].{}", path)).unwrap();
+
+        let mut kit = AdditiveKit::new();
+        kit.morf.envelope.loop_type = Loop::Loop2;
+        kit.formant_filter.mode = formant::Mode::Lfo;
+        kit.envelopes[5].loop_type = Loop::Loop1;
+        kit.envelopes[6].loop_type = Loop::Loop2;
+        kit.envelopes[6].decay2.level = HarmonicEnvelopeLevel::new(40);
+        let data = kit.to_bytes();
+
+        assert_eq!(find("morf.envelope.loop_type").choice_name(find("morf.envelope.loop_type").read(&data)), Some("Loop2"));
+        assert_eq!(find("formant_filter.mode").choice_name(find("formant_filter.mode").read(&data)), Some("LFO"));
+        let loop1 = find("envelopes[5].loop_type");
+        assert_eq!(loop1.choice_name(loop1.read(&data)), Some("Loop1"));
+        let loop2 = find("envelopes[6].loop_type");
+        assert_eq!(loop2.choice_name(loop2.read(&data)), Some("Loop2"));
+
+        let mut edited = data.clone();
+        loop2.write(&mut edited, 3).unwrap();
+        let edited = AdditiveKit::from_bytes(&edited).unwrap();
+        assert_eq!(edited.envelopes[6].loop_type, Loop::Loop1);
+        assert_eq!(edited.envelopes[6].decay2.level.value(), kit.envelopes[6].decay2.level.value());
+    }
+}
@@ -2,7 +2,6 @@
 //!
 
 use std::convert::TryFrom;
-use std::convert::TryInto;
 use std::fmt;
 use std::collections::BTreeMap;
 
@@ -11,30 +10,40 @@ use bit::BitIndex;
 use crate::{
     SystemExclusiveData,
     ParseError,
-    Checksum
+    Checksum,
+    MIDIChannel,
+    ValueError,
+    check_length,
+    invalid_field
 };
 use crate::k5000::control::{
     Polyphony,
     AmplitudeModulation,
     MacroController,
-    SwitchControl,
-    ControlDestination,
-    Switch
+    SwitchControl
 };
 use crate::k5000::effect::{
     EffectSettings,
     EffectControl
 };
 use crate::k5000::addkit::AdditiveKit;
-use crate::k5000::source::Source;
+use crate::k5000::wave::Wave;
+use crate::k5000::source::{Source, SourceControl};
+use crate::k5000::sysex::{
+    Message,
+    Function,
+    PatchKind,
+    BankIdentifier,
+    ToneNumber
+};
 use crate::k5000::{
     Volume,
-    MacroParameterDepth,
     PortamentoLevel
 };
 
 /// Portamento setting.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Portamento {
     Off,
     On(PortamentoLevel)
@@ -51,33 +60,66 @@ impl fmt::Display for Portamento {
 
 /// Single patch common data.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Common {
     pub effects: EffectSettings,
+
+    /// The drum mark byte before the name. It is not used in single
+    /// patches, but is kept so that the patch is written back as it was read.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "crate::serialize::is_default"))]
+    pub drum_mark: u8,
+
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "crate::serialize::fixed_name::<_, 8>"))]
     pub name: String,
     pub volume: Volume,
     pub polyphony: Polyphony,
+
+    /// The "no use" byte after the polyphony, kept like the drum mark.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "crate::serialize::is_default"))]
+    pub dummy: u8,
+
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_source_count"))]
     pub source_count: u8,
     pub source_mutes: [bool; 6],
     pub amplitude_modulation: AmplitudeModulation,
     pub effect_control: EffectControl,
     pub portamento: Portamento,
+
+    /// The portamento speed byte when portamento is off. The speed is
+    /// not used then, but is kept like the drum mark.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "crate::serialize::is_default"))]
+    pub portamento_dummy: u8,
+
     pub macros: [MacroController; 4],
     pub switches: SwitchControl,
     pub geq: [i8; 7],
 }
 
+/// Deserializes the number of sources, which is 1~6.
+#[cfg(feature = "serde")]
+fn deserialize_source_count<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+    let count = <u8 as serde::Deserialize>::deserialize(deserializer)?;
+    if !(1..=6).contains(&count) {
+        return Err(serde::de::Error::custom(ValueError(1, 6, count as i32)));
+    }
+    Ok(count)
+}
+
 impl Default for Common {
     fn default() -> Self {
         Common {
             effects: Default::default(),
+            drum_mark: 0,
             name: "NewSound".to_string(),
             volume: Volume::new(99),
             polyphony: Polyphony::Poly,
+            dummy: 0,
             source_count: 2,
             source_mutes: [false, false, true, true, true, true],
             amplitude_modulation: Default::default(),
             effect_control: Default::default(),
             portamento: Portamento::Off,
+            portamento_dummy: 0,
             macros: [Default::default(), Default::default(), Default::default(), Default::default()],
             switches: Default::default(),
             geq: [0, 0, 0, 0, 0, 0, 0],
@@ -97,148 +139,109 @@ impl fmt::Display for Common {
     }
 }
 
-fn vec_to_array(v: Vec<i8>) -> [i8; 7] {
-    v.try_into()
-        .unwrap_or_else(|v: Vec<i8>| panic!("Expected a Vec of length {} but it was {}", 4, v.len()))
-}
-
 impl SystemExclusiveData for Common {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
-        eprintln!("Common data ({} bytes): {:02X?}", data.len(), data);
+        check_length(data, Self::data_size())?;
 
         let mut offset = 0;
         let mut size = EffectSettings::data_size();
         let mut start = offset;
         let mut end = offset + size;
         let effects_data = &data[start..end];
-        let effects = EffectSettings::from_bytes(effects_data);
+        let effects = EffectSettings::from_bytes(effects_data)
+            .map_err(|e| e.in_field("effects"))?;
         offset += size;
 
-        eprintln!("GEQ data at offset {}", offset + 1);
         size = 7;
-        end = start + size;
-        let geq_data = data[start..end].to_vec();
-
-        let geq_values = geq_data.iter().map(|n| *n as i8 - 64).collect();  // 58(-6) ~ 70(+6), so 64 is zero
+        let mut geq = [0i8; 7];
+        for (i, value) in geq.iter_mut().enumerate() {
+            *value = (data[offset + i] & 0x7f) as i8 - 64;  // 58(-6) ~ 70(+6), so 64 is zero
+        }
         offset += size;
 
-        eprintln!("Drum mark at offset {}", offset + 1);
-        offset += 1;  // skip the drum mark
-        eprintln!("Skipped 'drum mark'");
+        let drum_mark = data[offset];
+        offset += 1;
 
         size = 8;
         start = offset;
         end = offset + size;
         let name_data = data[start..end].to_vec();
-        let name = String::from_utf8(name_data).unwrap();
-        eprintln!("Name at offset {}", offset + 1);
-        eprintln!("Name = {}", name);
+        let name = String::from_utf8(name_data).map_err(invalid_field("name", data, start))?;
         offset += size;
 
-        let volume = Volume::from(data[offset]);
-        eprintln!("Volume = {}", volume);
+        let volume = Volume::try_from(data[offset]).map_err(invalid_field("volume", data, offset))?;
         offset += 1;
 
-        let polyphony = Polyphony::try_from(data[offset]).unwrap();
-        eprintln!("Polyphony = {}", polyphony);
+        let polyphony = Polyphony::try_from(data[offset]).map_err(invalid_field("polyphony", data, offset))?;
         offset += 1;
 
-        offset += 1;  // skip the "no use" byte
-        eprintln!("Skipped 'no use'");
+        let dummy = data[offset];
+        offset += 1;
 
         let source_count = data[offset];
-        eprintln!("Sources: {}", source_count);
+        if !(1..=6).contains(&source_count) {
+            return Err(invalid_field("source_count", data, offset)(ValueError(1, 6, source_count as i32)));
+        }
         offset += 1;
 
         let mutes_byte = data[offset];
         let mut source_mutes: [bool; 6] = [false; 6];
-        for i in 0..6 {
-            source_mutes[i] = mutes_byte.bit(i);
+        for (i, mute) in source_mutes.iter_mut().enumerate() {
+            *mute = mutes_byte.bit(i);
         }
         offset += 1;
 
-        let amplitude_modulation = AmplitudeModulation::try_from(data[offset]).unwrap();
-        eprintln!("AM = {}", amplitude_modulation);
+        let amplitude_modulation = AmplitudeModulation::try_from(data[offset]).map_err(invalid_field("amplitude_modulation", data, offset))?;
         offset += 1;
 
         size = 6;
         start = offset;
         end = start + size;
         let effect_control_data = &data[start..end];
-        let effect_control = EffectControl::from_bytes(effect_control_data);
-        eprintln!("Effect control = {:?}", effect_control);
+        let effect_control = EffectControl::from_bytes(effect_control_data)
+            .map_err(|e| e.within("effect_control", start))?;
         offset += size;
 
-        let portamento = if data[offset] == 1 {
-            Portamento::On(PortamentoLevel::from(data[offset + 1]))
+        let (portamento, portamento_dummy) = if data[offset] == 1 {
+            (Portamento::On(PortamentoLevel::try_from(data[offset + 1]).map_err(invalid_field("portamento", data, offset + 1))?), 0)
         } else {
-            Portamento::Off
+            (Portamento::Off, data[offset + 1])
         };
-        eprintln!("Portamento: {}", portamento);
         offset += 2;
 
-        let mut macro_destinations = Vec::<u8>::new();
-        for i in 0..8 {
-            eprintln!("Macro {} destination: data = {}", i + 1, data[offset]);
-            macro_destinations.push(data[offset]);
-            offset += 1;
-        }
-        let mut macro_depths = Vec::<u8>::new();
-        for i in 0..8 {
-            eprintln!("Macro {} depth: data = {}", i + 1, data[offset]);
-            macro_depths.push(data[offset]);
-            offset += 1;
+        // The eight macro destinations come first, then the eight depths.
+        let destinations_offset = offset;
+        let depths_offset = offset + 8;
+        let mut macros: [MacroController; 4] = Default::default();
+        for (i, m) in macros.iter_mut().enumerate() {
+            let destination1 = destinations_offset + i * 2;
+            let destination2 = destination1 + 1;
+            let depth1 = depths_offset + i * 2;
+            let depth2 = depth1 + 1;
+            let offsets = [destination1, depth1, destination2, depth2];
+            let macro_data = offsets.map(|o| data[o]);
+            *m = MacroController::from_bytes(&macro_data)
+                .map_err(|e| e.in_field(format!("macros[{}]", i)).map_offset(|o| offsets[o]))?;
         }
+        offset += 16;
 
-        let macros: [MacroController; 4] = [
-            MacroController {
-                destination1: ControlDestination::try_from(macro_destinations[0]).unwrap(),
-                depth1: MacroParameterDepth::from(macro_depths[0]),
-                destination2: ControlDestination::try_from(macro_destinations[1]).unwrap(),
-                depth2: MacroParameterDepth::from(macro_depths[1]),
-            },
-
-            MacroController {
-                destination1: ControlDestination::try_from(macro_destinations[2]).unwrap(),
-                depth1: MacroParameterDepth::from(macro_depths[2]),
-                destination2: ControlDestination::try_from(macro_destinations[3]).unwrap(),
-                depth2: MacroParameterDepth::from(macro_depths[3]),
-            },
-
-            MacroController {
-                destination1: ControlDestination::try_from(macro_destinations[4]).unwrap(),
-                depth1: MacroParameterDepth::from(macro_depths[4]),
-                destination2: ControlDestination::try_from(macro_destinations[5]).unwrap(),
-                depth2: MacroParameterDepth::from(macro_depths[5]),
-            },
-
-            MacroController {
-                destination1: ControlDestination::try_from(macro_destinations[6]).unwrap(),
-                depth1: MacroParameterDepth::from(macro_depths[6]),
-                destination2: ControlDestination::try_from(macro_destinations[7]).unwrap(),
-                depth2: MacroParameterDepth::from(macro_depths[7]),
-            },
-        ];
-
-        let switches = SwitchControl {
-            switch1: Switch::try_from(data[offset]).unwrap(),
-            switch2: Switch::try_from(data[offset + 1]).unwrap(),
-            footswitch1: Switch::try_from(data[offset + 2]).unwrap(),
-            footswitch2: Switch::try_from(data[offset + 3]).unwrap(),
-        };
-        eprintln!("Switches: {:?}", switches);
+        let switches = SwitchControl::from_bytes(&data[offset..])
+            .map_err(|e| e.within("switches", offset))?;
 
         Ok(Common {
-            effects: effects?,
-            geq: vec_to_array(geq_values),
+            effects,
+            geq,
+            drum_mark,
             name,
             volume,
             polyphony,
+            dummy,
             source_count,
             source_mutes,
             amplitude_modulation,
-            effect_control: effect_control?,
+            effect_control,
             portamento,
+            portamento_dummy,
             macros,
             switches,
         })
@@ -249,11 +252,11 @@ impl SystemExclusiveData for Common {
 
         result.extend(self.effects.to_bytes());
         result.extend(self.geq.to_vec().iter().map(|n| (n + 64) as u8));
-        result.push(0);  // drum_mark
+        result.push(self.drum_mark);
         result.extend(self.name.clone().into_bytes());  // note clone()
         result.push(self.volume.into());  // converts value to u8 on the fly
         result.push(self.polyphony as u8);
-        result.push(0);  // "no use"
+        result.push(self.dummy);  // "no use"
         result.push(self.source_count);
 
         let mut mute_byte = 0x00;
@@ -270,7 +273,7 @@ impl SystemExclusiveData for Common {
         match self.portamento {
             Portamento::Off => {
                 result.push(0);
-                result.push(0);
+                result.push(self.portamento_dummy);
             },
             Portamento::On(speed) => {
                 result.push(1);
@@ -298,6 +301,7 @@ impl SystemExclusiveData for Common {
 }
 
 /// Single patch.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SinglePatch {
     pub common: Common,
     pub sources: Vec<Source>,
@@ -331,107 +335,105 @@ impl SinglePatch {
         }
 
         SinglePatch {
-            common: Default::default(),
+            common: Common { source_count: all_sources.len() as u8, ..Default::default() },
             sources: all_sources,
             additive_kits: kits,
         }
     }
 
-    pub fn get_size(data: Vec<u8>) -> usize {
-        let mut offset = 0;
-
-        let original_checksum = data[0];
-        eprintln!("original checksum = {:#02x}", original_checksum);
-
-        let common = Common::from_bytes(&data[1..82]);
-        offset += 81;
-        let mut sources = Vec::<Source>::new();
-        for _i in 0..common.unwrap().source_count {
-            let source = Source::from_bytes(&data[offset..offset + 86]);
-            sources.push(source.unwrap());
-            offset += 86;
-        }
-
-        let pcm_source_count = sources.iter().filter(|s| s.is_pcm()).count();
-        let additive_source_count = sources.iter().filter(|s| s.is_additive()).count();
-
-        (82 + pcm_source_count * 86 + additive_source_count * 462).try_into().unwrap()
+    /// Parses a single patch from the PCM bank B. The data is laid out
+    /// like any other single patch, but all the sources must be PCM,
+    /// so there are no additive kits, and the checksum must match.
+    pub fn pcm_from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        SinglePatch::parse(data, true)
     }
-}
 
-impl Checksum for SinglePatch {
-    fn checksum(&self) -> u8 {
-        // Bank A,D,E,F: check sum = {(common sum) + (source1 sum) [+ (source2~6 sum)] + 0xa5} & 0x7f
+    /// Returns true if this patch has only PCM sources,
+    /// and could therefore be stored in the PCM bank B.
+    pub fn is_pcm_only(&self) -> bool {
+        self.sources.iter().all(|s| s.is_pcm()) && self.additive_kits.is_empty()
+    }
 
-        let common_data = self.common.to_bytes();
-        let mut common_sum: u32 = 0;
-        for d in common_data.iter() {
-            common_sum += (d & 0xff) as u32;
+    /// Makes a single patch from a one single dump message.
+    /// Returns the bank and the tone number with the patch.
+    /// Error offsets are relative to the start of the message.
+    pub fn from_message(message: &Message) -> Result<(BankIdentifier, ToneNumber, SinglePatch), ParseError> {
+        let start = 6;  // F0, manufacturer, channel, function, group, machine
+        if message.function != Function::OneBlockDump {
+            return Err(ParseError::InvalidData(3, "expected a one block dump".to_string()));
+        }
+        if message.function_data.first() != Some(&(PatchKind::Single as u8)) {
+            return Err(ParseError::InvalidData(start as u32, "expected a single patch".to_string()));
         }
 
-        let mut total = common_sum & 0xff;
-
-        for source in self.sources.iter() {
-            let mut source_sum = 0;
-            let source_data = source.to_bytes();
-            for d in source_data.iter() {
-                source_sum = d & 0xff;
-            }
+        let function_data = &message.function_data;
+        check_length(function_data, 2).map_err(|e| e.offset_by(start))?;
+        let bank = BankIdentifier::try_from(function_data[1])
+            .map_err(|_| invalid_field("bank", function_data, 1)("unknown bank").offset_by(start))?;
 
-            total += (source_sum & 0xff) as u32;
-        }
+        let offset = start + function_data.len();
+        check_length(&message.subdata, 1).map_err(|e| e.offset_by(offset))?;
+        let tone_number = ToneNumber::try_new(message.subdata[0])
+            .map_err(invalid_field("tone_number", &message.subdata, 0))
+            .map_err(|e| e.offset_by(offset))?;
 
-        total += 0xa5;
+        let offset = offset + message.subdata.len();
+        let patch = if bank == BankIdentifier::B {
+            SinglePatch::pcm_from_bytes(&message.patch_data)
+        } else {
+            SinglePatch::from_bytes(&message.patch_data)
+        };
+        let patch = patch.map_err(|e| e.offset_by(offset))?;
 
-        (total & 0x7f) as u8
+        Ok((bank, tone_number, patch))
     }
-}
 
-impl Default for SinglePatch {
-    fn default() -> Self {
-        SinglePatch {
-            common: Default::default(),
-            sources: vec![Default::default(), Default::default()],
-            additive_kits: BTreeMap::<String, AdditiveKit>::new(),
+    /// Makes a one single dump message of this patch,
+    /// to be stored as `tone_number` in `bank`.
+    pub fn to_message(&self, channel: MIDIChannel, bank: BankIdentifier, tone_number: ToneNumber) -> Message {
+        Message {
+            channel,
+            function: Function::OneBlockDump,
+            function_data: vec![PatchKind::Single.into(), bank.into()],
+            subdata: vec![tone_number.into()],
+            patch_data: self.to_bytes(),
         }
     }
-}
 
-impl SystemExclusiveData for SinglePatch {
-    fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+    fn parse(data: &[u8], pcm_only: bool) -> Result<Self, ParseError> {
         let mut offset: usize = 0;
         let mut start: usize;
         let mut end: usize;
         let mut size: usize;
 
+        check_length(data, 1 + Common::data_size())?;
+
         let checksum = data[offset];
-        eprintln!("single patch checksum = {:#02x}", checksum);
         offset += 1;
 
         size = Common::data_size();
         start = offset;
         end = start + size;
         let common_data = &data[start..end];
-        let common = Common::from_bytes(common_data);
-        if common.is_err() {
-            return Err(common.unwrap_err());
-        }
+        let common = Common::from_bytes(common_data)
+            .map_err(|e| e.within("common", start))?;
         offset += size;
 
-        eprintln!("{:#04X}: starting to parse {} sources",
-            offset, common.as_ref().unwrap().source_count);
-
         size = Source::data_size();
-        eprintln!("source data size is reported as {} bytes", size);
         let mut sources = Vec::<Source>::new();
-        for i in 0..common.as_ref().unwrap().source_count {
+        for i in 0..common.source_count {
             start = offset;
             end = start + size;
+            check_length(data, end)?;
             let source_data = &data[start..end];
-            eprintln!("{:#04X}: parsing source {}, data start={} end={}",
-                offset, i + 1, start, end);
-            let source = Source::from_bytes(source_data);
-            sources.push(source?);
+            let source = Source::from_bytes(source_data)
+                .map_err(|e| e.within(format!("sources[{}]", i), start))?;
+            if pcm_only && source.is_additive() {
+                let wave_offset = start + SourceControl::data_size();
+                return Err(invalid_field("", data, wave_offset)("ADD source in PCM bank B")
+                    .in_field(format!("sources[{}].oscillator.wave", i)));
+            }
+            sources.push(source);
             offset += size;
         }
 
@@ -444,67 +446,168 @@ impl SystemExclusiveData for SinglePatch {
         while kit_index < kit_count {
             start = offset;
             end = start + size;
+            check_length(data, end)?;
             let kit_data = &data[start..end];
-            let kit = AdditiveKit::from_bytes(kit_data);
-            offset += size;
             let kit_name = format!("s{}", kit_index + 1);
-            additive_kits.insert(kit_name, kit?);
+            let kit = AdditiveKit::from_bytes(kit_data)
+                .map_err(|e| e.within(format!("additive_kits[{}]", kit_name), start))?;
+            offset += size;
+            additive_kits.insert(kit_name, kit);
             kit_index += 1;
         }
 
-        Ok(SinglePatch {
-            common: common?,
+        let patch = SinglePatch {
+            common,
             sources,
             additive_kits,
-        })
+        };
+
+        // The checksum of a PCM bank B patch covers all of its data,
+        // so it can be verified here.
+        if pcm_only && patch.checksum() != checksum {
+            return Err(ParseError::InvalidChecksum(patch.checksum(), checksum));
+        }
+
+        Ok(patch)
     }
 
-    fn to_bytes(&self) -> Vec<u8> {
-        let mut result: Vec<u8> = Vec::new();
+    /// Returns the size of this patch in bytes, including the checksum.
+    pub fn size(&self) -> usize {
+        1 + Common::data_size()
+            + self.sources.len() * Source::data_size()
+            + self.additive_kits.len() * AdditiveKit::data_size()
+    }
 
-        let mut total = 0;
+    /// Measures the size of the patch at the start of `data` in bytes,
+    /// including the checksum, without parsing the whole patch.
+    /// Only the source count and the source waves are examined,
+    /// since every ADD source is followed by an additive kit.
+    pub fn measure(data: &[u8]) -> Result<usize, ParseError> {
+        check_length(data, 1 + Common::data_size())?;
+
+        // The source count follows the effects, GEQ, drum mark, name,
+        // volume, polyphony and the "no use" byte.
+        let source_count_offset = 1 + EffectSettings::data_size() + 7 + 1 + 8 + 3;
+        let source_count = data[source_count_offset];
+        if !(1..=6).contains(&source_count) {
+            return Err(invalid_field("common.source_count", data, source_count_offset)(ValueError(1, 6, source_count as i32)));
+        }
 
-        result.push(self.checksum());
-        eprintln!("checksum, 1 byte");
-        total += 1;
+        let mut size = 1 + Common::data_size();
+        let mut kit_count = 0;
+        for i in 0..source_count as usize {
+            let start = size + SourceControl::data_size();
+            check_length(data, size + Source::data_size())?;
+            let wave = Wave::from_bytes(&data[start..start + Wave::data_size()])
+                .map_err(|e| e.within(format!("sources[{}].oscillator.wave", i), start))?;
+            if wave.is_additive() {
+                kit_count += 1;
+            }
+            size += Source::data_size();
+        }
+        size += kit_count * AdditiveKit::data_size();
+
+        check_length(data, size)?;
+        Ok(size)
+    }
+
+    /// Splits the patch data of a block dump into the patches of `tone_numbers`,
+    /// using `measure` to find out where each patch ends.
+    /// Returns the offset and the data of each patch.
+    pub fn split<'a>(data: &'a [u8], tone_numbers: &[ToneNumber]) -> Result<Vec<(usize, &'a [u8])>, ParseError> {
+        let mut result = Vec::new();
+        let mut offset = 0;
+        for tone_number in tone_numbers {
+            let rest = &data[offset.min(data.len())..];
+            let size = SinglePatch::measure(rest)
+                .map_err(|e| match e {
+                    ParseError::InvalidLength(actual, expected) =>
+                        ParseError::InvalidLength(offset + actual, offset + expected),
+                    other => other.within(format!("patches[{}]", tone_number), offset),
+                })?;
+            result.push((offset, &rest[..size]));
+            offset += size;
+        }
+        Ok(result)
+    }
+}
+
+impl Checksum for SinglePatch {
+    #[allow(clippy::identity_op)]
+    fn checksum(&self) -> u8 {
+        // Bank A,D,E,F: check sum = {(common sum) + (source1 sum) [+ (source2~6 sum)] + 0xa5} & 0x7f
+        // Bank B has only PCM sources, so the same sum covers the whole patch.
+
+        let common_data = self.common.to_bytes();
+        let mut common_sum: u32 = 0;
+        for d in common_data.iter() {
+            common_sum += (d & 0xff) as u32;
+        }
 
-        let common_bytes = self.common.to_bytes();
-        result.extend(&common_bytes);
-        eprintln!("single common, {} bytes", common_bytes.len());
-        total += common_bytes.len();
+        let mut total = common_sum & 0xff;
 
         for source in self.sources.iter() {
-            let source_bytes = source.to_bytes();
-            result.extend(&source_bytes);
-            eprintln!("source, {} bytes", source_bytes.len());
-            total += source_bytes.len();
+            let mut source_sum = 0;
+            let source_data = source.to_bytes();
+            for d in source_data.iter() {
+                source_sum += (d & 0xff) as u32;
+            }
+
+            total += source_sum & 0xff;
         }
 
-        for k in self.additive_kits.keys() {
-            let kit = self.additive_kits.get(k).unwrap();
-            let kit_bytes = kit.to_bytes();
-            result.extend(&kit_bytes);
-            eprintln!("additive kit, {} bytes", kit_bytes.len());
-            total += kit_bytes.len();
+        total += 0xa5;
+
+        (total & 0x7f) as u8
+    }
+}
+
+impl Default for SinglePatch {
+    fn default() -> Self {
+        SinglePatch {
+            common: Default::default(),
+            sources: vec![Default::default(), Default::default()],
+            additive_kits: BTreeMap::<String, AdditiveKit>::new(),
+        }
+    }
+}
+
+impl SystemExclusiveData for SinglePatch {
+    fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        SinglePatch::parse(data, false)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut result: Vec<u8> = Vec::new();
+
+        result.push(self.checksum());
+        result.extend(self.common.to_bytes());
+
+        for source in self.sources.iter() {
+            result.extend(source.to_bytes());
         }
 
-        eprintln!("total {} bytes", total);
+        for kit in self.additive_kits.values() {
+            result.extend(kit.to_bytes());
+        }
 
         result
     }
 
+    /// Returns the size of the checksum and the common data,
+    /// since the actual size depends on the sources.
+    /// Use `size` to get the size of a particular patch,
+    /// or `measure` to get it from System Exclusive data.
     fn data_size() -> usize {
-        todo!("Compute single patch size")
+        1 + Common::data_size()
     }
 }
 
 impl fmt::Display for SinglePatch {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut sources_str = String::new();
-        let mut source_num = 1;
-        for source in self.sources.iter() {
-            sources_str.push_str(format!("Source {}:\n{}\n\n", source_num, source).as_str());
-            source_num += 1;
+        for (i, source) in self.sources.iter().enumerate() {
+            sources_str.push_str(format!("Source {}:\n{}\n\n", i + 1, source).as_str());
         }
         write!(f, "{}\nSources:\n{}", self.common, sources_str)
     }
@@ -565,6 +668,22 @@ mod tests {
         assert_eq!(common.unwrap().name, "WizooIni");
     }
 
+    #[test]
+    fn test_common_unused_bytes_round_trip() {
+        let mut data = Common::default().to_bytes();
+        data[38] = 0x01;  // drum mark
+        data[49] = 0x22;  // "no use"
+        data[59] = 0x00;  // portamento off...
+        data[60] = 0x33;  // ...but with a speed
+
+        let common = Common::from_bytes(&data).unwrap();
+        assert_eq!(common.drum_mark, 0x01);
+        assert_eq!(common.dummy, 0x22);
+        assert!(matches!(common.portamento, Portamento::Off));
+        assert_eq!(common.portamento_dummy, 0x33);
+        assert_eq!(common.to_bytes(), data);
+    }
+
     #[test]
     fn test_single_patch_from_bytes() {
         let data = include_bytes!("WizooIni.syx");
@@ -573,4 +692,60 @@ mod tests {
         let single_patch = SinglePatch::from_bytes(&data[9..]);
         assert_eq!(single_patch.unwrap().common.name, "WizooIni");
     }
+
+    #[test]
+    fn test_single_patch_round_trip() {
+        let data = include_bytes!("WizooIni.syx");
+        let patch_data = &data[9..data.len() - 1];
+        let single_patch = SinglePatch::from_bytes(patch_data).unwrap();
+        assert_eq!(single_patch.to_bytes(), patch_data.to_vec());
+    }
+
+    #[test]
+    fn test_single_patch_size() {
+        let data = include_bytes!("WizooIni.syx");
+        let patch_data = &data[9..data.len() - 1];
+        assert_eq!(SinglePatch::measure(patch_data), Ok(patch_data.len()));
+        assert_eq!(SinglePatch::from_bytes(patch_data).unwrap().size(), patch_data.len());
+
+        let patch = SinglePatch::new(2, 2);
+        let patch_data = patch.to_bytes();
+        assert_eq!(patch.size(), patch_data.len());
+        assert_eq!(SinglePatch::measure(&patch_data), Ok(patch_data.len()));
+        assert!(matches!(SinglePatch::measure(&patch_data[..patch_data.len() - 1]),
+            Err(ParseError::InvalidLength(_, _))));
+    }
+
+    #[test]
+    fn test_split() {
+        let mut data = SinglePatch::new(1, 1).to_bytes();
+        let first_size = data.len();
+        data.extend(SinglePatch::new(3, 0).to_bytes());
+
+        let tone_numbers: Vec<ToneNumber> = ToneNumber::all().take(3).collect();
+        let patches = SinglePatch::split(&data, &tone_numbers[..2]).unwrap();
+        assert_eq!(patches.len(), 2);
+        assert_eq!(patches[0].0, 0);
+        assert_eq!(patches[1].0, first_size);
+        assert_eq!(patches[1].1.len(), data.len() - first_size);
+
+        assert_eq!(SinglePatch::split(&data, &tone_numbers).err(),
+            Some(ParseError::InvalidLength(data.len(), data.len() + SinglePatch::data_size())));
+    }
+
+    #[test]
+    fn test_split_error_path_uses_tone_number() {
+        let mut data = SinglePatch::new(1, 1).to_bytes();
+        let mut second = SinglePatch::new(1, 1).to_bytes();
+        let wave = 1 + Common::data_size() + SourceControl::data_size();
+        second[wave] = 0x07;  // wave number 1024, out of range
+        second[wave + 1] = 0x7f;
+        data.extend(second);
+
+        let tone_numbers = [ToneNumber::try_new(3).unwrap(), ToneNumber::try_new(64).unwrap()];
+        match SinglePatch::split(&data, &tone_numbers) {
+            Err(ParseError::InvalidField(error)) => assert!(error.path.starts_with("patches[64]"), "{}", error.path),
+            other => panic!("expected an invalid field error, got {:?}", other),
+        }
+    }
 }
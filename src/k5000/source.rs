@@ -10,7 +10,10 @@ use crate::k5000::control::{
 };
 use crate::{
     SystemExclusiveData,
-    ParseError
+    ParseError,
+    check_length,
+    invalid_field,
+    note_number
 };
 use crate::k5000::osc::*;
 use crate::k5000::filter::*;
@@ -23,7 +26,6 @@ use crate::k5000::{
     KeyOnDelay
 };
 
-use pretty_hex::*;
 
 /// Key in a keyboard zone.
 #[derive(Debug, Eq, PartialEq)]
@@ -35,13 +37,19 @@ pub struct Key {
 static NOTE_NAMES: &str = "C C#D D#E F F#G G#A A#B ";
 
 impl Key {
-    // TODO: Add constructor from note name
+    /// Finds the key with `name`, like "C#4", as returned by `name`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        note_number(name, -1)
+            .and_then(|n| u8::try_from(n).ok())
+            .filter(|&note| note < 128)
+            .map(|note| Key { note })
+    }
 
     pub fn name(&self) -> String {
         // Adapted from RIMD:
-        let octave = (self.note as f32 / 12 as f32).floor() - 1.0;
+        let octave = (self.note as f32 / 12.0).floor() - 1.0;
         let name_index = (self.note as usize % 12) * 2;
-        let slice = if NOTE_NAMES.as_bytes()[name_index + 1] == ' ' as u8 {
+        let slice = if NOTE_NAMES.as_bytes()[name_index + 1] == b' ' {
             &NOTE_NAMES[name_index..(name_index + 1)]
         } else {
             &NOTE_NAMES[name_index..(name_index + 2)]
@@ -56,8 +64,25 @@ impl fmt::Display for Key {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for Key {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Key {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = <String as serde::Deserialize>::deserialize(deserializer)?;
+        Key::from_name(&name)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid key name \"{}\", expected C-1...G9", name)))
+    }
+}
+
 /// Keyboard zone.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Zone {
     /// Low key of the zone.
     pub low: Key,
@@ -74,6 +99,7 @@ impl fmt::Display for Zone {
 
 impl SystemExclusiveData for Zone {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         Ok(Zone { low: Key { note: data[0] }, high: Key { note: data[1] } })
     }
 
@@ -89,6 +115,7 @@ impl SystemExclusiveData for Zone {
 
 /// Source control settings.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SourceControl {
     pub zone: Zone,
     pub vel_sw: VelocitySwitchSettings,
@@ -127,18 +154,17 @@ impl fmt::Display for SourceControl {
 
 impl SystemExclusiveData for SourceControl {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
-        eprintln!("Source control data = {}", simple_hex(&data));
-
+        check_length(data, Self::data_size())?;
         Ok(SourceControl {
             zone: Zone { low: Key { note: data[0] }, high: Key { note: data[1] } },
-            vel_sw: VelocitySwitchSettings::from_bytes(&[data[2]])?,
+            vel_sw: VelocitySwitchSettings::from_bytes(&data[2..3]).map_err(|e| e.within("vel_sw", 2))?,
             effect_path: data[3],
-            volume: Volume::from(data[4]),
-            bender_pitch: BenderPitch::from(data[5]),
-            bender_cutoff: BenderCutoff::from(data[6]),
-            modulation: ModulationSettings::from_bytes(&data[7..25])?,
-            key_on_delay: KeyOnDelay::from(data[25]),
-            pan: PanSettings::from_bytes(&data[26..28])?,
+            volume: Volume::try_from(data[4]).map_err(invalid_field("volume", data, 4))?,
+            bender_pitch: BenderPitch::try_from(data[5]).map_err(invalid_field("bender_pitch", data, 5))?,
+            bender_cutoff: BenderCutoff::try_from(data[6]).map_err(invalid_field("bender_cutoff", data, 6))?,
+            modulation: ModulationSettings::from_bytes(&data[7..25]).map_err(|e| e.within("modulation", 7))?,
+            key_on_delay: KeyOnDelay::try_from(data[25]).map_err(invalid_field("key_on_delay", data, 25))?,
+            pan: PanSettings::from_bytes(&data[26..28]).map_err(|e| e.within("pan", 26))?,
         })
     }
 
@@ -170,6 +196,7 @@ impl SystemExclusiveData for SourceControl {
 
 /// Source.
 #[derive(Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Source {
     pub oscillator: Oscillator,
     pub filter: Filter,
@@ -215,34 +242,13 @@ impl fmt::Display for Source {
 
 impl SystemExclusiveData for Source {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
-        //eprintln!("Source data ({} bytes): {:?}", data.len(), data);
-        eprintln!("Source data size = {} bytes", data.len());
-        eprintln!("Reported sizes:");
-        let source_control_size = SourceControl::data_size();
-        eprintln!("Source control = {} bytes",
-            source_control_size);
-        let amplifier_size = Amplifier::data_size();
-        eprintln!("Amplifier data = {} bytes",
-            amplifier_size);
-        let oscillator_size = Oscillator::data_size();
-        eprintln!("Oscillator data = {} bytes",
-            oscillator_size);
-        let filter_size = Filter::data_size();
-        eprintln!("Filter data = {} bytes",
-            filter_size);
-        let lfo_size = Lfo::data_size();
-        eprintln!("LFO data = {} bytes",
-            lfo_size);
-        let total_size = source_control_size + amplifier_size + oscillator_size
-            + filter_size + lfo_size;
-        eprintln!("Total = {} bytes", total_size);
-
+        check_length(data, Self::data_size())?;
         Ok(Source {
-            control: SourceControl::from_bytes(&data[..28])?,
-            oscillator: Oscillator::from_bytes(&data[28..40])?,
-            filter: Filter::from_bytes(&data[40..60])?,
-            amplifier: Amplifier::from_bytes(&data[60..75])?,
-            lfo: Lfo::from_bytes(&data[75..86])?,
+            control: SourceControl::from_bytes(&data[..28]).map_err(|e| e.in_field("control"))?,
+            oscillator: Oscillator::from_bytes(&data[28..40]).map_err(|e| e.within("oscillator", 28))?,
+            filter: Filter::from_bytes(&data[40..60]).map_err(|e| e.within("filter", 40))?,
+            amplifier: Amplifier::from_bytes(&data[60..75]).map_err(|e| e.within("amplifier", 60))?,
+            lfo: Lfo::from_bytes(&data[75..86]).map_err(|e| e.within("lfo", 75))?,
         })
     }
 
@@ -277,6 +283,14 @@ mod tests {
         assert_eq!(key.name(), "C4");
     }
 
+    #[test]
+    fn test_key_from_name() {
+        assert_eq!(Key::from_name("C4"), Some(Key { note: 60 }));
+        assert_eq!(Key::from_name("C-1"), Some(Key { note: 0 }));
+        assert_eq!(Key::from_name("G9"), Some(Key { note: 127 }));
+        assert_eq!(Key::from_name("G#9"), None);
+    }
+
     #[test]
     fn test_source_control_from_bytes() {
         let data = vec![
@@ -11,8 +11,13 @@ use strum_macros;
 use crate::{
     SystemExclusiveData,
     ParseError,
-    MIDIChannel
+    ValueError,
+    MIDIChannel,
+    check_length,
+    invalid_field
 };
+use crate::k5000::multi::MULTI_COUNT;
+use crate::k5000::drum::USER_INSTRUMENT_COUNT;
 
 /// Kawai K5000 System Exclusive functions.
 #[derive(Debug, Eq, PartialEq, Copy, Clone, TryFromPrimitive)]
@@ -34,45 +39,140 @@ pub enum Function {
 }
 
 /// K5000 System Exclusive message.
+///
+/// The complete message is `F0 40 <channel> <function> 00 0A`,
+/// followed by the function data, the subdata and the patch data,
+/// and terminated by `F7`.
+#[derive(Debug, Eq, PartialEq, Clone)]
 pub struct Message {
     pub channel: MIDIChannel,
     pub function: Function,
-    pub function_data: Vec<u8>,
-    pub subdata: Vec<u8>,
+    pub function_data: Vec<u8>,  // patch kind and bank, or the parameters
+    pub subdata: Vec<u8>,  // tone/instrument number, or tone map
     pub patch_data: Vec<u8>,
 }
 
+/// Group ID and machine ID bytes that follow the function in every message.
+const GROUP_AND_MACHINE: [u8; 2] = [0x00, 0x0A];
+
+/// Tone map size in bytes.
+const TONE_MAP_SIZE: usize = 19;
+
+impl Message {
+    /// Returns the sizes of the function data and the subdata
+    /// for the message `function`, given the data after the machine ID.
+    fn field_sizes(function: Function, data: &[u8]) -> Result<(usize, usize), ParseError> {
+        let sizes = match function {
+            Function::OneBlockDumpRequest | Function::OneBlockDump => {
+                check_length(data, 1)?;
+                match PatchKind::try_from(data[0]).map_err(invalid_field("kind", data, 0))? {
+                    PatchKind::Single => (2, 1),  // kind and bank, tone number
+                    PatchKind::Multi | PatchKind::DrumInstrument => (1, 1),
+                    PatchKind::DrumKit => (1, 0),
+                }
+            },
+            Function::AllBlockDumpRequest => {
+                check_length(data, 1)?;
+                match PatchKind::try_from(data[0]).map_err(invalid_field("kind", data, 0))? {
+                    PatchKind::Single => (2, 0),
+                    _ => (1, 0),
+                }
+            },
+            Function::AllBlockDump => {
+                check_length(data, 1)?;
+                match PatchKind::try_from(data[0]).map_err(invalid_field("kind", data, 0))? {
+                    PatchKind::Single => {
+                        check_length(data, 2)?;
+                        // Only the PCM bank B block has no tone map
+                        if data[1] == BankIdentifier::B as u8 { (2, 0) } else { (2, TONE_MAP_SIZE) }
+                    },
+                    _ => (1, 0),
+                }
+            },
+            Function::ParameterSend | Function::TrackControl
+                | Function::ModeChange | Function::Remote => (data.len(), 0),
+            Function::WriteComplete | Function::WriteError
+                | Function::WriteErrorByProtect | Function::WriteErrorByMemoryFull
+                | Function::WriteErrorByNoExpandedMemory => (0, 0),
+        };
+
+        check_length(data, sizes.0 + sizes.1)?;
+        Ok(sizes)
+    }
+
+    /// Returns the size of this message in bytes, including
+    /// the System Exclusive initiator and terminator.
+    pub fn size(&self) -> usize {
+        Self::data_size() + self.function_data.len() + self.subdata.len() + self.patch_data.len()
+    }
+}
+
 impl SystemExclusiveData for Message {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
+
+        if data[0] != 0xF0 {
+            return Err(invalid_field("initiator", data, 0)("expected F0H"));
+        }
+        if data[1] != 0x40 {
+            return Err(invalid_field("manufacturer", data, 1)("expected Kawai (40H)"));
+        }
+        let channel = MIDIChannel::from_bytes(&data[2..3]).map_err(|e| e.within("channel", 2))?;
+        let function = Function::try_from(data[3]).map_err(invalid_field("function", data, 3))?;
+        if data[4] != GROUP_AND_MACHINE[0] {
+            return Err(invalid_field("group", data, 4)("expected synthesizer group (00H)"));
+        }
+        if data[5] != GROUP_AND_MACHINE[1] {
+            return Err(invalid_field("machine", data, 5)("expected K5000 (0AH)"));
+        }
+        let last = data.len() - 1;
+        if data[last] != 0xF7 {
+            return Err(invalid_field("terminator", data, last)("expected F7H"));
+        }
+
+        let start = 6;
+        let body = &data[start..last];
+        let (function_data_size, subdata_size) = Message::field_sizes(function, body)
+            .map_err(|e| match e {
+                ParseError::InvalidLength(actual, expected) =>
+                    ParseError::InvalidLength(start + actual + 1, start + expected + 1),
+                other => other.offset_by(start),
+            })?;
+
+        let (function_data, rest) = body.split_at(function_data_size);
+        let (subdata, patch_data) = rest.split_at(subdata_size);
+
         Ok(Message {
-            channel: MIDIChannel::try_new(data[2].into()).unwrap(),
-            function: Function::try_from(data[3]).unwrap(),
-            function_data: Vec::<u8>::new(),  // TODO: fix this
-            subdata: Vec::<u8>::new(),  // TODO: fix this
-            patch_data: data[3..].to_vec(),
+            channel,
+            function,
+            function_data: function_data.to_vec(),
+            subdata: subdata.to_vec(),
+            patch_data: patch_data.to_vec(),
         })
     }
 
     fn to_bytes(&self) -> Vec<u8> {
         let mut result: Vec<u8> = Vec::new();
 
+        result.push(0xF0);
         result.push(0x40); // Kawai manufacturer ID
-        result.push(self.channel.value() as u8);
-
+        result.extend(self.channel.to_bytes());  // from 1...16 to 0...15
         result.push(self.function as u8);
+        result.extend(GROUP_AND_MACHINE);
         result.extend(&self.function_data);
         result.extend(&self.subdata);
         result.extend(&self.patch_data);
+        result.push(0xF7);
 
         result
     }
 
-    fn data_size() -> usize {
-        todo!("Compute K5000 message size")
-    }
+    /// Returns the size of the shortest possible message,
+    /// since the actual size depends on the function.
+    /// Use `size` to get the size of a particular message.
+    fn data_size() -> usize { 7 }
 }
 
-
 /// Cardinality of SysEx message (one patch or block of patches).
 #[derive(Debug, PartialEq, Copy, Clone, strum_macros::Display)]
 #[repr(u8)]
@@ -125,8 +225,10 @@ impl TryFrom<u8> for BankIdentifier {
     }
 }
 
+serialize_by_name!(BankIdentifier);
+
 /// Patch kind.
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Copy, Clone, TryFromPrimitive)]
 #[repr(u8)]
 pub enum PatchKind {
     Single = 0x00,
@@ -152,6 +254,240 @@ impl fmt::Display for PatchKind {
     }
 }
 
+/// Checks that `number` is less than `count`.
+fn check_number(number: u8, count: usize) -> Result<u8, ValueError> {
+    if (number as usize) < count {
+        Ok(number)
+    } else {
+        Err(ValueError(0, count as i32 - 1, number as i32))
+    }
+}
+
+/// Dump request for one patch or a block of patches.
+///
+/// One block: `F0 40 0n 00 00 0A <kind> [<bank>] [<number>] F7`,
+/// all block: `F0 40 0n 01 00 0A <kind> [<bank>] F7`.
+/// Only single patches have a bank, and only one single, multi
+/// or drum instrument has a number. There is only one drum kit.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct DumpRequest {
+    pub channel: MIDIChannel,
+    pub cardinality: Cardinality,
+    pub kind: PatchKind,
+    pub bank: Option<BankIdentifier>,
+    pub number: Option<u8>,
+}
+
+impl DumpRequest {
+    /// Makes a request for one single patch (tone number 0~127) in `bank`.
+    pub fn one_single(channel: MIDIChannel, bank: BankIdentifier, tone_number: u8) -> Result<Self, ValueError> {
+        let tone_number = ToneNumber::try_new(tone_number)?;
+        Ok(DumpRequest { channel, cardinality: Cardinality::One, kind: PatchKind::Single, bank: Some(bank), number: Some(tone_number.value()) })
+    }
+
+    /// Makes a request for all the single patches in `bank`.
+    pub fn block_single(channel: MIDIChannel, bank: BankIdentifier) -> Self {
+        DumpRequest { channel, cardinality: Cardinality::Block, kind: PatchKind::Single, bank: Some(bank), number: None }
+    }
+
+    /// Makes a request for one multi (or combi) patch (0~63).
+    pub fn one_multi(channel: MIDIChannel, number: u8) -> Result<Self, ValueError> {
+        let number = MultiNumber::try_new(number)?;
+        Ok(DumpRequest { channel, cardinality: Cardinality::One, kind: PatchKind::Multi, bank: None, number: Some(number.value()) })
+    }
+
+    /// Makes a request for all the multi (or combi) patches.
+    pub fn block_multi(channel: MIDIChannel) -> Self {
+        DumpRequest { channel, cardinality: Cardinality::Block, kind: PatchKind::Multi, bank: None, number: None }
+    }
+
+    /// Makes a request for the drum kit.
+    pub fn drum_kit(channel: MIDIChannel) -> Self {
+        DumpRequest { channel, cardinality: Cardinality::One, kind: PatchKind::DrumKit, bank: None, number: None }
+    }
+
+    /// Makes a request for one user drum instrument (0~31).
+    pub fn one_drum_instrument(channel: MIDIChannel, number: u8) -> Result<Self, ValueError> {
+        let number = InstrumentNumber::try_new(number)?;
+        Ok(DumpRequest { channel, cardinality: Cardinality::One, kind: PatchKind::DrumInstrument, bank: None, number: Some(number.value()) })
+    }
+
+    /// Makes a request for all the user drum instruments.
+    pub fn block_drum_instrument(channel: MIDIChannel) -> Self {
+        DumpRequest { channel, cardinality: Cardinality::Block, kind: PatchKind::DrumInstrument, bank: None, number: None }
+    }
+
+    /// Makes the request message.
+    pub fn to_message(&self) -> Message {
+        let mut function_data = vec![self.kind.into()];
+        if let Some(bank) = self.bank {
+            function_data.push(bank.into());
+        }
+        Message {
+            channel: self.channel,
+            function: match self.cardinality {
+                Cardinality::One => Function::OneBlockDumpRequest,
+                Cardinality::Block => Function::AllBlockDumpRequest,
+            },
+            function_data,
+            subdata: self.number.into_iter().collect(),
+            patch_data: vec![],
+        }
+    }
+
+    /// Parses a request from a message.
+    /// Error offsets are relative to the start of the message.
+    pub fn from_message(message: &Message) -> Result<Self, ParseError> {
+        let start = 6;  // F0, manufacturer, channel, function, group, machine
+        let cardinality = match message.function {
+            Function::OneBlockDumpRequest => Cardinality::One,
+            Function::AllBlockDumpRequest => Cardinality::Block,
+            _ => return Err(ParseError::InvalidData(3, "expected a dump request".to_string())),
+        };
+
+        let function_data = &message.function_data;
+        check_length(function_data, 1).map_err(|e| e.offset_by(start))?;
+        let kind = PatchKind::try_from(function_data[0])
+            .map_err(|e| invalid_field("kind", function_data, 0)(e).offset_by(start))?;
+        let bank = if kind == PatchKind::Single {
+            check_length(function_data, 2).map_err(|e| e.offset_by(start))?;
+            Some(BankIdentifier::try_from(function_data[1])
+                .map_err(|_| invalid_field("bank", function_data, 1)("unknown bank").offset_by(start))?)
+        } else {
+            None
+        };
+
+        if !message.patch_data.is_empty() {
+            let offset = start + function_data.len() + message.subdata.len();
+            return Err(ParseError::InvalidData(offset as u32, "unexpected data in request".to_string()));
+        }
+        if kind == PatchKind::DrumKit && cardinality == Cardinality::Block {
+            return Err(invalid_field("kind", function_data, 0)("there is no block of drum kits").offset_by(start));
+        }
+
+        // The message framing leaves a number only for one single, multi or drum instrument.
+        let number = match message.subdata.first() {
+            Some(&n) => {
+                let count = match kind {
+                    PatchKind::Multi => MULTI_COUNT,
+                    PatchKind::DrumInstrument => USER_INSTRUMENT_COUNT,
+                    _ => MAX_TONE_COUNT as usize,
+                };
+                Some(check_number(n, count)
+                    .map_err(|e| invalid_field("number", &message.subdata, 0)(e).offset_by(start + function_data.len()))?)
+            },
+            None => None,
+        };
+
+        Ok(DumpRequest { channel: message.channel, cardinality, kind, bank, number })
+    }
+}
+
+impl fmt::Display for DumpRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Dump request for {} {}", self.cardinality, self.kind)?;
+        if let Some(bank) = self.bank {
+            write!(f, " bank {}", bank)?;
+        }
+        if let Some(number) = self.number {
+            write!(f, " number {}", number + 1)?;
+        }
+        write!(f, ", channel {}", self.channel.value())
+    }
+}
+
+/// Reason why the K5000 could not write the data it received.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum WriteError {
+    General,
+    Protect,
+    MemoryFull,
+    NoExpandedMemory,
+}
+
+impl WriteError {
+    fn function(&self) -> Function {
+        match self {
+            WriteError::General => Function::WriteError,
+            WriteError::Protect => Function::WriteErrorByProtect,
+            WriteError::MemoryFull => Function::WriteErrorByMemoryFull,
+            WriteError::NoExpandedMemory => Function::WriteErrorByNoExpandedMemory,
+        }
+    }
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match self {
+            WriteError::General => "Write error",
+            WriteError::Protect => "Write error: memory is protected",
+            WriteError::MemoryFull => "Write error: memory is full",
+            WriteError::NoExpandedMemory => "Write error: no expanded memory installed",
+        })
+    }
+}
+
+impl std::error::Error for WriteError { }
+
+/// Response sent by the K5000 after receiving a dump:
+/// `F0 40 0n <function> 00 0A F7`.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct Response {
+    pub channel: MIDIChannel,
+    pub result: Result<(), WriteError>,
+}
+
+impl Response {
+    /// Makes a write complete response.
+    pub fn complete(channel: MIDIChannel) -> Self {
+        Response { channel, result: Ok(()) }
+    }
+
+    /// Makes a write error response.
+    pub fn error(channel: MIDIChannel, error: WriteError) -> Self {
+        Response { channel, result: Err(error) }
+    }
+
+    /// Makes the response message.
+    pub fn to_message(&self) -> Message {
+        Message {
+            channel: self.channel,
+            function: match self.result {
+                Ok(()) => Function::WriteComplete,
+                Err(error) => error.function(),
+            },
+            function_data: vec![],
+            subdata: vec![],
+            patch_data: vec![],
+        }
+    }
+
+    /// Parses a response from a message.
+    pub fn from_message(message: &Message) -> Result<Self, ParseError> {
+        let result = match message.function {
+            Function::WriteComplete => Ok(()),
+            Function::WriteError => Err(WriteError::General),
+            Function::WriteErrorByProtect => Err(WriteError::Protect),
+            Function::WriteErrorByMemoryFull => Err(WriteError::MemoryFull),
+            Function::WriteErrorByNoExpandedMemory => Err(WriteError::NoExpandedMemory),
+            _ => return Err(ParseError::InvalidData(3, "expected a write response".to_string())),
+        };
+        if !message.patch_data.is_empty() {
+            return Err(ParseError::InvalidData(6, "unexpected data in response".to_string()));
+        }
+        Ok(Response { channel: message.channel, result })
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.result {
+            Ok(()) => write!(f, "Write complete, channel {}", self.channel.value()),
+            Err(error) => write!(f, "{}, channel {}", error, self.channel.value()),
+        }
+    }
+}
+
 /// System Exclusive dump header.
 #[derive(Debug, PartialEq)]
 pub struct Header {
@@ -172,12 +508,12 @@ impl Header {
     ///
     /// * `buf` - a byte vector with the header data
     pub fn identify_vec(buf: &[u8]) -> Option<Header> {
-        let channel = MIDIChannel::try_new((buf[0] + 1).into()); // use 1...16
+        let channel = MIDIChannel::from_bytes(buf).ok()?; // use 1...16
         let result = match &buf[1..] {
             // One ADD Bank A (see 3.1.1b)
             [0x20, 0x00, 0x0A, 0x00, 0x00, sub1, ..] => {
                 Some(Header {
-                    channel: channel.unwrap(),
+                    channel,
                     cardinality: Cardinality::One,
                     bank_identifier: Some(BankIdentifier::A),
                     kind: PatchKind::Single,
@@ -188,7 +524,7 @@ impl Header {
             // One PCM Bank B (see 3.1.1d)
             [0x20, 0x00, 0x0A, 0x00, 0x01, sub1, ..] => {
                 Some(Header {
-                    channel: channel.unwrap(),
+                    channel,
                     cardinality: Cardinality::One,
                     bank_identifier: Some(BankIdentifier::B),
                     kind: PatchKind::Single,
@@ -199,7 +535,7 @@ impl Header {
             // One ADD Bank D (see 3.1.1k)
             [0x20, 0x00, 0x0A, 0x00, 0x02, sub1, ..] => {
                 Some(Header {
-                    channel: channel.unwrap(),
+                    channel,
                     cardinality: Cardinality::One,
                     bank_identifier: Some(BankIdentifier::D),
                     kind: PatchKind::Single,
@@ -210,7 +546,7 @@ impl Header {
             // One Exp Bank E (see 3.1.1m)
             [0x20, 0x00, 0x0A, 0x00, 0x03, sub1, ..] => {
                 Some(Header {
-                    channel: channel.unwrap(),
+                    channel,
                     cardinality: Cardinality::One,
                     bank_identifier: Some(BankIdentifier::E),
                     kind: PatchKind::Single,
@@ -221,7 +557,7 @@ impl Header {
             // One Exp Bank F (see 3.1.1o)
             [0x20, 0x00, 0x0A, 0x00, 0x04, sub1, ..] => {
                 Some(Header {
-                    channel: channel.unwrap(),
+                    channel,
                     cardinality: Cardinality::One,
                     bank_identifier: Some(BankIdentifier::F),
                     kind: PatchKind::Single,
@@ -232,7 +568,7 @@ impl Header {
             // One Multi/Combi (see 3.1.1i)
             [0x20, 0x00, 0x0A, 0x20, sub1, ..] => {
                 Some(Header {
-                    channel: channel.unwrap(),
+                    channel,
                     cardinality: Cardinality::One,
                     bank_identifier: None,
                     kind: PatchKind::Multi,
@@ -243,7 +579,7 @@ impl Header {
             // Block ADD Bank A (see 3.1.1a)
             [0x21, 0x00, 0x0A, 0x00, 0x00, tone_map @ ..] => {
                 Some(Header {
-                    channel: channel.unwrap(),
+                    channel,
                     cardinality: Cardinality::Block,
                     bank_identifier: Some(BankIdentifier::A),
                     kind: PatchKind::Single,
@@ -254,7 +590,7 @@ impl Header {
             // Block PCM Bank B -- all PCM data, no tone map
             [0x21, 0x00, 0x0A, 0x00, 0x01, ..] => {
                 Some(Header {
-                    channel: channel.unwrap(),
+                    channel,
                     cardinality: Cardinality::Block,
                     bank_identifier: Some(BankIdentifier::B),
                     kind: PatchKind::Single,
@@ -265,7 +601,7 @@ impl Header {
             // Block ADD Bank D (see 3.1.1j)
             [0x21, 0x00, 0x0A, 0x00, 0x02, tone_map @ ..] => {
                 Some(Header {
-                    channel: channel.unwrap(),
+                    channel,
                     cardinality: Cardinality::Block,
                     bank_identifier: Some(BankIdentifier::D),
                     kind: PatchKind::Single,
@@ -276,7 +612,7 @@ impl Header {
             // Block Exp Bank E (see 3.1.1l)
             [0x21, 0x00, 0x0A, 0x00, 0x03, tone_map @ ..] => {
                 Some(Header {
-                    channel: channel.unwrap(),
+                    channel,
                     cardinality: Cardinality::Block,
                     bank_identifier: Some(BankIdentifier::E),
                     kind: PatchKind::Single,
@@ -287,7 +623,7 @@ impl Header {
             // Block Exp Bank F (see 3.1.1n)
             [0x21, 0x00, 0x0A, 0x00, 0x04, tone_map @ ..] => {
                 Some(Header {
-                    channel: channel.unwrap(),
+                    channel,
                     cardinality: Cardinality::Block,
                     bank_identifier: Some(BankIdentifier::F),
                     kind: PatchKind::Single,
@@ -298,7 +634,7 @@ impl Header {
             // Block Multi/Combi (see 3.1.1h)
             [0x21, 0x00, 0x0A, 0x20, ..] => {
                 Some(Header {
-                    channel: channel.unwrap(),
+                    channel,
                     cardinality: Cardinality::Block,
                     bank_identifier: None,
                     kind: PatchKind::Multi,
@@ -309,7 +645,7 @@ impl Header {
             // One drum kit (see 3.1.1e)
             [0x20, 0x00, 0x0A, 0x10, ..] => {
                 Some(Header {
-                    channel: channel.unwrap(),
+                    channel,
                     cardinality: Cardinality::One,
                     bank_identifier: None,
                     kind: PatchKind::DrumKit,
@@ -320,7 +656,7 @@ impl Header {
             // One drum instrument (see 3.1.1g)
             [0x20, 0x00, 0x0A, 0x11, sub1, ..] => {
                 Some(Header {
-                    channel: channel.unwrap(),
+                    channel,
                     cardinality: Cardinality::One,
                     bank_identifier: None,
                     kind: PatchKind::DrumInstrument,
@@ -331,7 +667,7 @@ impl Header {
             // Block drum instrument (see 3.1.1f)
             [0x21, 0x00, 0x0A, 0x11, ..] => {
                 Some(Header {
-                    channel: channel.unwrap(),
+                    channel,
                     cardinality: Cardinality::Block,
                     bank_identifier: None,
                     kind: PatchKind::DrumInstrument,
@@ -391,7 +727,7 @@ impl fmt::Display for Header {
 
 impl SystemExclusiveData for Header {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
-        if let Some(header) = Header::identify_vec(&data) {
+        if let Some(header) = Header::identify_vec(data) {
             Ok(header)
         }
         else {
@@ -401,10 +737,9 @@ impl SystemExclusiveData for Header {
 
     fn to_bytes(&self) -> Vec<u8> {
         let mut result = vec![
-            // Every dump command header has the MIDI channel.
-            // into() converts channel to SysEx-compatible u8
-            // (from 1...16 to 0...15)
-            self.channel.value() as u8,
+            // Every dump command header has the MIDI channel,
+            // converted from 1...16 to 0...15 for SysEx.
+            self.channel.to_bytes()[0],
 
             self.cardinality.into(),
 
@@ -436,6 +771,120 @@ impl SystemExclusiveData for Header {
 
 pub const MAX_TONE_COUNT: u8 = 128;
 
+/// Tone number in a bank (0~127).
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(try_from = "u8", into = "u8"))]
+pub struct ToneNumber(u8);
+
+impl ToneNumber {
+    pub fn try_new(value: u8) -> Result<Self, ValueError> {
+        if value < MAX_TONE_COUNT {
+            Ok(Self(value))
+        } else {
+            Err(ValueError(0, MAX_TONE_COUNT as i32 - 1, value as i32))
+        }
+    }
+
+    pub fn value(&self) -> u8 {
+        self.0
+    }
+
+    /// Returns all the tone numbers in ascending order.
+    pub fn all() -> impl Iterator<Item = ToneNumber> {
+        (0..MAX_TONE_COUNT).map(ToneNumber)
+    }
+}
+
+impl TryFrom<u8> for ToneNumber {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        ToneNumber::try_new(value)
+    }
+}
+
+impl From<ToneNumber> for u8 {
+    fn from(tone_number: ToneNumber) -> Self {
+        tone_number.0
+    }
+}
+
+impl fmt::Display for ToneNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Multi (or combi) patch number (0~63).
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(try_from = "u8", into = "u8"))]
+pub struct MultiNumber(u8);
+
+impl MultiNumber {
+    pub fn try_new(value: u8) -> Result<Self, ValueError> {
+        check_number(value, MULTI_COUNT).map(Self)
+    }
+
+    pub fn value(&self) -> u8 {
+        self.0
+    }
+}
+
+impl TryFrom<u8> for MultiNumber {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        MultiNumber::try_new(value)
+    }
+}
+
+impl From<MultiNumber> for u8 {
+    fn from(number: MultiNumber) -> Self {
+        number.0
+    }
+}
+
+impl fmt::Display for MultiNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// User drum instrument number (0~31).
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(try_from = "u8", into = "u8"))]
+pub struct InstrumentNumber(u8);
+
+impl InstrumentNumber {
+    pub fn try_new(value: u8) -> Result<Self, ValueError> {
+        check_number(value, USER_INSTRUMENT_COUNT).map(Self)
+    }
+
+    pub fn value(&self) -> u8 {
+        self.0
+    }
+}
+
+impl TryFrom<u8> for InstrumentNumber {
+    type Error = ValueError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        InstrumentNumber::try_new(value)
+    }
+}
+
+impl From<InstrumentNumber> for u8 {
+    fn from(number: InstrumentNumber) -> Self {
+        number.0
+    }
+}
+
+impl fmt::Display for InstrumentNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 pub struct ToneMap {
     included: [bool; MAX_TONE_COUNT as usize],
 }
@@ -445,13 +894,29 @@ impl ToneMap {
         ToneMap { included: [false; MAX_TONE_COUNT as usize] }
     }
 
-    pub fn is_included(&self, tone_number: u8) -> bool {
-        self.included[tone_number as usize]
+    pub fn is_included(&self, tone_number: ToneNumber) -> bool {
+        self.included[tone_number.0 as usize]
     }
 
     pub fn included_count(&self) -> usize {
         self.included.into_iter().filter(|b| *b).count()
     }
+
+    /// Marks the tone `tone_number` as included or not.
+    pub fn set_included(&mut self, tone_number: ToneNumber, included: bool) {
+        self.included[tone_number.0 as usize] = included;
+    }
+
+    /// Returns the numbers of the included tones in ascending order.
+    pub fn tone_numbers(&self) -> Vec<ToneNumber> {
+        ToneNumber::all().filter(|n| self.included[n.0 as usize]).collect()
+    }
+}
+
+impl Default for ToneMap {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl fmt::Display for ToneMap {
@@ -468,6 +933,7 @@ impl fmt::Display for ToneMap {
 
 impl SystemExclusiveData for ToneMap {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        check_length(data, Self::data_size())?;
         let mut included = [false; MAX_TONE_COUNT as usize];
 
         let mut i = 0;
@@ -757,4 +1223,206 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_message_from_wizooini() {
+        let data = include_bytes!("WizooIni.syx");
+        let message = Message::from_bytes(data).unwrap();
+        assert_eq!(message.channel, MIDIChannel::try_new(1).unwrap());
+        assert_eq!(message.function, Function::OneBlockDump);
+        assert_eq!(message.function_data, vec![0x00, 0x00]);  // single, bank A
+        assert_eq!(message.subdata, vec![0x00]);  // tone number
+        assert_eq!(message.patch_data[0], 0x09);  // checksum
+        assert_eq!(message.patch_data.len(), data.len() - 10);
+        assert_eq!(message.size(), data.len());
+    }
+
+    #[test]
+    fn test_message_round_trip_wizooini() {
+        let data = include_bytes!("WizooIni.syx");
+        let message = Message::from_bytes(data).unwrap();
+        assert_eq!(message.to_bytes(), data.to_vec());
+    }
+
+    // The start of the block dump in `ClassicKAA.md`, cut after the common data
+    // of the first patch.
+    static CLASSIC_KAA: &[u8] = &[
+        0xf0, 0x40, 0x00, 0x21, 0x00, 0x0a, 0x00, 0x00,
+        0x7f, 0x7f, 0x7f, 0x7f, 0x7f, 0x7f, 0x7f, 0x7f,  // tone map
+        0x7f, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00,
+        0x69,  // checksum of patch 001
+        0x00, 0x04, 0x14, 0x14, 0x04, 0x1e, 0x04,
+        0x29, 0x0a, 0x59, 0x62, 0x00, 0x00,
+        0x0b, 0x00, 0x00, 0x05, 0x09, 0x00,
+        0x15, 0x00, 0x0d, 0x41, 0x0c, 0x00,
+        0x0c, 0x00, 0x32, 0x00, 0x32, 0x00,
+        0x43, 0x40, 0x3f, 0x3d, 0x3f, 0x42, 0x46,
+        0x00,
+        0x55, 0x70, 0x52, 0x69, 0x74, 0x65, 0x20, 0x20,  // "UpRite  "
+        0x7e, 0x00, 0x00, 0x04, 0x0f, 0x00,
+        0x02, 0x04, 0x5f, 0x02, 0x00, 0x40,
+        0x00, 0x00,
+        0x12, 0x00, 0x01, 0x00, 0x0a, 0x00, 0x0c, 0x00,
+        0x5f, 0x40, 0x5f, 0x40, 0x5f, 0x40, 0x5f, 0x40,
+        0x02, 0x07, 0x01, 0x04,
+        0xf7,
+    ];
+
+    #[test]
+    fn test_message_from_classic_kaa() {
+        let message = Message::from_bytes(CLASSIC_KAA).unwrap();
+        assert_eq!(message.function, Function::AllBlockDump);
+        assert_eq!(message.function_data, vec![0x00, 0x00]);  // single, ADD bank A
+        assert_eq!(message.subdata.len(), ToneMap::data_size());
+        assert_eq!(ToneMap::from_bytes(&message.subdata).unwrap().included_count(), 64);
+        assert_eq!(message.patch_data[0], 0x69);
+
+        let common = crate::k5000::single::Common::from_bytes(&message.patch_data[1..]).unwrap();
+        assert_eq!(common.name, "UpRite  ");
+    }
+
+    #[test]
+    fn test_message_round_trip_classic_kaa() {
+        let message = Message::from_bytes(CLASSIC_KAA).unwrap();
+        assert_eq!(message.to_bytes(), CLASSIC_KAA.to_vec());
+    }
+
+    #[test]
+    fn test_message_write_complete() {
+        let data = [0xf0, 0x40, 0x03, 0x40, 0x00, 0x0a, 0xf7];
+        let message = Message::from_bytes(&data).unwrap();
+        assert_eq!(message.channel, MIDIChannel::try_new(4).unwrap());
+        assert_eq!(message.function, Function::WriteComplete);
+        assert!(message.function_data.is_empty() && message.subdata.is_empty() && message.patch_data.is_empty());
+        assert_eq!(message.to_bytes(), data.to_vec());
+    }
+
+    #[test]
+    fn test_message_one_block_dump_request() {
+        let data = [0xf0, 0x40, 0x00, 0x00, 0x00, 0x0a, 0x20, 0x05, 0xf7];  // multi number 6
+        let message = Message::from_bytes(&data).unwrap();
+        assert_eq!(message.function, Function::OneBlockDumpRequest);
+        assert_eq!(message.function_data, vec![0x20]);
+        assert_eq!(message.subdata, vec![0x05]);
+        assert_eq!(message.to_bytes(), data.to_vec());
+    }
+
+    #[test]
+    fn test_message_truncated() {
+        let data = [0xf0, 0x40, 0x00, 0x21, 0x00, 0x0a, 0x00, 0x00, 0x7f, 0xf7];
+        assert!(matches!(Message::from_bytes(&data), Err(ParseError::InvalidLength(_, _))));
+    }
+
+    #[test]
+    fn test_one_single_requests() {
+        // 3.1.2 One block dump request, ADD A/PCM B/ADD D/Exp E/Exp F, tone number 2
+        let channel = MIDIChannel::try_new(1).unwrap();
+        let banks = [
+            (BankIdentifier::A, 0x00),
+            (BankIdentifier::B, 0x01),
+            (BankIdentifier::D, 0x02),
+            (BankIdentifier::E, 0x03),
+            (BankIdentifier::F, 0x04),
+        ];
+        for (bank, b) in banks {
+            let request = DumpRequest::one_single(channel, bank, 0x01).unwrap();
+            let data = request.to_message().to_bytes();
+            assert_eq!(data, vec![0xf0, 0x40, 0x00, 0x00, 0x00, 0x0a, 0x00, b, 0x01, 0xf7]);
+            let message = Message::from_bytes(&data).unwrap();
+            assert_eq!(DumpRequest::from_message(&message), Ok(request));
+        }
+    }
+
+    #[test]
+    fn test_block_single_requests() {
+        let channel = MIDIChannel::try_new(2).unwrap();
+        for bank in [BankIdentifier::A, BankIdentifier::B, BankIdentifier::D, BankIdentifier::E, BankIdentifier::F] {
+            let request = DumpRequest::block_single(channel, bank);
+            let data = request.to_message().to_bytes();
+            assert_eq!(data, vec![0xf0, 0x40, 0x01, 0x01, 0x00, 0x0a, 0x00, bank as u8, 0xf7]);
+            let message = Message::from_bytes(&data).unwrap();
+            assert_eq!(DumpRequest::from_message(&message), Ok(request));
+        }
+    }
+
+    #[test]
+    fn test_multi_and_drum_requests() {
+        let channel = MIDIChannel::try_new(16).unwrap();
+        let requests = [
+            (DumpRequest::one_multi(channel, 0x05).unwrap(), vec![0xf0, 0x40, 0x0f, 0x00, 0x00, 0x0a, 0x20, 0x05, 0xf7]),
+            (DumpRequest::block_multi(channel), vec![0xf0, 0x40, 0x0f, 0x01, 0x00, 0x0a, 0x20, 0xf7]),
+            (DumpRequest::drum_kit(channel), vec![0xf0, 0x40, 0x0f, 0x00, 0x00, 0x0a, 0x10, 0xf7]),
+            (DumpRequest::one_drum_instrument(channel, 0x1f).unwrap(), vec![0xf0, 0x40, 0x0f, 0x00, 0x00, 0x0a, 0x11, 0x1f, 0xf7]),
+            (DumpRequest::block_drum_instrument(channel), vec![0xf0, 0x40, 0x0f, 0x01, 0x00, 0x0a, 0x11, 0xf7]),
+        ];
+        for (request, data) in requests {
+            assert_eq!(request.to_message().to_bytes(), data);
+            let message = Message::from_bytes(&data).unwrap();
+            assert_eq!(DumpRequest::from_message(&message), Ok(request));
+        }
+    }
+
+    #[test]
+    fn test_request_number_range() {
+        let channel = MIDIChannel::try_new(1).unwrap();
+        assert!(DumpRequest::one_single(channel, BankIdentifier::E, 127).is_ok());
+        assert_eq!(DumpRequest::one_single(channel, BankIdentifier::E, 128), Err(ValueError(0, 127, 128)));
+        assert!(DumpRequest::one_multi(channel, 63).is_ok());
+        assert_eq!(DumpRequest::one_multi(channel, 0x40), Err(ValueError(0, 63, 64)));
+        assert!(DumpRequest::one_drum_instrument(channel, 31).is_ok());
+        assert_eq!(DumpRequest::one_drum_instrument(channel, 32), Err(ValueError(0, 31, 32)));
+    }
+
+    #[test]
+    fn test_bad_request_message() {
+        let data = [0xf0, 0x40, 0x00, 0x00, 0x00, 0x0a, 0x20, 0x40, 0xf7];  // one multi 64
+        let message = Message::from_bytes(&data).unwrap();
+        match DumpRequest::from_message(&message) {
+            Err(ParseError::InvalidField(error)) => {
+                assert_eq!(error.path, "number");
+                assert_eq!(error.offset, 7);
+            },
+            other => panic!("expected an invalid field error, got {:?}", other),
+        }
+
+        let data = [0xf0, 0x40, 0x00, 0x01, 0x00, 0x0a, 0x10, 0xf7];  // block drum kit
+        let message = Message::from_bytes(&data).unwrap();
+        match DumpRequest::from_message(&message) {
+            Err(ParseError::InvalidField(error)) => assert_eq!(error.offset, 6),
+            other => panic!("expected an invalid field error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_dump_is_not_request() {
+        let data = include_bytes!("WizooIni.syx");
+        let message = Message::from_bytes(data).unwrap();
+        assert!(DumpRequest::from_message(&message).is_err());
+    }
+
+    #[test]
+    fn test_write_responses() {
+        let channel = MIDIChannel::try_new(4).unwrap();
+        let responses = [
+            (Response::complete(channel), 0x40),
+            (Response::error(channel, WriteError::General), 0x41),
+            (Response::error(channel, WriteError::Protect), 0x42),
+            (Response::error(channel, WriteError::MemoryFull), 0x44),
+            (Response::error(channel, WriteError::NoExpandedMemory), 0x45),
+        ];
+        for (response, function) in responses {
+            let data = vec![0xf0, 0x40, 0x03, function, 0x00, 0x0a, 0xf7];
+            assert_eq!(response.to_message().to_bytes(), data);
+            let message = Message::from_bytes(&data).unwrap();
+            assert_eq!(Response::from_message(&message), Ok(response));
+        }
+    }
+
+    #[test]
+    fn test_write_error_is_error() {
+        let response = Response::error(MIDIChannel::try_new(1).unwrap(), WriteError::MemoryFull);
+        let error: Box<dyn std::error::Error> = Box::new(response.result.unwrap_err());
+        assert_eq!(error.to_string(), "Write error: memory is full");
+    }
 }
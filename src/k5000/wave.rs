@@ -5,7 +5,9 @@ use std::fmt;
 
 use crate::{
     SystemExclusiveData,
-    ParseError
+    ParseError,
+    check_length,
+    invalid_field
 };
 
 static WAVE_NAMES: &[&str] = &[
@@ -529,6 +531,56 @@ impl Wave {
     pub fn is_additive(&self) -> bool {
         self.number == 512
     }
+
+    /// Finds a wave by its name, ignoring case. If the name
+    /// appears more than once, the wave with the lowest number is returned.
+    pub fn from_name(name: &str) -> Option<Wave> {
+        if name.eq_ignore_ascii_case("ADD") {
+            return Some(Wave { number: 512 });
+        }
+        WAVE_NAMES.iter()
+            .skip(1)
+            .position(|n| n.eq_ignore_ascii_case(name))
+            .map(|index| Wave { number: index as u16 + 1 })
+    }
+}
+
+/// Waves are serialized by name. A name that belongs to more than one wave
+/// is written with the number, like "123 SynBass5", and either form is read.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Wave {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match Wave::from_name(&self.name()) {
+            Some(wave) if wave.number == self.number => serializer.collect_str(&self.name()),
+            _ => serializer.collect_str(self),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Wave {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let text = <String as serde::Deserialize>::deserialize(deserializer)?;
+        let numbered = text.split_once(' ')
+            .and_then(|(number, name)| number.parse::<u16>().ok().map(|number| (number, name)));
+        match numbered {
+            Some((number, name)) => {
+                if number == 0 || (number as usize >= WAVE_NAMES.len() && number != 512) {
+                    return Err(D::Error::custom(format!("wave number {} out of range", number)));
+                }
+                let wave = Wave { number };
+                if wave.name().eq_ignore_ascii_case(name) {
+                    Ok(wave)
+                } else {
+                    Err(D::Error::custom(format!("wave {} is \"{}\", not \"{}\"", number, wave.name(), name)))
+                }
+            },
+            None => Wave::from_name(&text)
+                .ok_or_else(|| D::Error::custom(format!("unknown wave \"{}\"", text))),
+        }
+    }
 }
 
 impl fmt::Display for Wave {
@@ -543,25 +595,23 @@ impl fmt::Display for Wave {
 
 impl SystemExclusiveData for Wave {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
-        let bit_str = format!("{:03b}{:07b}", data[0], data[1]);
-        let n = u16::from_str_radix(&bit_str, 2).unwrap();
-
-        eprintln!("Wave = {}", n);
+        check_length(data, Self::data_size())?;
+        let n = ((data[0] as u16 & 0x07) << 7) | (data[1] as u16 & 0x7f);
 
         if n == 512 {
             Ok(Wave { number: 512 })
         }
-        else {
+        else if ((n + 1) as usize) < WAVE_NAMES.len() {
             Ok(Wave { number: n + 1 })
         }
+        else {
+            Err(invalid_field("", data, 0)(format!("wave number {} out of range", n + 1)))
+        }
     }
 
     fn to_bytes(&self) -> Vec<u8> {
         let n = if self.number == 512 { 512 } else { self.number - 1 };
-        let bit_str = format!("{:010b}", n);
-        let msb = u8::from_str_radix(&bit_str[..3], 2).unwrap();
-        let lsb = u8::from_str_radix(&bit_str[3..10], 2).unwrap();
-        vec![msb, lsb]
+        vec![((n >> 7) & 0x07) as u8, (n & 0x7f) as u8]
     }
 
     fn data_size() -> usize { 2 }
@@ -577,6 +627,13 @@ mod tests {
         assert_eq!(wave.name(), "Syn Saw1 Cyc");
     }
 
+    #[test]
+    fn test_wave_from_name() {
+        assert_eq!(Wave::from_name("syn saw1 cyc").map(|w| w.number), Some(411));
+        assert_eq!(Wave::from_name("ADD").map(|w| w.number), Some(512));
+        assert!(Wave::from_name("Kazoo").is_none());
+    }
+
     #[test]
     fn test_wave_from_bytes() {
         let w = Wave::from_bytes(&[0x03, 0x1A]);
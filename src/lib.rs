@@ -2,8 +2,25 @@
 //!
 //! Patch manipulation helpers for Kawai digital synths.
 
+#[macro_use]
+mod serialize;
+
 pub mod k5000;
 pub mod k4;
+pub mod loader;
+pub mod registry;
+
+#[cfg(feature = "text")]
+pub mod text;
+
+#[cfg(feature = "diff")]
+pub mod diff;
+
+#[cfg(feature = "diff")]
+pub mod merge;
+
+#[cfg(test)]
+mod fuzz;
 
 use std::fmt;
 
@@ -13,15 +30,42 @@ pub enum ParseError {
     InvalidLength(usize, usize),  // actual, expected
     InvalidChecksum(u8, u8),  // actual, expected
     InvalidData(u32, String),  // offset in data, explanation
+    InvalidField(FieldError),  // offending field, with its path and offset
     Unidentified,  // can't identify this kind
 }
 
+/// Details of an invalid field value found while parsing.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct FieldError {
+    /// Path to the field from the outermost structure,
+    /// like `singles[12].sources[2].wave`.
+    pub path: String,
+
+    /// Offset of the offending byte, relative to the start
+    /// of the data given to the outermost parser.
+    pub offset: usize,
+
+    /// The offending byte.
+    pub value: u8,
+
+    /// Explanation of what was wrong with the value.
+    pub reason: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid value {:02X}H for {} at offset {}. Reason: {}",
+            self.value, self.path, self.offset, self.reason)
+    }
+}
+
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", match self {
             ParseError::InvalidLength(actual, expected) => format!("Got {} bytes of data, expected {} bytes.", actual, expected),
             ParseError::InvalidChecksum(actual, expected) => format!("Computed checksum was {}H, expected {}H.", actual, expected),
             ParseError::InvalidData(offset, message) => format!("Invalid data at offset {}. Reason: {}", offset, message),
+            ParseError::InvalidField(error) => error.to_string(),
             ParseError::Unidentified => String::from("Unable to identify this System Exclusive file."),
         })
     }
@@ -29,6 +73,69 @@ impl fmt::Display for ParseError {
 
 impl std::error::Error for ParseError { }
 
+impl ParseError {
+    /// Moves the offset of an `InvalidData` error forward by `amount` bytes.
+    /// Used when the error came from a nested structure, so that the offset
+    /// is relative to the start of the enclosing data.
+    pub fn offset_by(self, amount: usize) -> Self {
+        self.map_offset(|offset| offset + amount)
+    }
+
+    /// Maps the offset of an `InvalidData` or `InvalidField` error using `f`.
+    /// Useful when the nested structure was parsed from interleaved data.
+    pub fn map_offset<F: FnOnce(usize) -> usize>(self, f: F) -> Self {
+        match self {
+            ParseError::InvalidData(offset, message) => ParseError::InvalidData(f(offset as usize) as u32, message),
+            ParseError::InvalidField(error) => ParseError::InvalidField(FieldError { offset: f(error.offset), ..error }),
+            other => other,
+        }
+    }
+
+    /// Prepends `field` to the path of an `InvalidField` error.
+    /// Used by a parser when a nested structure fails, so that
+    /// the path leads from the enclosing structure to the field.
+    pub fn in_field<S: fmt::Display>(self, field: S) -> Self {
+        match self {
+            ParseError::InvalidField(error) => {
+                let path = if error.path.is_empty() {
+                    field.to_string()
+                } else {
+                    format!("{}.{}", field, error.path)
+                };
+                ParseError::InvalidField(FieldError { path, ..error })
+            },
+            other => other,
+        }
+    }
+
+    /// Combines `in_field` and `offset_by` for a nested structure
+    /// that starts at `amount` bytes into the enclosing data.
+    pub fn within<S: fmt::Display>(self, field: S, amount: usize) -> Self {
+        self.in_field(field).offset_by(amount)
+    }
+}
+
+/// Checks that there are at least `size` bytes of data.
+fn check_length(data: &[u8], size: usize) -> Result<(), ParseError> {
+    if data.len() < size {
+        Err(ParseError::InvalidLength(data.len(), size))
+    } else {
+        Ok(())
+    }
+}
+
+/// Makes a function for `map_err` that turns a value error into
+/// `ParseError::InvalidField` for the field `name` at `offset` in `data`.
+fn invalid_field<E: fmt::Display>(name: &'static str, data: &[u8], offset: usize) -> impl Fn(E) -> ParseError {
+    let value = data.get(offset).copied().unwrap_or_default();
+    move |e| ParseError::InvalidField(FieldError {
+        path: name.to_string(),
+        offset,
+        value,
+        reason: e.to_string(),
+    })
+}
+
 /// Parsing and generating MIDI System Exclusive data.
 pub trait SystemExclusiveData: Sized {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError>;
@@ -48,6 +155,7 @@ impl fmt::Display for ValueError {
 impl std::error::Error for ValueError { }
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(try_from = "i32", into = "i32"))]
 pub struct MIDIChannel(i32);
 
 impl MIDIChannel {
@@ -64,15 +172,27 @@ impl MIDIChannel {
     }
 }
 
+impl TryFrom<i32> for MIDIChannel {
+    type Error = ValueError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        Self::try_new(value)
+    }
+}
+
+impl From<MIDIChannel> for i32 {
+    fn from(val: MIDIChannel) -> Self {
+        val.0
+    }
+}
+
 impl SystemExclusiveData for MIDIChannel {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
-        if data.len() < 1 {
+        if data.is_empty() {
             Err(ParseError::InvalidLength(data.len(), 1))
         } else {
-            match MIDIChannel::try_new((data[0] + 1).into()) {  // bring into 1...16
-                Ok(ch) => Ok(ch),
-                Err(e) => Err(ParseError::InvalidData(0, format!("invalid value {}", e)))
-            }
+            MIDIChannel::try_new(data[0] as i32 + 1)  // bring into 1...16
+                .map_err(invalid_field("", data, 0))
         }
     }
 
@@ -107,17 +227,53 @@ impl MIDINote {
     
         format!("{}{}", name, octave)
     }
+
+    /// Finds the note with `name`, like "C#3", as returned by `name`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        note_number(name, -2).and_then(|n| Self::try_new(n).ok())
+    }
+}
+
+impl fmt::Display for MIDINote {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for MIDINote {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for MIDINote {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = <String as serde::Deserialize>::deserialize(deserializer)?;
+        MIDINote::from_name(&name)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid note name \"{}\", expected C-2...G8", name)))
+    }
+}
+
+/// Parses a note name like "C#3" or "A-1" into a note number,
+/// when the note number 0 is C in `lowest_octave`.
+fn note_number(name: &str, lowest_octave: i32) -> Option<i32> {
+    let notes = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B" ];
+    let split = name.find(|c: char| c == '-' || c.is_ascii_digit())?;
+    let (note, octave) = name.split_at(split);
+    let index = notes.iter().position(|n| n.eq_ignore_ascii_case(note))?;
+    let octave: i32 = octave.parse().ok()?;
+    Some((octave - lowest_octave) * 12 + index as i32)
 }
 
 impl SystemExclusiveData for MIDINote {
     fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
-        if data.len() < 1 {
+        if data.is_empty() {
             Err(ParseError::InvalidLength(data.len(), 1))
         } else {
-            match MIDINote::try_new(data[0].into()) {
-                Ok(ch) => Ok(ch),
-                Err(e) => Err(ParseError::InvalidData(0, format!("invalid value {}", e)))
-            }
+            MIDINote::try_new(data[0].into())
+                .map_err(invalid_field("", data, 0))
         }
     }
 
@@ -133,6 +289,7 @@ pub trait Checksum {
     fn checksum(&self) -> u8;
 }
 
+#[allow(dead_code)]
 trait StringUtils {
     fn substring(&self, start: usize, len: usize) -> Self;
 }
@@ -144,15 +301,7 @@ impl StringUtils for String {
 }
 
 fn every_nth_byte(v: &[u8], n: usize, start: usize) -> Vec<u8> {
-    let mut buf: Vec<u8> = Vec::new();
-
-    for (index, _value) in v.iter().enumerate() {
-        if index % n == 0 {
-            buf.push(v[index + start]);
-        }
-    }
-
-    buf
+    v.iter().skip(start).step_by(n).cloned().collect()
 }
 
 #[cfg(test)]
@@ -168,6 +317,32 @@ mod tests {
         assert_eq!(every_nth_byte(&data2, 4, 1), vec![2, 6, 10]);
     }
 
+    #[test]
+    fn test_note_name_round_trip() {
+        for n in 0..=127 {
+            let note = MIDINote::try_new(n).unwrap();
+            assert_eq!(MIDINote::from_name(&note.name()), Some(note));
+        }
+        assert_eq!(MIDINote::from_name("C-2"), MIDINote::try_new(0).ok());
+        assert_eq!(MIDINote::from_name("c#3"), MIDINote::try_new(61).ok());
+        assert_eq!(MIDINote::from_name("H3"), None);
+        assert_eq!(MIDINote::from_name("G#8"), None);  // 128
+    }
+
+    #[test]
+    fn test_field_error_path() {
+        let data = [0x00, 0x00, 0x7f];
+        let error = MIDIChannel::from_bytes(&data[2..])
+            .map_err(|e| e.within("channel", 2).within("sections[3]", 10))
+            .unwrap_err();
+        assert_eq!(error, ParseError::InvalidField(FieldError {
+            path: "sections[3].channel".to_string(),
+            offset: 12,
+            value: 0x7f,
+            reason: "expected 1...16, got 128".to_string(),
+        }));
+    }
+
     #[test]
     fn test_channel() {
         let ch = MIDIChannel::try_new(1);
@@ -0,0 +1,270 @@
+//! Loading System Exclusive files that contain many messages.
+//!
+//! A .syx file is often a concatenation of F0...F7 messages,
+//! like a block of one single dumps, or even a mix of K4 and K5000 dumps.
+//! The loader splits the file into messages, identifies the synth
+//! and the kind of dump of each one, and parses the patches.
+//! A message that fails to parse does not stop the rest of the file
+//! from loading; its error is reported with the message.
+//!
+
+use std::convert::TryFrom;
+
+use crate::{
+    SystemExclusiveData,
+    ParseError,
+    check_length,
+    invalid_field
+};
+use crate::k4;
+use crate::k5000;
+use crate::k5000::sysex::{
+    Message,
+    Function,
+    PatchKind,
+    BankIdentifier,
+    ToneNumber,
+    MultiNumber,
+    InstrumentNumber
+};
+
+/// Manufacturer ID of Kawai.
+const KAWAI: u8 = 0x40;
+
+/// Machine ID of the K4 and K4r.
+const K4_MACHINE: u8 = 0x04;
+
+/// Machine ID of the K5000 series.
+const K5000_MACHINE: u8 = 0x0A;
+
+/// A patch or a set of patches parsed from one dump message.
+/// The numbers are zero-based, as they appear in the message.
+pub enum Patch {
+    K4(Box<k4::sysex::PatchDump>),
+    K5000Single(BankIdentifier, ToneNumber, k5000::single::SinglePatch),
+    K5000Bank(BankIdentifier, k5000::bank::Bank),
+    K5000PCMBank(k5000::bank::PCMBank),
+    K5000Multi(MultiNumber, k5000::multi::MultiPatch),
+    K5000MultiBank(k5000::multi::MultiBank),
+    K5000CombiBank(k5000::multi::MultiBank),
+    K5000DrumKit(k5000::drum::DrumKit),
+    K5000DrumInstrument(InstrumentNumber, k5000::drum::DrumInstrument),
+    K5000InstrumentBank(k5000::drum::InstrumentBank),
+}
+
+/// The result of loading one message from a file.
+pub struct Item {
+    /// Offset of the message in the file.
+    pub offset: usize,
+
+    /// The patches in the message, or the reason why it could not be parsed.
+    /// Error offsets are relative to the start of the message.
+    pub result: Result<Patch, ParseError>,
+}
+
+/// Splits `data` into System Exclusive messages.
+/// Returns each message with its offset in `data`.
+/// Bytes outside of messages are skipped. A message that is
+/// cut short by the start of the next one or by the end of the data
+/// is returned without its terminator, so that parsing it will fail.
+pub fn split(data: &[u8]) -> Vec<(usize, &[u8])> {
+    let mut result = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = data[offset..].iter().position(|&b| b == 0xF0).map(|i| offset + i) {
+        let end = match data[start + 1..].iter().position(|&b| b == 0xF7 || b == 0xF0) {
+            Some(i) if data[start + 1 + i] == 0xF7 => start + i + 2,
+            Some(i) => start + i + 1,
+            None => data.len(),
+        };
+        result.push((start, &data[start..end]));
+        offset = end;
+    }
+    result
+}
+
+/// Loads all the System Exclusive messages in `data`.
+pub fn load(data: &[u8]) -> Vec<Item> {
+    split(data).into_iter()
+        .map(|(offset, message)| Item { offset, result: parse(message) })
+        .collect()
+}
+
+/// Identifies a single System Exclusive message and parses its patches.
+pub fn parse(data: &[u8]) -> Result<Patch, ParseError> {
+    check_length(data, 6)?;
+    if data[0] != 0xF0 {
+        return Err(invalid_field("initiator", data, 0)("expected F0H"));
+    }
+    if data[1] != KAWAI {
+        return Err(invalid_field("manufacturer", data, 1)("expected Kawai (40H)"));
+    }
+    match data[5] {
+        K4_MACHINE => k4::sysex::PatchDump::from_bytes(data).map(|dump| Patch::K4(Box::new(dump))),
+        K5000_MACHINE => parse_k5000(data),
+        _ => Err(invalid_field("machine", data, 5)("expected K4 (04H) or K5000 (0AH)")),
+    }
+}
+
+fn parse_k5000(data: &[u8]) -> Result<Patch, ParseError> {
+    let message = Message::from_bytes(data)?;
+    let start = 6;  // F0, manufacturer, channel, function, group, machine
+    if message.function != Function::OneBlockDump && message.function != Function::AllBlockDump {
+        return Err(ParseError::InvalidData(3, "not a dump".to_string()));
+    }
+    let kind = PatchKind::try_from(message.function_data[0])
+        .map_err(invalid_field("kind", &message.function_data, 0))
+        .map_err(|e| e.offset_by(start))?;
+
+    match (message.function, kind) {
+        (Function::OneBlockDump, PatchKind::Single) => {
+            let (bank, number, patch) = k5000::single::SinglePatch::from_message(&message)?;
+            Ok(Patch::K5000Single(bank, number, patch))
+        },
+        (Function::OneBlockDump, PatchKind::Multi) => {
+            let (number, patch) = k5000::multi::MultiPatch::from_message(&message)?;
+            Ok(Patch::K5000Multi(number, patch))
+        },
+        (Function::OneBlockDump, PatchKind::DrumKit) =>
+            Ok(Patch::K5000DrumKit(k5000::drum::DrumKit::from_message(&message)?)),
+        (Function::OneBlockDump, PatchKind::DrumInstrument) => {
+            let (number, instrument) = k5000::drum::DrumInstrument::from_message(&message)?;
+            Ok(Patch::K5000DrumInstrument(number, instrument))
+        },
+        (Function::AllBlockDump, PatchKind::Single) => {
+            let bank = BankIdentifier::try_from(message.function_data[1])
+                .map_err(|_| invalid_field("bank", &message.function_data, 1)("unknown bank").offset_by(start))?;
+            if bank == BankIdentifier::B {
+                Ok(Patch::K5000PCMBank(k5000::bank::PCMBank::from_message(&message)?))
+            } else {
+                Ok(Patch::K5000Bank(bank, k5000::bank::Bank::from_message(&message)?))
+            }
+        },
+        (Function::AllBlockDump, PatchKind::Multi) => {
+            let bank = k5000::multi::MultiBank::from_message(&message)?;
+            if bank.is_combi() {
+                Ok(Patch::K5000CombiBank(bank))
+            } else {
+                Ok(Patch::K5000MultiBank(bank))
+            }
+        },
+        (Function::AllBlockDump, PatchKind::DrumInstrument) =>
+            Ok(Patch::K5000InstrumentBank(k5000::drum::InstrumentBank::from_message(&message)?)),
+        _ => Err(ParseError::InvalidData(start as u32, format!("block dumps of {} are not supported", kind))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{*};
+    use crate::MIDIChannel;
+    use crate::k4::sysex::{DumpData, Locality};
+
+    static K4_DATA: &[u8] = include_bytes!("k4/A401.SYX");
+    static K5000_DATA: &[u8] = include_bytes!("k5000/WizooIni.syx");
+
+    #[test]
+    fn test_split() {
+        let data = [0x01, 0xF0, 0x40, 0xF7, 0x02, 0xF0, 0x41, 0xF0, 0x42, 0xF7, 0xF0, 0x43];
+        let messages = split(&data);
+        assert_eq!(messages, vec![
+            (1, &data[1..4]),
+            (5, &data[5..7]),  // cut short by the next message
+            (7, &data[7..10]),
+            (10, &data[10..]),  // no terminator
+        ]);
+    }
+
+    #[test]
+    fn test_load_mixed_file() {
+        let mut data = K4_DATA.to_vec();
+        data.extend([0xF0, 0x43, 0x10, 0x4C, 0x00, 0x00, 0x7E, 0x00, 0xF7]);  // not Kawai
+        data.extend(K5000_DATA);
+
+        let items = load(&data);
+        assert_eq!(items.len(), 3);
+
+        assert_eq!(items[0].offset, 0);
+        match &items[0].result {
+            Ok(Patch::K4(dump)) => {
+                assert_eq!(dump.locality, Locality::Internal);
+                assert!(matches!(&dump.data, DumpData::All(bank) if bank.singles.len() == 64));
+            },
+            _ => panic!("expected a K4 bank"),
+        }
+
+        assert!(matches!(&items[1].result, Err(ParseError::InvalidField(e)) if e.path == "manufacturer"));
+
+        assert_eq!(items[2].offset, K4_DATA.len() + 9);
+        match &items[2].result {
+            Ok(Patch::K5000Single(BankIdentifier::A, number, patch)) if number.value() == 0 => assert_eq!(patch.common.name, "WizooIni"),
+            _ => panic!("expected a K5000 single"),
+        }
+    }
+
+    #[test]
+    fn test_load_one_patch_dumps() {
+        let single = &K4_DATA[8..8 + k4::single::SinglePatch::data_size()];
+        let mut data = Vec::new();
+        for number in 0..3 {
+            data.extend([0xF0, 0x40, 0x00, 0x20, 0x00, 0x04, 0x00, number]);
+            data.extend(single);
+            data.push(0xF7);
+        }
+
+        let items = load(&data);
+        assert_eq!(items.len(), 3);
+        for (i, item) in items.iter().enumerate() {
+            match &item.result {
+                Ok(Patch::K4(dump)) => assert!(matches!(dump.data, DumpData::OneSingle(number, _) if number.into_inner() as usize == i)),
+                _ => panic!("expected a K4 single"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_offset_is_relative_to_message() {
+        let mut data = K5000_DATA.to_vec();
+        let mut k4 = K4_DATA.to_vec();
+        let offset = 8 + k4::single::SinglePatch::data_size() + 31;  // singles[1].sources[1].delay
+        k4[offset] = 0x7F;
+        data.extend(k4);
+
+        let items = load(&data);
+        assert_eq!(items.len(), 2);
+        assert!(items[0].result.is_ok());
+        assert_eq!(items[1].offset, K5000_DATA.len());
+        match &items[1].result {
+            Err(ParseError::InvalidField(error)) => {
+                assert_eq!(error.path, "singles[1].sources[1].delay");
+                assert_eq!(error.offset, offset);
+            },
+            _ => panic!("expected an invalid field error"),
+        }
+    }
+
+    #[test]
+    fn test_truncated_message() {
+        let data = &K5000_DATA[..100];
+        let items = load(data);
+        assert_eq!(items.len(), 1);
+        assert!(items[0].result.is_err());
+    }
+
+    #[test]
+    fn test_load_block_multi() {
+        let channel = MIDIChannel::try_new(1).unwrap();
+        let data = k5000::multi::MultiBank::new().to_message(channel).to_bytes();
+        match parse(&data) {
+            Ok(Patch::K5000MultiBank(bank)) => assert_eq!(bank.patches.len(), k5000::multi::MULTI_COUNT),
+            _ => panic!("expected a K5000 multi bank"),
+        }
+    }
+
+    #[test]
+    fn test_k5000_non_dump() {
+        let channel = MIDIChannel::try_new(1).unwrap();
+        let response = k5000::sysex::Response { channel, result: Ok(()) };
+        let data = response.to_message().to_bytes();
+        assert!(matches!(parse(&data), Err(ParseError::InvalidData(3, _))));
+    }
+}
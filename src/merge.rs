@@ -0,0 +1,197 @@
+//! Three-way merge of patches.
+//!
+//! Two edited versions of a patch, ours and theirs, are merged using
+//! the version they were both made from, the base. A field that was
+//! changed on one side only takes the changed value. A field that was
+//! changed on both sides to different values is a conflict: the merged
+//! patch keeps our value, and the conflict is reported with all three.
+//!
+//! Lists are merged item by item if they have the same length in all
+//! three versions, so edits to different sources of a patch combine.
+//! Otherwise a list that changed on both sides is one conflict.
+//!
+
+use std::fmt;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::diff::{Value, to_value, field_path};
+
+/// A field that was changed differently in both versions.
+/// The value is `None` in a version where the field does not exist.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Conflict {
+    pub path: String,
+    pub base: Option<Value>,
+    pub ours: Option<Value>,
+    pub theirs: Option<Value>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let show = |value: &Option<Value>| match value {
+            Some(value) => value.to_string(),
+            None => "(none)".to_string(),
+        };
+        write!(f, "{}: base {}, ours {}, theirs {}",
+            self.path, show(&self.base), show(&self.ours), show(&self.theirs))
+    }
+}
+
+/// The result of a merge.
+pub struct Merged<T> {
+    /// The merged patch, with our values for the conflicting fields.
+    pub patch: T,
+
+    /// The fields that were changed differently in both versions.
+    pub conflicts: Vec<Conflict>,
+}
+
+impl<T> Merged<T> {
+    /// Returns true if the versions were merged without conflicts.
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// Merges the edits of `ours` and `theirs` to `base`.
+/// Fails only if the merged fields do not make a valid patch,
+/// like when they are out of range together.
+pub fn merge<T>(base: &T, ours: &T, theirs: &T) -> Result<Merged<T>, serde_json::Error>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let mut conflicts = Vec::new();
+    let merged = merge_values("", Some(&to_value(base)), Some(&to_value(ours)), Some(&to_value(theirs)), &mut conflicts);
+    let patch = serde_json::from_value(merged.unwrap_or(Value::Null))?;
+    Ok(Merged { patch, conflicts })
+}
+
+fn merge_values(
+    path: &str,
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    conflicts: &mut Vec<Conflict>
+) -> Option<Value> {
+    if ours == theirs || theirs == base {
+        return ours.cloned();
+    }
+    if ours == base {
+        return theirs.cloned();
+    }
+
+    match (base, ours, theirs) {
+        (Some(Value::Object(b)), Some(Value::Object(o)), Some(Value::Object(t))) => {
+            let mut merged = serde_json::Map::new();
+            let fields = o.keys().chain(t.keys().filter(|field| !o.contains_key(*field)));
+            for field in fields {
+                let value = merge_values(&field_path(path, field), b.get(field), o.get(field), t.get(field), conflicts);
+                if let Some(value) = value {
+                    merged.insert(field.clone(), value);
+                }
+            }
+            Some(Value::Object(merged))
+        },
+        (Some(Value::Array(b)), Some(Value::Array(o)), Some(Value::Array(t)))
+            if b.len() == o.len() && o.len() == t.len() => {
+            let items = (0..b.len())
+                .map(|i| merge_values(&format!("{}[{}]", path, i), b.get(i), o.get(i), t.get(i), conflicts)
+                    .unwrap_or(Value::Null))
+                .collect();
+            Some(Value::Array(items))
+        },
+        _ => {
+            conflicts.push(Conflict {
+                path: path.to_string(),
+                base: base.cloned(),
+                ours: ours.cloned(),
+                theirs: theirs.cloned(),
+            });
+            ours.cloned()
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{*};
+    use crate::SystemExclusiveData;
+    use crate::k4;
+    use crate::k5000;
+
+    static K4_DATA: &[u8] = include_bytes!("k4/A401.SYX");
+    static K5000_DATA: &[u8] = include_bytes!("k5000/WizooIni.syx");
+
+    fn k4_single() -> k4::single::SinglePatch {
+        k4::single::SinglePatch::from_bytes(&K4_DATA[8..]).unwrap()
+    }
+
+    fn k5000_single() -> k5000::single::SinglePatch {
+        k5000::single::SinglePatch::from_bytes(&K5000_DATA[9..]).unwrap()
+    }
+
+    #[test]
+    fn test_edits_to_different_sources_combine() {
+        let base = k4_single();
+        let mut ours = k4_single();
+        ours.sources[0].wave = k4::wave::Wave::from_name("SAW 1").unwrap();
+        ours.amplifiers[0].level = k4::Level::try_new(90).unwrap();
+        let mut theirs = k4_single();
+        theirs.sources[2].coarse = k4::Coarse::try_new(7).unwrap();
+        theirs.name = "Merged    ".to_string();
+
+        let merged = merge(&base, &ours, &theirs).unwrap();
+        assert!(merged.is_clean());
+        let mut expected = ours.clone();
+        expected.sources[2].coarse = theirs.sources[2].coarse;
+        expected.name = theirs.name.clone();
+        assert_eq!(merged.patch.to_bytes(), expected.to_bytes());
+    }
+
+    #[test]
+    fn test_conflicting_edits() {
+        let base = k4_single();
+        let mut ours = k4_single();
+        ours.volume = k4::Level::try_new(80).unwrap();
+        ours.sources[1].fine = k4::Fine::try_new(10).unwrap();
+        let mut theirs = k4_single();
+        theirs.volume = k4::Level::try_new(70).unwrap();
+        theirs.sources[1].fine = k4::Fine::try_new(10).unwrap();  // same edit on both sides
+
+        let merged = merge(&base, &ours, &theirs).unwrap();
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(merged.conflicts[0].to_string(), "volume: base 100, ours 80, theirs 70");
+        assert_eq!(merged.patch.volume.into_inner(), 80);
+        assert_eq!(merged.patch.sources[1].fine.into_inner(), 10);
+    }
+
+    #[test]
+    fn test_k5000_merge() {
+        let base = k5000_single();
+        let mut ours = k5000_single();
+        ours.common.volume = k5000::Volume::new(100);
+        let mut theirs = k5000_single();
+        theirs.sources[1].control.zone.high = k5000::source::Key::from_name("C5").unwrap();
+
+        let merged = merge(&base, &ours, &theirs).unwrap();
+        assert!(merged.is_clean());
+        assert_eq!(merged.patch.common.volume.value(), 100);
+        assert_eq!(merged.patch.sources[1].control.zone.high.name(), "C5");
+    }
+
+    #[test]
+    fn test_added_source_conflicts_as_a_whole() {
+        let base = k5000::single::SinglePatch::new(2, 0);
+        let ours = k5000::single::SinglePatch::new(3, 0);
+        let mut theirs = k5000::single::SinglePatch::new(2, 0);
+        theirs.sources[0].control.key_on_delay = k5000::KeyOnDelay::new(5);
+
+        let merged = merge(&base, &ours, &theirs).unwrap();
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(merged.conflicts[0].path, "sources");
+        assert_eq!(merged.patch.sources.len(), 3);
+        assert_eq!(merged.patch.common.source_count, 3);
+    }
+}
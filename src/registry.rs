@@ -0,0 +1,364 @@
+//! Parameter metadata for generic editors and validators.
+//!
+//! Each model lists its parameters as `ParameterInfo` records, in the order
+//! they appear in the patch data: `k4::registry` for the K4 patches and
+//! `k5000::registry` for the K5000 patch groups. A record tells the
+//! display name and the path of the patch field, the range and default of
+//! the value, its unit, and where the value is stored in the System Exclusive
+//! data, so that it can be read from and written to the bytes of a patch
+//! without knowing the patch structure.
+//!
+//! The values are in the same units as in the patch models, so for example
+//! a K4 modulation depth is -50~+50 even though it is stored as 0~100.
+
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::ValueError;
+
+/// Unit of a parameter value.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Unit {
+    /// A plain number, like a patch number or a MIDI channel.
+    Number,
+
+    /// Level or amount, like a volume or an effect send.
+    Level,
+
+    /// Signed modulation depth, zero for no modulation.
+    Depth,
+
+    /// Envelope, LFO or delay time. Bigger values are longer.
+    Time,
+
+    /// Pitch in semitones.
+    Semitones,
+
+    /// Pitch in cents.
+    Cents,
+
+    /// MIDI note.
+    Note,
+
+    /// Wave number.
+    Wave,
+
+    /// On or off, as 1 or 0.
+    Switch,
+
+    /// One of the named choices.
+    Choice,
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match self {
+            Unit::Number => "number",
+            Unit::Level => "level",
+            Unit::Depth => "depth",
+            Unit::Time => "time",
+            Unit::Semitones => "semitones",
+            Unit::Cents => "cents",
+            Unit::Note => "note",
+            Unit::Wave => "wave",
+            Unit::Switch => "switch",
+            Unit::Choice => "choice",
+        })
+    }
+}
+
+/// Bits of one byte of System Exclusive data.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct BitField {
+    /// Offset of the byte in the data.
+    pub offset: usize,
+
+    /// The bits of the byte that belong to the field.
+    pub mask: u8,
+}
+
+impl BitField {
+    /// Makes a field of the bits in `mask` of the byte at `offset`.
+    pub fn new(offset: usize, mask: u8) -> Self {
+        BitField { offset, mask }
+    }
+
+    /// Makes a field of the whole seven-bit byte at `offset`.
+    pub fn byte(offset: usize) -> Self {
+        BitField::new(offset, 0x7f)
+    }
+
+    /// Returns the position of the lowest bit of the field.
+    pub fn shift(&self) -> u32 {
+        self.mask.trailing_zeros()
+    }
+
+    /// Returns the number of bits in the field.
+    pub fn width(&self) -> u32 {
+        self.mask.count_ones()
+    }
+}
+
+/// How a value is stored in System Exclusive data.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Encoding {
+    /// The bit fields of the stored number, most significant first.
+    pub fields: Vec<BitField>,
+
+    /// Added to the value to get the stored number, like 50 for a depth
+    /// of -50~+50 that is stored as 0~100.
+    pub bias: i32,
+
+    /// For a switch, tells that the bit is set when the switch is off.
+    pub inverted: bool,
+}
+
+impl Encoding {
+    /// Returns the number stored in the fields of `data`.
+    pub fn read(&self, data: &[u8]) -> i32 {
+        self.fields.iter().fold(0, |number, field| {
+            let bits = (data[field.offset] & field.mask) >> field.shift();
+            (number << field.width()) | bits as i32
+        })
+    }
+
+    /// Stores `number` in the fields of `data`, keeping the other bits.
+    pub fn write(&self, data: &mut [u8], number: i32) {
+        let mut number = number;
+        for field in self.fields.iter().rev() {
+            let bits = ((number << field.shift()) as u8) & field.mask;
+            data[field.offset] = (data[field.offset] & !field.mask) | bits;
+            number >>= field.width();
+        }
+    }
+}
+
+/// Description of a parameter.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct ParameterInfo {
+    /// Path of the field in the patch model, like `sources[1].coarse`.
+    pub path: String,
+
+    /// Display name, like "S2 Coarse".
+    pub name: String,
+
+    /// Range of allowed values.
+    pub range: RangeInclusive<i32>,
+
+    /// Value of the parameter in a new patch.
+    pub default: i32,
+
+    pub unit: Unit,
+
+    /// The allowed values with their names, for a parameter that has
+    /// named choices or gaps in its range. Empty if every value
+    /// in the range is allowed and has no name.
+    pub choices: Vec<(i32, String)>,
+
+    pub encoding: Encoding,
+}
+
+impl ParameterInfo {
+    /// Makes a parameter stored in the whole byte at `offset`,
+    /// with the default set to the minimum value.
+    pub fn new(path: &str, name: &str, range: RangeInclusive<i32>, unit: Unit, offset: usize) -> Self {
+        ParameterInfo {
+            path: path.to_string(),
+            name: name.to_string(),
+            default: *range.start(),
+            range,
+            unit,
+            choices: Vec::new(),
+            encoding: Encoding { fields: vec![BitField::byte(offset)], bias: 0, inverted: false },
+        }
+    }
+
+    /// Sets the stored number to the value plus `bias`.
+    pub fn bias(mut self, bias: i32) -> Self {
+        self.encoding.bias = bias;
+        self
+    }
+
+    /// Stores the value in the bits of `mask` of its byte.
+    pub fn bits(mut self, mask: u8) -> Self {
+        for field in self.encoding.fields.iter_mut() {
+            field.mask = mask;
+        }
+        self
+    }
+
+    /// Stores the value in `fields`, most significant first.
+    pub fn fields(mut self, fields: Vec<BitField>) -> Self {
+        self.encoding.fields = fields;
+        self
+    }
+
+    /// Marks a switch that is stored as 0 when it is on.
+    pub fn inverted(mut self) -> Self {
+        self.encoding.inverted = true;
+        self
+    }
+
+    /// Sets the allowed values and their names.
+    pub fn choices(mut self, choices: Vec<(i32, String)>) -> Self {
+        self.choices = choices;
+        self
+    }
+
+    /// Moves the offsets of the stored fields forward by `amount` bytes.
+    pub fn offset_by(mut self, amount: usize) -> Self {
+        for field in self.encoding.fields.iter_mut() {
+            field.offset += amount;
+        }
+        self
+    }
+
+    /// Prepends `field` to the path and `prefix` to the name.
+    pub fn within(mut self, field: &str, prefix: &str) -> Self {
+        self.path = format!("{}.{}", field, self.path);
+        self.name = format!("{} {}", prefix, self.name);
+        self
+    }
+
+    /// Returns true if `value` is allowed.
+    pub fn is_valid(&self, value: i32) -> bool {
+        self.range.contains(&value)
+            && (self.choices.is_empty() || self.choices.iter().any(|(choice, _)| *choice == value))
+    }
+
+    /// Returns the name of `value`, if it is one of the choices.
+    pub fn choice_name(&self, value: i32) -> Option<&str> {
+        self.choices.iter().find(|(choice, _)| *choice == value).map(|(_, name)| name.as_str())
+    }
+
+    /// Reads the value from the bytes of a patch.
+    pub fn read(&self, data: &[u8]) -> i32 {
+        let number = self.encoding.read(data);
+        let number = if self.encoding.inverted { 1 - number } else { number };
+        number - self.encoding.bias
+    }
+
+    /// Writes `value` to the bytes of a patch. The checksum of the patch,
+    /// if it has one, needs to be computed again.
+    pub fn write(&self, data: &mut [u8], value: i32) -> Result<(), ValueError> {
+        if !self.is_valid(value) {
+            return Err(ValueError(*self.range.start(), *self.range.end(), value));
+        }
+        let number = value + self.encoding.bias;
+        let number = if self.encoding.inverted { 1 - number } else { number };
+        self.encoding.write(data, number);
+        Ok(())
+    }
+}
+
+impl fmt::Display for ParameterInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({}): {}...{} {}, default {}",
+            self.name, self.path, self.range.start(), self.range.end(), self.unit, self.default)
+    }
+}
+
+/// Lists the values of an enum in `range` with their display names,
+/// where `T::try_from(n)` makes the enum value of the number `n`.
+pub(crate) fn choices<T>(range: RangeInclusive<i32>) -> Vec<(i32, String)>
+where
+    T: TryFrom<u8> + fmt::Display,
+{
+    range
+        .filter_map(|value| u8::try_from(value).ok()
+            .and_then(|n| T::try_from(n).ok())
+            .map(|choice| (value, choice.to_string())))
+        .collect()
+}
+
+/// Checks of the parameter lists, shared by the tests of the model registries.
+#[cfg(test)]
+pub(crate) mod checks {
+    use std::collections::HashSet;
+
+    use super::ParameterInfo;
+    use crate::SystemExclusiveData;
+
+    /// Checks that the values in `data` and the defaults are valid,
+    /// and that no two parameters share a bit.
+    pub fn check_parameters(parameters: &[ParameterInfo], data: &[u8]) {
+        let mut bits = HashSet::new();
+        for info in parameters {
+            assert!(info.is_valid(info.read(data)), "{} = {}", info, info.read(data));
+            assert!(info.is_valid(info.default), "{}", info);
+            for field in info.encoding.fields.iter() {
+                for bit in 0..7 {
+                    if field.mask & (1 << bit) != 0 {
+                        assert!(bits.insert((field.offset, bit)), "{} overlaps another parameter", info);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Writes the lowest and highest allowed value of each parameter
+    /// to `data`, and checks that the patch parses with the value.
+    pub fn check_writes<T: SystemExclusiveData>(parameters: &[ParameterInfo], data: &[u8]) {
+        for info in parameters {
+            let allowed: Vec<i32> = info.range.clone().filter(|value| info.is_valid(*value)).collect();
+            for value in [allowed[0], allowed[allowed.len() - 1]] {
+                let mut edited = data.to_vec();
+                info.write(&mut edited, value).unwrap();
+                let patch = T::from_bytes(&edited).unwrap_or_else(|e| panic!("{} = {}: {}", info, value, e));
+                assert_eq!(info.read(&patch.to_bytes()), value, "{}", info);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{*};
+
+    #[test]
+    fn test_split_field() {
+        // Like the K4 wave number: high bit in bit 0 of one byte,
+        // the low seven bits in another.
+        let info = ParameterInfo::new("wave", "Wave", 1..=256, Unit::Wave, 0)
+            .fields(vec![BitField::new(0, 0x01), BitField::new(4, 0x7f)])
+            .bias(-1);
+        let mut data = [0b0101_0000, 0, 0, 0, 0];
+        info.write(&mut data, 130).unwrap();
+        assert_eq!(data, [0b0101_0001, 0, 0, 0, 0x01]);
+        assert_eq!(info.read(&data), 130);
+        assert_eq!(info.write(&mut data, 257), Err(ValueError(1, 256, 257)));
+    }
+
+    #[test]
+    fn test_bits_and_bias() {
+        let info = ParameterInfo::new("coarse", "Coarse", -24..=24, Unit::Semitones, 0)
+            .bits(0b0011_1111)
+            .bias(24);
+        let mut data = [0b0100_0000];
+        info.write(&mut data, -24).unwrap();
+        assert_eq!(data, [0b0100_0000]);
+        info.write(&mut data, 24).unwrap();
+        assert_eq!(data, [0b0111_0000]);
+        assert_eq!(info.read(&data), 24);
+    }
+
+    #[test]
+    fn test_inverted_switch() {
+        let info = ParameterInfo::new("mute", "Mute", 0..=1, Unit::Switch, 0).bits(0b0000_0100).inverted();
+        let mut data = [0b0000_0100];
+        assert_eq!(info.read(&data), 0);
+        info.write(&mut data, 1).unwrap();
+        assert_eq!(data, [0]);
+        assert_eq!(info.read(&data), 1);
+    }
+
+    #[test]
+    fn test_choices_with_gaps() {
+        let info = ParameterInfo::new("key", "Key", 0..=3, Unit::Note, 0)
+            .choices(vec![(0, "Off".to_string()), (3, "On".to_string())]);
+        assert!(info.is_valid(3));
+        assert!(!info.is_valid(2));
+        assert_eq!(info.choice_name(0), Some("Off"));
+        assert!(info.write(&mut [0], 2).is_err());
+    }
+}
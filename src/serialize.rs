@@ -0,0 +1,280 @@
+//! Helpers for the optional `serde` support.
+//!
+//! Unit enums are serialized by their display names, like `"SAW"` or
+//! `"Pan pot"`, and the value types serialize as their plain numbers.
+//! Deserializing validates the value, so an out-of-range number or an
+//! unknown name becomes a deserialization error instead of a panic.
+//!
+//! The macros expand to nothing unless the `serde` feature is enabled.
+
+/// Implements `Serialize` and `Deserialize` for a unit enum using
+/// its display name. The variants are found by trying every `u8`
+/// value with `TryFrom`, or from the iterator given as the second argument.
+macro_rules! serialize_by_name {
+    ($type:ty) => {
+        serialize_by_name!($type, (0..=u8::MAX).filter_map(|b| <$type>::try_from(b).ok()));
+    };
+    ($type:ty, $variants:expr) => {
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for $type {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for $type {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let name = <String as serde::Deserialize>::deserialize(deserializer)?;
+                let variants: Vec<$type> = $variants.collect();
+                variants.iter()
+                    .find(|variant| variant.to_string() == name)
+                    .copied()
+                    .ok_or_else(|| {
+                        let names: Vec<String> = variants.iter().map(|v| format!("\"{}\"", v)).collect();
+                        serde::de::Error::custom(format!("unknown {} \"{}\", expected one of {}",
+                            stringify!($type), name, names.join(", ")))
+                    })
+            }
+        }
+    };
+}
+
+/// Implements `Serialize` and `Deserialize` for a `nutype` domain type
+/// as its inner value. Deserializing goes through `try_new`, so the
+/// value is checked against the range of the type.
+macro_rules! serialize_validated {
+    ($type:ty, $inner:ty) => {
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for $type {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serde::Serialize::serialize(&self.clone().into_inner(), serializer)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for $type {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let value = <$inner as serde::Deserialize>::deserialize(deserializer)?;
+                <$type>::try_new(value).map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+/// Tells if `value` has its default value. Used to leave out
+/// fields that are seldom set, like unused bytes.
+#[cfg(feature = "serde")]
+pub fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+/// Deserializes a patch name of `N` ASCII characters, padding a shorter
+/// name with spaces. Use with
+/// `#[serde(deserialize_with = "crate::serialize::fixed_name::<_, N>")]`.
+#[cfg(feature = "serde")]
+pub fn fixed_name<'de, D: serde::Deserializer<'de>, const N: usize>(deserializer: D) -> Result<String, D::Error> {
+    let name = <String as serde::Deserialize>::deserialize(deserializer)?;
+    if !name.is_ascii() || name.len() > N {
+        return Err(serde::de::Error::custom(
+            format!("invalid name \"{}\", expected at most {} ASCII characters", name, N)));
+    }
+    Ok(format!("{:<width$}", name, width = N))
+}
+
+/// Deserializes a list of exactly `N` items, like the patches of a bank.
+/// Use with `#[serde(deserialize_with = "crate::serialize::fixed_count::<_, _, N>")]`.
+#[cfg(feature = "serde")]
+pub fn fixed_count<'de, D: serde::Deserializer<'de>, T: serde::Deserialize<'de>, const N: usize>(deserializer: D) -> Result<Vec<T>, D::Error> {
+    let items = <Vec<T> as serde::Deserialize>::deserialize(deserializer)?;
+    if items.len() != N {
+        return Err(serde::de::Error::invalid_length(items.len(), &format!("a list of {} items", N).as_str()));
+    }
+    Ok(items)
+}
+
+/// Serializes arrays that are too long for the built-in array support
+/// of `serde`, as sequences. Use with `#[serde(with = "crate::serialize::big_array")]`.
+#[cfg(feature = "serde")]
+pub mod big_array {
+    use serde::{Serialize, Serializer, Deserialize, Deserializer};
+    use serde::de::Error;
+
+    pub fn serialize<S, T, const N: usize>(array: &[T; N], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Serialize,
+    {
+        serializer.collect_seq(array.iter())
+    }
+
+    pub fn deserialize<'de, D, T, const N: usize>(deserializer: D) -> Result<[T; N], D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de>,
+    {
+        let items = Vec::<T>::deserialize(deserializer)?;
+        let count = items.len();
+        items.try_into()
+            .map_err(|_| D::Error::invalid_length(count, &format!("an array of {} items", N).as_str()))
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use crate::{SystemExclusiveData, MIDINote};
+    use crate::k4;
+    use crate::k5000;
+
+    static K4_DATA: &[u8] = include_bytes!("k4/A401.SYX");
+    static K5000_DATA: &[u8] = include_bytes!("k5000/WizooIni.syx");
+
+    #[test]
+    fn test_k4_bank_round_trip() {
+        let bank = k4::bank::Bank::from_bytes(&K4_DATA[8..]).unwrap();
+        let json = serde_json::to_string(&bank).unwrap();
+        let restored: k4::bank::Bank = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.to_bytes(), bank.to_bytes());
+    }
+
+    #[test]
+    fn test_k5000_single_round_trip() {
+        let patch = k5000::single::SinglePatch::from_bytes(&K5000_DATA[9..]).unwrap();
+        let json = serde_json::to_string(&patch).unwrap();
+        let restored: k5000::single::SinglePatch = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.to_bytes(), patch.to_bytes());
+    }
+
+    #[test]
+    fn test_enum_is_serialized_by_name() {
+        assert_eq!(serde_json::to_string(&k4::lfo::Shape::Square).unwrap(), "\"SQR\"");
+        assert_eq!(serde_json::to_string(&k4::wave::WaveCategory::Drum).unwrap(), "\"Drum & Percussion\"");
+        let source: k5000::control::ControlSource = serde_json::from_str("\"Pan pot\"").unwrap();
+        assert_eq!(source, k5000::control::ControlSource::PanPot);
+    }
+
+    #[test]
+    fn test_every_enum_name_round_trips() {
+        macro_rules! check {
+            ($($type:ty),*) => {
+                $(
+                    for b in 0..=u8::MAX {
+                        if let Ok(variant) = <$type>::try_from(b) {
+                            let json = serde_json::to_string(&variant).unwrap();
+                            assert_eq!(serde_json::from_str::<$type>(&json).unwrap(), variant, "{}", json);
+                        }
+                    }
+                )*
+            };
+        }
+
+        check!(
+            k4::effect::Effect, k4::effect::Submix, k4::lfo::Shape,
+            k4::multi::VelocitySwitch, k4::multi::PlayMode,
+            k4::single::SourceMode, k4::single::PolyphonyMode, k4::single::WheelAssign,
+            k5000::control::VelocitySwitch, k5000::control::ControlSource,
+            k5000::control::ControlDestination, k5000::control::PanKind,
+            k5000::control::Switch, k5000::control::Polyphony,
+            k5000::control::AmplitudeModulation, k5000::control::VelocityCurve,
+            k5000::effect::Effect, k5000::effect::EffectAlgorithm, k5000::effect::EffectDestination,
+            k5000::filter::FilterMode, k5000::formant::Mode, k5000::formant::LFOShape,
+            k5000::lfo::Waveform, k5000::morf::HarmonicGroup, k5000::morf::Loop,
+            k5000::osc::KeyScaling
+        );
+    }
+
+    #[test]
+    fn test_unknown_enum_name() {
+        let error = serde_json::from_str::<k4::lfo::Shape>("\"SQUARE\"").unwrap_err();
+        assert!(error.to_string().starts_with("unknown Shape \"SQUARE\", expected one of \"TRI\""));
+    }
+
+    #[test]
+    fn test_out_of_range_nutype() {
+        let error = serde_json::from_str::<k4::Level>("101").unwrap_err();
+        assert!(error.to_string().contains("Level is too big"));
+        assert_eq!(serde_json::from_str::<k4::Coarse>("-24").unwrap().into_inner(), -24);
+    }
+
+    #[test]
+    fn test_out_of_range_ranged_integer() {
+        let error = serde_json::from_str::<k5000::Volume>("128").unwrap_err();
+        assert!(error.to_string().contains("expected 0...127, got 128"));
+        assert_eq!(serde_json::from_str::<k5000::Volume>("127").unwrap().value(), 127);
+
+    }
+
+    #[test]
+    fn test_out_of_range_tone_number() {
+        let mut bank = k5000::bank::Bank::new();
+        let tone_number = k5000::sysex::ToneNumber::try_new(5).unwrap();
+        bank.patches.insert(tone_number, k5000::single::SinglePatch::default());
+        let json = serde_json::to_string(&bank).unwrap().replace("\"5\"", "\"128\"");
+        let error = serde_json::from_str::<k5000::bank::Bank>(&json).map(|_| ()).unwrap_err();
+        assert!(error.to_string().contains("expected 0...127, got 128"));
+    }
+
+    #[test]
+    fn test_note_is_serialized_by_name() {
+        assert_eq!(serde_json::to_string(&MIDINote::try_new(60).unwrap()).unwrap(), "\"C3\"");
+        assert_eq!(serde_json::from_str::<MIDINote>("\"C#-2\"").unwrap().value(), 1);
+        let error = serde_json::from_str::<MIDINote>("\"H4\"").unwrap_err();
+        assert!(error.to_string().contains("invalid note name \"H4\""));
+    }
+
+    #[test]
+    fn test_name_length() {
+        let patch = k4::single::SinglePatch::default();
+        let json = serde_json::to_string(&patch).unwrap().replace("NewSound  ", "Much Too Long Name");
+        let error = serde_json::from_str::<k4::single::SinglePatch>(&json).map(|_| ()).unwrap_err();
+        assert!(error.to_string().contains("expected at most 10 ASCII characters"));
+
+        let json = serde_json::to_string(&patch).unwrap().replace("NewSound  ", "Short");
+        let restored = serde_json::from_str::<k4::single::SinglePatch>(&json).unwrap();
+        assert_eq!(restored.name, "Short     ");
+        assert_eq!(restored.to_bytes().len(), k4::single::SinglePatch::data_size());
+
+        let patch = k5000::single::SinglePatch::default();
+        let json = serde_json::to_string(&patch).unwrap().replace("NewSound", "Smörgås");
+        assert!(serde_json::from_str::<k5000::single::SinglePatch>(&json).is_err());
+    }
+
+    #[test]
+    fn test_source_count_range() {
+        let patch = k5000::single::SinglePatch::default();
+        let json = serde_json::to_string(&patch).unwrap().replace("\"source_count\":2", "\"source_count\":7");
+        let error = serde_json::from_str::<k5000::single::SinglePatch>(&json).map(|_| ()).unwrap_err();
+        assert!(error.to_string().contains("expected 1...6, got 7"));
+    }
+
+    #[test]
+    fn test_big_array_length() {
+        let levels = k5000::harmonic::Levels::default();
+        let mut value = serde_json::to_value(&levels).unwrap();
+        value["soft"].as_array_mut().unwrap().pop();
+        let error = serde_json::from_value::<k5000::harmonic::Levels>(value).map(|_| ()).unwrap_err();
+        assert!(error.to_string().contains("an array of 64 items"));
+    }
+
+    #[test]
+    fn test_bank_patch_count() {
+        let bank = k4::bank::Bank::from_bytes(&K4_DATA[8..]).unwrap();
+        for (field, count) in [("singles", 64), ("multis", 64), ("effects", 32)] {
+            let mut value = serde_json::to_value(&bank).unwrap();
+            value[field].as_array_mut().unwrap().pop();
+            let error = serde_json::from_value::<k4::bank::Bank>(value).map(|_| ()).unwrap_err();
+            assert!(error.to_string().contains(&format!("a list of {} items", count)), "{}", error);
+        }
+
+        let mut value = serde_json::to_value(k5000::bank::PCMBank::new()).unwrap();
+        value["patches"].as_array_mut().unwrap().pop();
+        let error = serde_json::from_value::<k5000::bank::PCMBank>(value).map(|_| ()).unwrap_err();
+        assert!(error.to_string().contains("a list of 128 items"));
+
+        let mut value = serde_json::to_value(k5000::multi::MultiBank::new()).unwrap();
+        let patch = value["patches"][0].clone();
+        value["patches"].as_array_mut().unwrap().push(patch);
+        let error = serde_json::from_value::<k5000::multi::MultiBank>(value).map(|_| ()).unwrap_err();
+        assert!(error.to_string().contains("a list of 64 items"));
+    }
+}
@@ -0,0 +1,354 @@
+//! Human-editable text format for patch dumps, in TOML or YAML.
+//!
+//! A document describes one dump message. The `dump` key tells the kind
+//! of dump, and the other top-level keys hold the header fields and the patches:
+//!
+//! ```toml
+//! dump = "k4_single"
+//! channel = 1
+//! locality = "INT"
+//! number = 0
+//!
+//! [single]
+//! name = "Melo Vox 1"
+//! volume = 100
+//! ...
+//! ```
+//!
+//! The patch numbers are zero-based, as they appear in the message,
+//! except that K4 effect numbers are 1~32.
+//! Enums are written by their display names, notes as names like `"C4"`,
+//! waves as names like `"SAW 1"`, and depths as signed values.
+//! A wave name shared by several waves is written with its number,
+//! like `"97 MARIMBA"`. Fields that keep unused bytes of the dump
+//! are left out when they are zero.
+//!
+//! Converting a dump to text and back produces identical bytes,
+//! including the checksums, which are computed when the dump is written.
+
+use std::fmt;
+
+use serde::{Serialize, Deserialize};
+
+use crate::{SystemExclusiveData, ParseError, MIDIChannel};
+use crate::loader::{self, Patch};
+use crate::k4;
+use crate::k4::sysex::{PatchDump, DumpData, Locality};
+use crate::k4::bank::{SINGLE_PATCH_COUNT, MULTI_PATCH_COUNT, EFFECT_PATCH_COUNT};
+use crate::k5000;
+use crate::k5000::sysex::{BankIdentifier, ToneNumber};
+
+/// Text syntax of a document.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Format {
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    /// Picks the format by the extension of `path`:
+    /// .toml, or .yaml or .yml.
+    pub fn from_path(path: &str) -> Option<Format> {
+        let extension = path.rsplit_once('.')?.1.to_ascii_lowercase();
+        match extension.as_str() {
+            "toml" => Some(Format::Toml),
+            "yaml" | "yml" => Some(Format::Yaml),
+            _ => None,
+        }
+    }
+}
+
+/// Error type for reading and writing documents.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum TextError {
+    /// The text is not valid, or does not describe a dump.
+    InvalidText(String),
+
+    /// The document could not be written in the format.
+    Unwritable(String),
+}
+
+impl fmt::Display for TextError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextError::InvalidText(message) => write!(f, "invalid text: {}", message),
+            TextError::Unwritable(message) => write!(f, "unable to write text: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for TextError {}
+
+/// One dump message as a document.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "dump", rename_all = "snake_case")]
+pub enum Document {
+    K4Single {
+        channel: MIDIChannel,
+        locality: Locality,
+        number: k4::PatchNumber,
+        single: k4::single::SinglePatch,
+    },
+    K4Multi {
+        channel: MIDIChannel,
+        locality: Locality,
+        number: k4::PatchNumber,
+        multi: k4::multi::MultiPatch,
+    },
+    K4Drum {
+        channel: MIDIChannel,
+        locality: Locality,
+        drum: k4::drum::DrumPatch,
+    },
+    K4Effect {
+        channel: MIDIChannel,
+        locality: Locality,
+        number: k4::EffectNumber,
+        effect: k4::effect::EffectPatch,
+    },
+    K4Singles {
+        channel: MIDIChannel,
+        locality: Locality,
+        singles: Vec<k4::single::SinglePatch>,
+    },
+    K4Multis {
+        channel: MIDIChannel,
+        locality: Locality,
+        multis: Vec<k4::multi::MultiPatch>,
+    },
+    K4Effects {
+        channel: MIDIChannel,
+        locality: Locality,
+        effects: Vec<k4::effect::EffectPatch>,
+    },
+    K4Bank {
+        channel: MIDIChannel,
+        locality: Locality,
+        bank: k4::bank::Bank,
+    },
+    K5000Single {
+        channel: MIDIChannel,
+        bank: BankIdentifier,
+        number: ToneNumber,
+        single: k5000::single::SinglePatch,
+    },
+}
+
+impl Document {
+    /// Parses one dump message, from F0H to F7H.
+    /// Of the K5000 dumps, only one single dumps are supported.
+    pub fn from_syx(data: &[u8]) -> Result<Document, ParseError> {
+        match loader::parse(data)? {
+            Patch::K4(dump) => {
+                let PatchDump { channel, locality, data } = *dump;
+                Ok(match data {
+                    DumpData::OneSingle(number, single) => Document::K4Single { channel, locality, number, single },
+                    DumpData::OneMulti(number, multi) => Document::K4Multi { channel, locality, number, multi },
+                    DumpData::Drum(drum) => Document::K4Drum { channel, locality, drum },
+                    DumpData::OneEffect(number, effect) => Document::K4Effect { channel, locality, number, effect },
+                    DumpData::BlockSingle(singles) => Document::K4Singles { channel, locality, singles },
+                    DumpData::BlockMulti(multis) => Document::K4Multis { channel, locality, multis },
+                    DumpData::BlockEffect(effects) => Document::K4Effects { channel, locality, effects },
+                    DumpData::All(bank) => Document::K4Bank { channel, locality, bank },
+                })
+            },
+            Patch::K5000Single(bank, number, single) => {
+                let message = k5000::sysex::Message::from_bytes(data)?;
+                Ok(Document::K5000Single { channel: message.channel, bank, number, single })
+            },
+            _ => Err(ParseError::InvalidData(0, "only K5000 one single dumps are supported".to_string())),
+        }
+    }
+
+    /// Emits the dump message, from F0H to F7H.
+    pub fn to_syx(&self) -> Vec<u8> {
+        let k4_dump = |channel: &MIDIChannel, locality: &Locality, data: DumpData| {
+            PatchDump { channel: *channel, locality: *locality, data }.to_bytes()
+        };
+
+        match self {
+            Document::K4Single { channel, locality, number, single } =>
+                k4_dump(channel, locality, DumpData::OneSingle(*number, single.clone())),
+            Document::K4Multi { channel, locality, number, multi } =>
+                k4_dump(channel, locality, DumpData::OneMulti(*number, multi.clone())),
+            Document::K4Drum { channel, locality, drum } =>
+                k4_dump(channel, locality, DumpData::Drum(drum.clone())),
+            Document::K4Effect { channel, locality, number, effect } =>
+                k4_dump(channel, locality, DumpData::OneEffect(*number, effect.clone())),
+            Document::K4Singles { channel, locality, singles } =>
+                k4_dump(channel, locality, DumpData::BlockSingle(singles.clone())),
+            Document::K4Multis { channel, locality, multis } =>
+                k4_dump(channel, locality, DumpData::BlockMulti(multis.clone())),
+            Document::K4Effects { channel, locality, effects } =>
+                k4_dump(channel, locality, DumpData::BlockEffect(effects.clone())),
+            Document::K4Bank { channel, locality, bank } =>
+                k4_dump(channel, locality, DumpData::All(bank.clone())),
+            Document::K5000Single { channel, bank, number, single } =>
+                single.to_message(*channel, *bank, *number).to_bytes(),
+        }
+    }
+
+    /// Reads a document from `text` in `format`. The values are checked
+    /// as they are read, and then the document is checked as a whole,
+    /// so that it can be written as a dump message.
+    pub fn from_text(text: &str, format: Format) -> Result<Document, TextError> {
+        let document: Document = match format {
+            Format::Toml => toml::from_str(text).map_err(|e| TextError::InvalidText(e.to_string()))?,
+            Format::Yaml => serde_yaml::from_str(text).map_err(|e| TextError::InvalidText(e.to_string()))?,
+        };
+        document.check()?;
+        Ok(document)
+    }
+
+    /// Checks what the types of the fields cannot: the number of patches
+    /// in blocks and banks, and the number of sources of a K5000 single.
+    fn check(&self) -> Result<(), TextError> {
+        let count = |field: &str, actual: usize, expected: usize| {
+            if actual == expected {
+                Ok(())
+            } else {
+                Err(TextError::InvalidText(format!("{} has {} patches, expected {}", field, actual, expected)))
+            }
+        };
+
+        match self {
+            Document::K4Singles { singles, .. } => count("singles", singles.len(), SINGLE_PATCH_COUNT),
+            Document::K4Multis { multis, .. } => count("multis", multis.len(), MULTI_PATCH_COUNT),
+            Document::K4Effects { effects, .. } => count("effects", effects.len(), EFFECT_PATCH_COUNT),
+            Document::K4Bank { bank, .. } => {
+                count("bank.singles", bank.singles.len(), SINGLE_PATCH_COUNT)?;
+                count("bank.multis", bank.multis.len(), MULTI_PATCH_COUNT)?;
+                count("bank.effects", bank.effects.len(), EFFECT_PATCH_COUNT)
+            },
+            Document::K5000Single { single, .. } => {
+                let source_count = single.common.source_count as usize;
+                if source_count != single.sources.len() {
+                    return Err(TextError::InvalidText(format!("single.common.source_count is {}, but there are {} sources",
+                        source_count, single.sources.len())));
+                }
+                let additive_count = single.sources.iter().filter(|s| s.is_additive()).count();
+                if additive_count != single.additive_kits.len() {
+                    return Err(TextError::InvalidText(format!("there are {} ADD sources, but {} additive kits",
+                        additive_count, single.additive_kits.len())));
+                }
+                Ok(())
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes this document as text in `format`.
+    pub fn to_text(&self, format: Format) -> Result<String, TextError> {
+        match format {
+            Format::Toml => toml::to_string(self).map_err(|e| TextError::Unwritable(e.to_string())),
+            Format::Yaml => serde_yaml::to_string(self).map_err(|e| TextError::Unwritable(e.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{*};
+
+    static K4_DATA: &[u8] = include_bytes!("k4/A401.SYX");
+    static K5000_DATA: &[u8] = include_bytes!("k5000/WizooIni.syx");
+
+    fn round_trip(data: &[u8], format: Format) -> String {
+        let document = Document::from_syx(data).unwrap();
+        let text = document.to_text(format).unwrap();
+        let restored = Document::from_text(&text, format).unwrap();
+        assert_eq!(restored.to_syx(), data);
+        text
+    }
+
+    #[test]
+    fn test_k4_bank_round_trip() {
+        let text = round_trip(K4_DATA, Format::Toml);
+        assert!(text.starts_with("dump = \"k4_bank\""));
+        assert!(text.contains("wave = \"SAW 1\""));
+        assert!(text.contains("high_key = \"G8\""));
+
+        round_trip(K4_DATA, Format::Yaml);
+    }
+
+    #[test]
+    fn test_k4_single_round_trip() {
+        let mut data = vec![0xF0, 0x40, 0x00, 0x20, 0x00, 0x04, 0x00, 0x05];
+        data.extend(&K4_DATA[8..8 + k4::single::SinglePatch::data_size()]);
+        data.push(0xF7);
+        let text = round_trip(&data, Format::Toml);
+        assert!(text.contains("number = 5"));
+    }
+
+    #[test]
+    fn test_k5000_single_round_trip() {
+        let text = round_trip(K5000_DATA, Format::Toml);
+        assert!(text.contains("name = \"WizooIni\""));
+
+        round_trip(K5000_DATA, Format::Yaml);
+    }
+
+    #[test]
+    fn test_edited_text() {
+        let document = Document::from_syx(K4_DATA).unwrap();
+        let text = document.to_text(Format::Toml).unwrap()
+            .replacen("high_key = \"G8\"", "high_key = \"C6\"", 1);
+        match Document::from_text(&text, Format::Toml).unwrap() {
+            Document::K4Bank { bank, .. } => assert_eq!(bank.multis[0].sections[0].zone.high_key.note.name(), "C6"),
+            _ => panic!("expected a K4 bank"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_text() {
+        let error = Document::from_text("dump = \"k4_drum\"\nchannel = 17", Format::Toml).map(|_| ()).unwrap_err();
+        assert!(matches!(error, TextError::InvalidText(_)));
+        assert!(Document::from_text("dump: k3_single", Format::Yaml).is_err());
+    }
+
+    #[test]
+    fn test_long_name() {
+        let text = Document::from_syx(K4_DATA).unwrap().to_text(Format::Toml).unwrap()
+            .replacen("name = \"Melo Vox 1\"", "name = \"Melo Vox 1 Extended\"", 1);
+        let error = Document::from_text(&text, Format::Toml).map(|_| ()).unwrap_err();
+        assert!(matches!(error, TextError::InvalidText(message) if message.contains("expected at most 10 ASCII characters")));
+    }
+
+    #[test]
+    fn test_source_count_mismatch() {
+        let text = Document::from_syx(K5000_DATA).unwrap().to_text(Format::Toml).unwrap()
+            .replacen("source_count = 2", "source_count = 5", 1);
+        let error = Document::from_text(&text, Format::Toml).map(|_| ()).unwrap_err();
+        assert_eq!(error, TextError::InvalidText("single.common.source_count is 5, but there are 2 sources".to_string()));
+    }
+
+    #[test]
+    fn test_bad_patch_number() {
+        let mut data = vec![0xF0, 0x40, 0x00, 0x20, 0x00, 0x04, 0x00, 0x05];
+        data.extend(&K4_DATA[8..8 + k4::single::SinglePatch::data_size()]);
+        data.push(0xF7);
+        let text = Document::from_syx(&data).unwrap().to_text(Format::Toml).unwrap()
+            .replacen("number = 5", "number = 70", 1);
+        let error = Document::from_text(&text, Format::Toml).map(|_| ()).unwrap_err();
+        assert!(matches!(error, TextError::InvalidText(_)));
+    }
+
+    #[test]
+    fn test_short_block() {
+        let document = Document::K4Effects {
+            channel: MIDIChannel::try_new(1).unwrap(),
+            locality: Locality::Internal,
+            effects: vec![Default::default(); 3],
+        };
+        let text = document.to_text(Format::Yaml).unwrap();
+        let error = Document::from_text(&text, Format::Yaml).map(|_| ()).unwrap_err();
+        assert_eq!(error, TextError::InvalidText("effects has 3 patches, expected 32".to_string()));
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(Format::from_path("patch.TOML"), Some(Format::Toml));
+        assert_eq!(Format::from_path("bank.yml"), Some(Format::Yaml));
+        assert_eq!(Format::from_path("bank.syx"), None);
+    }
+}